@@ -0,0 +1,154 @@
+// Decoding throughput (events per second) of typical payloads. Each stream
+// is decoded with:
+//  - read_event(): a new event (and base event) is allocated per event,
+//  - read_event_into(): the memory of the previous event is reused,
+//  - read_event_into() with compact events: field names are shared with the
+//    klass instead of being copied into every event.
+// Run with `cargo bench --bench decode`; criterion reports the change since
+// the previous run.
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use hawktracer_parser::data_provider::DataError;
+use hawktracer_parser::{Event, EventKlassRegistry, EventReaderBuilder, ReadEventError};
+use hawktracer_parser_test_utilities::stream::{TestStreamBuilder, TestValue};
+
+const EVENT_COUNT: u64 = 10_000;
+const NESTING_DEPTH: u32 = 8;
+
+// Events with a few integer fields.
+fn make_fixed_stream() -> Vec<u8> {
+    let mut builder = TestStreamBuilder::new().klass(
+        100,
+        "fixed",
+        &[
+            ("HT_Event", "base"),
+            ("uint64_t", "duration"),
+            ("uint32_t", "thread_id"),
+            ("int32_t", "value"),
+        ],
+    );
+    for i in 0..EVENT_COUNT {
+        builder = builder.event(
+            100,
+            i,
+            &[
+                TestValue::U64(i * 3),
+                TestValue::U32(1),
+                TestValue::I32(i as i32),
+            ],
+        );
+    }
+    builder.build()
+}
+
+// Events with several string fields.
+fn make_string_stream() -> Vec<u8> {
+    let mut builder = TestStreamBuilder::new().klass(
+        100,
+        "strings",
+        &[
+            ("HT_Event", "base"),
+            ("const char*", "label"),
+            ("const char*", "file"),
+            ("const char*", "message"),
+        ],
+    );
+    for i in 0..EVENT_COUNT {
+        builder = builder.event(
+            100,
+            i,
+            &[
+                TestValue::Str(format!("render_frame_{}", i % 100)),
+                TestValue::Str("src/renderer/pipeline.cpp".to_owned()),
+                TestValue::Str(format!("frame {} submitted to the queue", i)),
+            ],
+        );
+    }
+    builder.build()
+}
+
+// Events with a chain of nested structs (each level has a struct field and
+// an integer field).
+fn make_nested_stream() -> Vec<u8> {
+    let mut builder = TestStreamBuilder::new();
+    for level in 0..NESTING_DEPTH {
+        let name = format!("level_{}", level);
+        builder = if level == 0 {
+            builder.klass(200, &name, &[("uint32_t", "value")])
+        } else {
+            let type_name = format!("level_{}", level - 1);
+            builder.klass(
+                200 + level,
+                &name,
+                &[(&type_name, "inner"), ("uint32_t", "value")],
+            )
+        };
+    }
+    let type_name = format!("level_{}", NESTING_DEPTH - 1);
+    builder = builder.klass(100, "nested", &[("HT_Event", "base"), (&type_name, "root")]);
+    let values: Vec<TestValue> = (0..NESTING_DEPTH).map(TestValue::U32).collect();
+    for i in 0..EVENT_COUNT {
+        builder = builder.event(100, i, &values);
+    }
+    builder.build()
+}
+
+fn decode(data: Vec<u8>) -> u64 {
+    let mut reader = EventReaderBuilder::new().build(Box::new(std::io::Cursor::new(data)));
+    let mut registry = EventKlassRegistry::new();
+    let mut count = 0;
+    loop {
+        match reader.read_event(&mut registry) {
+            Ok(_) => count += 1,
+            Err(ReadEventError::DataError(DataError::EndOfStream)) => return count,
+            Err(err) => panic!("Cannot read event: {:?}", err),
+        }
+    }
+}
+
+fn decode_into(data: Vec<u8>, compact: bool) -> u64 {
+    let mut reader = EventReaderBuilder::new()
+        .compact(compact)
+        .build(Box::new(std::io::Cursor::new(data)));
+    let mut registry = EventKlassRegistry::new();
+    let mut event = Event::new(0, fnv::FnvHashMap::default());
+    let mut count = 0;
+    loop {
+        match reader.read_event_into(&mut registry, &mut event) {
+            Ok(()) => count += 1,
+            Err(ReadEventError::DataError(DataError::EndOfStream)) => return count,
+            Err(err) => panic!("Cannot read event: {:?}", err),
+        }
+    }
+}
+
+fn bench_stream(c: &mut Criterion, name: &str, data: Vec<u8>) {
+    let mut group = c.benchmark_group(name);
+    group.throughput(Throughput::Elements(EVENT_COUNT));
+    group.bench_function("read_event", |b| {
+        b.iter_batched(|| data.clone(), decode, BatchSize::LargeInput)
+    });
+    group.bench_function("read_event_into", |b| {
+        b.iter_batched(
+            || data.clone(),
+            |data| decode_into(data, false),
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("read_event_into_compact", |b| {
+        b.iter_batched(
+            || data.clone(),
+            |data| decode_into(data, true),
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+fn decode_benchmark(c: &mut Criterion) {
+    bench_stream(c, "fixed", make_fixed_stream());
+    bench_stream(c, "strings", make_string_stream());
+    bench_stream(c, "nested", make_nested_stream());
+}
+
+criterion_group!(benches, decode_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,17 @@
+// Decodes arbitrary bytes. Run with `cargo fuzz run read_event`; a
+// dictionary generated by hawktracer_parser::fuzz_dictionary can be passed
+// with `-- -dict=<file>`.
+#![no_main]
+use hawktracer_parser::{EventKlassRegistry, EventReaderBuilder};
+use libfuzzer_sys::fuzz_target;
+
+// Keeps a single string field from allocating the whole input.
+const MAX_STRING_LENGTH: usize = 4096;
+
+fuzz_target!(|data: &[u8]| {
+    let mut reader = EventReaderBuilder::new()
+        .max_string_length(MAX_STRING_LENGTH)
+        .build(Box::new(std::io::Cursor::new(data.to_vec())));
+    let mut registry = EventKlassRegistry::new();
+    while reader.read_event(&mut registry).is_ok() {}
+});
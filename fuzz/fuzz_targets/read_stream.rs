@@ -0,0 +1,17 @@
+// Decodes streams with mostly valid metadata, so the fuzzer spends its time
+// on decoding events of arbitrary klasses. Run with
+// `cargo fuzz run read_stream`.
+#![no_main]
+use hawktracer_parser::{EventKlassRegistry, EventReaderBuilder};
+use hawktracer_parser_test_utilities::stream::TraceStream;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|stream: TraceStream| {
+    for strict in &[true, false] {
+        let mut reader = EventReaderBuilder::new()
+            .strict(*strict)
+            .build(Box::new(std::io::Cursor::new(stream.encode())));
+        let mut registry = EventKlassRegistry::new();
+        while reader.read_event(&mut registry).is_ok() {}
+    }
+});
@@ -0,0 +1,246 @@
+use crate::event::Event;
+use crate::registry::EventKlassRegistry;
+use crate::spans::{PairingStrategy, Span, SpanBuilder};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallNode {
+    pub label: String,
+    pub start: u64,
+    pub duration: u64,
+    pub children: Vec<CallNode>,
+}
+
+impl CallNode {
+    fn new(span: Span) -> CallNode {
+        CallNode {
+            label: span.label,
+            start: span.start,
+            duration: span.duration,
+            children: Vec::new(),
+        }
+    }
+
+    pub fn get_end(&self) -> u64 {
+        self.start.saturating_add(self.duration)
+    }
+
+    // Time spent in the scope itself, excluding the children.
+    pub fn get_self_duration(&self) -> u64 {
+        let children_duration: u64 = self.children.iter().map(|child| child.duration).sum();
+        self.duration.saturating_sub(children_duration)
+    }
+}
+
+// Sorts spans of a thread by start time (enclosing spans first) and returns
+// the index of the parent of each span, given its (start, duration). A span
+// which only partially overlaps with the enclosing one is treated as a
+// sibling.
+pub(crate) fn nest_spans<T, F>(spans: &mut [T], get_range: F) -> Vec<Option<usize>>
+where
+    F: Fn(&T) -> (u64, u64),
+{
+    spans.sort_by(|a, b| {
+        let (a_start, a_duration) = get_range(a);
+        let (b_start, b_duration) = get_range(b);
+        a_start.cmp(&b_start).then(b_duration.cmp(&a_duration))
+    });
+
+    let mut parents = Vec::with_capacity(spans.len());
+    let mut stack: Vec<usize> = Vec::new();
+    for (index, span) in spans.iter().enumerate() {
+        let (start, duration) = get_range(span);
+        while let Some(&top) = stack.last() {
+            let (top_start, top_duration) = get_range(&spans[top]);
+            if start >= top_start
+                && start.saturating_add(duration) <= top_start.saturating_add(top_duration)
+            {
+                break;
+            }
+            stack.pop();
+        }
+        parents.push(stack.last().copied());
+        stack.push(index);
+    }
+    parents
+}
+
+// Builds per-thread call trees from callstack events (events with label,
+// duration and thread id fields). Events may arrive in any order (HawkTracer
+// emits a scope when it ends, so children come before their parents); the
+// nesting is derived from the time ranges when the trees are built.
+pub struct CallstackBuilder {
+    span_builder: SpanBuilder,
+    threads: std::collections::BTreeMap<u64, Vec<Span>>,
+}
+
+impl CallstackBuilder {
+    pub fn new() -> CallstackBuilder {
+        CallstackBuilder {
+            span_builder: SpanBuilder::new(PairingStrategy::Nesting),
+            threads: std::collections::BTreeMap::new(),
+        }
+    }
+
+    // Returns false if the event is not a callstack event.
+    pub fn update(&mut self, event: &Event, registry: &EventKlassRegistry) -> bool {
+        match self.span_builder.update(event, registry) {
+            Some(span) => {
+                self.add_span(span);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn add_span(&mut self, span: Span) {
+        self.threads.entry(span.thread_id).or_default().push(span);
+    }
+
+    pub fn get_thread_ids(&self) -> Vec<u64> {
+        self.threads.keys().cloned().collect()
+    }
+
+    // Returns root scopes of each thread, ordered by start time. A scope
+    // which only partially overlaps with the enclosing one is treated as
+    // a sibling.
+    pub fn build(self) -> std::collections::BTreeMap<u64, Vec<CallNode>> {
+        self.threads
+            .into_iter()
+            .map(|(thread_id, spans)| (thread_id, CallstackBuilder::build_tree(spans)))
+            .collect()
+    }
+
+    fn build_tree(mut spans: Vec<Span>) -> Vec<CallNode> {
+        let parents = nest_spans(&mut spans, |span| (span.start, span.duration));
+
+        // Parents precede their children, so the nodes are attached from
+        // the last one.
+        let mut nodes: Vec<CallNode> = spans.into_iter().map(CallNode::new).collect();
+        let mut roots = Vec::new();
+        while let Some(mut node) = nodes.pop() {
+            node.children.reverse();
+            match parents[nodes.len()] {
+                Some(parent) => nodes[parent].children.push(node),
+                None => roots.push(node),
+            }
+        }
+        roots.reverse();
+        roots
+    }
+}
+
+impl Default for CallstackBuilder {
+    fn default() -> CallstackBuilder {
+        CallstackBuilder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::Value;
+    use crate::event_klass::EventKlass;
+
+    fn span(label: &str, thread_id: u64, start: u64, duration: u64) -> Span {
+        Span {
+            label: label.to_owned(),
+            thread_id,
+            start,
+            duration,
+        }
+    }
+
+    fn node(label: &str, start: u64, duration: u64, children: Vec<CallNode>) -> CallNode {
+        CallNode {
+            label: label.to_owned(),
+            start,
+            duration,
+            children,
+        }
+    }
+
+    #[test]
+    fn spans_should_be_nested_by_time_ranges() {
+        let mut builder = CallstackBuilder::new();
+        // Inner scopes end (and are reported) before the outer ones.
+        builder.add_span(span("inner1", 1, 2, 3));
+        builder.add_span(span("inner2", 1, 6, 2));
+        builder.add_span(span("outer", 1, 1, 10));
+        builder.add_span(span("next", 1, 12, 1));
+        builder.add_span(span("other_thread", 2, 2, 3));
+
+        let trees = builder.build();
+        assert_eq!(trees.len(), 2);
+        assert_eq!(
+            trees[&1],
+            vec![
+                node(
+                    "outer",
+                    1,
+                    10,
+                    vec![node("inner1", 2, 3, vec![]), node("inner2", 6, 2, vec![])]
+                ),
+                node("next", 12, 1, vec![]),
+            ]
+        );
+        assert_eq!(trees[&2], vec![node("other_thread", 2, 3, vec![])]);
+        assert_eq!(trees[&1][0].get_self_duration(), 5);
+    }
+
+    #[test]
+    fn scopes_with_same_range_should_be_nested() {
+        let mut builder = CallstackBuilder::new();
+        builder.add_span(span("a", 1, 5, 5));
+        builder.add_span(span("b", 1, 5, 5));
+        builder.add_span(span("c", 1, 5, 2));
+
+        let trees = builder.build();
+        assert_eq!(trees[&1].len(), 1);
+        assert_eq!(trees[&1][0].children.len(), 1);
+        assert_eq!(trees[&1][0].children[0].children.len(), 1);
+    }
+
+    #[test]
+    fn callstack_events_should_be_converted_to_spans() {
+        let mut registry = EventKlassRegistry::new();
+        registry.add_klass(EventKlass::new(100, "callstack".to_owned()));
+        registry.add_klass(EventKlass::new(101, "other".to_owned()));
+        let make_event = |klass_id, values: Vec<(&str, Value)>| {
+            let mut map = fnv::FnvHashMap::default();
+            for (name, value) in values {
+                map.insert(name.to_owned(), value);
+            }
+            Event::new(klass_id, map)
+        };
+
+        let mut builder = CallstackBuilder::new();
+        assert!(builder.update(
+            &make_event(
+                100,
+                vec![
+                    ("timestamp", Value::U64(4)),
+                    ("duration", Value::U64(2)),
+                    ("label", Value::Str("foo".to_owned())),
+                    ("thread_id", Value::U32(3)),
+                ],
+            ),
+            &registry
+        ));
+        assert!(!builder.update(
+            &make_event(101, vec![("timestamp", Value::U64(4))]),
+            &registry
+        ));
+
+        assert_eq!(builder.get_thread_ids(), vec![3]);
+        assert_eq!(builder.build()[&3], vec![node("foo", 4, 2, vec![])]);
+    }
+
+    #[test]
+    fn partially_overlapping_scopes_should_be_siblings() {
+        let mut builder = CallstackBuilder::new();
+        builder.add_span(span("a", 1, 0, 5));
+        builder.add_span(span("b", 1, 3, 5));
+
+        assert_eq!(builder.build()[&1].len(), 2);
+    }
+}
@@ -0,0 +1,81 @@
+use crate::analysis::callstack::CallNode;
+use crate::json::escape_string;
+
+// Microseconds with nanosecond precision; HawkTracer timestamps are in
+// nanoseconds.
+fn format_micros(nanos: u64) -> String {
+    format!("{}.{:03}", nanos / 1000, nanos % 1000)
+}
+
+fn write_node(
+    node: &CallNode,
+    thread_id: u64,
+    separator: &mut &str,
+    writer: &mut dyn std::io::Write,
+) -> std::io::Result<()> {
+    write!(
+        writer,
+        "{}{{\"name\":{},\"ph\":\"X\",\"ts\":{},\"dur\":{},\"pid\":0,\"tid\":{}}}",
+        separator,
+        escape_string(&node.label),
+        format_micros(node.start),
+        format_micros(node.duration),
+        thread_id
+    )?;
+    *separator = ",";
+    for child in &node.children {
+        write_node(child, thread_id, separator, writer)?;
+    }
+    Ok(())
+}
+
+// Writes call trees in the Trace Event format (complete "X" events), which
+// can be opened with chrome://tracing and Perfetto.
+pub fn write_chrome_trace(
+    trees: &std::collections::BTreeMap<u64, Vec<CallNode>>,
+    writer: &mut dyn std::io::Write,
+) -> std::io::Result<()> {
+    write!(writer, "{{\"traceEvents\":[")?;
+    let mut separator = "";
+    for (thread_id, roots) in trees {
+        for root in roots {
+            write_node(root, *thread_id, &mut separator, writer)?;
+        }
+    }
+    write!(writer, "],\"displayTimeUnit\":\"ns\"}}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn call_trees_should_be_written_as_complete_events() {
+        let mut trees = std::collections::BTreeMap::new();
+        trees.insert(
+            3,
+            vec![CallNode {
+                label: "main\"".to_owned(),
+                start: 1500,
+                duration: 20_000,
+                children: vec![CallNode {
+                    label: "draw".to_owned(),
+                    start: 2000,
+                    duration: 7,
+                    children: vec![],
+                }],
+            }],
+        );
+        let mut output = Vec::new();
+
+        write_chrome_trace(&trees, &mut output).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{\"traceEvents\":[\
+             {\"name\":\"main\\\"\",\"ph\":\"X\",\"ts\":1.500,\"dur\":20.000,\"pid\":0,\"tid\":3},\
+             {\"name\":\"draw\",\"ph\":\"X\",\"ts\":2.000,\"dur\":0.007,\"pid\":0,\"tid\":3}\
+             ],\"displayTimeUnit\":\"ns\"}"
+        );
+    }
+}
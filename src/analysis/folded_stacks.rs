@@ -0,0 +1,141 @@
+use crate::analysis::callstack::CallNode;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FoldedValue {
+    // Time spent in the scope itself, excluding its children.
+    SelfDuration,
+    // Number of scope occurrences.
+    Count,
+}
+
+// Writes call trees as folded stacks ("a;b;c value" lines), the input format
+// of flamegraph.pl and inferno. Identical stacks are merged; stacks with
+// zero value are skipped.
+pub struct FoldedStacksWriter {
+    value: FoldedValue,
+    thread_frames: bool,
+}
+
+fn escape_frame(label: &str) -> String {
+    label.replace([';', '\n'], "_")
+}
+
+impl FoldedStacksWriter {
+    pub fn new(value: FoldedValue) -> FoldedStacksWriter {
+        FoldedStacksWriter {
+            value,
+            thread_frames: false,
+        }
+    }
+
+    // Adds a root frame ("thread_<id>") so threads are displayed separately.
+    pub fn with_thread_frames(mut self, thread_frames: bool) -> FoldedStacksWriter {
+        self.thread_frames = thread_frames;
+        self
+    }
+
+    pub fn fold(
+        &self,
+        trees: &std::collections::BTreeMap<u64, Vec<CallNode>>,
+    ) -> std::collections::BTreeMap<String, u64> {
+        let mut stacks = std::collections::BTreeMap::new();
+        for (thread_id, roots) in trees {
+            let prefix = if self.thread_frames {
+                format!("thread_{};", thread_id)
+            } else {
+                String::new()
+            };
+            for root in roots {
+                self.fold_node(root, &prefix, &mut stacks);
+            }
+        }
+        stacks.retain(|_, value| *value > 0);
+        stacks
+    }
+
+    pub fn write(
+        &self,
+        trees: &std::collections::BTreeMap<u64, Vec<CallNode>>,
+        writer: &mut dyn std::io::Write,
+    ) -> std::io::Result<()> {
+        for (stack, value) in self.fold(trees) {
+            writeln!(writer, "{} {}", stack, value)?;
+        }
+        Ok(())
+    }
+
+    fn fold_node(
+        &self,
+        node: &CallNode,
+        prefix: &str,
+        stacks: &mut std::collections::BTreeMap<String, u64>,
+    ) {
+        let stack = format!("{}{}", prefix, escape_frame(&node.label));
+        let value = match self.value {
+            FoldedValue::SelfDuration => node.get_self_duration(),
+            FoldedValue::Count => 1,
+        };
+        *stacks.entry(stack.clone()).or_default() += value;
+
+        let prefix = stack + ";";
+        for child in &node.children {
+            self.fold_node(child, &prefix, stacks);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(label: &str, duration: u64, children: Vec<CallNode>) -> CallNode {
+        CallNode {
+            label: label.to_owned(),
+            start: 0,
+            duration,
+            children,
+        }
+    }
+
+    fn make_trees() -> std::collections::BTreeMap<u64, Vec<CallNode>> {
+        let mut trees = std::collections::BTreeMap::new();
+        trees.insert(
+            1,
+            vec![
+                node(
+                    "main",
+                    10,
+                    vec![node("a;b", 3, vec![]), node("draw", 7, vec![])],
+                ),
+                node("main", 4, vec![node("draw", 1, vec![])]),
+            ],
+        );
+        trees.insert(2, vec![node("worker", 5, vec![])]);
+        trees
+    }
+
+    #[test]
+    fn stacks_should_be_merged_with_self_durations() {
+        let mut output = Vec::new();
+        FoldedStacksWriter::new(FoldedValue::SelfDuration)
+            .write(&make_trees(), &mut output)
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "main 3\nmain;a_b 3\nmain;draw 8\nworker 5\n"
+        );
+    }
+
+    #[test]
+    fn thread_frames_should_prefix_stacks() {
+        let stacks = FoldedStacksWriter::new(FoldedValue::Count)
+            .with_thread_frames(true)
+            .fold(&make_trees());
+
+        assert_eq!(stacks["thread_1;main"], 2);
+        assert_eq!(stacks["thread_1;main;draw"], 2);
+        assert_eq!(stacks["thread_2;worker"], 1);
+        assert_eq!(stacks.len(), 4);
+    }
+}
@@ -0,0 +1,110 @@
+use crate::event::Event;
+
+// Counts events of each klass in fixed-size time buckets. Rows (klasses) and
+// columns (buckets) form a 2D histogram which can be rendered as a density
+// strip of a trace. Events without a timestamp are ignored.
+pub struct KlassHeatmap {
+    bucket_duration: u64,
+    counts: std::collections::HashMap<(u32, u64), u64, fnv::FnvBuildHasher>,
+    first_bucket: u64,
+    last_bucket: u64,
+}
+
+impl KlassHeatmap {
+    pub fn new(bucket_duration: u64) -> KlassHeatmap {
+        KlassHeatmap {
+            bucket_duration: bucket_duration.max(1),
+            counts: std::collections::HashMap::default(),
+            first_bucket: u64::MAX,
+            last_bucket: 0,
+        }
+    }
+
+    pub fn update(&mut self, event: &Event) {
+        let timestamp = match event.get_timestamp() {
+            Some(timestamp) => timestamp,
+            None => return,
+        };
+        let bucket = timestamp / self.bucket_duration;
+        *self
+            .counts
+            .entry((event.get_klass_id(), bucket))
+            .or_default() += 1;
+        self.first_bucket = self.first_bucket.min(bucket);
+        self.last_bucket = self.last_bucket.max(bucket);
+    }
+
+    pub fn get_bucket_duration(&self) -> u64 {
+        self.bucket_duration
+    }
+
+    // Start timestamp of the first bucket, and the number of buckets.
+    pub fn get_range(&self) -> Option<(u64, usize)> {
+        if self.counts.is_empty() {
+            return None;
+        }
+        Some((
+            self.first_bucket * self.bucket_duration,
+            (self.last_bucket - self.first_bucket + 1) as usize,
+        ))
+    }
+
+    pub fn get_klass_ids(&self) -> Vec<u32> {
+        let mut klass_ids: Vec<u32> = self.counts.keys().map(|(klass_id, _)| *klass_id).collect();
+        klass_ids.sort_unstable();
+        klass_ids.dedup();
+        klass_ids
+    }
+
+    // Counts of the klass in all the buckets of the range; empty if no
+    // event was counted.
+    pub fn get_row(&self, klass_id: u32) -> Vec<u64> {
+        let bucket_count = match self.get_range() {
+            Some((_, bucket_count)) => bucket_count,
+            None => return Vec::new(),
+        };
+        (0..bucket_count as u64)
+            .map(|offset| {
+                self.counts
+                    .get(&(klass_id, self.first_bucket + offset))
+                    .cloned()
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::Value;
+    use crate::test_events::make_plain_event;
+
+    fn make_event(klass_id: u32, timestamp: u64) -> Event {
+        make_plain_event(klass_id, vec![("timestamp", Value::U64(timestamp))])
+    }
+
+    #[test]
+    fn events_should_be_counted_per_klass_and_bucket() {
+        let mut heatmap = KlassHeatmap::new(10);
+        for (klass_id, timestamp) in &[(1, 25), (1, 27), (2, 31), (1, 58), (2, 20)] {
+            heatmap.update(&make_event(*klass_id, *timestamp));
+        }
+        heatmap.update(&Event::new(3, fnv::FnvHashMap::default()));
+
+        assert_eq!(heatmap.get_range(), Some((20, 4)));
+        assert_eq!(heatmap.get_klass_ids(), vec![1, 2]);
+        assert_eq!(heatmap.get_row(1), vec![2, 0, 0, 1]);
+        assert_eq!(heatmap.get_row(2), vec![1, 1, 0, 0]);
+        assert_eq!(heatmap.get_row(3), vec![0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn empty_heatmap_should_have_no_range() {
+        let heatmap = KlassHeatmap::new(0);
+
+        assert_eq!(heatmap.get_bucket_duration(), 1);
+        assert_eq!(heatmap.get_range(), None);
+        assert!(heatmap.get_row(1).is_empty());
+    }
+}
@@ -0,0 +1,9 @@
+pub mod callstack;
+pub mod chrome_trace;
+pub mod folded_stacks;
+pub mod heatmap;
+#[cfg(feature = "unstable")]
+pub mod otel;
+#[cfg(feature = "pprof")]
+pub mod pprof;
+pub mod sliding_window;
@@ -0,0 +1,219 @@
+use crate::analysis::callstack::nest_spans;
+use crate::event::{Event, Value};
+use crate::registry::EventKlassRegistry;
+use crate::spans::{find_string, find_u64};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeValue {
+    Int(i64),
+    String(String),
+}
+
+// Span structure following the OpenTelemetry data model (OTLP), so it can
+// be mapped directly to the span type of an exporter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OtelSpan {
+    pub trace_id: [u8; 16],
+    pub span_id: [u8; 8],
+    pub parent_span_id: Option<[u8; 8]>,
+    pub name: String,
+    pub start_time_unix_nano: u64,
+    pub end_time_unix_nano: u64,
+    pub attributes: Vec<(String, AttributeValue)>,
+}
+
+// Fields describing the span itself, not included in the attributes.
+const SPAN_FIELDS: [&str; 7] = [
+    "base",
+    "type",
+    "id",
+    "timestamp",
+    "duration",
+    "label",
+    "thread_id",
+];
+
+fn collect_attributes(event: &Event, attributes: &mut Vec<(String, AttributeValue)>) {
+    for (name, value) in event.get_all_values() {
+        let name: &str = name;
+        match value {
+            Value::Struct(base) if name == "base" => collect_attributes(base, attributes),
+            Value::Struct(_) => {}
+            Value::Str(v) if !SPAN_FIELDS.contains(&name) => {
+                attributes.push((name.to_owned(), AttributeValue::String(v.clone())))
+            }
+            value if !SPAN_FIELDS.contains(&name) => {
+                let value = match value.as_i64() {
+                    Some(v) => AttributeValue::Int(v),
+                    None => AttributeValue::String(value.to_string()),
+                };
+                attributes.push((name.to_owned(), value));
+            }
+            _ => {}
+        }
+    }
+}
+
+// Converts events with a duration (e.g. callstack events, or spans paired by
+// the SpanBuilder and passed as events) into OpenTelemetry spans of a single
+// trace. Parents are assigned per thread based on the time ranges, when all
+// the events were added. Timestamps are expected to be Unix time in
+// nanoseconds.
+pub struct OtelConverter {
+    trace_id: [u8; 16],
+    spans: Vec<(u64, OtelSpan)>,
+}
+
+impl OtelConverter {
+    pub fn new(trace_id: [u8; 16]) -> OtelConverter {
+        OtelConverter {
+            trace_id,
+            spans: Vec::new(),
+        }
+    }
+
+    // Returns false if the event doesn't describe a span.
+    pub fn update(&mut self, event: &Event, registry: &EventKlassRegistry) -> bool {
+        let (start, duration) = match (event.get_timestamp(), find_u64(event, "duration")) {
+            (Some(start), Some(duration)) => (start, duration),
+            _ => return false,
+        };
+        let name = match find_string(event, "label") {
+            Some(label) => label,
+            None => match registry.get_klass_by_id(event.get_klass_id()) {
+                Some(klass) => klass.get_name().clone(),
+                None => return false,
+            },
+        };
+        let thread_id = find_u64(event, "thread_id").unwrap_or_default();
+
+        let mut attributes = Vec::new();
+        collect_attributes(event, &mut attributes);
+        attributes.sort_by(|a, b| a.0.cmp(&b.0));
+        attributes.insert(
+            0,
+            (
+                "thread.id".to_owned(),
+                AttributeValue::Int(thread_id as i64),
+            ),
+        );
+
+        self.spans.push((
+            thread_id,
+            OtelSpan {
+                trace_id: self.trace_id,
+                span_id: [0; 8],
+                parent_span_id: None,
+                name,
+                start_time_unix_nano: start,
+                end_time_unix_nano: start.saturating_add(duration),
+                attributes,
+            },
+        ));
+        true
+    }
+
+    // Returns spans ordered by thread and start time. Span ids are assigned
+    // sequentially.
+    pub fn finish(self) -> Vec<OtelSpan> {
+        let mut threads: std::collections::BTreeMap<u64, Vec<OtelSpan>> =
+            std::collections::BTreeMap::new();
+        for (thread_id, span) in self.spans {
+            threads.entry(thread_id).or_default().push(span);
+        }
+
+        let mut spans: Vec<OtelSpan> = Vec::new();
+        for mut thread_spans in threads.into_values() {
+            let parents = nest_spans(&mut thread_spans, |span| {
+                (
+                    span.start_time_unix_nano,
+                    span.end_time_unix_nano - span.start_time_unix_nano,
+                )
+            });
+            let offset = spans.len();
+            for (mut span, parent) in thread_spans.into_iter().zip(parents) {
+                span.span_id = (spans.len() as u64 + 1).to_be_bytes();
+                span.parent_span_id = parent.map(|parent| spans[offset + parent].span_id);
+                spans.push(span);
+            }
+        }
+        spans
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event_klass::EventKlass;
+    use crate::test_events;
+
+    fn make_event(timestamp: u64, duration: u64, label: &str, thread_id: u32) -> Event {
+        test_events::make_event_with_id(
+            100,
+            timestamp,
+            1,
+            vec![
+                ("duration", Value::U64(duration)),
+                ("label", Value::Str(label.to_owned())),
+                ("thread_id", Value::U32(thread_id)),
+                ("frame", Value::U16(7)),
+                ("scene", Value::Str("menu".to_owned())),
+            ],
+        )
+    }
+
+    #[test]
+    fn events_should_be_converted_to_spans_with_attributes() {
+        let mut registry = EventKlassRegistry::new();
+        registry.add_klass(EventKlass::new(100, "callstack".to_owned()));
+        let mut converter = OtelConverter::new([7; 16]);
+
+        assert!(converter.update(&make_event(100, 50, "draw", 2), &registry));
+        assert!(!converter.update(&Event::new(100, fnv::FnvHashMap::default()), &registry));
+
+        assert_eq!(
+            converter.finish(),
+            vec![OtelSpan {
+                trace_id: [7; 16],
+                span_id: [0, 0, 0, 0, 0, 0, 0, 1],
+                parent_span_id: None,
+                name: "draw".to_owned(),
+                start_time_unix_nano: 100,
+                end_time_unix_nano: 150,
+                attributes: vec![
+                    ("thread.id".to_owned(), AttributeValue::Int(2)),
+                    ("frame".to_owned(), AttributeValue::Int(7)),
+                    (
+                        "scene".to_owned(),
+                        AttributeValue::String("menu".to_owned())
+                    ),
+                ],
+            }]
+        );
+    }
+
+    #[test]
+    fn nested_spans_should_have_parents() {
+        let registry = EventKlassRegistry::new();
+        let mut converter = OtelConverter::new([1; 16]);
+        converter.update(&make_event(12, 3, "child", 1), &registry);
+        converter.update(&make_event(10, 10, "parent", 1), &registry);
+        converter.update(&make_event(21, 3, "sibling", 1), &registry);
+        converter.update(&make_event(12, 3, "other_thread", 2), &registry);
+
+        let spans = converter.finish();
+        let relations: Vec<(&str, Option<[u8; 8]>)> = spans
+            .iter()
+            .map(|span| (span.name.as_str(), span.parent_span_id))
+            .collect();
+        assert_eq!(
+            relations,
+            vec![
+                ("parent", None),
+                ("child", Some(spans[0].span_id)),
+                ("sibling", None),
+                ("other_thread", None),
+            ]
+        );
+    }
+}
@@ -0,0 +1,255 @@
+use crate::analysis::callstack::CallNode;
+use std::io::Write;
+
+// Minimal encoder of the subset of the protobuf wire format used by
+// profile.proto (varints and length-delimited fields).
+#[derive(Default)]
+struct ProtoWriter {
+    buffer: Vec<u8>,
+}
+
+impl ProtoWriter {
+    fn write_varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.buffer.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+        self.buffer.push(value as u8);
+    }
+
+    fn write_uint64(&mut self, field: u32, value: u64) {
+        if value != 0 {
+            self.write_varint(u64::from(field) << 3);
+            self.write_varint(value);
+        }
+    }
+
+    fn write_bytes(&mut self, field: u32, value: &[u8]) {
+        self.write_varint((u64::from(field) << 3) | 2);
+        self.write_varint(value.len() as u64);
+        self.buffer.extend_from_slice(value);
+    }
+
+    fn write_packed(&mut self, field: u32, values: &[u64]) {
+        let mut packed = ProtoWriter::default();
+        for value in values {
+            packed.write_varint(*value);
+        }
+        self.write_bytes(field, &packed.buffer);
+    }
+
+    fn write_message<F: FnOnce(&mut ProtoWriter)>(&mut self, field: u32, write: F) {
+        let mut message = ProtoWriter::default();
+        write(&mut message);
+        self.write_bytes(field, &message.buffer);
+    }
+}
+
+#[derive(Default)]
+struct StringTable {
+    strings: Vec<String>,
+    indices: std::collections::HashMap<String, u64>,
+}
+
+impl StringTable {
+    fn new() -> StringTable {
+        let mut table = StringTable::default();
+        table.get_index("");
+        table
+    }
+
+    fn get_index(&mut self, value: &str) -> u64 {
+        if let Some(index) = self.indices.get(value) {
+            return *index;
+        }
+        let index = self.strings.len() as u64;
+        self.strings.push(value.to_owned());
+        self.indices.insert(value.to_owned(), index);
+        index
+    }
+}
+
+// Key: thread id and location ids (leaf first); value: count and duration.
+type Samples = std::collections::BTreeMap<(u64, Vec<u64>), (u64, u64)>;
+
+struct ProfileBuilder {
+    strings: StringTable,
+    // Function (and location) ids are indices of labels + 1.
+    functions: Vec<u64>,
+    function_ids: std::collections::HashMap<String, u64>,
+    samples: Samples,
+}
+
+impl ProfileBuilder {
+    fn get_function_id(&mut self, label: &str) -> u64 {
+        if let Some(id) = self.function_ids.get(label) {
+            return *id;
+        }
+        let name = self.strings.get_index(label);
+        self.functions.push(name);
+        let id = self.functions.len() as u64;
+        self.function_ids.insert(label.to_owned(), id);
+        id
+    }
+
+    fn add_node(&mut self, thread_id: u64, node: &CallNode, stack: &mut Vec<u64>) {
+        stack.push(self.get_function_id(&node.label));
+        let location_ids: Vec<u64> = stack.iter().rev().cloned().collect();
+        let sample = self.samples.entry((thread_id, location_ids)).or_default();
+        sample.0 += 1;
+        sample.1 += node.get_self_duration();
+        for child in &node.children {
+            self.add_node(thread_id, child, stack);
+        }
+        stack.pop();
+    }
+}
+
+// Encodes call trees as an uncompressed profile.proto message. Each scope is
+// a sample with two values: count and self duration (in nanoseconds, as
+// reported by HawkTracer); samples are labeled with the thread id.
+pub fn encode_profile(trees: &std::collections::BTreeMap<u64, Vec<CallNode>>) -> Vec<u8> {
+    let mut builder = ProfileBuilder {
+        strings: StringTable::new(),
+        functions: Vec::new(),
+        function_ids: std::collections::HashMap::new(),
+        samples: Samples::new(),
+    };
+    let mut start = u64::MAX;
+    let mut end = 0;
+    for (thread_id, roots) in trees {
+        for root in roots {
+            builder.add_node(*thread_id, root, &mut Vec::new());
+            start = start.min(root.start);
+            end = end.max(root.get_end());
+        }
+    }
+
+    let sample_types = [
+        (
+            builder.strings.get_index("samples"),
+            builder.strings.get_index("count"),
+        ),
+        (
+            builder.strings.get_index("wall"),
+            builder.strings.get_index("nanoseconds"),
+        ),
+    ];
+    let thread_key = builder.strings.get_index("thread_id");
+
+    let mut profile = ProtoWriter::default();
+    for (value_type, unit) in &sample_types {
+        profile.write_message(1, |message| {
+            message.write_uint64(1, *value_type);
+            message.write_uint64(2, *unit);
+        });
+    }
+    for ((thread_id, location_ids), (count, duration)) in &builder.samples {
+        profile.write_message(2, |message| {
+            message.write_packed(1, location_ids);
+            message.write_packed(2, &[*count, *duration]);
+            message.write_message(3, |label| {
+                label.write_uint64(1, thread_key);
+                label.write_uint64(3, *thread_id);
+            });
+        });
+    }
+    for id in 1..=builder.functions.len() as u64 {
+        profile.write_message(4, |location| {
+            location.write_uint64(1, id);
+            location.write_message(4, |line| line.write_uint64(1, id));
+        });
+    }
+    for (index, name) in builder.functions.iter().enumerate() {
+        profile.write_message(5, |function| {
+            function.write_uint64(1, index as u64 + 1);
+            function.write_uint64(2, *name);
+            function.write_uint64(3, *name);
+        });
+    }
+    for string in &builder.strings.strings {
+        profile.write_bytes(6, string.as_bytes());
+    }
+    if start <= end {
+        profile.write_uint64(9, start);
+        profile.write_uint64(10, end - start);
+    }
+    profile.buffer
+}
+
+// Writes call trees as a gzip-compressed profile, which can be opened with
+// `go tool pprof` and compatible viewers.
+pub fn write_profile(
+    trees: &std::collections::BTreeMap<u64, Vec<CallNode>>,
+    writer: &mut dyn std::io::Write,
+) -> std::io::Result<()> {
+    let mut encoder = flate2::write::GzEncoder::new(writer, flate2::Compression::default());
+    encoder.write_all(&encode_profile(trees))?;
+    encoder.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn node(label: &str, start: u64, duration: u64, children: Vec<CallNode>) -> CallNode {
+        CallNode {
+            label: label.to_owned(),
+            start,
+            duration,
+            children,
+        }
+    }
+
+    fn make_trees() -> std::collections::BTreeMap<u64, Vec<CallNode>> {
+        let mut trees = std::collections::BTreeMap::new();
+        trees.insert(
+            3,
+            vec![node("main", 10, 5, vec![node("foo", 11, 2, vec![])])],
+        );
+        trees
+    }
+
+    #[test]
+    fn varints_should_be_encoded_in_groups_of_seven_bits() {
+        let mut writer = ProtoWriter::default();
+        writer.write_varint(1);
+        writer.write_varint(300);
+        assert_eq!(writer.buffer, vec![1, 0xac, 0x02]);
+    }
+
+    #[test]
+    fn profile_should_contain_samples_with_durations() {
+        let profile = encode_profile(&make_trees());
+
+        let mut expected = ProtoWriter::default();
+        // Sample of "foo": location ids (foo, main), count 1, duration 2.
+        expected.write_message(2, |message| {
+            message.write_packed(1, &[2, 1]);
+            message.write_packed(2, &[1, 2]);
+            message.write_message(3, |label| {
+                label.write_uint64(1, 7);
+                label.write_uint64(3, 3);
+            });
+        });
+        let contains = |data: &[u8]| profile.windows(data.len()).any(|window| window == data);
+        assert!(contains(&expected.buffer));
+        assert!(contains(b"\x32\x04main\x32\x03foo"));
+        // time_nanos and duration_nanos.
+        assert!(profile.ends_with(&[0x48, 10, 0x50, 5]));
+    }
+
+    #[test]
+    fn written_profile_should_be_compressed() {
+        let mut output = Vec::new();
+        write_profile(&make_trees(), &mut output).unwrap();
+
+        let mut decoded = Vec::new();
+        flate2::read::GzDecoder::new(output.as_slice())
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, encode_profile(&make_trees()));
+    }
+}
@@ -0,0 +1,298 @@
+use crate::event::Event;
+use crate::spans::{find_string, find_u64};
+
+const NANOSECONDS_PER_SECOND: f64 = 1_000_000_000.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LabelStats {
+    pub label: String,
+    pub count: usize,
+    pub p50: u64,
+    pub p90: u64,
+    pub p99: u64,
+    pub max: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowStats {
+    pub start: u64,
+    pub end: u64,
+    pub event_count: usize,
+    pub events_per_second: f64,
+    // Sorted by label.
+    pub labels: Vec<LabelStats>,
+}
+
+struct WindowEvent {
+    timestamp: u64,
+    label_duration: Option<(String, u64)>,
+}
+
+pub type WindowCallback = Box<dyn FnMut(&WindowStats)>;
+
+// Rolling statistics of a live stream over the last window_duration
+// nanoseconds of event timestamps. Every report_interval the statistics are
+// computed and passed to the callback. Events are expected to arrive
+// (roughly) in timestamp order; late events older than the window are
+// dropped.
+pub struct SlidingWindow {
+    window_duration: u64,
+    report_interval: u64,
+    callback: WindowCallback,
+    events: std::collections::VecDeque<WindowEvent>,
+    next_report: Option<u64>,
+    // Set when the time of the next report doesn't fit in u64; no more
+    // windows are reported then.
+    reports_exhausted: bool,
+}
+
+fn percentile(sorted_values: &[u64], percentile: usize) -> u64 {
+    let rank = (sorted_values.len() * percentile).div_ceil(100).max(1);
+    sorted_values[rank - 1]
+}
+
+impl SlidingWindow {
+    pub fn new(
+        window_duration: u64,
+        report_interval: u64,
+        callback: WindowCallback,
+    ) -> SlidingWindow {
+        SlidingWindow {
+            window_duration: window_duration.max(1),
+            report_interval: report_interval.max(1),
+            callback,
+            events: std::collections::VecDeque::new(),
+            next_report: None,
+            reports_exhausted: false,
+        }
+    }
+
+    pub fn update(&mut self, event: &Event) {
+        let timestamp = match event.get_timestamp() {
+            Some(timestamp) => timestamp,
+            None => return,
+        };
+
+        if !self.reports_exhausted {
+            let next_report = *self
+                .next_report
+                .get_or_insert(timestamp.saturating_add(self.report_interval));
+            if timestamp >= next_report {
+                self.report(timestamp);
+            }
+        }
+        if timestamp < self.get_window_start(timestamp.max(self.get_latest_timestamp())) {
+            return;
+        }
+
+        let label_duration = match (find_string(event, "label"), find_u64(event, "duration")) {
+            (Some(label), Some(duration)) => Some((label, duration)),
+            _ => None,
+        };
+        self.events.push_back(WindowEvent {
+            timestamp,
+            label_duration,
+        });
+    }
+
+    // Statistics of the window ending at the given timestamp.
+    pub fn get_stats(&self, end: u64) -> WindowStats {
+        let start = self.get_window_start(end);
+        let mut durations: std::collections::BTreeMap<&str, Vec<u64>> =
+            std::collections::BTreeMap::new();
+        let mut event_count = 0;
+        for event in &self.events {
+            if event.timestamp < start || event.timestamp >= end {
+                continue;
+            }
+            event_count += 1;
+            if let Some((label, duration)) = &event.label_duration {
+                durations.entry(label).or_default().push(*duration);
+            }
+        }
+
+        let labels = durations
+            .into_iter()
+            .map(|(label, mut values)| {
+                values.sort_unstable();
+                LabelStats {
+                    label: label.to_owned(),
+                    count: values.len(),
+                    p50: percentile(&values, 50),
+                    p90: percentile(&values, 90),
+                    p99: percentile(&values, 99),
+                    max: values[values.len() - 1],
+                }
+            })
+            .collect();
+
+        WindowStats {
+            start,
+            end,
+            event_count,
+            events_per_second: event_count as f64 * NANOSECONDS_PER_SECOND
+                / (end - start).max(1) as f64,
+            labels,
+        }
+    }
+
+    fn get_window_start(&self, end: u64) -> u64 {
+        end.saturating_sub(self.window_duration)
+    }
+
+    fn get_latest_timestamp(&self) -> u64 {
+        self.events.back().map_or(0, |event| event.timestamp)
+    }
+
+    // Reports all the windows which closed before the timestamp. A gap
+    // without any event is reported as a single empty window, and the next
+    // report is moved to the end of the interval containing the timestamp.
+    fn report(&mut self, timestamp: u64) {
+        while let Some(next_report) = self.next_report.filter(|next| *next <= timestamp) {
+            let window_start = self.get_window_start(next_report);
+            while self
+                .events
+                .front()
+                .is_some_and(|event| event.timestamp < window_start)
+            {
+                self.events.pop_front();
+            }
+            let stats = self.get_stats(next_report);
+            (self.callback)(&stats);
+
+            // All the buffered events are older than the reported window, so
+            // the following windows until the timestamp are empty too.
+            let skipped_intervals = if stats.event_count == 0 {
+                (timestamp - next_report) / self.report_interval
+            } else {
+                0
+            };
+            self.next_report = skipped_intervals
+                .checked_add(1)
+                .and_then(|intervals| intervals.checked_mul(self.report_interval))
+                .and_then(|offset| next_report.checked_add(offset));
+            if self.next_report.is_none() {
+                self.reports_exhausted = true;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::Value;
+    use crate::test_events::make_plain_event;
+
+    fn make_event(timestamp: u64, label: &str, duration: u64) -> Event {
+        make_plain_event(
+            100,
+            vec![
+                ("timestamp", Value::U64(timestamp)),
+                ("label", Value::Str(label.to_owned())),
+                ("duration", Value::U64(duration)),
+            ],
+        )
+    }
+
+    fn make_window(
+        window_duration: u64,
+        report_interval: u64,
+    ) -> (
+        SlidingWindow,
+        std::rc::Rc<std::cell::RefCell<Vec<WindowStats>>>,
+    ) {
+        let reports = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let reports_clone = reports.clone();
+        let window = SlidingWindow::new(
+            window_duration,
+            report_interval,
+            Box::new(move |stats| reports_clone.borrow_mut().push(stats.clone())),
+        );
+        (window, reports)
+    }
+
+    #[test]
+    fn stats_should_be_computed_per_label() {
+        let (mut window, reports) = make_window(1_000_000_000, 1_000_000_000);
+        for i in 0..10 {
+            window.update(&make_event(100 + i * 10, "a", (i + 1) * 100));
+        }
+        window.update(&make_event(500, "b", 7));
+
+        let stats = window.get_stats(1_000);
+        assert!(reports.borrow().is_empty());
+        assert_eq!(stats.event_count, 11);
+        assert_eq!(
+            stats.labels,
+            vec![
+                LabelStats {
+                    label: "a".to_owned(),
+                    count: 10,
+                    p50: 500,
+                    p90: 900,
+                    p99: 1000,
+                    max: 1000,
+                },
+                LabelStats {
+                    label: "b".to_owned(),
+                    count: 1,
+                    p50: 7,
+                    p90: 7,
+                    p99: 7,
+                    max: 7,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn callback_should_be_invoked_on_window_close() {
+        let (mut window, reports) = make_window(200, 100);
+        window.update(&make_event(0, "a", 1));
+        window.update(&make_event(50, "a", 1));
+        window.update(&make_event(150, "a", 1));
+        assert_eq!(reports.borrow().len(), 1);
+        window.update(&make_event(420, "a", 1));
+        window.update(&make_event(10, "a", 1));
+
+        let reports = reports.borrow();
+        let windows: Vec<(u64, u64, usize)> = reports
+            .iter()
+            .map(|stats| (stats.start, stats.end, stats.event_count))
+            .collect();
+        assert_eq!(
+            windows,
+            vec![(0, 100, 2), (0, 200, 3), (100, 300, 1), (200, 400, 0)]
+        );
+        assert_eq!(reports[1].events_per_second, 15_000_000.0);
+    }
+
+    #[test]
+    fn gap_should_be_reported_as_single_empty_window() {
+        let (mut window, reports) = make_window(200, 100);
+        window.update(&make_event(50, "a", 1));
+        window.update(&make_event(1_000_000_050, "a", 1));
+        window.update(&make_event(1_000_000_120, "a", 1));
+        window.update(&make_event(1_000_000_150, "a", 1));
+
+        let windows: Vec<(u64, usize)> = reports
+            .borrow()
+            .iter()
+            .map(|stats| (stats.end, stats.event_count))
+            .collect();
+        assert_eq!(
+            windows,
+            vec![(150, 1), (250, 1), (350, 0), (1_000_000_150, 2)]
+        );
+    }
+
+    #[test]
+    fn reports_should_stop_at_maximum_timestamp() {
+        let (mut window, reports) = make_window(200, 100);
+        window.update(&make_event(50, "a", 1));
+        window.update(&make_event(u64::MAX, "a", 1));
+        window.update(&make_event(u64::MAX, "a", 1));
+        assert_eq!(reports.borrow().len(), 3);
+    }
+}
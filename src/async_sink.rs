@@ -0,0 +1,226 @@
+use crate::event::Event;
+use crate::pipeline::{SharedBuffer, WriterSinkConstructor};
+use crate::registry::EventKlassRegistry;
+use crate::sink::EventSink;
+use tokio::io::AsyncWriteExt;
+
+// Futures aren't Send, since the synchronous sinks wrapped by the adapter
+// hold a Box<dyn Write>; they can be run e.g. on a current-thread runtime.
+pub type SinkFuture<'a> =
+    std::pin::Pin<Box<dyn std::future::Future<Output = std::io::Result<()>> + 'a>>;
+
+// Asynchronous counterpart of EventSink. Implementations have to be
+// cancellation-safe: if a future is dropped before it completes, no partial
+// record may be lost, and the next flush() has to write the rest of it.
+pub trait AsyncTraceSink {
+    fn write_event<'a>(
+        &'a mut self,
+        event: &'a Event,
+        registry: &'a EventKlassRegistry,
+    ) -> SinkFuture<'a>;
+
+    fn flush(&mut self) -> SinkFuture<'_>;
+}
+
+// Runs a synchronous sink (e.g. CsvWriter or JsonLinesWriter) on top of an
+// asynchronous writer. Events are encoded into a buffer, so only whole
+// records are queued; bytes are removed from the queue only after the writer
+// accepted them, so an aborted write or flush doesn't tear a record.
+pub struct AsyncSinkAdapter<W> {
+    sink: Box<dyn EventSink>,
+    buffer: SharedBuffer,
+    pending: Vec<u8>,
+    // Bytes of the pending data already accepted by the writer.
+    written: usize,
+    max_pending: usize,
+    writer: W,
+}
+
+impl<W: tokio::io::AsyncWrite + Unpin> AsyncSinkAdapter<W> {
+    pub fn new(writer: W, constructor: WriterSinkConstructor) -> AsyncSinkAdapter<W> {
+        let buffer = SharedBuffer::default();
+        AsyncSinkAdapter {
+            sink: constructor(Box::new(buffer.clone())),
+            buffer,
+            pending: Vec::new(),
+            written: 0,
+            max_pending: 64 * 1024,
+            writer,
+        }
+    }
+
+    // Pending data is written by write_event() when it exceeds the limit.
+    pub fn with_max_pending(mut self, max_pending: usize) -> AsyncSinkAdapter<W> {
+        self.max_pending = max_pending;
+        self
+    }
+
+    pub fn get_pending_size(&self) -> usize {
+        self.pending.len() - self.written
+    }
+
+    pub fn get_writer(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn into_writer(self) -> W {
+        self.writer
+    }
+
+    fn take_encoded(&mut self) {
+        self.pending.append(&mut self.buffer.0.borrow_mut());
+    }
+
+    async fn write_pending(&mut self) -> std::io::Result<()> {
+        while self.written < self.pending.len() {
+            let count = self.writer.write(&self.pending[self.written..]).await?;
+            if count == 0 {
+                return Err(std::io::ErrorKind::WriteZero.into());
+            }
+            self.written += count;
+        }
+        self.pending.clear();
+        self.written = 0;
+        Ok(())
+    }
+}
+
+impl<W: tokio::io::AsyncWrite + Unpin> AsyncTraceSink for AsyncSinkAdapter<W> {
+    fn write_event<'a>(
+        &'a mut self,
+        event: &'a Event,
+        registry: &'a EventKlassRegistry,
+    ) -> SinkFuture<'a> {
+        Box::pin(async move {
+            self.sink.write_event(event, registry)?;
+            self.take_encoded();
+            if self.get_pending_size() > self.max_pending {
+                self.write_pending().await?;
+            }
+            Ok(())
+        })
+    }
+
+    fn flush(&mut self) -> SinkFuture<'_> {
+        Box::pin(async move {
+            self.sink.flush()?;
+            self.take_encoded();
+            self.write_pending().await?;
+            self.writer.flush().await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::JsonLinesWriter;
+    use crate::test_events::make_event;
+    use crate::Value;
+
+    // Accepts at most 4 bytes per write, and none while it's blocked.
+    #[derive(Default)]
+    struct SlowWriter {
+        data: Vec<u8>,
+        blocked: bool,
+    }
+
+    impl tokio::io::AsyncWrite for SlowWriter {
+        fn poll_write(
+            mut self: std::pin::Pin<&mut Self>,
+            _: &mut std::task::Context<'_>,
+            buf: &[u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            if self.blocked {
+                return std::task::Poll::Pending;
+            }
+            self.blocked = true;
+            let count = buf.len().min(4);
+            self.data.extend_from_slice(&buf[..count]);
+            std::task::Poll::Ready(Ok(count))
+        }
+
+        fn poll_flush(
+            self: std::pin::Pin<&mut Self>,
+            _: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(
+            self: std::pin::Pin<&mut Self>,
+            _: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+    }
+
+    fn noop_waker() -> std::task::Waker {
+        fn clone(_: *const ()) -> std::task::RawWaker {
+            std::task::RawWaker::new(std::ptr::null(), &VTABLE)
+        }
+        fn noop(_: *const ()) {}
+        static VTABLE: std::task::RawWakerVTable =
+            std::task::RawWakerVTable::new(clone, noop, noop, noop);
+        unsafe { std::task::Waker::from_raw(clone(std::ptr::null())) }
+    }
+
+    fn poll(future: &mut SinkFuture) -> std::task::Poll<std::io::Result<()>> {
+        let waker = noop_waker();
+        future
+            .as_mut()
+            .poll(&mut std::task::Context::from_waker(&waker))
+    }
+
+    fn make_adapter() -> AsyncSinkAdapter<SlowWriter> {
+        AsyncSinkAdapter::new(
+            SlowWriter::default(),
+            Box::new(|writer| Box::new(JsonLinesWriter::new(writer))),
+        )
+    }
+
+    // Flushes the adapter, unblocking the writer whenever it's blocked.
+    fn flush(adapter: &mut AsyncSinkAdapter<SlowWriter>) {
+        loop {
+            if let std::task::Poll::Ready(result) = poll(&mut adapter.flush()) {
+                return result.unwrap();
+            }
+            adapter.writer.blocked = false;
+        }
+    }
+
+    #[test]
+    fn aborted_flush_should_not_tear_records() {
+        let registry = EventKlassRegistry::new();
+        let event = make_event(100, 5, vec![("value", Value::U32(7))]);
+
+        let buffer = SharedBuffer::default();
+        let mut expected = JsonLinesWriter::new(Box::new(buffer.clone()));
+        expected.write_event(&event, &registry).unwrap();
+        EventSink::flush(&mut expected).unwrap();
+        let expected_data = buffer.0.take();
+
+        let mut adapter = make_adapter();
+        assert!(poll(&mut adapter.write_event(&event, &registry)).is_ready());
+        assert!(adapter.get_writer().data.is_empty());
+
+        // The flush is aborted after the first chunk is written.
+        assert!(poll(&mut adapter.flush()).is_pending());
+        assert_eq!(adapter.get_writer().data.len(), 4);
+        assert_eq!(adapter.get_pending_size(), expected_data.len() - 4);
+
+        flush(&mut adapter);
+        assert_eq!(adapter.get_pending_size(), 0);
+        assert_eq!(adapter.into_writer().data, expected_data);
+    }
+
+    #[test]
+    fn pending_data_should_be_written_when_limit_is_exceeded() {
+        let registry = EventKlassRegistry::new();
+        let mut adapter = make_adapter().with_max_pending(0);
+
+        let event = make_event(100, 5, vec![]);
+        assert!(poll(&mut adapter.write_event(&event, &registry)).is_pending());
+        assert_eq!(adapter.get_writer().data.len(), 4);
+    }
+}
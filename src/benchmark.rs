@@ -0,0 +1,88 @@
+use crate::data_provider::{DataError, DataProvider};
+use crate::data_struct_reader::ReadEventError;
+use crate::event_reader::EventReader;
+use crate::registry::EventKlassRegistry;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchmarkReport {
+    pub bytes: u64,
+    pub events: u64,
+    pub duration: std::time::Duration,
+}
+
+impl BenchmarkReport {
+    pub fn get_megabytes_per_second(&self) -> f64 {
+        self.bytes as f64 / (1024.0 * 1024.0) / self.get_seconds()
+    }
+
+    pub fn get_events_per_second(&self) -> f64 {
+        self.events as f64 / self.get_seconds()
+    }
+
+    fn get_seconds(&self) -> f64 {
+        self.duration.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+// Parses the whole stream through the cheapest path available (payloads are
+// skipped, only headers and metadata events are decoded), so the result is
+// an upper bound of the parser throughput.
+pub fn run_benchmark(reader: Box<dyn std::io::Read>) -> Result<BenchmarkReport, ReadEventError> {
+    let mut event_reader = EventReader::new(DataProvider::new(reader));
+    let mut registry = EventKlassRegistry::new();
+    let mut events = 0;
+    let start = std::time::Instant::now();
+
+    loop {
+        match event_reader.skip_event(&mut registry) {
+            Ok(_) => events += 1,
+            Err(ReadEventError::DataError(DataError::EndOfStream)) => break,
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(BenchmarkReport {
+        bytes: event_reader.get_position(),
+        events,
+        duration: start.elapsed(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hawktracer_parser_test_utilities::FakeDataReader;
+
+    #[test]
+    fn benchmark_should_count_events_and_bytes() {
+        let mut data = vec![
+            2, 0, 0, 0, // type
+            1, 0, 0, 0, 0, 0, 0, 0, // timestamp
+            1, 0, 0, 0, 0, 0, 0, 0, // id
+            100, 0, 0, 0, // info_klass_id
+            102, 111, 111, 0, // foo
+            0, // field_count
+        ];
+        for id in 2..5 {
+            data.extend_from_slice(&[100, 0, 0, 0, id, 0, 0, 0, 0, 0, 0, 0]);
+            data.extend_from_slice(&[id, 0, 0, 0, 0, 0, 0, 0]);
+        }
+
+        let report = run_benchmark(Box::new(FakeDataReader::new(data, false))).unwrap();
+
+        assert_eq!(report.events, 4);
+        assert_eq!(report.bytes, 89);
+        assert!(report.get_megabytes_per_second() > 0.0);
+        assert!(report.get_events_per_second() > 0.0);
+    }
+
+    #[test]
+    fn benchmark_should_fail_for_unknown_klass() {
+        let data = vec![100, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0];
+
+        assert_eq!(
+            run_benchmark(Box::new(FakeDataReader::new(data, false))).unwrap_err(),
+            ReadEventError::UnknownKlassId(100)
+        );
+    }
+}
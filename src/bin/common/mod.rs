@@ -0,0 +1,58 @@
+// Helpers shared by the command line tools.
+
+// Other exit codes are given by RunStatus::exit_code().
+pub const USAGE_EXIT_CODE: i32 = 2;
+
+// Sources are trace files, or HawkTracer TCP listeners given as
+// "tcp://HOST:PORT".
+pub fn open_source(source: &str) -> std::io::Result<Box<dyn std::io::Read>> {
+    match source.strip_prefix("tcp://") {
+        Some(address) => Ok(Box::new(std::net::TcpStream::connect(address)?)),
+        None => Ok(Box::new(std::fs::File::open(source)?)),
+    }
+}
+
+// Prints warnings about the errors recovered from, and returns the status
+// of the run.
+pub fn get_run_status(report: &hawktracer_parser::RunReport) -> hawktracer_parser::RunStatus {
+    if report.truncated_bytes > 0 {
+        eprintln!(
+            "Warning: the stream ends with an incomplete event ({} bytes)",
+            report.truncated_bytes
+        );
+    }
+    if report.ignored_error_count > 0 {
+        eprintln!(
+            "Warning: {} errors were ignored",
+            report.ignored_error_count
+        );
+    }
+    report.get_status()
+}
+
+const PROGRESS_INTERVAL: u64 = 10_000;
+
+// Prints the progress on the standard error output, as a percentage if the
+// size of the source is known.
+pub fn make_progress_reporter(source: &str) -> hawktracer_parser::ProgressReporter {
+    let reporter = hawktracer_parser::ProgressReporter::new(PROGRESS_INTERVAL, |progress| {
+        match progress.get_fraction() {
+            Some(fraction) => eprint!(
+                "\r{:.1}% ({} events)",
+                fraction * 100.0,
+                progress.event_count
+            ),
+            None => eprint!(
+                "\r{} bytes ({} events)",
+                progress.bytes_consumed, progress.event_count
+            ),
+        }
+    });
+    if source.starts_with("tcp://") {
+        return reporter;
+    }
+    match std::fs::metadata(source) {
+        Ok(metadata) => reporter.with_total_bytes(metadata.len()),
+        Err(_) => reporter,
+    }
+}
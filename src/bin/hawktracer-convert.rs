@@ -0,0 +1,329 @@
+mod common;
+
+use common::{get_run_status, make_progress_reporter, open_source, USAGE_EXIT_CODE};
+use hawktracer_parser::analysis::callstack::CallstackBuilder;
+use hawktracer_parser::analysis::chrome_trace::write_chrome_trace;
+use hawktracer_parser::analysis::folded_stacks::{FoldedStacksWriter, FoldedValue};
+use hawktracer_parser::csv_writer::CsvColumns;
+use hawktracer_parser::{
+    CoreEventKlassId, CsvWriter, Event, EventKlassRegistry, EventReaderBuilder, EventSink,
+    JsonLinesWriter, LabelResolver, Pipeline, RunStatus,
+};
+
+const USAGE: &str =
+    "Usage: hawktracer-convert --from <FILE|tcp://HOST:PORT> --to <FORMAT> [--output <FILE>]
+       hawktracer-convert --config <FILE>
+
+Formats:
+    chrome-trace    Callstacks in the Trace Event format (chrome://tracing, Perfetto)
+    csv             Events as CSV rows, one column per field
+    folded          Callstacks as folded stacks (flamegraph.pl, inferno)
+    json            Events as JSON Lines
+    pprof           Callstacks as a pprof profile (requires the pprof feature)
+
+Options:
+    --config <FILE>  Run the pipeline described by a TOML file, with sinks of
+                     the built-in types and of the formats above, e.g.
+                     { type = \"folded\", options = { path = \"a.txt\" } }
+                     (requires the config feature)
+    --output <FILE>  Output file (default: standard output)
+    --progress       Print the progress on the standard error output
+    -h, --help       Print this message
+
+Parquet output is not supported: a Parquet file needs its schema up front,
+while klasses of a trace are only known once their metadata events are read.
+Use csv, or the sqlite writer of the library, to load events into tables.";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    ChromeTrace,
+    Csv,
+    Folded,
+    Json,
+    #[cfg(feature = "pprof")]
+    Pprof,
+}
+
+impl Format {
+    fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "chrome-trace" => Some(Format::ChromeTrace),
+            "csv" => Some(Format::Csv),
+            "folded" => Some(Format::Folded),
+            "json" => Some(Format::Json),
+            #[cfg(feature = "pprof")]
+            "pprof" => Some(Format::Pprof),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Convert(Options),
+    #[cfg(feature = "config")]
+    RunConfig(String),
+}
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    from: String,
+    to: Format,
+    output: Option<String>,
+    progress: bool,
+}
+
+impl Command {
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Command, String> {
+        #[cfg(feature = "config")]
+        let mut config = None;
+        let mut from = None;
+        let mut to = None;
+        let mut output = None;
+        let mut progress = false;
+
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .ok_or_else(|| format!("Missing value of {}", name))
+            };
+            match arg.as_str() {
+                #[cfg(feature = "config")]
+                "--config" => config = Some(value("--config")?),
+                "--from" => from = Some(value("--from")?),
+                "--to" => {
+                    let name = value("--to")?;
+                    to = match Format::from_name(&name) {
+                        Some(format) => Some(format),
+                        None => return Err(format!("Unsupported format {}", name)),
+                    }
+                }
+                "--output" => output = Some(value("--output")?),
+                "--progress" => progress = true,
+                "-h" | "--help" => return Err(String::new()),
+                arg => return Err(format!("Unexpected argument {}", arg)),
+            }
+        }
+
+        #[cfg(feature = "config")]
+        if let Some(config) = config {
+            if from.is_some() || to.is_some() || output.is_some() || progress {
+                return Err("--config can't be combined with other options".to_owned());
+            }
+            return Ok(Command::RunConfig(config));
+        }
+
+        match (from, to) {
+            (Some(from), Some(to)) => Ok(Command::Convert(Options {
+                from,
+                to,
+                output,
+                progress,
+            })),
+            (None, _) => Err("Missing --from option".to_owned()),
+            (_, None) => Err("Missing --to option".to_owned()),
+        }
+    }
+}
+
+// Passes all the events except metadata events to the sink.
+struct SkipMetadata(Box<dyn EventSink>);
+
+impl EventSink for SkipMetadata {
+    fn write_event(&mut self, event: &Event, registry: &EventKlassRegistry) -> std::io::Result<()> {
+        if CoreEventKlassId::is_metadata_klass(event.get_klass_id()) {
+            return Ok(());
+        }
+        self.0.write_event(event, registry)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+}
+
+// Collects the callstacks of all the events; the output is written when
+// the sink is flushed at the end of the stream.
+struct CallstackSink {
+    format: Format,
+    output: Box<dyn std::io::Write>,
+    label_resolver: LabelResolver,
+    callstack_builder: CallstackBuilder,
+}
+
+impl CallstackSink {
+    fn new(format: Format, output: Box<dyn std::io::Write>) -> CallstackSink {
+        CallstackSink {
+            format,
+            output,
+            label_resolver: LabelResolver::new().with_rewrite_fields(&["label"]),
+            callstack_builder: CallstackBuilder::new(),
+        }
+    }
+}
+
+impl EventSink for CallstackSink {
+    fn write_event(&mut self, event: &Event, registry: &EventKlassRegistry) -> std::io::Result<()> {
+        let mut event = event.clone();
+        self.label_resolver.process(&mut event, registry);
+        self.callstack_builder.update(&event, registry);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        let trees = std::mem::take(&mut self.callstack_builder).build();
+        let output = &mut self.output;
+        match self.format {
+            Format::ChromeTrace => write_chrome_trace(&trees, output),
+            #[cfg(feature = "pprof")]
+            Format::Pprof => hawktracer_parser::analysis::pprof::write_profile(&trees, output),
+            Format::Folded => FoldedStacksWriter::new(FoldedValue::SelfDuration)
+                .with_thread_frames(true)
+                .write(&trees, output),
+            Format::Csv | Format::Json => {
+                unreachable!("{:?} is not a callstack format", self.format)
+            }
+        }?;
+        output.flush()
+    }
+}
+
+fn create_output(path: Option<&str>) -> Result<Box<dyn std::io::Write>, String> {
+    match path {
+        Some(path) => match std::fs::File::create(path) {
+            Ok(file) => Ok(Box::new(std::io::BufWriter::new(file))),
+            Err(err) => Err(format!("Cannot create {}: {}", path, err)),
+        },
+        None => Ok(Box::new(std::io::BufWriter::new(std::io::stdout()))),
+    }
+}
+
+fn convert(options: &Options) -> Result<RunStatus, String> {
+    let source = match open_source(&options.from) {
+        Ok(source) => source,
+        Err(err) => return Err(format!("Cannot open {}: {}", options.from, err)),
+    };
+    let output = create_output(options.output.as_deref())?;
+
+    let sink: Box<dyn EventSink> = match options.to {
+        Format::Csv => Box::new(CsvWriter::new(output, CsvColumns::Union)),
+        Format::Json => Box::new(JsonLinesWriter::new(output)),
+        format => Box::new(CallstackSink::new(format, output)),
+    };
+    let mut builder = EventReaderBuilder::new();
+    if options.progress {
+        builder = builder.progress_reporter(make_progress_reporter(&options.from));
+    }
+    let mut pipeline = Pipeline::new(builder.build(source), Box::new(SkipMetadata(sink)));
+    let result = pipeline.run();
+    if options.progress {
+        eprintln!();
+    }
+    let report = result.map_err(|err| err.to_string())?;
+    Ok(get_run_status(&report))
+}
+
+// Callstack formats are registered as sinks on top of the built-in ones.
+#[cfg(feature = "config")]
+fn run_config(path: &str) -> Result<RunStatus, String> {
+    use hawktracer_parser::pipeline::SinkFactory;
+
+    let config =
+        std::fs::read_to_string(path).map_err(|err| format!("Cannot read {}: {}", path, err))?;
+    let mut factory = SinkFactory::new();
+    for name in &["chrome-trace", "folded", "pprof"] {
+        let format = match Format::from_name(name) {
+            Some(format) => format,
+            None => continue,
+        };
+        factory = factory.register(
+            name,
+            Box::new(move |options| {
+                let path = match options.get("path") {
+                    Some(path) => Some(path.as_str().ok_or("'path' must be a string")?),
+                    None => None,
+                };
+                let sink = CallstackSink::new(format, create_output(path)?);
+                Ok(Box::new(SkipMetadata(Box::new(sink))))
+            }),
+        );
+    }
+
+    let mut pipeline = Pipeline::from_config(&config, &factory).map_err(|err| err.to_string())?;
+    let report = pipeline.run().map_err(|err| err.to_string())?;
+    Ok(get_run_status(&report))
+}
+
+fn main() {
+    let command = match Command::parse(std::env::args().skip(1)) {
+        Ok(command) => command,
+        Err(err) => {
+            if !err.is_empty() {
+                eprintln!("{}\n", err);
+            }
+            eprintln!("{}", USAGE);
+            std::process::exit(USAGE_EXIT_CODE);
+        }
+    };
+
+    let result = match command {
+        Command::Convert(options) => convert(&options),
+        #[cfg(feature = "config")]
+        Command::RunConfig(path) => run_config(&path),
+    };
+    match result {
+        Ok(status) => std::process::exit(status.exit_code()),
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(RunStatus::Failure.exit_code());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Command, String> {
+        Command::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn parse_options(args: &[&str]) -> Options {
+        match parse(args) {
+            Ok(Command::Convert(options)) => options,
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn options_should_be_parsed() {
+        assert_eq!(
+            parse(&["--to", "chrome-trace", "--from", "tcp://localhost:8765"]),
+            Ok(Command::Convert(Options {
+                from: "tcp://localhost:8765".to_owned(),
+                to: Format::ChromeTrace,
+                output: None,
+                progress: false,
+            }))
+        );
+        assert_eq!(
+            parse_options(&["--from", "a.htdump", "--to", "csv", "--output", "a.csv"]).output,
+            Some("a.csv".to_owned())
+        );
+        assert!(parse_options(&["--from", "a.htdump", "--to", "json", "--progress"]).progress);
+        assert!(parse(&["--from", "a.htdump", "--to", "xml"]).is_err());
+        assert!(parse(&["--from", "a.htdump"]).is_err());
+        assert!(parse(&["--to", "csv"]).is_err());
+        assert!(parse(&["a.htdump"]).is_err());
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn config_option_should_be_parsed() {
+        assert_eq!(
+            parse(&["--config", "pipeline.toml"]),
+            Ok(Command::RunConfig("pipeline.toml".to_owned()))
+        );
+        assert!(parse(&["--config", "pipeline.toml", "--to", "csv"]).is_err());
+    }
+}
@@ -0,0 +1,336 @@
+mod common;
+
+use common::{get_run_status, make_progress_reporter, open_source, USAGE_EXIT_CODE};
+use hawktracer_parser::data_provider::DataError;
+use hawktracer_parser::fuzz_dictionary::generate_fuzz_dictionary;
+use hawktracer_parser::json;
+use hawktracer_parser::sampling::EventSampler;
+use hawktracer_parser::{
+    Endianness, EventKlassRegistry, EventReaderBuilder, FilterExpression, KlassFilter,
+    ReadEventError, RunReport, RunStatus,
+};
+
+const USAGE: &str = "Usage: hawktracer-dump [OPTIONS] <FILE|tcp://HOST:PORT>
+
+Options:
+    --format <text|json|fuzz-dictionary>
+                            Output format (default: text); fuzz-dictionary prints
+                            an AFL/libFuzzer dictionary of the klasses of the trace
+    --filter-klass <KLASS>  Print only events of the klass (name or id); can be repeated
+    --filter <EXPR>         Print only events matching the expression, e.g.
+                            'klass == \"foo\" && duration > 1000 && label =~ \"render.*\"'
+    --flatten               Merge fields of base events into the event
+    --sample <N>            Decode only 1 in N events
+    --max-events-per-second <N>
+                            Decode at most N events per second of the trace
+    --sample-per-klass      Apply --sample and --max-events-per-second to each klass
+    --limit <N>             Stop after printing N events
+    --save <FILE>           Save the raw stream (e.g. of a TCP session) to the file
+    --progress              Print the progress on the standard error output
+    -h, --help              Print this message";
+
+#[derive(Debug, PartialEq)]
+enum Format {
+    Text,
+    Json,
+    FuzzDictionary,
+}
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    source: String,
+    format: Format,
+    klasses: Vec<String>,
+    filter: Option<FilterExpression>,
+    flatten: bool,
+    sample_rate: Option<u64>,
+    max_events_per_second: Option<u64>,
+    sample_per_klass: bool,
+    limit: Option<usize>,
+    save: Option<String>,
+    progress: bool,
+}
+
+fn parse_number<T: std::str::FromStr>(name: &str, value: String) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value of {}: {}", name, value))
+}
+
+impl Options {
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+        let mut source = None;
+        let mut options = Options {
+            source: String::new(),
+            format: Format::Text,
+            klasses: Vec::new(),
+            filter: None,
+            flatten: false,
+            sample_rate: None,
+            max_events_per_second: None,
+            sample_per_klass: false,
+            limit: None,
+            save: None,
+            progress: false,
+        };
+
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .ok_or_else(|| format!("Missing value of {}", name))
+            };
+            match arg.as_str() {
+                "--format" => {
+                    options.format = match value("--format")?.as_str() {
+                        "text" => Format::Text,
+                        "json" => Format::Json,
+                        "fuzz-dictionary" => Format::FuzzDictionary,
+                        format => return Err(format!("Unknown format {}", format)),
+                    }
+                }
+                "--filter-klass" => options.klasses.push(value("--filter-klass")?),
+                "--filter" => {
+                    let filter = value("--filter")?;
+                    options.filter = match FilterExpression::parse(&filter) {
+                        Ok(filter) => Some(filter),
+                        Err(err) => return Err(format!("Invalid filter: {}", err)),
+                    }
+                }
+                "--flatten" => options.flatten = true,
+                "--sample" => {
+                    options.sample_rate = Some(parse_number("--sample", value("--sample")?)?)
+                }
+                "--max-events-per-second" => {
+                    options.max_events_per_second = Some(parse_number(
+                        "--max-events-per-second",
+                        value("--max-events-per-second")?,
+                    )?)
+                }
+                "--sample-per-klass" => options.sample_per_klass = true,
+                "--save" => options.save = Some(value("--save")?),
+                "--progress" => options.progress = true,
+                "--limit" => options.limit = Some(parse_number("--limit", value("--limit")?)?),
+                "-h" | "--help" => return Err(String::new()),
+                arg if arg.starts_with('-') => return Err(format!("Unknown option {}", arg)),
+                _ if source.is_some() => return Err(format!("Unexpected argument {}", arg)),
+                _ => source = Some(arg),
+            }
+        }
+
+        match source {
+            Some(source) => options.source = source,
+            None => return Err("Missing trace file or endpoint".to_owned()),
+        }
+        Ok(options)
+    }
+
+    fn get_sampler(&self) -> Option<EventSampler> {
+        if self.sample_rate.is_none() && self.max_events_per_second.is_none() {
+            return None;
+        }
+        let mut sampler = EventSampler::new()
+            .with_rate(self.sample_rate.unwrap_or(1))
+            .with_per_klass(self.sample_per_klass);
+        if let Some(max_events_per_second) = self.max_events_per_second {
+            sampler = sampler.with_max_events_per_second(max_events_per_second);
+        }
+        Some(sampler)
+    }
+
+    // Klass names which are numbers are treated as klass ids.
+    fn get_klass_filter(&self) -> Option<KlassFilter> {
+        if self.klasses.is_empty() {
+            return None;
+        }
+        Some(
+            self.klasses
+                .iter()
+                .fold(KlassFilter::new(), |filter, klass| match klass.parse() {
+                    Ok(klass_id) => filter.allow_id(klass_id),
+                    Err(_) => filter.allow_name(klass),
+                }),
+        )
+    }
+}
+
+fn dump(options: &Options) -> Result<RunStatus, String> {
+    let source = match open_source(&options.source) {
+        Ok(source) => source,
+        Err(err) => return Err(format!("Cannot open {}: {}", options.source, err)),
+    };
+    let klass_filter = options.get_klass_filter();
+    let mut builder = EventReaderBuilder::new().flatten(options.flatten);
+    if let Some(klass_filter) = &klass_filter {
+        builder = builder.klass_filter(klass_filter.clone());
+    }
+    if let Some(sampler) = options.get_sampler() {
+        builder = builder.sampler(sampler);
+    }
+    if let Some(filter) = &options.filter {
+        builder = builder.filter_expression(filter.clone());
+    }
+    if options.progress {
+        builder = builder.progress_reporter(make_progress_reporter(&options.source));
+    }
+    if let Some(path) = &options.save {
+        match std::fs::File::create(path) {
+            Ok(file) => builder = builder.tee(Box::new(std::io::BufWriter::new(file))),
+            Err(err) => return Err(format!("Cannot create {}: {}", path, err)),
+        }
+    }
+    let mut reader = builder.build(source);
+    let mut registry = EventKlassRegistry::new();
+    let stdout = std::io::stdout();
+    let mut output = std::io::BufWriter::new(stdout.lock());
+
+    let mut report = RunReport::default();
+    while options
+        .limit
+        .map_or(true, |limit| report.event_count < limit as u64)
+    {
+        let event = match reader.read_event(&mut registry) {
+            Ok(event) => event,
+            Err(ReadEventError::DataError(DataError::EndOfStream)) => {
+                report.update_from_reader(&reader);
+                break;
+            }
+            Err(err) => return Err(format!("Cannot read event: {}", err)),
+        };
+        // Metadata events are returned by the reader even if they are not
+        // allowed by the filter.
+        if let Some(klass_filter) = &klass_filter {
+            match registry.get_klass_by_id(event.get_klass_id()) {
+                Some(klass) if klass_filter.is_allowed(klass) => {}
+                _ => continue,
+            }
+        }
+
+        let result = match options.format {
+            Format::Text => {
+                std::io::Write::write_all(&mut output, event.pretty(&registry).as_bytes())
+            }
+            Format::Json => json::write_event(&mut output, &event, &registry)
+                .and_then(|()| std::io::Write::write_all(&mut output, b"\n")),
+            Format::FuzzDictionary => Ok(()),
+        };
+        if let Err(err) = result {
+            return Err(format!("Cannot write event: {}", err));
+        }
+        report.event_count += 1;
+    }
+
+    if options.progress {
+        eprintln!();
+    }
+    if let Some(mut tee) = reader.take_tee() {
+        tee.flush()
+            .map_err(|err| format!("Cannot save the stream: {}", err))?;
+    }
+    if options.format == Format::FuzzDictionary {
+        let dictionary = generate_fuzz_dictionary(&registry, Endianness::Native);
+        std::io::Write::write_all(&mut output, dictionary.as_bytes())
+            .map_err(|err| format!("Cannot write the dictionary: {}", err))?;
+    }
+    std::io::Write::flush(&mut output).map_err(|err| format!("Cannot write event: {}", err))?;
+    Ok(get_run_status(&report))
+}
+
+fn main() {
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            if !err.is_empty() {
+                eprintln!("{}\n", err);
+            }
+            eprintln!("{}", USAGE);
+            std::process::exit(USAGE_EXIT_CODE);
+        }
+    };
+
+    match dump(&options) {
+        Ok(status) => std::process::exit(status.exit_code()),
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(RunStatus::Failure.exit_code());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn options_should_be_parsed() {
+        assert_eq!(
+            parse(&[
+                "--filter-klass",
+                "foo",
+                "--flatten",
+                "trace.htdump",
+                "--filter-klass",
+                "100",
+                "--format",
+                "json",
+                "--limit",
+                "5",
+                "--progress"
+            ]),
+            Ok(Options {
+                source: "trace.htdump".to_owned(),
+                format: Format::Json,
+                klasses: vec!["foo".to_owned(), "100".to_owned()],
+                filter: None,
+                flatten: true,
+                sample_rate: None,
+                max_events_per_second: None,
+                sample_per_klass: false,
+                limit: Some(5),
+                save: None,
+                progress: true,
+            })
+        );
+        assert_eq!(
+            parse(&["--filter", "duration > 5", "trace.htdump"])
+                .unwrap()
+                .filter,
+            Some(FilterExpression::parse("duration > 5").unwrap())
+        );
+        assert!(parse(&["--filter", "duration >", "trace.htdump"]).is_err());
+        assert_eq!(
+            parse(&["--save", "session.htdump", "tcp://localhost:8765"])
+                .unwrap()
+                .save,
+            Some("session.htdump".to_owned())
+        );
+        let options = parse(&[
+            "--sample",
+            "10",
+            "--max-events-per-second",
+            "1000",
+            "--sample-per-klass",
+            "trace.htdump",
+        ])
+        .unwrap();
+        assert_eq!(options.sample_rate, Some(10));
+        assert_eq!(options.max_events_per_second, Some(1000));
+        assert!(options.sample_per_klass);
+        assert!(options.get_sampler().is_some());
+        assert!(parse(&["--sample", "-1", "trace.htdump"]).is_err());
+        assert!(parse(&["--limit", "x", "trace.htdump"]).is_err());
+        assert_eq!(
+            parse(&["--format", "fuzz-dictionary", "trace.htdump"])
+                .unwrap()
+                .format,
+            Format::FuzzDictionary
+        );
+        assert!(parse(&["--format", "xml", "trace.htdump"]).is_err());
+        assert!(parse(&["--limit"]).is_err());
+        assert!(parse(&["a", "b"]).is_err());
+        assert!(parse(&[]).is_err());
+    }
+}
@@ -0,0 +1,311 @@
+use crate::data_provider::DataProvider;
+use crate::event::{ArrayLength, DataType, IntegerType};
+use crate::event_klass::{EventKlass, EventKlassField};
+use crate::event_reader::EventReader;
+use crate::registry::{CoreEventKlassId, EventKlassRegistry};
+
+const CHECKPOINT_MAGIC: &[u8; 4] = b"HTCP";
+const CHECKPOINT_VERSION: u32 = 2;
+
+const DATA_TYPES: [DataType; 10] = [
+    DataType::U8,
+    DataType::I8,
+    DataType::U16,
+    DataType::I16,
+    DataType::U32,
+    DataType::I32,
+    DataType::U64,
+    DataType::I64,
+    DataType::Str,
+    DataType::Struct,
+];
+
+// Opaque fields are followed by their size, arrays by the element type and
+// the element count (PREFIXED_ARRAY_COUNT for length-prefixed arrays).
+const BYTES_DATA_TYPE: u8 = DATA_TYPES.len() as u8;
+const ARRAY_DATA_TYPE: u8 = BYTES_DATA_TYPE + 1;
+const PREFIXED_ARRAY_COUNT: u64 = u64::MAX;
+// Written for fields without a declared size.
+const NO_DECLARED_SIZE: u64 = u64::MAX;
+
+// State of the parsing: klasses registered from metadata events and the
+// stream offset of the next event. Should be taken between read_event()
+// calls (an event found by seek_to_timestamp() is not included).
+#[derive(Debug, PartialEq)]
+pub struct Checkpoint {
+    offset: u64,
+    klasses: Vec<EventKlass>,
+}
+
+fn invalid_data(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+fn write_string(writer: &mut dyn std::io::Write, value: &str) -> std::io::Result<()> {
+    writer.write_all(&(value.len() as u32).to_le_bytes())?;
+    writer.write_all(value.as_bytes())
+}
+
+fn read_u32(reader: &mut dyn std::io::Read) -> std::io::Result<u32> {
+    let mut buffer = [0u8; 4];
+    reader.read_exact(&mut buffer)?;
+    Ok(u32::from_le_bytes(buffer))
+}
+
+fn read_string(reader: &mut dyn std::io::Read) -> std::io::Result<String> {
+    let mut buffer = vec![0u8; read_u32(reader)? as usize];
+    reader.read_exact(&mut buffer)?;
+    String::from_utf8(buffer).map_err(|_| invalid_data("Invalid string in checkpoint"))
+}
+
+fn read_u64(reader: &mut dyn std::io::Read) -> std::io::Result<u64> {
+    let mut buffer = [0u8; 8];
+    reader.read_exact(&mut buffer)?;
+    Ok(u64::from_le_bytes(buffer))
+}
+
+fn read_size(reader: &mut dyn std::io::Read) -> std::io::Result<usize> {
+    std::convert::TryFrom::try_from(read_u64(reader)?)
+        .map_err(|_| invalid_data("Invalid field size in checkpoint"))
+}
+
+fn write_data_type(writer: &mut dyn std::io::Write, data_type: DataType) -> std::io::Result<()> {
+    match data_type {
+        DataType::Bytes(size) => {
+            writer.write_all(&[BYTES_DATA_TYPE])?;
+            writer.write_all(&(size as u64).to_le_bytes())
+        }
+        DataType::Array(element_type, length) => {
+            writer.write_all(&[ARRAY_DATA_TYPE])?;
+            write_data_type(writer, element_type.get_data_type())?;
+            let count = match length {
+                ArrayLength::Fixed(count) => count as u64,
+                ArrayLength::Prefixed => PREFIXED_ARRAY_COUNT,
+            };
+            writer.write_all(&count.to_le_bytes())
+        }
+        data_type => {
+            let data_type = DATA_TYPES
+                .iter()
+                .position(|known_type| *known_type == data_type)
+                .unwrap_or_default();
+            writer.write_all(&[data_type as u8])
+        }
+    }
+}
+
+fn read_data_type(reader: &mut dyn std::io::Read) -> std::io::Result<DataType> {
+    let mut data_type = [0u8; 1];
+    reader.read_exact(&mut data_type)?;
+    match data_type[0] {
+        BYTES_DATA_TYPE => Ok(DataType::Bytes(read_size(reader)?)),
+        ARRAY_DATA_TYPE => {
+            let element_type = IntegerType::from_data_type(read_data_type(reader)?)
+                .ok_or_else(|| invalid_data("Invalid array element type in checkpoint"))?;
+            let length = match read_u64(reader)? {
+                PREFIXED_ARRAY_COUNT => ArrayLength::Prefixed,
+                count => ArrayLength::Fixed(
+                    std::convert::TryFrom::try_from(count)
+                        .map_err(|_| invalid_data("Invalid field size in checkpoint"))?,
+                ),
+            };
+            Ok(DataType::Array(element_type, length))
+        }
+        data_type => DATA_TYPES
+            .get(data_type as usize)
+            .copied()
+            .ok_or_else(|| invalid_data("Invalid data type in checkpoint")),
+    }
+}
+
+impl Checkpoint {
+    pub fn new(reader: &EventReader, registry: &EventKlassRegistry) -> Checkpoint {
+        Checkpoint {
+            offset: reader.get_position(),
+            klasses: registry
+                .get_klasses()
+                .into_iter()
+                .filter(|klass| !CoreEventKlassId::is_core_klass(klass.get_id()))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    pub fn get_offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub fn create_registry(&self) -> EventKlassRegistry {
+        let mut registry = EventKlassRegistry::new();
+        for klass in &self.klasses {
+            registry.add_klass(klass.clone());
+        }
+        registry
+    }
+
+    // The stream is moved to the checkpoint offset; positions reported by
+    // the reader are offsets in the whole stream.
+    pub fn resume<R: std::io::Read + std::io::Seek + 'static>(
+        &self,
+        mut stream: R,
+    ) -> std::io::Result<(EventReader, EventKlassRegistry)> {
+        stream.seek(std::io::SeekFrom::Start(self.offset))?;
+        let mut data_provider = DataProvider::new(Box::new(stream));
+        data_provider.set_position(self.offset);
+
+        Ok((EventReader::new(data_provider), self.create_registry()))
+    }
+
+    pub fn write(&self, writer: &mut dyn std::io::Write) -> std::io::Result<()> {
+        writer.write_all(CHECKPOINT_MAGIC)?;
+        writer.write_all(&CHECKPOINT_VERSION.to_le_bytes())?;
+        writer.write_all(&self.offset.to_le_bytes())?;
+        writer.write_all(&(self.klasses.len() as u32).to_le_bytes())?;
+        for klass in &self.klasses {
+            writer.write_all(&klass.get_id().to_le_bytes())?;
+            write_string(writer, klass.get_name())?;
+            writer.write_all(&(klass.get_fields().len() as u32).to_le_bytes())?;
+            for field in klass.get_fields() {
+                write_string(writer, field.get_name())?;
+                write_string(writer, field.get_type_name())?;
+                write_data_type(writer, *field.get_data_type())?;
+                let declared_size = field.get_declared_size().unwrap_or(NO_DECLARED_SIZE);
+                writer.write_all(&declared_size.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read(reader: &mut dyn std::io::Read) -> std::io::Result<Checkpoint> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != CHECKPOINT_MAGIC || read_u32(reader)? != CHECKPOINT_VERSION {
+            return Err(invalid_data("Invalid checkpoint header"));
+        }
+
+        let offset = read_u64(reader)?;
+        let klass_count = read_u32(reader)?;
+        let mut klasses = Vec::new();
+        for _ in 0..klass_count {
+            let id = read_u32(reader)?;
+            let mut klass = EventKlass::new(id, read_string(reader)?);
+            for _ in 0..read_u32(reader)? {
+                let name = read_string(reader)?;
+                let type_name = read_string(reader)?;
+                let mut field = EventKlassField::new(name, type_name, read_data_type(reader)?);
+                match read_u64(reader)? {
+                    NO_DECLARED_SIZE => {}
+                    size => field = field.with_declared_size(size),
+                }
+                klass.add_klass_field(field);
+            }
+            klasses.push(klass);
+        }
+
+        Ok(Checkpoint { offset, klasses })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hawktracer_parser_test_utilities::FakeDataReader;
+
+    fn make_stream() -> Vec<u8> {
+        let mut data = vec![
+            2, 0, 0, 0, // type
+            1, 0, 0, 0, 0, 0, 0, 0, // timestamp
+            1, 0, 0, 0, 0, 0, 0, 0, // id
+            100, 0, 0, 0, // info_klass_id
+            102, 111, 111, 0, // foo
+            1, // field_count
+            3, 0, 0, 0, // type
+            2, 0, 0, 0, 0, 0, 0, 0, // timestamp
+            2, 0, 0, 0, 0, 0, 0, 0, // id
+            100, 0, 0, 0, // info_klass_id
+        ];
+        data.extend_from_slice(b"const char*\0name\0");
+        data.extend_from_slice(&[8, 0, 0, 0, 0, 0, 0, 0, 2]);
+        for id in 3..5 {
+            data.extend_from_slice(&[100, 0, 0, 0, id, 0, 0, 0, 0, 0, 0, 0]);
+            data.extend_from_slice(&[id, 0, 0, 0, 0, 0, 0, 0, 65 + id, 0]);
+        }
+        data
+    }
+
+    #[test]
+    fn parsing_should_be_resumed_from_checkpoint() {
+        let mut reader = EventReader::new(DataProvider::new(Box::new(FakeDataReader::new(
+            make_stream(),
+            false,
+        ))));
+        let mut registry = EventKlassRegistry::new();
+        for _ in 0..3 {
+            reader.read_event(&mut registry).unwrap();
+        }
+
+        let mut data = Vec::new();
+        Checkpoint::new(&reader, &registry)
+            .write(&mut data)
+            .unwrap();
+        let checkpoint = Checkpoint::read(&mut &data[..]).unwrap();
+        assert_eq!(checkpoint, Checkpoint::new(&reader, &registry));
+        assert_eq!(
+            checkpoint.klasses[0].get_fields()[0].get_declared_size(),
+            Some(8)
+        );
+
+        let (mut resumed_reader, mut resumed_registry) = checkpoint
+            .resume(std::io::Cursor::new(make_stream()))
+            .unwrap();
+        let event = resumed_reader.read_event(&mut resumed_registry).unwrap();
+        let expected = reader.read_event(&mut registry).unwrap();
+
+        assert_eq!(event, expected);
+        assert_eq!(event.get_value_string("name").unwrap(), "E");
+        assert_eq!(resumed_reader.get_position(), reader.get_position());
+    }
+
+    #[test]
+    fn opaque_and_array_fields_should_be_stored_in_checkpoint() {
+        let mut klass = EventKlass::new(100, "foo".to_owned());
+        klass.add_field(
+            "blob".to_owned(),
+            "vendor_t".to_owned(),
+            DataType::Bytes(12),
+        );
+        klass.add_field("name".to_owned(), "const char*".to_owned(), DataType::Str);
+        klass.add_field(
+            "counters".to_owned(),
+            "uint32_t[4]".to_owned(),
+            DataType::Array(IntegerType::U32, ArrayLength::Fixed(4)),
+        );
+        klass.add_field(
+            "samples".to_owned(),
+            "int16_t[]".to_owned(),
+            DataType::Array(IntegerType::I16, ArrayLength::Prefixed),
+        );
+        let checkpoint = Checkpoint {
+            offset: 5,
+            klasses: vec![klass],
+        };
+        let mut data = Vec::new();
+        checkpoint.write(&mut data).unwrap();
+
+        assert_eq!(Checkpoint::read(&mut &data[..]).unwrap(), checkpoint);
+    }
+
+    #[test]
+    fn invalid_checkpoint_should_be_rejected() {
+        let checkpoint = Checkpoint {
+            offset: 5,
+            klasses: vec![EventKlass::new(100, "foo".to_owned())],
+        };
+        let mut data = Vec::new();
+        checkpoint.write(&mut data).unwrap();
+
+        assert!(Checkpoint::read(&mut &data[..data.len() - 1]).is_err());
+        data[0] = b'X';
+        assert!(Checkpoint::read(&mut &data[..]).is_err());
+    }
+}
@@ -0,0 +1,233 @@
+use crate::event::{DataType, IntegerType};
+use crate::event_klass::{EventKlass, EventKlassField};
+use crate::registry::{CoreEventKlassId, EventKlassRegistry};
+
+const RUST_KEYWORDS: [&str; 34] = [
+    "as", "async", "await", "break", "const", "continue", "dyn", "else", "enum", "extern", "false",
+    "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref",
+    "return", "static", "struct", "trait", "true", "type", "unsafe", "use", "where", "while",
+];
+
+// Keywords which can't be raw identifiers.
+const RESERVED_PATH_KEYWORDS: [&str; 4] = ["crate", "self", "Self", "super"];
+
+// HT_CallstackIntEvent -> HtCallstackIntEvent
+fn get_struct_name(klass_name: &str) -> String {
+    let mut name = String::new();
+    for part in klass_name.split(|c: char| !c.is_ascii_alphanumeric()) {
+        let is_uppercase = !part.chars().any(|c| c.is_ascii_lowercase());
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            name.push(first.to_ascii_uppercase());
+            if is_uppercase {
+                name.extend(chars.map(|c| c.to_ascii_lowercase()));
+            } else {
+                name.extend(chars);
+            }
+        }
+    }
+    match name.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() && name != "Self" => name,
+        _ => format!("Klass{}", name),
+    }
+}
+
+fn get_field_name(field_name: &str) -> String {
+    let name: String = field_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if RUST_KEYWORDS.contains(&name.as_str()) {
+        format!("r#{}", name)
+    } else if RESERVED_PATH_KEYWORDS.contains(&name.as_str()) {
+        format!("{}_", name)
+    } else if name.starts_with(|c: char| c.is_ascii_digit()) || name.is_empty() {
+        format!("_{}", name)
+    } else {
+        name
+    }
+}
+
+fn get_integer_type_name(integer_type: &IntegerType) -> &'static str {
+    match integer_type {
+        IntegerType::U8 => "u8",
+        IntegerType::I8 => "i8",
+        IntegerType::U16 => "u16",
+        IntegerType::I16 => "i16",
+        IntegerType::U32 => "u32",
+        IntegerType::I32 => "i32",
+        IntegerType::U64 => "u64",
+        IntegerType::I64 => "i64",
+    }
+}
+
+// Rust type of the field and the expression converting it from the event.
+fn get_field_type(field: &EventKlassField, registry: &EventKlassRegistry) -> (String, String) {
+    let name = field.get_name();
+    let (type_name, getter) = match field.get_data_type() {
+        DataType::U8 => ("u8", "u8"),
+        DataType::I8 => ("i8", "i8"),
+        DataType::U16 => ("u16", "u16"),
+        DataType::I16 => ("i16", "i16"),
+        DataType::U32 => ("u32", "u32"),
+        DataType::I32 => ("i32", "i32"),
+        DataType::U64 => ("u64", "u64"),
+        DataType::I64 => ("i64", "i64"),
+        DataType::Str => {
+            return (
+                "String".to_owned(),
+                format!("event.get_value_string({:?})?.clone()", name),
+            )
+        }
+        DataType::Struct => {
+            let struct_name = match registry.get_klass_by_name(field.get_type_name()) {
+                Some(klass) => get_struct_name(klass.get_name()),
+                None => get_struct_name(field.get_type_name()),
+            };
+            let conversion = format!(
+                "{}::try_from(event.get_value_struct({:?})?)?",
+                struct_name, name
+            );
+            return (struct_name, conversion);
+        }
+        DataType::Bytes(_) => {
+            return (
+                "Vec<u8>".to_owned(),
+                format!("event.get_value_bytes({:?})?.clone()", name),
+            )
+        }
+        DataType::Array(element_type, _) => {
+            let element_type_name = get_integer_type_name(element_type);
+            let conversion = format!(
+                "event.get_value_array({name:?})?.iter().map(|value| \
+                 hawktracer_parser::event::FromValue::from_value(value).ok_or_else(|| \
+                 hawktracer_parser::event::ValueError::new({name:?}, \
+                 hawktracer_parser::event::ErrorKind::InvalidType))).collect::<Result<Vec<{element}>, _>>()?",
+                name = name,
+                element = element_type_name
+            );
+            return (format!("Vec<{}>", element_type_name), conversion);
+        }
+    };
+    (
+        type_name.to_owned(),
+        format!("event.get_value_{}({:?})?", getter, name),
+    )
+}
+
+fn generate_klass_code(klass: &EventKlass, registry: &EventKlassRegistry) -> String {
+    let struct_name = get_struct_name(klass.get_name());
+    let mut definition = format!(
+        "#[derive(Debug, Clone, PartialEq)]\npub struct {} {{\n",
+        struct_name
+    );
+    let mut conversion = String::new();
+    for field in klass.get_fields() {
+        let field_name = get_field_name(field.get_name());
+        let (type_name, expression) = get_field_type(field, registry);
+        definition.push_str(&format!("    pub {}: {},\n", field_name, type_name));
+        conversion.push_str(&format!("            {}: {},\n", field_name, expression));
+    }
+    definition.push_str("}\n\n");
+
+    format!(
+        "{definition}impl {name} {{\n    \
+         pub const KLASS_NAME: &'static str = {klass_name:?};\n}}\n\n\
+         impl std::convert::TryFrom<&hawktracer_parser::Event> for {name} {{\n    \
+         type Error = hawktracer_parser::event::ValueError;\n\n    \
+         fn try_from(event: &hawktracer_parser::Event) -> Result<{name}, Self::Error> {{\n        \
+         Ok({name} {{\n{conversion}        }})\n    }}\n}}\n",
+        definition = definition,
+        name = struct_name,
+        klass_name = klass.get_name(),
+        conversion = conversion
+    )
+}
+
+// Generates Rust structs (with TryFrom<&Event> implementations) for all the
+// klasses of the registry, e.g. to be written to OUT_DIR by a build script.
+// Metadata klasses are skipped; HT_Event is included, as it's the type of
+// the "base" fields. Conversions expect nested (not flattened) events.
+pub fn generate_rust_code(registry: &EventKlassRegistry) -> String {
+    let mut code = String::from(
+        "// Generated by hawktracer-parser, do not edit.\n\
+         #[allow(unused_imports)]\n\
+         use std::convert::TryFrom;\n",
+    );
+    for klass in registry.get_klasses() {
+        if CoreEventKlassId::is_core_klass(klass.get_id())
+            && klass.get_id() != CoreEventKlassId::Base as u32
+        {
+            continue;
+        }
+        code.push('\n');
+        code.push_str(&generate_klass_code(klass, registry));
+    }
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::ArrayLength;
+
+    #[test]
+    fn names_should_be_valid_rust_identifiers() {
+        assert_eq!(
+            get_struct_name("HT_CallstackIntEvent"),
+            "HtCallstackIntEvent"
+        );
+        assert_eq!(get_struct_name("my-klass"), "MyKlass");
+        assert_eq!(get_struct_name("3d_point"), "Klass3dPoint");
+        assert_eq!(get_struct_name("self"), "KlassSelf");
+        assert_eq!(get_field_name("type"), "r#type");
+        assert_eq!(get_field_name("self"), "self_");
+        assert_eq!(get_field_name("Self"), "Self_");
+        assert_eq!(get_field_name("crate"), "crate_");
+        assert_eq!(get_field_name("field.name"), "field_name");
+        assert_eq!(get_field_name("1st"), "_1st");
+    }
+
+    #[test]
+    fn code_should_contain_structs_and_conversions() {
+        let mut registry = EventKlassRegistry::new();
+        let mut klass = EventKlass::new(100, "my_klass".to_owned());
+        klass.add_field("base".to_owned(), "HT_Event".to_owned(), DataType::Struct);
+        klass.add_field("label".to_owned(), "const char*".to_owned(), DataType::Str);
+        klass.add_field("duration".to_owned(), "uint64_t".to_owned(), DataType::U64);
+        registry.add_klass(klass);
+
+        let code = generate_rust_code(&registry);
+
+        assert!(code.contains("pub struct HtEvent {\n    pub r#type: u32,\n"));
+        assert!(code.contains(
+            "pub struct MyKlass {\n    pub base: HtEvent,\n    pub label: String,\n    pub duration: u64,\n}"
+        ));
+        assert!(code.contains("pub const KLASS_NAME: &'static str = \"my_klass\";"));
+        assert!(
+            code.contains("impl std::convert::TryFrom<&hawktracer_parser::Event> for MyKlass {")
+        );
+        assert!(code.contains("base: HtEvent::try_from(event.get_value_struct(\"base\")?)?,"));
+        assert!(code.contains("label: event.get_value_string(\"label\")?.clone(),"));
+        assert!(code.contains("duration: event.get_value_u64(\"duration\")?,"));
+        assert!(!code.contains("HtEventKlassInfoEvent"));
+    }
+
+    #[test]
+    fn array_fields_should_be_converted_to_vectors() {
+        let mut registry = EventKlassRegistry::new();
+        let mut klass = EventKlass::new(100, "counters".to_owned());
+        klass.add_field(
+            "values".to_owned(),
+            "uint32_t[4]".to_owned(),
+            DataType::Array(IntegerType::U32, ArrayLength::Fixed(4)),
+        );
+        registry.add_klass(klass);
+
+        let code = generate_rust_code(&registry);
+
+        assert!(code.contains("pub struct Counters {\n    pub values: Vec<u32>,\n}"));
+        assert!(code.contains("values: event.get_value_array(\"values\")?.iter()"));
+        assert!(code.contains(".collect::<Result<Vec<u32>, _>>()?,"));
+    }
+}
@@ -0,0 +1,294 @@
+use crate::event::{DataType, Event, Value};
+use crate::event_klass::EventKlass;
+use crate::registry::{CoreEventKlassId, EventKlassRegistry};
+use crate::sink::EventSink;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CsvColumns {
+    // Fields of the klass; events of other klasses are skipped.
+    Klass(String),
+    // Union of fields of all the (non-core) klasses, preceded by the klass
+    // name column.
+    Union,
+}
+
+// Writes events as CSV rows. Columns are derived from the registry when the
+// first event is written (so klasses have to be defined by then), using
+// the flattened field names (base fields become top-level columns). Missing
+// values are written as empty cells.
+pub struct CsvWriter {
+    writer: Box<dyn std::io::Write>,
+    columns_source: CsvColumns,
+    delimiter: char,
+    selected_fields: Option<Vec<String>>,
+    columns: Option<Vec<String>>,
+}
+
+// Names of fields of flattened events of the klass (see Event::flat_event()).
+pub(crate) fn add_flat_field_names(
+    klass: &EventKlass,
+    registry: &EventKlassRegistry,
+    names: &mut Vec<String>,
+) {
+    add_flat_field_names_of(klass, registry, names, &mut vec![klass.get_id()]);
+}
+
+// Base klasses which are already being expanded (the stream can define
+// klasses which are their own base) are kept as a single column.
+fn add_flat_field_names_of(
+    klass: &EventKlass,
+    registry: &EventKlassRegistry,
+    names: &mut Vec<String>,
+    expanded_klass_ids: &mut Vec<u32>,
+) {
+    for field in klass.get_fields() {
+        let base_klass = match field.get_data_type() {
+            DataType::Struct if field.get_name() == "base" => registry
+                .get_klass_by_name(field.get_type_name())
+                .filter(|base_klass| !expanded_klass_ids.contains(&base_klass.get_id())),
+            _ => None,
+        };
+        match base_klass {
+            Some(base_klass) => {
+                expanded_klass_ids.push(base_klass.get_id());
+                add_flat_field_names_of(base_klass, registry, names, expanded_klass_ids);
+                expanded_klass_ids.pop();
+            }
+            None => {
+                if !names.iter().any(|name| name == field.get_name()) {
+                    names.push(field.get_name().to_owned());
+                }
+            }
+        }
+    }
+}
+
+impl CsvWriter {
+    pub fn new(writer: Box<dyn std::io::Write>, columns: CsvColumns) -> CsvWriter {
+        CsvWriter {
+            writer,
+            columns_source: columns,
+            delimiter: ',',
+            selected_fields: None,
+            columns: None,
+        }
+    }
+
+    pub fn with_delimiter(mut self, delimiter: char) -> CsvWriter {
+        self.delimiter = delimiter;
+        self
+    }
+
+    // Only the listed fields are written, in the given order.
+    pub fn with_fields(mut self, field_names: &[&str]) -> CsvWriter {
+        self.selected_fields = Some(field_names.iter().map(|name| name.to_string()).collect());
+        self
+    }
+
+    fn escape(&self, value: &str) -> String {
+        if value.contains([self.delimiter, '"', '\n', '\r']) {
+            format!("\"{}\"", value.replace('"', "\"\""))
+        } else {
+            value.to_owned()
+        }
+    }
+
+    fn resolve_columns(&self, registry: &EventKlassRegistry) -> Vec<String> {
+        if let Some(selected_fields) = &self.selected_fields {
+            return selected_fields.clone();
+        }
+
+        let mut columns = Vec::new();
+        match &self.columns_source {
+            CsvColumns::Klass(name) => {
+                if let Some(klass) = registry.get_klass_by_name(name) {
+                    add_flat_field_names(klass, registry, &mut columns);
+                }
+            }
+            CsvColumns::Union => {
+                columns.push("klass".to_owned());
+                for klass in registry.get_klasses() {
+                    if !CoreEventKlassId::is_core_klass(klass.get_id()) {
+                        add_flat_field_names(klass, registry, &mut columns);
+                    }
+                }
+            }
+        }
+        columns
+    }
+
+    fn write_row(&mut self, cells: Vec<String>) -> std::io::Result<()> {
+        let row = cells.join(&self.delimiter.to_string());
+        writeln!(self.writer, "{}", row)
+    }
+}
+
+impl EventSink for CsvWriter {
+    fn write_event(&mut self, event: &Event, registry: &EventKlassRegistry) -> std::io::Result<()> {
+        let klass_name = match registry.get_klass_by_id(event.get_klass_id()) {
+            Some(klass) => klass.get_name().as_str(),
+            None => return Ok(()),
+        };
+        let is_included = match &self.columns_source {
+            CsvColumns::Klass(name) => name == klass_name,
+            CsvColumns::Union => !CoreEventKlassId::is_core_klass(event.get_klass_id()),
+        };
+        if !is_included {
+            return Ok(());
+        }
+
+        if self.columns.is_none() {
+            let columns = self.resolve_columns(registry);
+            let header = columns.iter().map(|column| self.escape(column)).collect();
+            self.write_row(header)?;
+            self.columns = Some(columns);
+        }
+
+        let event = event.clone().flat_event();
+        let cells = self
+            .columns
+            .iter()
+            .flatten()
+            .map(|column| match event.get_raw_value(column) {
+                Some(Value::Str(value)) => self.escape(value),
+                Some(Value::Struct(_)) => String::new(),
+                // Arrays and bytes are printed with separators.
+                Some(value) => self.escape(&value.to_string()),
+                None if column == "klass" && self.columns_source == CsvColumns::Union => {
+                    self.escape(klass_name)
+                }
+                None => String::new(),
+            })
+            .collect();
+        self.write_row(cells)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{ArrayLength, IntegerType};
+    use crate::test_events;
+    use hawktracer_parser_test_utilities::FakeDataWriter;
+
+    fn make_registry() -> EventKlassRegistry {
+        let mut registry = EventKlassRegistry::new();
+        let mut foo = EventKlass::new(100, "foo".to_owned());
+        foo.add_field("base".to_owned(), "HT_Event".to_owned(), DataType::Struct);
+        foo.add_field("name".to_owned(), "const char*".to_owned(), DataType::Str);
+        registry.add_klass(foo);
+        let mut bar = EventKlass::new(101, "bar".to_owned());
+        bar.add_field("base".to_owned(), "HT_Event".to_owned(), DataType::Struct);
+        bar.add_field("value".to_owned(), "uint32_t".to_owned(), DataType::U32);
+        registry.add_klass(bar);
+        registry
+    }
+
+    fn make_event(klass_id: u32, timestamp: u64, field: (&str, Value)) -> Event {
+        test_events::make_event(klass_id, timestamp, vec![field])
+    }
+
+    fn write(writer: CsvWriter, buffer: FakeDataWriter) -> String {
+        let registry = make_registry();
+        let mut writer = writer;
+        writer
+            .write_event(
+                &make_event(100, 1, ("name", Value::Str("a,b".to_owned()))),
+                &registry,
+            )
+            .unwrap();
+        writer
+            .write_event(&make_event(101, 2, ("value", Value::U32(7))), &registry)
+            .unwrap();
+        writer.flush().unwrap();
+        let output = buffer.get_data();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn klass_columns_should_be_derived_from_registry() {
+        let buffer = FakeDataWriter::new(false);
+        let writer = CsvWriter::new(
+            Box::new(buffer.clone()),
+            CsvColumns::Klass("foo".to_owned()),
+        );
+
+        assert_eq!(
+            write(writer, buffer),
+            "type,timestamp,id,name\n100,1,1,\"a,b\"\n"
+        );
+    }
+
+    #[test]
+    fn union_columns_should_include_all_klasses() {
+        let buffer = FakeDataWriter::new(false);
+        let writer =
+            CsvWriter::new(Box::new(buffer.clone()), CsvColumns::Union).with_delimiter(';');
+
+        assert_eq!(
+            write(writer, buffer),
+            "klass;type;timestamp;id;name;value\nfoo;100;1;1;a,b;\nbar;101;2;2;;7\n"
+        );
+    }
+
+    #[test]
+    fn cyclic_base_klasses_should_not_be_expanded_again() {
+        let mut registry = make_registry();
+        let mut cyclic = EventKlass::new(102, "cyclic".to_owned());
+        cyclic.add_field("base".to_owned(), "other".to_owned(), DataType::Struct);
+        cyclic.add_field("value".to_owned(), "uint32_t".to_owned(), DataType::U32);
+        registry.add_klass(cyclic);
+        let mut other = EventKlass::new(103, "other".to_owned());
+        other.add_field("base".to_owned(), "cyclic".to_owned(), DataType::Struct);
+        other.add_field("name".to_owned(), "const char*".to_owned(), DataType::Str);
+        registry.add_klass(other);
+
+        let mut names = Vec::new();
+        add_flat_field_names(
+            registry.get_klass_by_id(102).unwrap(),
+            &registry,
+            &mut names,
+        );
+        assert_eq!(names, vec!["base", "name", "value"]);
+    }
+
+    #[test]
+    fn array_values_should_be_escaped() {
+        let buffer = FakeDataWriter::new(false);
+        let mut writer = CsvWriter::new(
+            Box::new(buffer.clone()),
+            CsvColumns::Klass("bar".to_owned()),
+        );
+        let mut registry = make_registry();
+        let mut bar = EventKlass::new(101, "bar".to_owned());
+        bar.add_field("base".to_owned(), "HT_Event".to_owned(), DataType::Struct);
+        bar.add_field(
+            "value".to_owned(),
+            "uint8_t[2]".to_owned(),
+            DataType::Array(IntegerType::U8, ArrayLength::Fixed(2)),
+        );
+        registry.add_klass(bar);
+
+        let value = Value::Array(vec![Value::U8(1), Value::U8(2)]);
+        writer
+            .write_event(&make_event(101, 2, ("value", value)), &registry)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(buffer.get_data()).unwrap(),
+            "type,timestamp,id,value\n101,2,2,\"[1, 2]\"\n"
+        );
+    }
+
+    #[test]
+    fn selected_fields_should_be_written_in_given_order() {
+        let buffer = FakeDataWriter::new(false);
+        let writer = CsvWriter::new(Box::new(buffer.clone()), CsvColumns::Union)
+            .with_fields(&["value", "timestamp"]);
+
+        assert_eq!(write(writer, buffer), "value,timestamp\n,1\n7,2\n");
+    }
+}
@@ -1,14 +1,37 @@
 pub struct DataProvider {
     reader: Box<dyn std::io::Read>,
-    buffer: [u8; 512],
+    buffer: Vec<u8>,
     data_pointer: usize,
     data_available: usize,
+    position: u64,
+    max_string_length: Option<usize>,
+    capture: Option<Vec<u8>>,
+    min_buffer_size: usize,
+    max_buffer_size: usize,
+    full_reads: u32,
+    short_reads: u32,
+    // Number of bytes returned with unread() which haven't been consumed
+    // again yet.
+    unread_bytes: usize,
+    max_unread_size: usize,
+    tee: Option<Box<dyn std::io::Write>>,
 }
 
+// Number of consecutive reads filling the whole buffer before it grows, and
+// number of consecutive reads using less than a quarter of the buffer
+// before it shrinks.
+const GROW_AFTER_FULL_READS: u32 = 4;
+const SHRINK_AFTER_SHORT_READS: u32 = 16;
+
+pub const DEFAULT_MAX_UNREAD_SIZE: usize = 4096;
+
 #[derive(Debug)]
 pub enum DataError {
     EndOfStream,
     Utf8Error,
+    StringTooLong,
+    // Too many bytes returned with DataProvider::unread().
+    UnreadOverflow,
     IOError(std::io::Error),
 }
 
@@ -18,22 +41,126 @@ impl PartialEq for DataError {
             (DataError::IOError(_e1), DataError::IOError(_e2)) => true, // Assume error is the same if the type matches
             (DataError::EndOfStream, DataError::EndOfStream) => true,
             (DataError::Utf8Error, DataError::Utf8Error) => true,
+            (DataError::StringTooLong, DataError::StringTooLong) => true,
+            (DataError::UnreadOverflow, DataError::UnreadOverflow) => true,
             _ => false,
         }
     }
 }
 
+impl std::fmt::Display for DataError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DataError::EndOfStream => write!(f, "Unexpected end of stream"),
+            DataError::Utf8Error => write!(f, "String is not valid UTF-8"),
+            DataError::StringTooLong => write!(f, "String exceeds the length limit"),
+            DataError::UnreadOverflow => write!(f, "Too many bytes returned to the provider"),
+            DataError::IOError(err) => write!(f, "I/O error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for DataError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DataError::IOError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
 impl DataProvider {
     pub fn new(reader: Box<dyn std::io::Read>) -> DataProvider {
+        DataProvider::with_adaptive_buffer(reader, 512, 64 * 1024)
+    }
+
+    pub fn with_buffer_size(reader: Box<dyn std::io::Read>, buffer_size: usize) -> DataProvider {
+        DataProvider::with_adaptive_buffer(reader, buffer_size, buffer_size)
+    }
+
+    // The buffer grows (up to the maximum size) when the reader keeps filling
+    // it completely, and shrinks back when reads return only small chunks.
+    pub fn with_adaptive_buffer(
+        reader: Box<dyn std::io::Read>,
+        min_buffer_size: usize,
+        max_buffer_size: usize,
+    ) -> DataProvider {
+        let min_buffer_size = std::cmp::max(min_buffer_size, 1);
         DataProvider {
             reader,
-            buffer: [0; 512],
+            buffer: vec![0; min_buffer_size],
             data_pointer: 0,
             data_available: 0,
+            position: 0,
+            max_string_length: None,
+            capture: None,
+            min_buffer_size,
+            max_buffer_size: std::cmp::max(max_buffer_size, min_buffer_size),
+            full_reads: 0,
+            short_reads: 0,
+            unread_bytes: 0,
+            max_unread_size: DEFAULT_MAX_UNREAD_SIZE,
+            tee: None,
+        }
+    }
+
+    // Maximum number of bytes which can be returned with unread() before
+    // they are consumed again.
+    pub fn set_max_unread_size(&mut self, max_unread_size: usize) {
+        self.max_unread_size = max_unread_size;
+    }
+
+    pub fn set_max_string_length(&mut self, max_string_length: Option<usize>) {
+        self.max_string_length = max_string_length;
+    }
+
+    pub fn get_buffer_size(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn get_position(&self) -> u64 {
+        self.position
+    }
+
+    // Sets the position of the current byte, e.g. when the underlying reader
+    // doesn't start at the beginning of the stream.
+    pub fn set_position(&mut self, position: u64) {
+        self.position = position;
+    }
+
+    // Keeps a copy of all the bytes consumed (read or skipped) until
+    // finish_capture() is called.
+    pub fn start_capture(&mut self) {
+        self.capture = Some(Vec::new());
+    }
+
+    pub fn finish_capture(&mut self) -> Vec<u8> {
+        self.capture.take().unwrap_or_default()
+    }
+
+    // Copies all the consumed bytes (read or skipped) to the writer, e.g. to
+    // archive a live stream while it's parsed. Bytes returned with unread()
+    // are assumed to be consumed before, so they aren't copied again. The
+    // provider doesn't buffer the writes.
+    pub fn set_tee(&mut self, tee: Option<Box<dyn std::io::Write>>) {
+        self.tee = tee;
+    }
+
+    // Returns the writer, so it can be flushed or closed.
+    pub fn take_tee(&mut self) -> Option<Box<dyn std::io::Write>> {
+        self.tee.take()
+    }
+
+    // Loads more data if everything in the buffer has been consumed; returns
+    // whether the buffer was refilled.
+    pub fn fill_buffer(&mut self) -> Result<bool, DataError> {
+        if self.data_pointer < self.data_available {
+            return Ok(false);
         }
+        self.ensure_data().map(|()| true)
     }
 
-    fn get_next_byte(&mut self) -> Result<u8, DataError> {
+    fn ensure_data(&mut self) -> Result<(), DataError> {
         if self.data_pointer == self.data_available {
             match self.load_data() {
                 Err(err) => return Err(DataError::IOError(err)),
@@ -44,33 +171,109 @@ impl DataProvider {
                 }
             }
         }
+        Ok(())
+    }
+
+    // Consumes bytes which are already in the buffer.
+    fn consume(&mut self, count: usize) -> Result<(), DataError> {
+        let data = &self.buffer[self.data_pointer..self.data_pointer + count];
+        if let Some(capture) = &mut self.capture {
+            capture.extend_from_slice(data);
+        }
+        if let Some(tee) = &mut self.tee {
+            let unread_count = std::cmp::min(self.unread_bytes, count);
+            tee.write_all(&data[unread_count..])
+                .map_err(DataError::IOError)?;
+        }
+        self.data_pointer += count;
+        self.position += count as u64;
+        self.unread_bytes = self.unread_bytes.saturating_sub(count);
+        Ok(())
+    }
+
+    // Returns bytes to the provider, so they are read again before the rest
+    // of the stream (the last byte of the slice is read last). Usually these
+    // are bytes which were just read, but any bytes can be pushed back. The
+    // position moves back by the number of bytes, and the bytes are removed
+    // from the capture.
+    pub fn unread(&mut self, data: &[u8]) -> Result<(), DataError> {
+        if self.unread_bytes + data.len() > self.max_unread_size {
+            return Err(DataError::UnreadOverflow);
+        }
+
+        if data.len() <= self.data_pointer {
+            self.data_pointer -= data.len();
+            self.buffer[self.data_pointer..self.data_pointer + data.len()].copy_from_slice(data);
+        } else {
+            let size = std::cmp::max(
+                self.buffer.len(),
+                data.len() + self.data_available - self.data_pointer,
+            );
+            let mut buffer = Vec::with_capacity(size);
+            buffer.extend_from_slice(data);
+            buffer.extend_from_slice(&self.buffer[self.data_pointer..self.data_available]);
+            self.data_available = buffer.len();
+            buffer.resize(size, 0);
+            self.buffer = buffer;
+            self.data_pointer = 0;
+        }
 
-        let data = Ok(self.buffer[self.data_pointer]);
-        self.data_pointer += 1;
-        data
+        self.unread_bytes += data.len();
+        self.position = self.position.saturating_sub(data.len() as u64);
+        if let Some(capture) = &mut self.capture {
+            capture.truncate(capture.len().saturating_sub(data.len()));
+        }
+        Ok(())
     }
 
+    // Data is copied from the buffer in chunks rather than byte by byte,
+    // which matters for string-heavy streams.
     pub fn read_bytes(&mut self, buffer: &mut [u8]) -> Result<(), DataError> {
-        // TODO do it more efficiently by copying a whole slice
-        for b in buffer {
-            *b = match self.get_next_byte() {
-                Ok(value) => value,
-                Err(err) => return Err(err),
-            }
+        let mut offset = 0;
+        while offset < buffer.len() {
+            self.ensure_data()?;
+            let chunk = std::cmp::min(
+                buffer.len() - offset,
+                self.data_available - self.data_pointer,
+            );
+            buffer[offset..offset + chunk]
+                .copy_from_slice(&self.buffer[self.data_pointer..self.data_pointer + chunk]);
+            self.consume(chunk)?;
+            offset += chunk;
         }
 
         Ok(())
     }
 
-    pub fn read_string(&mut self) -> Result<String, DataError> {
-        let mut data = std::vec::Vec::new();
+    // Consumes a zero-terminated string (including the terminator), passing
+    // its bytes to the function chunk by chunk.
+    fn consume_string<F: FnMut(&[u8])>(&mut self, mut f: F) -> Result<(), DataError> {
+        let mut length = 0;
         loop {
-            match self.get_next_byte() {
-                Ok(0) => break,
-                Ok(b) => data.push(b),
-                Err(err) => return Err(err),
+            self.ensure_data()?;
+            let available = &self.buffer[self.data_pointer..self.data_available];
+            let (chunk, terminated) = match available.iter().position(|b| *b == 0) {
+                Some(end) => (end, true),
+                None => (available.len(), false),
             };
+            if let Some(max_string_length) = self.max_string_length {
+                if length + chunk > max_string_length {
+                    self.consume(max_string_length - length + 1)?;
+                    return Err(DataError::StringTooLong);
+                }
+            }
+            f(&available[..chunk]);
+            length += chunk;
+            self.consume(chunk + terminated as usize)?;
+            if terminated {
+                return Ok(());
+            }
         }
+    }
+
+    pub fn read_string(&mut self) -> Result<String, DataError> {
+        let mut data = std::vec::Vec::new();
+        self.consume_string(|chunk| data.extend_from_slice(chunk))?;
 
         match String::from_utf8(data) {
             Ok(res) => Ok(res),
@@ -78,14 +281,71 @@ impl DataProvider {
         }
     }
 
+    pub fn skip_bytes(&mut self, count: usize) -> Result<(), DataError> {
+        let mut remaining = count;
+        while remaining > 0 {
+            self.ensure_data()?;
+            let chunk = std::cmp::min(remaining, self.data_available - self.data_pointer);
+            self.consume(chunk)?;
+            remaining -= chunk;
+        }
+
+        Ok(())
+    }
+
+    pub fn skip_string(&mut self) -> Result<(), DataError> {
+        self.consume_string(|_| {})
+    }
+
+    // Interrupted reads are retried; other errors (e.g. WouldBlock) are
+    // returned, and the next read continues where the reader stopped.
     fn load_data(&mut self) -> std::io::Result<usize> {
+        self.adapt_buffer_size();
         self.data_pointer = 0;
-        match self.reader.read(&mut self.buffer) {
-            Ok(size) => {
-                self.data_available = size;
-                Ok(size)
+        self.data_available = 0;
+        loop {
+            match self.reader.read(&mut self.buffer) {
+                Ok(size) => {
+                    self.data_available = size;
+                    self.update_read_stats(size);
+                    return Ok(size);
+                }
+                Err(ref err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
             }
-            Err(err) => Err(err),
+        }
+    }
+
+    fn update_read_stats(&mut self, size: usize) {
+        if size == self.buffer.len() {
+            self.full_reads += 1;
+            self.short_reads = 0;
+        } else if size > 0 && size < self.buffer.len() / 4 {
+            self.short_reads += 1;
+            self.full_reads = 0;
+        } else {
+            self.full_reads = 0;
+            self.short_reads = 0;
+        }
+    }
+
+    // Must only be called when all the buffered data has been consumed.
+    // The buffer may also need to shrink after it was enlarged by unread().
+    fn adapt_buffer_size(&mut self) {
+        let size = self.buffer.len();
+        let new_size = if self.full_reads >= GROW_AFTER_FULL_READS {
+            std::cmp::min(size * 2, self.max_buffer_size)
+        } else if self.short_reads >= SHRINK_AFTER_SHORT_READS {
+            std::cmp::max(size / 2, self.min_buffer_size)
+        } else {
+            std::cmp::min(size, self.max_buffer_size)
+        };
+
+        if new_size != size {
+            self.buffer.resize(new_size, 0);
+            self.buffer.shrink_to_fit();
+            self.full_reads = 0;
+            self.short_reads = 0;
         }
     }
 }
@@ -93,10 +353,10 @@ impl DataProvider {
 #[cfg(test)]
 pub mod tests {
     use super::*;
-    use hawktracer_parser_test_utilities::FakeDataReader;
+    use hawktracer_parser_test_utilities::{FakeDataReader, FakeDataWriter};
 
     fn buffers_equal(b1: &[u8], b2: &[u8]) -> usize {
-        return b1.iter().zip(b2).map(|(a, b)| assert_eq!(a, b)).count();
+        b1.iter().zip(b2).map(|(a, b)| assert_eq!(a, b)).count()
     }
 
     #[test]
@@ -126,6 +386,34 @@ pub mod tests {
         assert!(provider.read_bytes(&mut buf).is_err());
     }
 
+    #[test]
+    fn position_should_count_consumed_bytes() {
+        let mut provider =
+            DataProvider::new(Box::new(FakeDataReader::new(vec![1, 2, 65, 0, 3], false)));
+        let mut buf = [0u8; 2];
+        assert!(provider.read_bytes(&mut buf).is_ok());
+        assert_eq!(provider.get_position(), 2);
+
+        assert!(provider.read_string().is_ok());
+        assert_eq!(provider.get_position(), 4);
+    }
+
+    #[test]
+    fn skip_should_move_position_across_buffer_refills() {
+        let mut provider = DataProvider::with_buffer_size(
+            Box::new(FakeDataReader::new(vec![1, 2, 3, 65, 66, 0, 4], false)),
+            2,
+        );
+        assert!(provider.skip_bytes(3).is_ok());
+        assert!(provider.skip_string().is_ok());
+        assert_eq!(provider.get_position(), 6);
+
+        let mut buf = [0u8; 1];
+        assert!(provider.read_bytes(&mut buf).is_ok());
+        assert_eq!(buf[0], 4);
+        assert_eq!(provider.skip_bytes(1), Err(DataError::EndOfStream));
+    }
+
     #[test]
     fn read_string_should_not_fail_if_valid_string() {
         let mut provider = DataProvider::new(Box::new(FakeDataReader::new(vec![65, 66, 0], false)));
@@ -144,6 +432,50 @@ pub mod tests {
         assert!(message.is_err());
     }
 
+    #[test]
+    fn read_string_should_fail_if_string_exceeds_limit() {
+        let mut provider = DataProvider::new(Box::new(FakeDataReader::new(
+            vec![65, 66, 0, 65, 66, 67, 0],
+            false,
+        )));
+        provider.set_max_string_length(Some(2));
+
+        assert_eq!(provider.read_string().unwrap(), "AB");
+        assert_eq!(
+            provider.read_string().unwrap_err(),
+            DataError::StringTooLong
+        );
+    }
+
+    #[test]
+    fn strings_and_bytes_should_be_read_across_buffer_refills() {
+        let mut provider = DataProvider::with_buffer_size(
+            Box::new(FakeDataReader::new(
+                vec![65, 66, 67, 68, 69, 0, 1, 2, 3, 4, 5, 70, 71, 72, 0],
+                false,
+            )),
+            4,
+        );
+        provider.set_max_string_length(Some(5));
+        let mut buf = [0u8; 5];
+
+        assert_eq!(provider.read_string().unwrap(), "ABCDE");
+        provider.read_bytes(&mut buf).unwrap();
+        assert_eq!(buf, [1, 2, 3, 4, 5]);
+        provider.skip_string().unwrap();
+        assert_eq!(provider.get_position(), 15);
+    }
+
+    #[test]
+    fn small_buffer_should_not_affect_read_data() {
+        let mut provider =
+            DataProvider::with_buffer_size(Box::new(FakeDataReader::new(vec![1, 2, 3], false)), 1);
+        let mut buf = [0u8; 3];
+        assert!(provider.read_bytes(&mut buf).is_ok());
+
+        buffers_equal(&buf, &[1, 2, 3]);
+    }
+
     #[test]
     fn read_string_should_fail_if_non_utf8_string() {
         let mut provider =
@@ -152,4 +484,181 @@ pub mod tests {
         let message = provider.read_string();
         assert!(message.is_err());
     }
+
+    #[test]
+    fn capture_should_contain_read_and_skipped_bytes() {
+        let mut provider = DataProvider::with_buffer_size(
+            Box::new(FakeDataReader::new(vec![1, 2, 3, 65, 0, 4, 5], false)),
+            2,
+        );
+        let mut buf = [0u8; 1];
+        provider.read_bytes(&mut buf).unwrap();
+
+        provider.start_capture();
+        provider.skip_bytes(2).unwrap();
+        provider.skip_string().unwrap();
+        provider.read_bytes(&mut buf).unwrap();
+        assert_eq!(provider.finish_capture(), vec![2, 3, 65, 0, 4]);
+
+        provider.read_bytes(&mut buf).unwrap();
+        assert_eq!(provider.finish_capture(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn adaptive_buffer_should_grow_for_large_streams() {
+        let mut provider = DataProvider::with_adaptive_buffer(
+            Box::new(FakeDataReader::new(vec![7; 10000], false)),
+            16,
+            128,
+        );
+
+        provider.skip_bytes(9999).unwrap();
+        assert_eq!(provider.get_buffer_size(), 128);
+        let mut buf = [0u8; 1];
+        provider.read_bytes(&mut buf).unwrap();
+        assert_eq!(buf[0], 7);
+        assert_eq!(provider.get_position(), 10000);
+    }
+
+    #[test]
+    fn adaptive_buffer_should_shrink_for_small_reads() {
+        let mut provider = DataProvider::with_adaptive_buffer(
+            Box::new(FakeDataReader::new(vec![1; 1000], false).with_chunk_size(1)),
+            16,
+            128,
+        );
+        // Start with a grown buffer.
+        provider.buffer.resize(128, 0);
+        provider.skip_bytes(100).unwrap();
+        assert_eq!(provider.get_buffer_size(), 16);
+    }
+
+    #[test]
+    fn fixed_buffer_should_not_change_size() {
+        let mut provider =
+            DataProvider::with_buffer_size(Box::new(FakeDataReader::new(vec![7; 1000], false)), 16);
+        provider.skip_bytes(1000).unwrap();
+        assert_eq!(provider.get_buffer_size(), 16);
+    }
+
+    #[test]
+    fn interrupted_reads_should_be_retried() {
+        let reader = FakeDataReader::new(vec![1, 2, 3, 4, 5], false)
+            .with_chunk_size(2)
+            .with_error_at(0, std::io::ErrorKind::Interrupted)
+            .with_error_at(3, std::io::ErrorKind::Interrupted);
+        let mut provider = DataProvider::new(Box::new(reader));
+        let mut buf = [0u8; 5];
+        provider.read_bytes(&mut buf).unwrap();
+        assert_eq!(buf, [1, 2, 3, 4, 5]);
+        assert_eq!(
+            provider.read_bytes(&mut buf[..1]),
+            Err(DataError::EndOfStream)
+        );
+    }
+
+    #[test]
+    fn reading_should_continue_after_would_block() {
+        let reader = FakeDataReader::new(vec![1, 2, 65, 0, 3], false)
+            .with_error_at(2, std::io::ErrorKind::WouldBlock);
+        let mut provider = DataProvider::new(Box::new(reader));
+        let mut buf = [0u8; 2];
+        provider.read_bytes(&mut buf).unwrap();
+        match provider.read_string() {
+            Err(DataError::IOError(err)) => assert_eq!(err.kind(), std::io::ErrorKind::WouldBlock),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        assert_eq!(provider.read_string(), Ok("A".to_owned()));
+        provider.read_bytes(&mut buf[..1]).unwrap();
+        assert_eq!(buf[0], 3);
+        assert_eq!(provider.get_position(), 5);
+    }
+
+    #[test]
+    fn should_fail_after_reader_stops_returning_data() {
+        let reader = FakeDataReader::new(vec![1; 10], false)
+            .with_chunk_size(3)
+            .with_fail_after(4);
+        let mut provider = DataProvider::new(Box::new(reader));
+        let mut buf = [0u8; 4];
+        provider.read_bytes(&mut buf).unwrap();
+        assert!(matches!(
+            provider.read_bytes(&mut buf[..1]),
+            Err(DataError::IOError(_))
+        ));
+    }
+
+    #[test]
+    fn unread_bytes_should_be_read_again() {
+        let mut provider = DataProvider::with_buffer_size(
+            Box::new(FakeDataReader::new(vec![1, 2, 3, 4, 5], false)),
+            2,
+        );
+        let mut buf = [0u8; 3];
+        provider.start_capture();
+        provider.read_bytes(&mut buf).unwrap();
+        // Only the last byte is still in the buffer.
+        provider.unread(&buf[1..]).unwrap();
+        assert_eq!(provider.get_position(), 1);
+        assert_eq!(provider.finish_capture(), vec![1]);
+
+        let mut buf = [0u8; 4];
+        provider.read_bytes(&mut buf).unwrap();
+        assert_eq!(buf, [2, 3, 4, 5]);
+        provider.unread(&[5]).unwrap();
+        provider.unread(&[9]).unwrap();
+        provider.read_bytes(&mut buf[..2]).unwrap();
+        assert_eq!(buf[..2], [9, 5]);
+        assert_eq!(
+            provider.read_bytes(&mut buf[..1]),
+            Err(DataError::EndOfStream)
+        );
+        assert_eq!(provider.get_position(), 5);
+    }
+
+    #[test]
+    fn unread_should_be_bounded() {
+        let mut provider = DataProvider::new(Box::new(FakeDataReader::new(vec![0; 8], false)));
+        provider.set_max_unread_size(4);
+        provider.unread(&[1, 2, 3]).unwrap();
+        assert_eq!(provider.unread(&[4, 5]), Err(DataError::UnreadOverflow));
+
+        // Consumed bytes no longer count to the limit.
+        let mut buf = [0u8; 2];
+        provider.read_bytes(&mut buf).unwrap();
+        provider.unread(&[4, 5]).unwrap();
+        assert_eq!(provider.read_string(), Ok("\u{4}\u{5}\u{3}".to_owned()));
+    }
+
+    #[test]
+    fn tee_should_receive_consumed_bytes_once() {
+        let mut provider = DataProvider::with_buffer_size(
+            Box::new(FakeDataReader::new(b"abcdef\0gh".to_vec(), false)),
+            3,
+        );
+        let tee = FakeDataWriter::new(false);
+        provider.set_tee(Some(Box::new(tee.clone())));
+        let mut buf = [0u8; 2];
+        provider.read_bytes(&mut buf).unwrap();
+        provider.unread(&buf[1..]).unwrap();
+        assert_eq!(tee.get_data(), b"ab");
+        assert_eq!(provider.read_string(), Ok("bcdef".to_owned()));
+        assert_eq!(tee.get_data(), b"abcdef\0");
+
+        assert!(provider.take_tee().is_some());
+        provider.read_bytes(&mut buf).unwrap();
+        assert_eq!(tee.get_data(), b"abcdef\0");
+    }
+
+    #[test]
+    fn tee_errors_should_be_returned() {
+        let mut provider = DataProvider::new(Box::new(FakeDataReader::new(vec![1, 2], false)));
+        provider.set_tee(Some(Box::new(FakeDataWriter::new(true))));
+        let mut buf = [0u8; 1];
+        assert!(matches!(
+            provider.read_bytes(&mut buf),
+            Err(DataError::IOError(_))
+        ));
+    }
 }
@@ -1,32 +1,112 @@
 use crate::data_provider::{DataError, DataProvider};
-use crate::event::{DataType, Event, Value};
+use crate::event::{ArrayLength, DataType, Event, IntegerType, Value};
 use crate::event_klass::{EventKlass, EventKlassField};
+use crate::field_projection::FieldProjection;
+use crate::quota::QuotaViolation;
 use crate::registry::EventKlassRegistry;
+use crate::registry_updater::RegistryUpdateError;
 
 #[derive(Debug, PartialEq)]
 pub enum ReadEventError {
     DataError(DataError),
     UnknownKlass(String),
     UnknownKlassId(u32),
-    RegistryUpdateFailed(String),
+    RegistryUpdateFailed(RegistryUpdateError),
+    MissingBaseKlass,
+    MissingBaseEvent,
+    MissingField(String),
+    QuotaExceeded(QuotaViolation),
+    // Structs are nested deeper than the limit.
+    StructDepthExceeded(usize),
+    // Struct of the klass (directly or indirectly) contains itself.
+    KlassCycle(String),
+    // Klass has fewer fields than declared by its KlassInfo event.
+    IncompleteKlass(u32),
+    // Size of an array with the given number of elements overflows usize.
+    ArrayTooLarge(usize),
+}
+
+impl std::fmt::Display for ReadEventError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ReadEventError::DataError(err) => write!(f, "{}", err),
+            ReadEventError::UnknownKlass(name) => write!(f, "Unknown klass {}", name),
+            ReadEventError::UnknownKlassId(id) => write!(f, "Unknown klass id {}", id),
+            ReadEventError::RegistryUpdateFailed(err) => {
+                write!(f, "Cannot update the registry: {}", err)
+            }
+            ReadEventError::MissingBaseKlass => write!(f, "Missing base klass"),
+            ReadEventError::MissingBaseEvent => write!(f, "Missing base event"),
+            ReadEventError::MissingField(name) => write!(f, "Missing field {}", name),
+            ReadEventError::QuotaExceeded(violation) => match violation {
+                QuotaViolation::ByteRate(limit) => {
+                    write!(f, "Quota exceeded: more than {} bytes per second", limit)
+                }
+                QuotaViolation::KlassCount(limit) => {
+                    write!(f, "Quota exceeded: more than {} klasses", limit)
+                }
+                QuotaViolation::BufferedBytes(limit) => {
+                    write!(f, "Quota exceeded: more than {} buffered bytes", limit)
+                }
+            },
+            ReadEventError::StructDepthExceeded(limit) => {
+                write!(f, "Structs are nested deeper than {} levels", limit)
+            }
+            ReadEventError::KlassCycle(name) => write!(f, "Klass {} contains itself", name),
+            ReadEventError::IncompleteKlass(id) => {
+                write!(f, "Klass {} has fewer fields than declared", id)
+            }
+            ReadEventError::ArrayTooLarge(count) => {
+                write!(f, "Array of {} elements is too large", count)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReadEventError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReadEventError::DataError(err) => Some(err),
+            ReadEventError::RegistryUpdateFailed(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+pub const DEFAULT_MAX_STRUCT_DEPTH: usize = 32;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Endianness {
+    Little,
+    Big,
+    Native,
 }
 
 pub struct DataStructReader<'a> {
     data_provider: &'a mut DataProvider,
     registry: &'a EventKlassRegistry,
-    base_event: Option<Event>,
+    base_event: Option<Box<Event>>,
     klass: &'a EventKlass,
+    endianness: Endianness,
+    flatten: bool,
+    compact: bool,
+    projection: Option<&'a FieldProjection>,
+    max_struct_depth: usize,
+    // Klasses of the structs being decoded (excluding the top-level klass).
+    struct_stack: Vec<u32>,
 }
 
+use crate::event::{Storage, Values};
+
 macro_rules! get_integer {
     ($self: ident, $type: ty, $size: expr, $data_type: ident) => {{
         let mut buffer: [u8; $size] = [0; $size];
         match $self.data_provider.read_bytes(&mut buffer) {
-            Ok(()) => unsafe {
-                Ok(Value::$data_type(
-                    std::mem::transmute::<[u8; $size], $type>(buffer),
-                ))
-            },
+            Ok(()) => Ok(Value::$data_type(match $self.endianness {
+                Endianness::Little => <$type>::from_le_bytes(buffer),
+                Endianness::Big => <$type>::from_be_bytes(buffer),
+                Endianness::Native => <$type>::from_ne_bytes(buffer),
+            })),
             Err(err) => Err(ReadEventError::DataError(err)),
         }
     }};
@@ -42,22 +122,241 @@ impl<'a> DataStructReader<'a> {
         DataStructReader {
             data_provider,
             registry,
-            base_event,
+            base_event: base_event.map(Box::new),
             klass,
+            endianness: Endianness::Native,
+            flatten: false,
+            compact: false,
+            projection: None,
+            max_struct_depth: DEFAULT_MAX_STRUCT_DEPTH,
+            struct_stack: Vec::new(),
         }
     }
 
+    // Same as passing the base event to new(), but avoids boxing it again.
+    pub fn with_base_event(mut self, base_event: Box<Event>) -> DataStructReader<'a> {
+        self.base_event = Some(base_event);
+        self
+    }
+
+    pub fn with_projection(
+        mut self,
+        projection: Option<&'a FieldProjection>,
+    ) -> DataStructReader<'a> {
+        self.projection = projection;
+        self
+    }
+
+    // Produces the same result as Event::flat_event(), but base fields are
+    // written directly to the event instead of building nested events first.
+    pub fn with_flatten(mut self, flatten: bool) -> DataStructReader<'a> {
+        self.flatten = flatten;
+        self
+    }
+
+    // Decoded events (including nested ones) use the compact layout, see
+    // Event::compact().
+    pub fn with_compact(mut self, compact: bool) -> DataStructReader<'a> {
+        self.compact = compact;
+        self
+    }
+
+    // Streams defining deeply nested (or self-referencing) klasses fail with
+    // an error instead of overflowing the stack.
+    pub fn with_max_struct_depth(mut self, max_struct_depth: usize) -> DataStructReader<'a> {
+        self.max_struct_depth = max_struct_depth;
+        self
+    }
+
+    pub fn with_endianness(mut self, endianness: Endianness) -> DataStructReader<'a> {
+        self.endianness = endianness;
+        self
+    }
+
     pub fn read_event(&mut self) -> Result<Event, ReadEventError> {
-        self.read_event_internal(self.klass)
+        if self.flatten {
+            self.read_flat_event()
+        } else {
+            self.read_event_internal(self.klass)
+        }
+    }
+
+    // Decodes the event into the given one, reusing the storage of its
+    // values. Flattening is not supported. If decoding fails, the content of
+    // the event is unspecified.
+    pub fn read_event_into(&mut self, event: &mut Event) -> Result<(), ReadEventError> {
+        let klass = self.klass;
+        let values = event.reset(klass.get_id(), self.compact);
+        values.retain(|name| {
+            klass
+                .get_fields()
+                .iter()
+                .any(|field| field.get_name() == name && self.is_projected(klass, field))
+        });
+
+        for field in klass.get_fields() {
+            if !self.is_projected(klass, field) {
+                self.skip_field(field)?;
+                continue;
+            }
+            let value = self.read_field(field)?;
+            match values.get_mut(field.get_name()) {
+                Some(old_value) => *old_value = value,
+                None => {
+                    values.insert(field.get_shared_name().clone(), value);
+                }
+            }
+        }
+
+        Ok(())
     }
 
     fn read_event_internal(&mut self, klass: &EventKlass) -> Result<Event, ReadEventError> {
-        let mut values = std::collections::HashMap::<String, Value, fnv::FnvBuildHasher>::default();
+        if self.compact {
+            let mut values = Vec::with_capacity(klass.get_fields().len());
+            for field in klass.get_fields() {
+                if self.is_projected(klass, field) {
+                    values.push((field.get_shared_name().clone(), self.read_field(field)?));
+                } else {
+                    self.skip_field(field)?;
+                }
+            }
+            return Ok(Event::from_compact_values(klass.get_id(), values));
+        }
+
+        let mut values = Values::default();
         for field in klass.get_fields() {
-            values.insert(field.get_name().clone(), self.read_field(&field)?);
+            if self.is_projected(klass, field) {
+                values.insert(field.get_shared_name().clone(), self.read_field(field)?);
+            } else {
+                self.skip_field(field)?;
+            }
+        }
+
+        Ok(Event::from_values(klass.get_id(), values))
+    }
+
+    fn read_flat_event(&mut self) -> Result<Event, ReadEventError> {
+        let mut values = Storage::new(self.compact);
+        self.read_flat_fields(self.klass, &mut values)?;
+
+        Ok(Event::from_storage(self.klass.get_id(), values))
+    }
+
+    // Fields of base events override fields of the derived event, as in flat_event().
+    fn read_flat_fields(
+        &mut self,
+        klass: &EventKlass,
+        values: &mut Storage,
+    ) -> Result<(), ReadEventError> {
+        for field in klass.get_fields() {
+            if !self.is_projected(klass, field) {
+                self.skip_field(field)?;
+            } else if field.get_name() != "base" || *field.get_data_type() != DataType::Struct {
+                let value = self.read_field(field)?;
+                values.insert_if_absent(field.get_shared_name().clone(), value);
+            } else if field.get_type_name() == "HT_Event" {
+                match self.base_event.take() {
+                    Some(base_event) => base_event.flat_event_internal(values),
+                    None => return Err(ReadEventError::MissingBaseEvent),
+                }
+            } else if let Some(base_klass) = self.registry.get_klass_by_name(field.get_type_name())
+            {
+                self.enter_struct(base_klass)?;
+                let result = self.read_flat_fields(base_klass, values);
+                self.struct_stack.pop();
+                result?;
+            } else {
+                return Err(ReadEventError::UnknownKlass(field.get_type_name().clone()));
+            }
         }
 
-        Ok(Event::new(klass.get_id(), values))
+        Ok(())
+    }
+
+    pub fn skip_event(&mut self) -> Result<(), ReadEventError> {
+        self.skip_fields(self.klass)
+    }
+
+    fn skip_fields(&mut self, klass: &EventKlass) -> Result<(), ReadEventError> {
+        if let Some(size) = self.get_fixed_size(klass, 0) {
+            return self.skip_bytes(size);
+        }
+
+        for field in klass.get_fields() {
+            self.skip_field(field)?;
+        }
+
+        Ok(())
+    }
+
+    fn skip_field(&mut self, field: &EventKlassField) -> Result<(), ReadEventError> {
+        match field.get_data_type() {
+            DataType::Str => match self.data_provider.skip_string() {
+                Ok(()) => Ok(()),
+                Err(err) => Err(ReadEventError::DataError(err)),
+            },
+            DataType::Struct => {
+                if field.is_base_event_field() {
+                    return Ok(());
+                }
+                match self.registry.get_klass_by_name(field.get_type_name()) {
+                    Some(klass) => {
+                        self.enter_struct(klass)?;
+                        let result = self.skip_fields(klass);
+                        self.struct_stack.pop();
+                        result
+                    }
+                    None => Err(ReadEventError::UnknownKlass(field.get_type_name().clone())),
+                }
+            }
+            DataType::Array(element_type, ArrayLength::Prefixed) => {
+                let count = self.read_array_count()?;
+                self.skip_bytes(get_array_size(*element_type, count)?)
+            }
+            data_type => self.skip_bytes(data_type.get_fixed_size().unwrap_or(0)),
+        }
+    }
+
+    fn is_projected(&self, klass: &EventKlass, field: &EventKlassField) -> bool {
+        match self.projection {
+            Some(projection) => projection.is_field_included(klass, field.get_name()),
+            None => true,
+        }
+    }
+
+    fn skip_bytes(&mut self, size: usize) -> Result<(), ReadEventError> {
+        match self.data_provider.skip_bytes(size) {
+            Ok(()) => Ok(()),
+            Err(err) => Err(ReadEventError::DataError(err)),
+        }
+    }
+
+    // Size of the encoded payload, if none of the fields (including nested ones)
+    // has a variable length. None is also returned for structs nested deeper
+    // than the limit, so skip_fields() reports the error, and if the size
+    // overflows usize.
+    fn get_fixed_size(&self, klass: &EventKlass, depth: usize) -> Option<usize> {
+        if depth > self.max_struct_depth {
+            return None;
+        }
+        let mut size: usize = 0;
+        for field in klass.get_fields() {
+            let field_size = match field.get_data_type() {
+                DataType::Str => return None,
+                DataType::Struct => {
+                    if field.is_base_event_field() {
+                        0
+                    } else {
+                        let klass = self.registry.get_klass_by_name(field.get_type_name())?;
+                        self.get_fixed_size(klass, depth + 1)?
+                    }
+                }
+                data_type => data_type.get_fixed_size()?,
+            };
+            size = size.checked_add(field_size)?;
+        }
+        Some(size)
     }
 
     fn read_field(&mut self, field: &EventKlassField) -> Result<Value, ReadEventError> {
@@ -72,18 +371,74 @@ impl<'a> DataStructReader<'a> {
             DataType::I64 => get_integer!(self, i64, 8, I64),
             DataType::Str => self.read_string(),
             DataType::Struct => self.read_struct(field),
+            DataType::Bytes(size) => self.read_bytes(*size),
+            DataType::Array(element_type, length) => self.read_array(*element_type, *length),
+        }
+    }
+
+    fn read_integer(&mut self, integer_type: IntegerType) -> Result<Value, ReadEventError> {
+        match integer_type {
+            IntegerType::U8 => get_integer!(self, u8, 1, U8),
+            IntegerType::I8 => get_integer!(self, i8, 1, I8),
+            IntegerType::U16 => get_integer!(self, u16, 2, U16),
+            IntegerType::I16 => get_integer!(self, i16, 2, I16),
+            IntegerType::U32 => get_integer!(self, u32, 4, U32),
+            IntegerType::I32 => get_integer!(self, i32, 4, I32),
+            IntegerType::U64 => get_integer!(self, u64, 8, U64),
+            IntegerType::I64 => get_integer!(self, i64, 8, I64),
+        }
+    }
+
+    fn read_array_count(&mut self) -> Result<usize, ReadEventError> {
+        let count = self.read_integer(IntegerType::U32)?;
+        Ok(count.as_u64().unwrap_or_default() as usize)
+    }
+
+    // As with bytes, the count may come from the stream, so the array is
+    // not preallocated.
+    fn read_array(
+        &mut self,
+        element_type: IntegerType,
+        length: ArrayLength,
+    ) -> Result<Value, ReadEventError> {
+        let count = match length {
+            ArrayLength::Fixed(count) => count,
+            ArrayLength::Prefixed => self.read_array_count()?,
+        };
+        let mut values = Vec::new();
+        for _ in 0..count {
+            values.push(self.read_integer(element_type)?);
+        }
+        Ok(Value::Array(values))
+    }
+
+    // The size comes from the stream, so the buffer grows as the data is read
+    // instead of being allocated upfront.
+    fn read_bytes(&mut self, size: usize) -> Result<Value, ReadEventError> {
+        let mut data = Vec::new();
+        let mut chunk = [0; 4096];
+        while data.len() < size {
+            let chunk_size = std::cmp::min(chunk.len(), size - data.len());
+            match self.data_provider.read_bytes(&mut chunk[..chunk_size]) {
+                Ok(()) => data.extend_from_slice(&chunk[..chunk_size]),
+                Err(err) => return Err(ReadEventError::DataError(err)),
+            }
         }
+        Ok(Value::Bytes(data))
     }
 
     fn read_struct(&mut self, field: &EventKlassField) -> Result<Value, ReadEventError> {
-        if field.get_type_name() == "HT_Event" && field.get_name() == "base" {
-            let base_event = std::mem::replace(&mut self.base_event, None);
-            Ok(Value::Struct(base_event.expect(
-                "Base event must be provided for non-base events.",
-            )))
+        if field.is_base_event_field() {
+            match self.base_event.take() {
+                Some(base_event) => Ok(Value::Struct(base_event)),
+                None => Err(ReadEventError::MissingBaseEvent),
+            }
         } else if let Some(klass) = self.registry.get_klass_by_name(field.get_type_name()) {
-            match self.read_event_internal(klass) {
-                Ok(value) => Ok(Value::Struct(value)),
+            self.enter_struct(klass)?;
+            let result = self.read_event_internal(klass);
+            self.struct_stack.pop();
+            match result {
+                Ok(value) => Ok(Value::Struct(Box::new(value))),
                 Err(err) => Err(err),
             }
         } else {
@@ -91,6 +446,19 @@ impl<'a> DataStructReader<'a> {
         }
     }
 
+    // Has to be followed by popping the klass from the stack once the struct
+    // is decoded.
+    fn enter_struct(&mut self, klass: &EventKlass) -> Result<(), ReadEventError> {
+        if klass.get_id() == self.klass.get_id() || self.struct_stack.contains(&klass.get_id()) {
+            return Err(ReadEventError::KlassCycle(klass.get_name().clone()));
+        }
+        if self.struct_stack.len() >= self.max_struct_depth {
+            return Err(ReadEventError::StructDepthExceeded(self.max_struct_depth));
+        }
+        self.struct_stack.push(klass.get_id());
+        Ok(())
+    }
+
     fn read_string(&mut self) -> Result<Value, ReadEventError> {
         match self.data_provider.read_string() {
             Ok(data) => Ok(Value::Str(data)),
@@ -99,6 +467,13 @@ impl<'a> DataStructReader<'a> {
     }
 }
 
+fn get_array_size(element_type: IntegerType, count: usize) -> Result<usize, ReadEventError> {
+    element_type
+        .get_size()
+        .checked_mul(count)
+        .ok_or(ReadEventError::ArrayTooLarge(count))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -162,6 +537,85 @@ mod tests {
             value_from_bytes(vec![65, 66, 67, 0], DataType::Str),
             Value::Str("ABC".to_owned())
         );
+
+        assert_eq!(
+            value_from_bytes(vec![0, 66, 255, 7], DataType::Bytes(3)),
+            Value::Bytes(vec![0, 66, 255])
+        );
+        assert_eq!(
+            value_from_bytes(vec![], DataType::Bytes(0)),
+            Value::Bytes(vec![])
+        );
+
+        assert_eq!(
+            value_from_bytes(
+                vec![1, 0, 255, 255],
+                DataType::Array(IntegerType::I16, ArrayLength::Fixed(2))
+            ),
+            Value::Array(vec![Value::I16(1), Value::I16(-1)])
+        );
+        assert_eq!(
+            value_from_bytes(
+                vec![3, 0, 0, 0, 7, 8, 9],
+                DataType::Array(IntegerType::U8, ArrayLength::Prefixed)
+            ),
+            Value::Array(vec![Value::U8(7), Value::U8(8), Value::U8(9)])
+        );
+    }
+
+    #[test]
+    fn array_size_overflow_should_be_reported() {
+        assert_eq!(get_array_size(IntegerType::U16, 3), Ok(6));
+        assert_eq!(
+            get_array_size(IntegerType::U64, usize::MAX / 4),
+            Err(ReadEventError::ArrayTooLarge(usize::MAX / 4))
+        );
+    }
+
+    #[test]
+    fn prefixed_array_should_be_skipped() {
+        let mut klass = EventKlass::new(100, "foo".to_owned());
+        klass.add_field(
+            "samples".to_owned(),
+            "uint16_t[]".to_owned(),
+            DataType::Array(IntegerType::U16, ArrayLength::Prefixed),
+        );
+        klass.add_field("u8_field".to_owned(), "uint8_t".to_owned(), DataType::U8);
+        let mut reg = EventKlassRegistry::new();
+        reg.add_klass(klass.clone());
+        let projection = FieldProjection::new().include("foo", &["u8_field"]);
+
+        let mut data_provider = DataProvider::new(Box::new(FakeDataReader::new(
+            vec![2, 0, 0, 0, 1, 0, 2, 0, 42],
+            false,
+        )));
+        let event = DataStructReader::new(&mut data_provider, &reg, &klass, None)
+            .with_projection(Some(&projection))
+            .read_event()
+            .unwrap();
+
+        assert_eq!(event.get_value_count(), 1);
+        assert_eq!(event.get_value_u8("u8_field").unwrap(), 42);
+    }
+
+    #[test]
+    fn read_field_value_with_explicit_endianness() {
+        let read = |endianness| {
+            let mut data_provider =
+                DataProvider::new(Box::new(FakeDataReader::new(vec![1, 2], false)));
+            let klass = EventKlass::new(99, "foo".to_owned());
+            DataStructReader::new(&mut data_provider, &EventKlassRegistry::new(), &klass, None)
+                .with_endianness(endianness)
+                .read_field(&EventKlassField::new(
+                    "foo".to_owned(),
+                    "bar".to_owned(),
+                    DataType::U16,
+                ))
+                .unwrap()
+        };
+
+        assert_eq!(read(Endianness::Little), Value::U16(513));
+        assert_eq!(read(Endianness::Big), Value::U16(258));
     }
 
     #[test]
@@ -192,17 +646,148 @@ mod tests {
 
         let res = reader.read_event().unwrap();
         assert_eq!(res.get_klass_id(), 100);
-        match res.get_raw_value(&"child_klass").unwrap() {
+        match res.get_raw_value("child_klass").unwrap() {
             Value::Struct(event) => {
-                assert_eq!(event.get_raw_value(&"i8_field").unwrap(), &Value::I8(-128))
+                assert_eq!(event.get_raw_value("i8_field").unwrap(), &Value::I8(-128))
             }
-            _ => assert!(false),
+            _ => panic!("child_klass is not a struct"),
         };
         assert_eq!(
-            res.get_raw_value(&"str_field").unwrap(),
+            res.get_raw_value("str_field").unwrap(),
             &Value::Str("ABC".to_owned())
         );
-        assert_eq!(res.get_raw_value(&"u32_field").unwrap(), &Value::U32(301));
+        assert_eq!(res.get_raw_value("u32_field").unwrap(), &Value::U32(301));
+    }
+
+    #[test]
+    fn flat_reading_should_match_flattened_event() {
+        let mut middle_klass = EventKlass::new(99, "Middle".to_owned());
+        middle_klass.add_field("base".to_owned(), "HT_Event".to_owned(), DataType::Struct);
+        middle_klass.add_field("timestamp".to_owned(), "uint8_t".to_owned(), DataType::U8);
+        middle_klass.add_field("duration".to_owned(), "uint8_t".to_owned(), DataType::U8);
+
+        let mut klass = EventKlass::new(100, "foo".to_owned());
+        klass.add_field("base".to_owned(), "Middle".to_owned(), DataType::Struct);
+        klass.add_field("duration".to_owned(), "uint8_t".to_owned(), DataType::U8);
+        klass.add_field("label".to_owned(), "char*".to_owned(), DataType::Str);
+
+        let mut reg = EventKlassRegistry::new();
+        reg.add_klass(middle_klass);
+
+        let read = |flatten| {
+            let mut base_values = fnv::FnvHashMap::default();
+            base_values.insert("timestamp".to_owned(), Value::U64(5));
+            base_values.insert("id".to_owned(), Value::U64(6));
+            let base_event = Event::new(1, base_values);
+
+            let data = vec![7, 8, 9, 65, 0];
+            let mut data_provider = DataProvider::new(Box::new(FakeDataReader::new(data, false)));
+            DataStructReader::new(&mut data_provider, &reg, &klass, Some(base_event))
+                .with_flatten(flatten)
+                .read_event()
+                .unwrap()
+        };
+
+        let flat_event = read(true);
+        assert_eq!(flat_event, read(false).flat_event());
+        assert_eq!(flat_event.get_value_u64("timestamp").unwrap(), 5);
+        assert_eq!(flat_event.get_value_u8("duration").unwrap(), 8);
+        assert_eq!(flat_event.get_value_count(), 4);
+    }
+
+    #[test]
+    fn skip_event_should_consume_whole_payload() {
+        let mut child_klass = EventKlass::new(99, "ChildKlass".to_owned());
+        child_klass.add_field("u16_field".to_owned(), "uint16_t".to_owned(), DataType::U16);
+
+        let mut fixed_klass = EventKlass::new(100, "fixed".to_owned());
+        fixed_klass.add_field("base".to_owned(), "HT_Event".to_owned(), DataType::Struct);
+        fixed_klass.add_field(
+            "child".to_owned(),
+            "ChildKlass".to_owned(),
+            DataType::Struct,
+        );
+        fixed_klass.add_field("u32_field".to_owned(), "uint32_t".to_owned(), DataType::U32);
+
+        let mut variable_klass = EventKlass::new(101, "variable".to_owned());
+        variable_klass.add_field("str_field".to_owned(), "char*".to_owned(), DataType::Str);
+        variable_klass.add_field(
+            "child".to_owned(),
+            "ChildKlass".to_owned(),
+            DataType::Struct,
+        );
+
+        let mut reg = EventKlassRegistry::new();
+        reg.add_klass(child_klass);
+
+        let data = vec![1, 2, 3, 4, 5, 6, 65, 66, 0, 7, 8, 9];
+        let mut data_provider = DataProvider::new(Box::new(FakeDataReader::new(data, false)));
+
+        let reader = DataStructReader::new(&mut data_provider, &reg, &fixed_klass, None);
+        assert_eq!(reader.get_fixed_size(&fixed_klass, 0), Some(6));
+        assert_eq!(reader.get_fixed_size(&variable_klass, 0), None);
+
+        assert!(
+            DataStructReader::new(&mut data_provider, &reg, &fixed_klass, None)
+                .skip_event()
+                .is_ok()
+        );
+        assert_eq!(data_provider.get_position(), 6);
+        assert!(
+            DataStructReader::new(&mut data_provider, &reg, &variable_klass, None)
+                .skip_event()
+                .is_ok()
+        );
+        assert_eq!(data_provider.get_position(), 11);
+    }
+
+    #[test]
+    fn reader_should_skip_fields_not_included_in_projection() {
+        let mut child_klass = EventKlass::new(99, "ChildKlass".to_owned());
+        child_klass.add_field("a".to_owned(), "uint16_t".to_owned(), DataType::U16);
+        child_klass.add_field("b".to_owned(), "uint8_t".to_owned(), DataType::U8);
+
+        let mut klass = EventKlass::new(100, "foo".to_owned());
+        klass.add_field(
+            "child".to_owned(),
+            "ChildKlass".to_owned(),
+            DataType::Struct,
+        );
+        klass.add_field("str_field".to_owned(), "char*".to_owned(), DataType::Str);
+        klass.add_field("u32_field".to_owned(), "uint32_t".to_owned(), DataType::U32);
+
+        let mut reg = EventKlassRegistry::new();
+        reg.add_klass(child_klass);
+
+        let projection = FieldProjection::new()
+            .include("foo", &["child", "u32_field"])
+            .include("ChildKlass", &["b"]);
+        let data = vec![1, 2, 3, 65, 66, 0, 45, 1, 0, 0];
+
+        for flatten in &[false, true] {
+            let mut data_provider =
+                DataProvider::new(Box::new(FakeDataReader::new(data.clone(), false)));
+            let event = DataStructReader::new(&mut data_provider, &reg, &klass, None)
+                .with_projection(Some(&projection))
+                .with_flatten(*flatten)
+                .read_event()
+                .unwrap();
+
+            assert_eq!(event.get_value_count(), 2);
+            assert_eq!(event.get_value_u32("u32_field").unwrap(), 301);
+            assert_eq!(
+                event.get_value_struct("child").unwrap().get_value_count(),
+                1
+            );
+            assert_eq!(
+                event
+                    .get_value_struct("child")
+                    .unwrap()
+                    .get_value_u8("b")
+                    .unwrap(),
+                3
+            );
+        }
     }
 
     #[test]
@@ -231,6 +816,21 @@ mod tests {
         );
     }
 
+    #[test]
+    fn reader_should_fail_if_base_event_is_not_provided() {
+        let mut klass = EventKlass::new(100, "foo".to_owned());
+        klass.add_field("base".to_owned(), "HT_Event".to_owned(), DataType::Struct);
+
+        let reg = EventKlassRegistry::new();
+        let mut data_provider = DataProvider::new(Box::new(FakeDataReader::new(vec![], false)));
+        let mut reader = DataStructReader::new(&mut data_provider, &reg, &klass, None);
+
+        assert_eq!(
+            ReadEventError::MissingBaseEvent,
+            reader.read_event().unwrap_err()
+        );
+    }
+
     #[test]
     fn reader_should_fail_for_invalid_string() {
         let data = vec![65, 66, 67];
@@ -247,4 +847,67 @@ mod tests {
 
         assert_eq!(ReadEventError::DataError(DataError::EndOfStream), err);
     }
+
+    #[test]
+    fn cyclic_klasses_should_fail() {
+        let mut klass_a = EventKlass::new(100, "A".to_owned());
+        klass_a.add_field("b".to_owned(), "B".to_owned(), DataType::Struct);
+        let mut klass_b = EventKlass::new(101, "B".to_owned());
+        klass_b.add_field("base".to_owned(), "A".to_owned(), DataType::Struct);
+        let mut reg = EventKlassRegistry::new();
+        reg.add_klass(klass_a.clone());
+        reg.add_klass(klass_b.clone());
+
+        let mut data_provider = DataProvider::new(Box::new(FakeDataReader::new(vec![0; 8], false)));
+        let mut reader = DataStructReader::new(&mut data_provider, &reg, &klass_a, None);
+        assert_eq!(
+            reader.read_event(),
+            Err(ReadEventError::KlassCycle("A".to_owned()))
+        );
+        assert_eq!(
+            reader.skip_event(),
+            Err(ReadEventError::KlassCycle("A".to_owned()))
+        );
+
+        let mut reader =
+            DataStructReader::new(&mut data_provider, &reg, &klass_b, None).with_flatten(true);
+        assert_eq!(
+            reader.read_event(),
+            Err(ReadEventError::KlassCycle("B".to_owned()))
+        );
+    }
+
+    #[test]
+    fn structs_nested_deeper_than_limit_should_fail() {
+        let mut reg = EventKlassRegistry::new();
+        for id in 100..103 {
+            let mut klass = EventKlass::new(id, format!("K{}", id));
+            klass.add_field("value".to_owned(), "uint8_t".to_owned(), DataType::U8);
+            if id < 102 {
+                klass.add_field("child".to_owned(), format!("K{}", id + 1), DataType::Struct);
+            }
+            reg.add_klass(klass);
+        }
+        let klass = reg.get_klass_by_id(100).unwrap();
+
+        let read = |max_struct_depth| {
+            let mut data_provider =
+                DataProvider::new(Box::new(FakeDataReader::new(vec![1, 2, 3], false)));
+            DataStructReader::new(&mut data_provider, &reg, klass, None)
+                .with_max_struct_depth(max_struct_depth)
+                .read_event()
+        };
+
+        assert_eq!(read(1), Err(ReadEventError::StructDepthExceeded(1)));
+        let event = read(2).unwrap();
+        let child = event.get_value_struct("child").unwrap();
+        assert_eq!(
+            child
+                .get_value_struct("child")
+                .unwrap()
+                .get_value_u8("value")
+                .unwrap(),
+            3
+        );
+    }
 }
@@ -0,0 +1,124 @@
+use crate::event::Event;
+use crate::registry::EventKlassRegistry;
+use crate::sink::EventSink;
+
+// Routes events to sinks based on the name of their klass, so a single
+// parsing pass can feed multiple outputs. Events of klasses without a route
+// go to the default sink, or are dropped if there is none.
+#[derive(Default)]
+pub struct Demux {
+    sinks: Vec<Box<dyn EventSink>>,
+    routes: std::collections::HashMap<String, usize>,
+    default_sink: Option<usize>,
+}
+
+impl Demux {
+    pub fn new() -> Demux {
+        Demux::default()
+    }
+
+    pub fn route(mut self, klass_names: &[&str], sink: Box<dyn EventSink>) -> Demux {
+        self.sinks.push(sink);
+        for klass_name in klass_names {
+            self.routes
+                .insert(klass_name.to_string(), self.sinks.len() - 1);
+        }
+        self
+    }
+
+    pub fn default_route(mut self, sink: Box<dyn EventSink>) -> Demux {
+        self.sinks.push(sink);
+        self.default_sink = Some(self.sinks.len() - 1);
+        self
+    }
+
+    fn get_sink_index(&self, event: &Event, registry: &EventKlassRegistry) -> Option<usize> {
+        match registry.get_klass_by_id(event.get_klass_id()) {
+            Some(klass) => match self.routes.get(klass.get_name()) {
+                Some(index) => Some(*index),
+                None => self.default_sink,
+            },
+            None => self.default_sink,
+        }
+    }
+}
+
+impl EventSink for Demux {
+    fn write_event(&mut self, event: &Event, registry: &EventKlassRegistry) -> std::io::Result<()> {
+        match self.get_sink_index(event, registry) {
+            Some(index) => self.sinks[index].write_event(event, registry),
+            None => Ok(()),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        for sink in &mut self.sinks {
+            sink.flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event_klass::EventKlass;
+
+    struct RecordingSink {
+        events: std::rc::Rc<std::cell::RefCell<Vec<u32>>>,
+    }
+
+    impl EventSink for RecordingSink {
+        fn write_event(
+            &mut self,
+            event: &Event,
+            _registry: &EventKlassRegistry,
+        ) -> std::io::Result<()> {
+            self.events.borrow_mut().push(event.get_klass_id());
+            Ok(())
+        }
+    }
+
+    fn make_sink() -> (
+        Box<dyn EventSink>,
+        std::rc::Rc<std::cell::RefCell<Vec<u32>>>,
+    ) {
+        let events = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let sink = RecordingSink {
+            events: events.clone(),
+        };
+        (Box::new(sink), events)
+    }
+
+    #[test]
+    fn demux_should_route_events_by_klass_name() {
+        let mut registry = EventKlassRegistry::new();
+        registry.add_klass(EventKlass::new(100, "foo".to_owned()));
+        registry.add_klass(EventKlass::new(101, "bar".to_owned()));
+        registry.add_klass(EventKlass::new(102, "baz".to_owned()));
+
+        let (foo_sink, foo_events) = make_sink();
+        let (default_sink, default_events) = make_sink();
+        let mut demux = Demux::new()
+            .route(&["foo", "bar"], foo_sink)
+            .default_route(default_sink);
+
+        for klass_id in &[100, 101, 102, 999] {
+            let event = Event::new(*klass_id, fnv::FnvHashMap::default());
+            demux.write_event(&event, &registry).unwrap();
+        }
+        demux.flush().unwrap();
+
+        assert_eq!(*foo_events.borrow(), vec![100, 101]);
+        assert_eq!(*default_events.borrow(), vec![102, 999]);
+    }
+
+    #[test]
+    fn demux_should_drop_unrouted_events_without_default_sink() {
+        let registry = EventKlassRegistry::new();
+        let mut demux = Demux::new();
+
+        let event = Event::new(1, fnv::FnvHashMap::default());
+        assert!(demux.write_event(&event, &registry).is_ok());
+    }
+}
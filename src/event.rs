@@ -1,6 +1,9 @@
+use crate::event_klass::{EventKlass, EventKlassField};
+use crate::registry::EventKlassRegistry;
 use fnv;
 
 #[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DataType {
     U8,
     I8,
@@ -12,6 +15,160 @@ pub enum DataType {
     I64,
     Str,
     Struct,
+    // Opaque field of the given size in bytes.
+    Bytes(usize),
+    Array(IntegerType, ArrayLength),
+}
+
+impl DataType {
+    // Number of bytes the value takes in the stream, None for types of
+    // variable length (strings, prefixed arrays) and for structs (their size
+    // depends on the klass).
+    pub fn get_fixed_size(self) -> Option<usize> {
+        match self {
+            DataType::U8 | DataType::I8 => Some(1),
+            DataType::U16 | DataType::I16 => Some(2),
+            DataType::U32 | DataType::I32 => Some(4),
+            DataType::U64 | DataType::I64 => Some(8),
+            DataType::Bytes(size) => Some(size),
+            DataType::Array(element_type, ArrayLength::Fixed(count)) => {
+                element_type.get_size().checked_mul(count)
+            }
+            DataType::Str | DataType::Struct | DataType::Array(_, ArrayLength::Prefixed) => None,
+        }
+    }
+
+    // Parses C type names used by HawkTracer, e.g. "uint32_t", "const char*"
+    // or "int16_t[4]" (a fixed-size array) and "int16_t[]" (an array
+    // prefixed by its count). There's no floating point data type, so
+    // float and double are carried as opaque bytes. None is returned for
+    // other types (including structs).
+    pub fn from_type_name(type_name: &str) -> Option<DataType> {
+        let type_name = type_name.trim();
+        if let Some(element_type) = type_name.strip_suffix(']') {
+            let start = element_type.rfind('[')?;
+            let element_type = DataType::from_type_name(&element_type[..start])?;
+            let element_type = IntegerType::from_data_type(element_type)?;
+            let length = match element_type_count(&type_name[start + 1..type_name.len() - 1])? {
+                Some(count) => ArrayLength::Fixed(count),
+                None => ArrayLength::Prefixed,
+            };
+            return Some(DataType::Array(element_type, length));
+        }
+
+        let type_name: String = type_name.split_whitespace().collect::<Vec<_>>().join(" ");
+        match type_name.as_str() {
+            "uint8_t" => Some(DataType::U8),
+            "int8_t" => Some(DataType::I8),
+            "uint16_t" => Some(DataType::U16),
+            "int16_t" => Some(DataType::I16),
+            "uint32_t" => Some(DataType::U32),
+            "int32_t" => Some(DataType::I32),
+            "uint64_t" => Some(DataType::U64),
+            "int64_t" => Some(DataType::I64),
+            "const char*" | "const char *" | "char*" | "char *" => Some(DataType::Str),
+            "float" => Some(DataType::Bytes(4)),
+            "double" => Some(DataType::Bytes(8)),
+            _ => None,
+        }
+    }
+
+    // Inverse of from_type_name(); opaque fields are described as byte
+    // arrays. Structs are named after their klass, so None is returned.
+    pub fn c_type_name(self) -> Option<String> {
+        let type_name = match self {
+            DataType::U8 => "uint8_t",
+            DataType::I8 => "int8_t",
+            DataType::U16 => "uint16_t",
+            DataType::I16 => "int16_t",
+            DataType::U32 => "uint32_t",
+            DataType::I32 => "int32_t",
+            DataType::U64 => "uint64_t",
+            DataType::I64 => "int64_t",
+            DataType::Str => "const char*",
+            DataType::Struct => return None,
+            DataType::Bytes(size) => return Some(format!("uint8_t[{}]", size)),
+            DataType::Array(element_type, ArrayLength::Fixed(count)) => {
+                return Some(format!("{}[{}]", element_type.get_data_type().c_type_name()?, count))
+            }
+            DataType::Array(element_type, ArrayLength::Prefixed) => {
+                return Some(format!("{}[]", element_type.get_data_type().c_type_name()?))
+            }
+        };
+        Some(type_name.to_owned())
+    }
+}
+
+// Element count between the brackets of an array type name; None for
+// arrays prefixed by their count.
+fn element_type_count(count: &str) -> Option<Option<usize>> {
+    match count.trim() {
+        "" => Some(None),
+        count => match count.parse() {
+            Ok(count) if count > 0 => Some(Some(count)),
+            _ => None,
+        },
+    }
+}
+
+// Types of array elements.
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum IntegerType {
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+    U64,
+    I64,
+}
+
+impl IntegerType {
+    pub fn from_data_type(data_type: DataType) -> Option<IntegerType> {
+        match data_type {
+            DataType::U8 => Some(IntegerType::U8),
+            DataType::I8 => Some(IntegerType::I8),
+            DataType::U16 => Some(IntegerType::U16),
+            DataType::I16 => Some(IntegerType::I16),
+            DataType::U32 => Some(IntegerType::U32),
+            DataType::I32 => Some(IntegerType::I32),
+            DataType::U64 => Some(IntegerType::U64),
+            DataType::I64 => Some(IntegerType::I64),
+            _ => None,
+        }
+    }
+
+    pub fn get_data_type(self) -> DataType {
+        match self {
+            IntegerType::U8 => DataType::U8,
+            IntegerType::I8 => DataType::I8,
+            IntegerType::U16 => DataType::U16,
+            IntegerType::I16 => DataType::I16,
+            IntegerType::U32 => DataType::U32,
+            IntegerType::I32 => DataType::I32,
+            IntegerType::U64 => DataType::U64,
+            IntegerType::I64 => DataType::I64,
+        }
+    }
+
+    pub fn get_size(self) -> usize {
+        match self {
+            IntegerType::U8 | IntegerType::I8 => 1,
+            IntegerType::U16 | IntegerType::I16 => 2,
+            IntegerType::U32 | IntegerType::I32 => 4,
+            IntegerType::U64 | IntegerType::I64 => 8,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ArrayLength {
+    Fixed(usize),
+    // Elements are preceded by their count (uint32_t).
+    Prefixed,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -20,10 +177,211 @@ pub enum ErrorKind {
     InvalidType,
 }
 
-#[derive(Debug, PartialEq)]
+// Field names are shared with the klass fields, so decoded events don't
+// allocate their own copies.
+pub type Values = std::collections::HashMap<std::sync::Arc<str>, Value, fnv::FnvBuildHasher>;
+
+// Values of compact events are kept in a vector sorted by field name. For
+// events with a handful of fields it's faster to build and takes less memory
+// than the hash map, but lookups are O(log n).
+#[derive(Debug, Clone)]
+pub(crate) enum Storage {
+    Map(Values),
+    Compact(Vec<(std::sync::Arc<str>, Value)>),
+}
+
+impl Storage {
+    pub(crate) fn new(compact: bool) -> Storage {
+        if compact {
+            Storage::Compact(Vec::new())
+        } else {
+            Storage::Map(Values::default())
+        }
+    }
+
+    fn is_compact(&self) -> bool {
+        matches!(self, Storage::Compact(_))
+    }
+
+    fn get_key_value(&self, name: &str) -> Option<(&std::sync::Arc<str>, &Value)> {
+        match self {
+            Storage::Map(values) => values.get_key_value(name),
+            Storage::Compact(values) => match values.binary_search_by(|(key, _)| (**key).cmp(name)) {
+                Ok(pos) => Some((&values[pos].0, &values[pos].1)),
+                Err(_) => None,
+            },
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<&Value> {
+        self.get_key_value(name).map(|(_, value)| value)
+    }
+
+    pub(crate) fn get_mut(&mut self, name: &str) -> Option<&mut Value> {
+        match self {
+            Storage::Map(values) => values.get_mut(name),
+            Storage::Compact(values) => match values.binary_search_by(|(key, _)| (**key).cmp(name)) {
+                Ok(pos) => Some(&mut values[pos].1),
+                Err(_) => None,
+            },
+        }
+    }
+
+    pub(crate) fn insert(&mut self, name: std::sync::Arc<str>, value: Value) -> Option<Value> {
+        match self {
+            Storage::Map(values) => values.insert(name, value),
+            Storage::Compact(values) => match values.binary_search_by(|(key, _)| key.cmp(&name)) {
+                Ok(pos) => Some(std::mem::replace(&mut values[pos].1, value)),
+                Err(pos) => {
+                    values.insert(pos, (name, value));
+                    None
+                }
+            },
+        }
+    }
+
+    // Existing values are kept.
+    pub(crate) fn insert_if_absent(&mut self, name: std::sync::Arc<str>, value: Value) {
+        match self {
+            Storage::Map(values) => {
+                values.entry(name).or_insert(value);
+            }
+            Storage::Compact(values) => {
+                if let Err(pos) = values.binary_search_by(|(key, _)| key.cmp(&name)) {
+                    values.insert(pos, (name, value));
+                }
+            }
+        }
+    }
+
+    fn remove(&mut self, name: &str) -> Option<Value> {
+        match self {
+            Storage::Map(values) => values.remove(name),
+            Storage::Compact(values) => match values.binary_search_by(|(key, _)| (**key).cmp(name)) {
+                Ok(pos) => Some(values.remove(pos).1),
+                Err(_) => None,
+            },
+        }
+    }
+
+    pub(crate) fn retain<F: FnMut(&str) -> bool>(&mut self, mut f: F) {
+        match self {
+            Storage::Map(values) => values.retain(|name, _| f(name)),
+            Storage::Compact(values) => values.retain(|(name, _)| f(name)),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Storage::Map(values) => values.len(),
+            Storage::Compact(values) => values.len(),
+        }
+    }
+
+    fn iter(&self) -> ValuesIter<'_> {
+        match self {
+            Storage::Map(values) => ValuesIter(ValuesIterInner::Map(values.iter())),
+            Storage::Compact(values) => ValuesIter(ValuesIterInner::Compact(values.iter())),
+        }
+    }
+
+    fn into_vec(self) -> Vec<(std::sync::Arc<str>, Value)> {
+        match self {
+            Storage::Map(values) => values.into_iter().collect(),
+            Storage::Compact(values) => values,
+        }
+    }
+
+    fn into_iter(self) -> IntoValues {
+        match self {
+            Storage::Map(values) => IntoValues(IntoValuesInner::Map(values.into_iter())),
+            Storage::Compact(values) => IntoValues(IntoValuesInner::Compact(values.into_iter())),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Storage {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.iter())
+    }
+}
+
+// Deserialized events always use the hash map.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Storage {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Storage, D::Error> {
+        Ok(Storage::Map(Values::deserialize(deserializer)?))
+    }
+}
+
+enum ValuesIterInner<'a> {
+    Map(std::collections::hash_map::Iter<'a, std::sync::Arc<str>, Value>),
+    Compact(std::slice::Iter<'a, (std::sync::Arc<str>, Value)>),
+}
+
+// Iterates values of an event; compact events yield them sorted by name,
+// other events in an arbitrary order.
+pub struct ValuesIter<'a>(ValuesIterInner<'a>);
+
+impl<'a> Iterator for ValuesIter<'a> {
+    type Item = (&'a std::sync::Arc<str>, &'a Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.0 {
+            ValuesIterInner::Map(iter) => iter.next(),
+            ValuesIterInner::Compact(iter) => iter.next().map(|(name, value)| (name, value)),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.0 {
+            ValuesIterInner::Map(iter) => iter.size_hint(),
+            ValuesIterInner::Compact(iter) => iter.size_hint(),
+        }
+    }
+}
+
+enum IntoValuesInner {
+    Map(std::collections::hash_map::IntoIter<std::sync::Arc<str>, Value>),
+    Compact(std::vec::IntoIter<(std::sync::Arc<str>, Value)>),
+}
+
+// Consuming counterpart of ValuesIter.
+pub struct IntoValues(IntoValuesInner);
+
+impl Iterator for IntoValues {
+    type Item = (std::sync::Arc<str>, Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.0 {
+            IntoValuesInner::Map(iter) => iter.next(),
+            IntoValuesInner::Compact(iter) => iter.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.0 {
+            IntoValuesInner::Map(iter) => iter.size_hint(),
+            IntoValuesInner::Compact(iter) => iter.size_hint(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Event {
     klass_id: u32,
-    values: std::collections::HashMap<String, Value, fnv::FnvBuildHasher>,
+    values: Storage,
+}
+
+// Events are equal if they have the same values, regardless of the layout.
+impl PartialEq for Event {
+    fn eq(&self, other: &Event) -> bool {
+        self.klass_id == other.klass_id
+            && self.values.len() == other.values.len()
+            && self.values.iter().all(|(name, value)| other.values.get(name) == Some(value))
+    }
 }
 
 #[derive(Debug)]
@@ -55,7 +413,8 @@ impl std::fmt::Display for ValueError {
 
 // Keep in sync with DataType
 // TODO: can we merge those two enums?
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Value {
     U8(u8),
     I8(i8),
@@ -66,7 +425,9 @@ pub enum Value {
     U64(u64),
     I64(i64),
     Str(String),
-    Struct(Event),
+    Struct(Box<Event>),
+    Bytes(Vec<u8>),
+    Array(Vec<Value>),
 }
 
 impl std::fmt::Display for Value {
@@ -82,10 +443,204 @@ impl std::fmt::Display for Value {
             Value::I64(v) => write!(f, "{}", v),
             Value::Str(v) => write!(f, "\"{}\"", v),
             Value::Struct(v) => write!(f, "<Event {}>", v.get_klass_id()),
+            Value::Bytes(v) => {
+                write!(f, "0x")?;
+                for byte in v {
+                    write!(f, "{:02x}", byte)?;
+                }
+                Ok(())
+            }
+            Value::Array(v) => {
+                write!(f, "[")?;
+                for (i, value) in v.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+// Numeric coercion: integer values are converted regardless of their width,
+// so analysis code keeps working when a field changes its type (e.g. from
+// uint32_t to uint64_t) between sessions. None is returned for non-numeric
+// values and for values out of the range of the target type.
+impl Value {
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Value::U8(v) => Some(u64::from(*v)),
+            Value::U16(v) => Some(u64::from(*v)),
+            Value::U32(v) => Some(u64::from(*v)),
+            Value::U64(v) => Some(*v),
+            Value::I8(v) => std::convert::TryFrom::try_from(*v).ok(),
+            Value::I16(v) => std::convert::TryFrom::try_from(*v).ok(),
+            Value::I32(v) => std::convert::TryFrom::try_from(*v).ok(),
+            Value::I64(v) => std::convert::TryFrom::try_from(*v).ok(),
+            Value::Str(_) | Value::Struct(_) | Value::Bytes(_) | Value::Array(_) => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::I8(v) => Some(i64::from(*v)),
+            Value::I16(v) => Some(i64::from(*v)),
+            Value::I32(v) => Some(i64::from(*v)),
+            Value::I64(v) => Some(*v),
+            Value::U8(v) => Some(i64::from(*v)),
+            Value::U16(v) => Some(i64::from(*v)),
+            Value::U32(v) => Some(i64::from(*v)),
+            Value::U64(v) => std::convert::TryFrom::try_from(*v).ok(),
+            Value::Str(_) | Value::Struct(_) | Value::Bytes(_) | Value::Array(_) => None,
+        }
+    }
+
+    // 64-bit values may lose precision.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::U64(v) => Some(*v as f64),
+            Value::I64(v) => Some(*v as f64),
+            value => value.as_i64().map(|v| v as f64),
+        }
+    }
+}
+
+// Implemented only for the types of Value, so new variants can be supported
+// without breaking downstream code.
+mod sealed {
+    pub trait Sealed {}
+}
+
+// Types which can be read from event values with Event::get_value(). As with
+// the get_value_*() methods, the type has to match the type of the value
+// exactly; use the as_*() methods of Value for numeric coercion. The trait is
+// sealed, it can't be implemented outside of the crate.
+pub trait FromValue<'a>: Sized + sealed::Sealed {
+    fn from_value(value: &'a Value) -> Option<Self>;
+}
+
+macro_rules! impl_from_value {
+    ($data_type: ident, $type: ty) => (
+        impl sealed::Sealed for $type {}
+
+        impl<'a> FromValue<'a> for $type {
+            fn from_value(value: &'a Value) -> Option<$type> {
+                match value {
+                    Value::$data_type(data) => Some(*data),
+                    _ => None
+                }
+            }
+        }
+    )
+}
+
+impl_from_value!(U8, u8);
+impl_from_value!(I8, i8);
+impl_from_value!(U16, u16);
+impl_from_value!(I16, i16);
+impl_from_value!(U32, u32);
+impl_from_value!(I32, i32);
+impl_from_value!(U64, u64);
+impl_from_value!(I64, i64);
+
+impl sealed::Sealed for &str {}
+
+impl<'a> FromValue<'a> for &'a str {
+    fn from_value(value: &'a Value) -> Option<&'a str> {
+        match value {
+            Value::Str(data) => Some(data),
+            _ => None
+        }
+    }
+}
+
+impl sealed::Sealed for &String {}
+
+impl<'a> FromValue<'a> for &'a String {
+    fn from_value(value: &'a Value) -> Option<&'a String> {
+        match value {
+            Value::Str(data) => Some(data),
+            _ => None
+        }
+    }
+}
+
+impl sealed::Sealed for &[u8] {}
+
+impl<'a> FromValue<'a> for &'a [u8] {
+    fn from_value(value: &'a Value) -> Option<&'a [u8]> {
+        match value {
+            Value::Bytes(data) => Some(data),
+            _ => None
+        }
+    }
+}
+
+impl sealed::Sealed for &[Value] {}
+
+impl<'a> FromValue<'a> for &'a [Value] {
+    fn from_value(value: &'a Value) -> Option<&'a [Value]> {
+        match value {
+            Value::Array(data) => Some(data),
+            _ => None
+        }
+    }
+}
+
+impl sealed::Sealed for &Event {}
+
+impl<'a> FromValue<'a> for &'a Event {
+    fn from_value(value: &'a Value) -> Option<&'a Event> {
+        match value {
+            Value::Struct(data) => Some(data),
+            _ => None
         }
     }
 }
 
+impl sealed::Sealed for &Value {}
+
+impl<'a> FromValue<'a> for &'a Value {
+    fn from_value(value: &'a Value) -> Option<&'a Value> {
+        Some(value)
+    }
+}
+
+// Single-line representation with values sorted by name, e.g.
+// `<Event 99> {base: <Event 1> {id: 2}, name: "foo"}`.
+impl std::fmt::Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut values: Vec<(&std::sync::Arc<str>, &Value)> = self.values.iter().collect();
+        values.sort_by(|a, b| a.0.cmp(b.0));
+
+        write!(f, "<Event {}> {{", self.klass_id)?;
+        for (i, (name, value)) in values.into_iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            match value {
+                Value::Struct(event) => write!(f, "{}: {}", name, event)?,
+                value => write!(f, "{}: {}", name, value)?,
+            }
+        }
+        write!(f, "}}")
+    }
+}
+
+// Looks for the field in the klass and (for flattened events) in its base klasses.
+fn find_field<'a>(klass: &'a EventKlass, registry: &'a EventKlassRegistry, name: &str) -> Option<&'a EventKlassField> {
+    klass.get_fields().iter().find(|field| field.get_name() == name).or_else(|| {
+        klass
+            .get_fields()
+            .iter()
+            .filter(|field| field.get_name() == "base" && *field.get_data_type() == DataType::Struct)
+            .filter_map(|field| registry.get_klass_by_name(field.get_type_name()))
+            .find_map(|base_klass| find_field(base_klass, registry, name))
+    })
+}
+
 macro_rules! make_field_getter {
     ($function_name: ident, $data_type: ident, $type: ty) => (
         pub fn $function_name(&self, name: &str) -> Result<$type, ValueError> {
@@ -121,10 +676,47 @@ macro_rules! make_field_getter_ref {
 }
 
 impl Event {
+    // Field names are converted to shared strings; use from_values() to avoid
+    // the conversion.
     pub fn new(klass_id: u32, values: std::collections::HashMap<String, Value, fnv::FnvBuildHasher>) -> Event {
+        Event::from_values(klass_id, values.into_iter().map(|(name, value)| (name.into(), value)).collect())
+    }
+
+    pub fn from_values(klass_id: u32, values: Values) -> Event {
+        Event { klass_id, values: Storage::Map(values) }
+    }
+
+    // Creates a compact event. If a field name is repeated, the last value
+    // is kept.
+    pub fn from_compact_values(klass_id: u32, mut values: Vec<(std::sync::Arc<str>, Value)>) -> Event {
+        values.reverse();
+        values.sort_by(|a, b| a.0.cmp(&b.0));
+        values.dedup_by(|a, b| a.0 == b.0);
+        Event { klass_id, values: Storage::Compact(values) }
+    }
+
+    pub(crate) fn from_storage(klass_id: u32, values: Storage) -> Event {
         Event { klass_id, values }
     }
 
+    pub fn is_compact(&self) -> bool {
+        self.values.is_compact()
+    }
+
+    // Converts the event (and its nested events) to the compact layout.
+    pub fn compact(self) -> Event {
+        let values = self
+            .values
+            .into_vec()
+            .into_iter()
+            .map(|(name, value)| match value {
+                Value::Struct(event) => (name, Value::Struct(Box::new(event.compact()))),
+                value => (name, value),
+            })
+            .collect();
+        Event::from_compact_values(self.klass_id, values)
+    }
+
     make_field_getter!(get_value_u8, U8, u8);
     make_field_getter!(get_value_i8, I8, i8);
     make_field_getter!(get_value_u16, U16, u16);
@@ -135,31 +727,241 @@ impl Event {
     make_field_getter!(get_value_i64, I64, i64);
     make_field_getter_ref!(get_value_string, Str, &String);
     make_field_getter_ref!(get_value_struct, Struct, &Event);
+    make_field_getter_ref!(get_value_bytes, Bytes, &Vec<u8>);
+    make_field_getter_ref!(get_value_array, Array, &Vec<Value>);
+
+    // Generic version of the get_value_*() methods, e.g. get_value::<u32>("id")
+    // or get_value::<&str>("name").
+    pub fn get_value<'a, T: FromValue<'a>>(&'a self, name: &str) -> Result<T, ValueError> {
+        match self.values.get(name) {
+            Some(value) => T::from_value(value).ok_or_else(|| ValueError::new(name, ErrorKind::InvalidType)),
+            None => Err(ValueError::new(name, ErrorKind::NotFound))
+        }
+    }
+
+    pub fn get_value_as_u64(&self, name: &str) -> Result<u64, ValueError> {
+        match self.values.get(name) {
+            Some(value) => value.as_u64().ok_or_else(|| ValueError::new(name, ErrorKind::InvalidType)),
+            None => Err(ValueError::new(name, ErrorKind::NotFound))
+        }
+    }
+
+    pub fn get_value_as_i64(&self, name: &str) -> Result<i64, ValueError> {
+        match self.values.get(name) {
+            Some(value) => value.as_i64().ok_or_else(|| ValueError::new(name, ErrorKind::InvalidType)),
+            None => Err(ValueError::new(name, ErrorKind::NotFound))
+        }
+    }
 
     pub fn get_raw_value(&self, name: &str) -> Option<&Value> {
         self.values.get(name)
     }
 
-    pub fn get_all_values(&self) -> &std::collections::HashMap<String, Value, fnv::FnvBuildHasher> {
-        &self.values
+    // Path of dot-separated field names, e.g. "base.timestamp".
+    pub fn get_path(&self, path: &str) -> Option<&Value> {
+        let mut names = path.split('.');
+        let mut value = self.values.get(names.next()?)?;
+        for name in names {
+            match value {
+                Value::Struct(event) => value = event.values.get(name)?,
+                _ => return None,
+            }
+        }
+        Some(value)
+    }
+
+    pub fn get_path_as_u64(&self, path: &str) -> Result<u64, ValueError> {
+        match self.get_path(path) {
+            Some(value) => value.as_u64().ok_or_else(|| ValueError::new(path, ErrorKind::InvalidType)),
+            None => Err(ValueError::new(path, ErrorKind::NotFound))
+        }
+    }
+
+    pub fn get_path_as_i64(&self, path: &str) -> Result<i64, ValueError> {
+        match self.get_path(path) {
+            Some(value) => value.as_i64().ok_or_else(|| ValueError::new(path, ErrorKind::InvalidType)),
+            None => Err(ValueError::new(path, ErrorKind::NotFound))
+        }
+    }
+
+    pub fn get_path_string(&self, path: &str) -> Result<&String, ValueError> {
+        match self.get_path(path) {
+            Some(Value::Str(value)) => Ok(value),
+            Some(_) => Err(ValueError::new(path, ErrorKind::InvalidType)),
+            None => Err(ValueError::new(path, ErrorKind::NotFound))
+        }
+    }
+
+    // Returns the previous value of the field, if there was one.
+    pub fn set_value(&mut self, name: &str, value: Value) -> Option<Value> {
+        self.values.insert(name.into(), value)
+    }
+
+    pub fn get_all_values(&self) -> ValuesIter<'_> {
+        self.values.iter()
+    }
+
+    pub fn get_value_count(&self) -> usize {
+        self.values.len()
+    }
+
+    // Moves the values out of the event, so strings and nested events don't
+    // have to be cloned.
+    pub fn into_values(self) -> IntoValues {
+        self.values.into_iter()
+    }
+
+    // Removes the value from the event and returns it.
+    pub fn take_value(&mut self, name: &str) -> Option<Value> {
+        self.values.remove(name)
+    }
+
+    // Unless the event is compact, values are stored in a hash map, so
+    // get_all_values() iterates them in an arbitrary order. This returns them
+    // in the order of the klass definition (with fields of base klasses of
+    // flattened events in place of the "base" field), followed by the values
+    // not defined by the klass in alphabetical order.
+    pub fn get_ordered_values<'a>(
+        &'a self,
+        registry: &EventKlassRegistry,
+    ) -> Vec<(&'a str, &'a Value)> {
+        let mut values = Vec::with_capacity(self.values.len());
+        if let Some(klass) = registry.get_klass_by_id(self.klass_id) {
+            self.add_ordered_values(klass, registry, &mut values);
+        }
+        let mut other_values: Vec<(&str, &Value)> = self
+            .values
+            .iter()
+            .map(|(name, value)| (&**name, value))
+            .filter(|(name, _)| !values.iter().any(|(ordered_name, _)| ordered_name == name))
+            .collect();
+        other_values.sort_by(|a, b| a.0.cmp(b.0));
+        values.extend(other_values);
+        values
+    }
+
+    // Multi-line dump of the event, one value per line in the order of
+    // get_ordered_values(), with the klass and field types resolved using the
+    // registry. Values of nested events are indented.
+    pub fn pretty(&self, registry: &EventKlassRegistry) -> String {
+        let mut output = format!(
+            "{} ({})\n",
+            self.get_klass_name(registry).unwrap_or("<unknown klass>"),
+            self.klass_id
+        );
+        self.write_pretty_values(registry, 1, &mut output);
+        output
+    }
+
+    fn write_pretty_values(&self, registry: &EventKlassRegistry, depth: usize, output: &mut String) {
+        let klass = registry.get_klass_by_id(self.klass_id);
+        for (name, value) in self.get_ordered_values(registry) {
+            output.push_str(&"  ".repeat(depth));
+            output.push_str(name);
+            if let Some(field) = klass.and_then(|klass| find_field(klass, registry, name)) {
+                output.push_str(": ");
+                output.push_str(field.get_type_name());
+            }
+            match value {
+                Value::Struct(event) => {
+                    output.push('\n');
+                    event.write_pretty_values(registry, depth + 1, output);
+                }
+                value => output.push_str(&format!(" = {}\n", value)),
+            }
+        }
+    }
+
+    fn add_ordered_values<'a>(
+        &'a self,
+        klass: &EventKlass,
+        registry: &EventKlassRegistry,
+        values: &mut Vec<(&'a str, &'a Value)>,
+    ) {
+        for field in klass.get_fields() {
+            match self.values.get_key_value(field.get_name()) {
+                Some((name, value)) if !values.iter().any(|(ordered_name, _)| *ordered_name == &**name) => {
+                    values.push((name, value))
+                }
+                Some(_) => {}
+                None if field.get_name() == "base" && *field.get_data_type() == DataType::Struct => {
+                    if let Some(base_klass) = registry.get_klass_by_name(field.get_type_name()) {
+                        self.add_ordered_values(base_klass, registry, values);
+                    }
+                }
+                None => {}
+            }
+        }
     }
 
     pub fn get_klass_id(&self) -> u32 {
         self.klass_id
     }
 
+    // Changes the klass of the event, keeping the storage of its values, so
+    // it can be reused for decoding another event. The storage is replaced
+    // if its layout doesn't match.
+    pub(crate) fn reset(&mut self, klass_id: u32, compact: bool) -> &mut Storage {
+        self.klass_id = klass_id;
+        if self.values.is_compact() != compact {
+            self.values = Storage::new(compact);
+        }
+        &mut self.values
+    }
+
+    // Takes the base event out (leaving a placeholder value), so its storage
+    // can be reused for decoding the header of the next event.
+    pub(crate) fn take_base_event(&mut self) -> Box<Event> {
+        match self.values.get_mut("base") {
+            Some(value @ Value::Struct(_)) => match std::mem::replace(value, Value::U8(0)) {
+                Value::Struct(base) => base,
+                _ => unreachable!(),
+            },
+            _ => Box::new(Event::from_values(0, Values::default())),
+        }
+    }
+
+    pub fn get_klass_name<'a>(&self, registry: &'a EventKlassRegistry) -> Option<&'a str> {
+        registry.get_klass_by_id(self.klass_id).map(|klass| klass.get_name().as_str())
+    }
+
+    // Core fields are looked up in the event first, then in its base events,
+    // so they're found in both nested and flattened events.
+    pub fn get_timestamp(&self) -> Option<u64> {
+        self.find_value("timestamp")?.as_u64()
+    }
+
+    pub fn get_id(&self) -> Option<u64> {
+        self.find_value("id")?.as_u64()
+    }
+
+    pub fn get_type(&self) -> Option<u32> {
+        std::convert::TryFrom::try_from(self.find_value("type")?.as_u64()?).ok()
+    }
+
+    pub(crate) fn find_value(&self, name: &str) -> Option<&Value> {
+        match self.values.get(name) {
+            Some(value) => Some(value),
+            None => match self.values.get("base") {
+                Some(Value::Struct(base)) => base.find_value(name),
+                _ => None,
+            },
+        }
+    }
+
+    // The flat event keeps the layout of the event.
     pub fn flat_event(self) -> Event {
-        let mut new_values = std::collections::HashMap::<String, Value, fnv::FnvBuildHasher>::default();
+        let mut new_values = Storage::new(self.is_compact());
         let klass_id = self.get_klass_id();
         self.flat_event_internal(&mut new_values);
 
-        Event::new(klass_id, new_values)
+        Event::from_storage(klass_id, new_values)
     }
 
-    fn flat_event_internal(mut self, new_values: &mut std::collections::HashMap<String, Value, fnv::FnvBuildHasher>) {
+    pub(crate) fn flat_event_internal(mut self, new_values: &mut Storage) {
         let base_value = self.values.remove("base");
 
-        for (name, value) in self.values {
+        for (name, value) in self.values.into_iter() {
             new_values.insert(name, value);
         }
 
@@ -167,7 +969,7 @@ impl Event {
             if let Value::Struct(event) = base_value {
                 event.flat_event_internal(new_values);
             } else {
-                new_values.insert("base".to_string(), base_value);
+                new_values.insert("base".into(), base_value);
             }
         }
     }
@@ -176,19 +978,52 @@ impl Event {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::collections::HashMap;
+    use fnv::FnvHashMap;
+
+    #[test]
+    fn data_type_should_be_parsed_from_type_name() {
+        for (type_name, data_type) in &[
+            ("uint8_t", DataType::U8),
+            ("int64_t", DataType::I64),
+            ("const  char *", DataType::Str),
+            ("double", DataType::Bytes(8)),
+            ("int16_t[4]", DataType::Array(IntegerType::I16, ArrayLength::Fixed(4))),
+            ("uint32_t[ ]", DataType::Array(IntegerType::U32, ArrayLength::Prefixed)),
+        ] {
+            assert_eq!(DataType::from_type_name(type_name), Some(*data_type));
+        }
+        for type_name in &["HT_Event", "uint32_t[0]", "const char*[2]", "uint8_t[x]", "]"] {
+            assert_eq!(DataType::from_type_name(type_name), None);
+        }
+    }
+
+    #[test]
+    fn c_type_name_should_be_parsed_back() {
+        for data_type in &[
+            DataType::U16,
+            DataType::I32,
+            DataType::Str,
+            DataType::Array(IntegerType::I8, ArrayLength::Fixed(3)),
+            DataType::Array(IntegerType::U64, ArrayLength::Prefixed),
+        ] {
+            let type_name = data_type.c_type_name().unwrap();
+            assert_eq!(DataType::from_type_name(&type_name), Some(*data_type));
+        }
+        assert_eq!(DataType::Bytes(16).c_type_name().unwrap(), "uint8_t[16]");
+        assert_eq!(DataType::Struct.c_type_name(), None);
+    }
 
     #[test]
     fn getting_klass_id_should_return_correct_value() {
         let klass_id = 5;
-        let event = Event::new(klass_id, HashMap::<String, Value>::new());
+        let event = Event::new(klass_id, FnvHashMap::<String, Value>::default());
         assert_eq!(klass_id, event.get_klass_id());
     }
 
     #[test]
     fn getting_valid_type_should_not_fail() {
         let u32_value = 492;
-        let mut values = HashMap::<String, Value>::new();
+        let mut values = FnvHashMap::<String, Value>::default();
         values.insert("v1".to_string(), Value::U32(u32_value));
         let event = Event::new(1, values);
 
@@ -200,7 +1035,7 @@ mod tests {
 
     #[test]
     fn getting_non_existing_value_should_fail() {
-        let event = Event::new(1, HashMap::<String, Value>::new());
+        let event = Event::new(1, FnvHashMap::<String, Value>::default());
 
         assert_eq!(
             event.get_value_u32("non-existing").unwrap_err().kind(),
@@ -210,7 +1045,7 @@ mod tests {
 
     #[test]
     fn getting_non_existing_string_value_should_fail() {
-        let event = Event::new(1, HashMap::<String, Value>::new());
+        let event = Event::new(1, FnvHashMap::<String, Value>::default());
 
         assert_eq!(
             event.get_value_string("non-existing").unwrap_err().kind(),
@@ -220,7 +1055,7 @@ mod tests {
 
     #[test]
     fn getting_invalid_type_should_fail() {
-        let mut values = HashMap::<String, Value>::new();
+        let mut values = FnvHashMap::<String, Value>::default();
         values.insert("v1".to_string(), Value::U32(2));
         let event = Event::new(1, values);
 
@@ -230,9 +1065,42 @@ mod tests {
         );
     }
 
+    #[test]
+    fn integer_values_should_be_coerced_regardless_of_width() {
+        let mut values = FnvHashMap::<String, Value>::default();
+        values.insert("u32".to_string(), Value::U32(7));
+        values.insert("i8".to_string(), Value::I8(-2));
+        values.insert("u64".to_string(), Value::U64(u64::MAX));
+        values.insert("str".to_string(), Value::Str("7".to_string()));
+        let event = Event::new(1, values);
+
+        assert_eq!(event.get_value_as_u64("u32").unwrap(), 7);
+        assert_eq!(event.get_value_as_i64("u32").unwrap(), 7);
+        assert_eq!(event.get_value_as_i64("i8").unwrap(), -2);
+        assert_eq!(event.get_value_as_u64("i8").unwrap_err().kind(), ErrorKind::InvalidType);
+        assert_eq!(event.get_value_as_u64("u64").unwrap(), u64::MAX);
+        assert_eq!(event.get_value_as_i64("u64").unwrap_err().kind(), ErrorKind::InvalidType);
+        assert_eq!(event.get_value_as_u64("str").unwrap_err().kind(), ErrorKind::InvalidType);
+        assert_eq!(event.get_value_as_u64("none").unwrap_err().kind(), ErrorKind::NotFound);
+        assert_eq!(Value::I8(-2).as_f64(), Some(-2.0));
+        assert_eq!(Value::U64(3).as_f64(), Some(3.0));
+    }
+
+    #[test]
+    fn setting_value_should_replace_previous_value() {
+        let mut values = FnvHashMap::<String, Value>::default();
+        values.insert("v1".to_string(), Value::U32(2));
+        let mut event = Event::new(1, values);
+
+        assert_eq!(event.set_value("v1", Value::Str("a".to_string())), Some(Value::U32(2)));
+        assert_eq!(event.set_value("v2", Value::U8(3)), None);
+        assert_eq!(event.get_value_string("v1").unwrap(), "a");
+        assert_eq!(event.get_value_u8("v2").unwrap(), 3);
+    }
+
     #[test]
     fn getting_invalid_integer_type_should_fail() {
-        let mut values = HashMap::<String, Value>::new();
+        let mut values = FnvHashMap::<String, Value>::default();
         values.insert("v1".to_string(), Value::U8(2));
         let event = Event::new(1, values);
 
@@ -244,29 +1112,29 @@ mod tests {
 
     #[test]
     fn flatten_event_should_collapse_all_base_struct_events() {
-        let mut super_base_values = HashMap::<String, Value>::new();
+        let mut super_base_values = FnvHashMap::<String, Value>::default();
         super_base_values.insert("timestamp".to_string(), Value::U64(999));
         super_base_values.insert("xxx".to_string(), Value::U64(876));
 
-        let mut base_values = HashMap::<String, Value>::new();
+        let mut base_values = FnvHashMap::<String, Value>::default();
         base_values.insert(
             "base".to_string(),
-            Value::Struct(Event::new(1, super_base_values)),
+            Value::Struct(Box::new(Event::new(1, super_base_values))),
         );
         base_values.insert("timestamp".to_string(), Value::U64(123));
         base_values.insert("id".to_string(), Value::U64(456));
 
-        let mut values = HashMap::<String, Value>::new();
+        let mut values = FnvHashMap::<String, Value>::default();
         values.insert(
             "base".to_string(),
-            Value::Struct(Event::new(1, base_values)),
+            Value::Struct(Box::new(Event::new(1, base_values))),
         );
         values.insert("name".to_string(), Value::Str("some_name".to_string()));
         let event = Event::new(3, values);
 
         let event = event.flat_event();
 
-        assert_eq!(4, event.values.len());
+        assert_eq!(4, event.get_value_count());
         assert_eq!(3, event.get_klass_id());
         assert_eq!(event.get_value_u64("timestamp").unwrap(), 999);
         assert_eq!(event.get_value_u64("id").unwrap(), 456);
@@ -276,15 +1144,245 @@ mod tests {
 
     #[test]
     fn flatten_event_should_not_collapse_non_event_fields() {
-        let mut values = HashMap::<String, Value>::new();
+        let mut values = FnvHashMap::<String, Value>::default();
         values.insert("base".to_string(), Value::U64(2));
         values.insert("name".to_string(), Value::Str("some_name".to_string()));
         let event = Event::new(3, values);
 
         let event = event.flat_event();
 
-        assert_eq!(2, event.values.len());
+        assert_eq!(2, event.get_value_count());
         assert_eq!(event.get_value_u64("base").unwrap(), 2);
         assert_eq!(event.get_value_string("name").unwrap(), "some_name");
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialized_event_should_deserialize_to_equal_event() {
+        let mut base_values = FnvHashMap::<String, Value>::default();
+        base_values.insert("timestamp".to_string(), Value::U64(999));
+        let mut values = FnvHashMap::<String, Value>::default();
+        values.insert("base".to_string(), Value::Struct(Box::new(Event::new(1, base_values))));
+        values.insert("name".to_string(), Value::Str("some_name".to_string()));
+        values.insert("value".to_string(), Value::I8(-3));
+        let event = Event::new(100, values);
+
+        let json = serde_json::to_string(&event).unwrap();
+        let deserialized: Event = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(deserialized, event);
+        let json = serde_json::to_string(&DataType::Str).unwrap();
+        assert_eq!(serde_json::from_str::<DataType>(&json).unwrap(), DataType::Str);
+    }
+
+    #[test]
+    fn get_path_should_traverse_nested_structs() {
+        let mut base_values = FnvHashMap::<String, Value>::default();
+        base_values.insert("timestamp".to_string(), Value::U64(999));
+        let mut values = FnvHashMap::<String, Value>::default();
+        values.insert("base".to_string(), Value::Struct(Box::new(Event::new(1, base_values))));
+        values.insert("name".to_string(), Value::Str("some_name".to_string()));
+        values.insert("value".to_string(), Value::I32(-5));
+        let event = Event::new(3, values);
+
+        assert_eq!(event.get_path("base.timestamp"), Some(&Value::U64(999)));
+        assert_eq!(event.get_path_as_u64("base.timestamp").unwrap(), 999);
+        assert_eq!(event.get_path_as_i64("value").unwrap(), -5);
+        assert_eq!(event.get_path_string("name").unwrap(), "some_name");
+        assert!(event.get_path("base.xxx").is_none());
+        assert!(event.get_path("name.xxx").is_none());
+        assert_eq!(event.get_path_as_u64("value").unwrap_err().kind(), ErrorKind::InvalidType);
+        assert_eq!(event.get_path_string("base.id").unwrap_err().kind(), ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn core_field_accessors_should_find_nested_and_flat_values() {
+        let mut base_values = FnvHashMap::<String, Value>::default();
+        base_values.insert("type".to_string(), Value::U32(99));
+        base_values.insert("timestamp".to_string(), Value::U64(999));
+        base_values.insert("id".to_string(), Value::U64(456));
+        let mut values = FnvHashMap::<String, Value>::default();
+        values.insert("base".to_string(), Value::Struct(Box::new(Event::new(1, base_values))));
+        let event = Event::new(99, values);
+
+        assert_eq!(event.get_timestamp(), Some(999));
+        assert_eq!(event.get_id(), Some(456));
+        assert_eq!(event.get_type(), Some(99));
+
+        let event = event.flat_event();
+        assert_eq!(event.get_timestamp(), Some(999));
+        assert_eq!(event.get_id(), Some(456));
+        assert_eq!(Event::new(99, FnvHashMap::default()).get_timestamp(), None);
+
+        let mut registry = EventKlassRegistry::new();
+        registry.add_klass(crate::event_klass::EventKlass::new(99, "foo".to_string()));
+        assert_eq!(event.get_klass_name(&registry), Some("foo"));
+        assert_eq!(Event::new(100, FnvHashMap::default()).get_klass_name(&registry), None);
+    }
+
+    #[test]
+    fn ordered_values_should_follow_klass_definition() {
+        let mut registry = EventKlassRegistry::new();
+        let mut klass = EventKlass::new(99, "foo".to_string());
+        klass.add_field("base".to_string(), "HT_Event".to_string(), DataType::Struct);
+        klass.add_field("zzz".to_string(), "uint8_t".to_string(), DataType::U8);
+        klass.add_field("aaa".to_string(), "uint8_t".to_string(), DataType::U8);
+        registry.add_klass(klass);
+
+        let mut base_values = FnvHashMap::<String, Value>::default();
+        base_values.insert("type".to_string(), Value::U32(99));
+        base_values.insert("timestamp".to_string(), Value::U64(999));
+        base_values.insert("id".to_string(), Value::U64(456));
+        let mut values = FnvHashMap::<String, Value>::default();
+        values.insert("base".to_string(), Value::Struct(Box::new(Event::new(1, base_values))));
+        values.insert("aaa".to_string(), Value::U8(1));
+        values.insert("zzz".to_string(), Value::U8(2));
+        values.insert("extra_b".to_string(), Value::U8(3));
+        values.insert("extra_a".to_string(), Value::U8(4));
+        let event = Event::new(99, values);
+
+        let names = |event: &Event| -> Vec<String> {
+            event.get_ordered_values(&registry).iter().map(|(name, _)| name.to_string()).collect()
+        };
+        assert_eq!(names(&event), vec!["base", "zzz", "aaa", "extra_a", "extra_b"]);
+        assert_eq!(
+            names(&event.flat_event()),
+            vec!["type", "timestamp", "id", "zzz", "aaa", "extra_a", "extra_b"]
+        );
+    }
+
+    #[test]
+    fn value_should_not_be_larger_than_string() {
+        assert!(std::mem::size_of::<Value>() <= std::mem::size_of::<String>() + 8);
+    }
+
+    #[test]
+    fn compact_event_should_be_equal_to_map_event() {
+        let mut base_values = FnvHashMap::<String, Value>::default();
+        base_values.insert("timestamp".to_string(), Value::U64(999));
+        let mut values = FnvHashMap::<String, Value>::default();
+        values.insert("base".to_string(), Value::Struct(Box::new(Event::new(1, base_values))));
+        values.insert("name".to_string(), Value::Str("some_name".to_string()));
+        let event = Event::new(99, values);
+
+        let mut compact_event = event.clone().compact();
+        assert!(compact_event.is_compact());
+        assert!(compact_event.get_value_struct("base").unwrap().is_compact());
+        assert_eq!(compact_event, event);
+        assert_eq!(compact_event.get_timestamp(), Some(999));
+        let names: Vec<&str> = compact_event.get_all_values().map(|(name, _)| &**name).collect();
+        assert_eq!(names, vec!["base", "name"]);
+
+        assert_eq!(compact_event.set_value("aaa", Value::U8(1)), None);
+        assert_eq!(compact_event.set_value("name", Value::U8(2)), Some(Value::Str("some_name".to_string())));
+        assert_ne!(compact_event, event);
+        let names: Vec<&str> = compact_event.get_all_values().map(|(name, _)| &**name).collect();
+        assert_eq!(names, vec!["aaa", "base", "name"]);
+
+        let flat_event = compact_event.flat_event();
+        assert!(flat_event.is_compact());
+        assert_eq!(flat_event.get_value_count(), 3);
+        assert_eq!(flat_event.get_value_u64("timestamp").unwrap(), 999);
+    }
+
+    #[test]
+    fn from_compact_values_should_keep_last_duplicated_value() {
+        let event = Event::from_compact_values(
+            1,
+            vec![("b".into(), Value::U8(1)), ("a".into(), Value::U8(2)), ("b".into(), Value::U8(3))],
+        );
+        assert_eq!(event.get_value_count(), 2);
+        assert_eq!(event.get_value_u8("a").unwrap(), 2);
+        assert_eq!(event.get_value_u8("b").unwrap(), 3);
+    }
+
+    #[test]
+    fn get_value_should_return_value_of_requested_type() {
+        let mut values = FnvHashMap::<String, Value>::default();
+        values.insert("u32".to_string(), Value::U32(7));
+        values.insert("str".to_string(), Value::Str("abc".to_string()));
+        let event = Event::new(1, values);
+
+        assert_eq!(event.get_value::<u32>("u32").unwrap(), 7);
+        assert_eq!(event.get_value::<&str>("str").unwrap(), "abc");
+        assert_eq!(event.get_value::<&Value>("u32").unwrap(), &Value::U32(7));
+        assert_eq!(event.get_value::<u64>("u32").unwrap_err().kind(), ErrorKind::InvalidType);
+        assert_eq!(event.get_value::<&Event>("str").unwrap_err().kind(), ErrorKind::InvalidType);
+        assert_eq!(event.get_value::<i8>("xxx").unwrap_err().kind(), ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn values_should_be_moved_out_of_event() {
+        let mut values = FnvHashMap::<String, Value>::default();
+        values.insert("u32".to_string(), Value::U32(7));
+        values.insert("str".to_string(), Value::Str("abc".to_string()));
+        values.insert("name".to_string(), Value::Str("foo".to_string()));
+        let mut event = Event::new(1, values);
+
+        assert_eq!(event.take_value("name"), Some(Value::Str("foo".to_string())));
+        assert_eq!(event.take_value("name"), None);
+        assert_eq!(event.get_value_count(), 2);
+
+        for event in [event.clone(), event.compact()] {
+            let mut values: Vec<(std::sync::Arc<str>, Value)> = event.into_values().collect();
+            values.sort_by(|a, b| a.0.cmp(&b.0));
+            assert_eq!(
+                values,
+                vec![("str".into(), Value::Str("abc".to_string())), ("u32".into(), Value::U32(7))]
+            );
+        }
+    }
+
+    #[test]
+    fn event_should_be_displayed_with_sorted_values() {
+        let mut base_values = FnvHashMap::<String, Value>::default();
+        base_values.insert("timestamp".to_string(), Value::U64(999));
+        base_values.insert("id".to_string(), Value::U64(2));
+        let mut values = FnvHashMap::<String, Value>::default();
+        values.insert("name".to_string(), Value::Str("foo".to_string()));
+        values.insert("base".to_string(), Value::Struct(Box::new(Event::new(1, base_values))));
+        let event = Event::new(99, values);
+
+        assert_eq!(
+            event.to_string(),
+            "<Event 99> {base: <Event 1> {id: 2, timestamp: 999}, name: \"foo\"}"
+        );
+        assert_eq!(Event::new(5, FnvHashMap::default()).to_string(), "<Event 5> {}");
+    }
+
+    #[test]
+    fn pretty_should_resolve_klass_and_field_types() {
+        let mut registry = EventKlassRegistry::new();
+        let mut klass = EventKlass::new(99, "foo".to_string());
+        klass.add_field("base".to_string(), "HT_Event".to_string(), DataType::Struct);
+        klass.add_field("name".to_string(), "const char*".to_string(), DataType::Str);
+        registry.add_klass(klass);
+
+        let mut base_values = FnvHashMap::<String, Value>::default();
+        base_values.insert("type".to_string(), Value::U32(99));
+        base_values.insert("timestamp".to_string(), Value::U64(999));
+        base_values.insert("id".to_string(), Value::U64(2));
+        let mut values = FnvHashMap::<String, Value>::default();
+        values.insert("base".to_string(), Value::Struct(Box::new(Event::new(1, base_values))));
+        values.insert("name".to_string(), Value::Str("bar".to_string()));
+        values.insert("extra".to_string(), Value::I8(-1));
+        let event = Event::new(99, values);
+
+        assert_eq!(
+            event.pretty(&registry),
+            "foo (99)\n  base: HT_Event\n    type: uint32_t = 99\n    timestamp: uint64_t = 999\n    id: uint64_t = 2\n  name: const char* = \"bar\"\n  extra = -1\n"
+        );
+        assert_eq!(
+            event.flat_event().pretty(&registry),
+            "foo (99)\n  type: uint32_t = 99\n  timestamp: uint64_t = 999\n  id: uint64_t = 2\n  name: const char* = \"bar\"\n  extra = -1\n"
+        );
+        assert_eq!(Event::new(5, FnvHashMap::default()).pretty(&registry), "<unknown klass> (5)\n");
+    }
+
+    #[test]
+    fn bytes_and_arrays_should_be_displayed() {
+        assert_eq!(Value::Bytes(vec![0, 15, 255]).to_string(), "0x000fff");
+        assert_eq!(Value::Array(vec![Value::U8(1), Value::I64(-2)]).to_string(), "[1, -2]");
+        assert_eq!(Value::Array(vec![]).to_string(), "[]");
+    }
 }
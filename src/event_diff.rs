@@ -0,0 +1,163 @@
+use crate::event::{Event, Value};
+use crate::registry::EventKlassRegistry;
+
+fn describe_klass(klass_id: u32, registry: &EventKlassRegistry) -> String {
+    match registry.get_klass_by_id(klass_id) {
+        Some(klass) => format!("{} ({})", klass.get_name(), klass_id),
+        None => format!("<unknown> ({})", klass_id),
+    }
+}
+
+fn describe_value(value: Option<&Value>, registry: &EventKlassRegistry) -> String {
+    match value {
+        Some(Value::Struct(event)) => {
+            format!("<{}>", describe_klass(event.get_klass_id(), registry))
+        }
+        Some(value) => value.to_string(),
+        None => "<missing>".to_owned(),
+    }
+}
+
+fn diff_events_internal(
+    expected: &Event,
+    actual: &Event,
+    registry: &EventKlassRegistry,
+    path: &str,
+    differences: &mut Vec<String>,
+) {
+    if expected.get_klass_id() != actual.get_klass_id() {
+        differences.push(format!(
+            "{}klass: expected {}, actual {}",
+            path,
+            describe_klass(expected.get_klass_id(), registry),
+            describe_klass(actual.get_klass_id(), registry)
+        ));
+    }
+
+    let klass = registry.get_klass_by_id(expected.get_klass_id());
+    let mut names: Vec<&str> = expected
+        .get_all_values()
+        .chain(actual.get_all_values())
+        .map(|(name, _)| &**name)
+        .collect();
+    names.sort();
+    names.dedup();
+
+    for name in names {
+        let expected_value = expected.get_raw_value(name);
+        let actual_value = actual.get_raw_value(name);
+        if expected_value == actual_value {
+            continue;
+        }
+        if let (Some(Value::Struct(expected_struct)), Some(Value::Struct(actual_struct))) =
+            (expected_value, actual_value)
+        {
+            let path = format!("{}{}.", path, name);
+            diff_events_internal(expected_struct, actual_struct, registry, &path, differences);
+            continue;
+        }
+
+        let type_name = klass
+            .and_then(|klass| {
+                klass
+                    .get_fields()
+                    .iter()
+                    .find(|field| field.get_name() == name)
+            })
+            .map_or("?", |field| field.get_type_name().as_str());
+        differences.push(format!(
+            "{}{} ({}): expected {}, actual {}",
+            path,
+            name,
+            type_name,
+            describe_value(expected_value, registry),
+            describe_value(actual_value, registry)
+        ));
+    }
+}
+
+// Returns a line for each difference between the events, with klass names
+// and field types resolved from the registry; nested structs are compared
+// field by field. Empty if the events are equal.
+pub fn diff_events(expected: &Event, actual: &Event, registry: &EventKlassRegistry) -> Vec<String> {
+    let mut differences = Vec::new();
+    diff_events_internal(expected, actual, registry, "", &mut differences);
+    differences
+}
+
+// Asserts that two events are equal; on failure, the panic message lists
+// all the differences (see diff_events()).
+#[macro_export]
+macro_rules! assert_events_eq {
+    ($registry:expr, $expected:expr, $actual:expr $(,)?) => {{
+        let differences = $crate::event_diff::diff_events(&$expected, &$actual, &$registry);
+        if !differences.is_empty() {
+            panic!(
+                "assertion failed: events are not equal\n  {}",
+                differences.join("\n  ")
+            );
+        }
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::DataType;
+    use crate::event_klass::EventKlass;
+    use crate::test_events::make_event;
+
+    fn make_registry() -> EventKlassRegistry {
+        let mut registry = EventKlassRegistry::new();
+        let mut klass = EventKlass::new(100, "foo".to_owned());
+        klass.add_field("base".to_owned(), "HT_Event".to_owned(), DataType::Struct);
+        klass.add_field("value".to_owned(), "uint32_t".to_owned(), DataType::U32);
+        klass.add_field("name".to_owned(), "const char*".to_owned(), DataType::Str);
+        registry.add_klass(klass);
+        registry
+    }
+
+    #[test]
+    fn equal_events_should_have_no_differences() {
+        let event = make_event(100, 5, vec![("value", Value::U32(1))]);
+
+        assert!(diff_events(&event, &event.clone(), &make_registry()).is_empty());
+        assert_events_eq!(make_registry(), event, event.clone());
+    }
+
+    #[test]
+    fn differences_should_be_listed_with_types() {
+        let expected = make_event(
+            100,
+            5,
+            vec![
+                ("value", Value::U32(1)),
+                ("name", Value::Str("a".to_owned())),
+            ],
+        );
+        let actual = make_event(101, 6, vec![("value", Value::U32(2))]);
+
+        assert_eq!(
+            diff_events(&expected, &actual, &make_registry()),
+            vec![
+                "klass: expected foo (100), actual <unknown> (101)",
+                "base.id (uint64_t): expected 5, actual 6",
+                "base.timestamp (uint64_t): expected 5, actual 6",
+                "base.type (uint32_t): expected 100, actual 101",
+                "name (const char*): expected \"a\", actual <missing>",
+                "value (uint32_t): expected 1, actual 2",
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "value (uint32_t): expected 1, actual 2")]
+    fn assert_events_eq_should_panic_with_differences() {
+        let registry = make_registry();
+        assert_events_eq!(
+            registry,
+            make_event(100, 5, vec![("value", Value::U32(1))]),
+            make_event(100, 5, vec![("value", Value::U32(2))]),
+        );
+    }
+}
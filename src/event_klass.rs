@@ -1,15 +1,32 @@
 use crate::event::DataType;
 
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EventKlassField {
-    name: String,
+    name: std::sync::Arc<str>,
     type_name: String,
     data_type: DataType,
+    // Size reported by the FieldInfo event (i.e. sizeof() of the C type).
+    #[cfg_attr(feature = "serde", serde(default))]
+    declared_size: Option<u64>,
 }
 
+// The declared size is not part of the layout of the field (it's not
+// available for fields defined by the user), so it's not compared.
+impl PartialEq for EventKlassField {
+    fn eq(&self, other: &EventKlassField) -> bool {
+        self.name == other.name
+            && self.type_name == other.type_name
+            && self.data_type == other.data_type
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EventKlass {
-    fields: std::vec::Vec<EventKlassField>,
-    name: String,
     id: u32,
+    name: String,
+    fields: std::vec::Vec<EventKlassField>,
 }
 
 impl EventKlass {
@@ -25,6 +42,11 @@ impl EventKlass {
         &self.name
     }
 
+    pub fn with_id(mut self, id: u32) -> EventKlass {
+        self.id = id;
+        self
+    }
+
     pub fn get_id(&self) -> u32 {
         self.id
     }
@@ -34,26 +56,55 @@ impl EventKlass {
     }
 
     pub fn add_field(&mut self, name: String, type_name: String, data_type: DataType) {
-        for field in &self.fields {
-            if *field.get_name() == name {
+        self.add_klass_field(EventKlassField::new(name, type_name, data_type));
+    }
+
+    pub fn add_klass_field(&mut self, field: EventKlassField) {
+        for existing_field in &self.fields {
+            if existing_field.get_name() == field.get_name() {
                 return; // TODO error?
             }
         }
-        self.fields
-            .push(EventKlassField::new(name, type_name, data_type));
+        self.fields.push(field);
+    }
+
+    // Size of the payload of events of the klass, if all the fields have a
+    // fixed size. The base event (the header) is not included. Other struct
+    // fields depend on the registry, so the size is unknown for them.
+    pub fn fixed_payload_size(&self) -> Option<usize> {
+        let mut size: usize = 0;
+        for field in &self.fields {
+            let field_size = match field.get_data_type() {
+                DataType::Struct if field.is_base_event_field() => 0,
+                data_type => data_type.get_fixed_size()?,
+            };
+            size = size.checked_add(field_size)?;
+        }
+        Some(size)
     }
 }
 
 impl EventKlassField {
     pub fn new(name: String, type_name: String, data_type: DataType) -> EventKlassField {
         EventKlassField {
-            name,
+            name: name.into(),
             type_name,
             data_type,
+            declared_size: None,
         }
     }
 
-    pub fn get_name(&self) -> &String {
+    pub fn with_declared_size(mut self, declared_size: u64) -> EventKlassField {
+        self.declared_size = Some(declared_size);
+        self
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    // Name shared with the events of the klass (see event::Values).
+    pub fn get_shared_name(&self) -> &std::sync::Arc<str> {
         &self.name
     }
 
@@ -64,6 +115,17 @@ impl EventKlassField {
     pub fn get_type_name(&self) -> &String {
         &self.type_name
     }
+
+    // Only available for fields defined by FieldInfo events. Note that it
+    // doesn't have to match the number of bytes in the stream (e.g. for
+    // strings it's the size of a pointer).
+    pub fn get_declared_size(&self) -> Option<u64> {
+        self.declared_size
+    }
+
+    pub fn is_base_event_field(&self) -> bool {
+        self.name.as_ref() == "base" && self.type_name == "HT_Event"
+    }
 }
 
 #[cfg(test)]
@@ -109,4 +171,25 @@ mod tests {
 
         assert_eq!(klass.get_fields().len(), 1);
     }
+
+    #[test]
+    fn fixed_payload_size_should_be_none_for_variable_length_fields() {
+        let mut klass = EventKlass::new(9, "klass_name".to_string());
+        klass.add_field("base".to_string(), "HT_Event".to_string(), DataType::Struct);
+        klass.add_field("a".to_string(), "uint32_t".to_string(), DataType::U32);
+        klass.add_klass_field(
+            EventKlassField::new("b".to_string(), "uint8_t".to_string(), DataType::U8)
+                .with_declared_size(1),
+        );
+        assert_eq!(klass.fixed_payload_size(), Some(5));
+        assert_eq!(klass.get_fields()[1].get_declared_size(), None);
+        assert_eq!(klass.get_fields()[2].get_declared_size(), Some(1));
+
+        klass.add_field("c".to_string(), "const char*".to_string(), DataType::Str);
+        assert_eq!(klass.fixed_payload_size(), None);
+
+        let mut klass = EventKlass::new(10, "klass_name".to_string());
+        klass.add_field("s".to_string(), "other_t".to_string(), DataType::Struct);
+        assert_eq!(klass.fixed_payload_size(), None);
+    }
 }
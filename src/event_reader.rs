@@ -1,43 +1,733 @@
-use crate::data_provider::DataProvider;
-use crate::data_struct_reader::{DataStructReader, ReadEventError};
+use crate::data_provider::{DataError, DataProvider};
+use crate::data_struct_reader::{
+    DataStructReader, Endianness, ReadEventError, DEFAULT_MAX_STRUCT_DEPTH,
+};
 use crate::event::Event;
+use crate::field_projection::FieldProjection;
+use crate::filter_expression::FilterExpression;
+use crate::index::TraceIndex;
+use crate::klass_filter::KlassFilter;
+use crate::progress::ProgressReporter;
+use crate::raw_event::RawEvent;
 use crate::registry::{CoreEventKlassId, EventKlassRegistry};
 use crate::registry_updater::RegistryUpdater;
+use crate::reorder_buffer::{ReorderBuffer, ReorderWindow};
+use crate::resource_usage::ResourceUsage;
+use crate::sampling::EventSampler;
+use crate::shared_registry::SharedEventKlassRegistry;
+
+pub type EventFilter = Box<dyn Fn(&Event) -> bool>;
 
 pub struct EventReader {
     data_provider: DataProvider,
+    endianness: Endianness,
+    strict: bool,
+    flatten: bool,
+    compact: bool,
+    max_struct_depth: usize,
+    filter: Option<EventFilter>,
+    filter_expression: Option<FilterExpression>,
+    klass_filter: Option<KlassFilter>,
+    sampler: Option<EventSampler>,
+    projection: Option<FieldProjection>,
+    // Event found by seek_to_timestamp() with its timestamp, returned by the
+    // next read_event() call.
+    pending_event: Option<(u64, Event)>,
+    ignored_error_count: u64,
+    // Position right after the last event which was fully read or skipped.
+    last_event_end: u64,
+    reorder_buffer: Option<ReorderBuffer>,
+    progress_reporter: Option<ProgressReporter>,
 }
 
 impl EventReader {
     pub fn new(data_provider: DataProvider) -> EventReader {
-        EventReader { data_provider }
+        let last_event_end = data_provider.get_position();
+        EventReader {
+            data_provider,
+            endianness: Endianness::Native,
+            strict: true,
+            flatten: false,
+            compact: false,
+            max_struct_depth: DEFAULT_MAX_STRUCT_DEPTH,
+            filter: None,
+            filter_expression: None,
+            klass_filter: None,
+            sampler: None,
+            projection: None,
+            pending_event: None,
+            ignored_error_count: 0,
+            last_event_end,
+            reorder_buffer: None,
+            progress_reporter: None,
+        }
+    }
+
+    pub fn from_file(file: std::fs::File) -> EventReader {
+        EventReader::new(DataProvider::new(Box::new(std::io::BufReader::new(file))))
+    }
+
+    pub fn from_path<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<EventReader> {
+        Ok(EventReader::from_file(std::fs::File::open(path)?))
+    }
+
+    pub fn get_position(&self) -> u64 {
+        self.data_provider.get_position()
+    }
+
+    // If reading fails, the bytes between this position and get_position()
+    // belong to the incomplete event.
+    pub fn get_last_event_end(&self) -> u64 {
+        self.last_event_end
+    }
+
+    // Loads more data if all the buffered data has been consumed; returns
+    // whether the buffer was refilled. Reading events refills the buffer
+    // anyway, this only allows doing it separately.
+    pub fn fill_buffer(&mut self) -> Result<bool, ReadEventError> {
+        self.data_provider
+            .fill_buffer()
+            .map_err(ReadEventError::DataError)
+    }
+
+    pub fn get_resource_usage(&self, registry: &EventKlassRegistry) -> ResourceUsage {
+        let mut usage = ResourceUsage::from_registry(registry);
+        usage.buffer_bytes = self.data_provider.get_buffer_size();
+        if let Some(reorder_buffer) = &self.reorder_buffer {
+            usage.reorder_buffer_events = reorder_buffer.get_size();
+        }
+        usage
+    }
+
+    // See DataProvider::set_tee().
+    pub fn set_tee(&mut self, tee: Option<Box<dyn std::io::Write>>) {
+        self.data_provider.set_tee(tee);
+    }
+
+    pub fn take_tee(&mut self) -> Option<Box<dyn std::io::Write>> {
+        self.data_provider.take_tee()
+    }
+
+    // Number of errors ignored because of the non-strict mode.
+    pub fn get_ignored_error_count(&self) -> u64 {
+        self.ignored_error_count
+    }
+
+    pub fn set_endianness(&mut self, endianness: Endianness) {
+        self.endianness = endianness;
+    }
+
+    // In non-strict mode, metadata events which can't be applied to the registry
+    // are still returned instead of failing the read.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    pub fn set_flatten(&mut self, flatten: bool) {
+        self.flatten = flatten;
+    }
+
+    // Decoded events use the compact layout (see Event::compact()), which is
+    // cheaper for events with a few fields.
+    pub fn set_compact(&mut self, compact: bool) {
+        self.compact = compact;
+    }
+
+    pub fn set_max_struct_depth(&mut self, max_struct_depth: usize) {
+        self.max_struct_depth = max_struct_depth;
+    }
+
+    pub fn set_filter(&mut self, filter: Option<EventFilter>) {
+        self.filter = filter;
+    }
+
+    // Same as the filter, but the expression can refer to klass names as
+    // events are matched with the registry.
+    pub fn set_filter_expression(&mut self, filter_expression: Option<FilterExpression>) {
+        self.filter_expression = filter_expression;
+    }
+
+    fn is_expression_matched(&self, event: &Event, registry: &EventKlassRegistry) -> bool {
+        match &self.filter_expression {
+            Some(filter_expression) => filter_expression.matches(event, registry),
+            None => true,
+        }
+    }
+
+    // Events of klasses rejected by the klass filter are skipped without being
+    // decoded. Metadata events are always decoded so the registry stays complete.
+    pub fn set_klass_filter(&mut self, klass_filter: Option<KlassFilter>) {
+        self.klass_filter = klass_filter;
+    }
+
+    // Events rejected by the sampler are skipped without being decoded, the
+    // same way as events rejected by the klass filter.
+    pub fn set_sampler(&mut self, sampler: Option<EventSampler>) {
+        self.sampler = sampler;
+    }
+
+    pub fn get_sampler(&self) -> Option<&EventSampler> {
+        self.sampler.as_ref()
+    }
+
+    // Events are buffered and returned in timestamp order, as long as they
+    // are not delayed by more than the window.
+    pub fn set_reorder_window(&mut self, window: Option<ReorderWindow>) {
+        self.reorder_buffer = window.map(ReorderBuffer::new);
+    }
+
+    pub fn set_projection(&mut self, projection: Option<FieldProjection>) {
+        self.projection = projection;
+    }
+
+    // The reporter counts events returned by read_event(), read_event_into(),
+    // read_raw_event() and skip_event().
+    pub fn set_progress_reporter(&mut self, progress_reporter: Option<ProgressReporter>) {
+        self.progress_reporter = progress_reporter;
+    }
+
+    pub fn get_progress_reporter(&self) -> Option<&ProgressReporter> {
+        self.progress_reporter.as_ref()
+    }
+
+    fn report_progress<T>(
+        &mut self,
+        result: Result<T, ReadEventError>,
+    ) -> Result<T, ReadEventError> {
+        if let Some(progress_reporter) = &mut self.progress_reporter {
+            let position = self.data_provider.get_position();
+            match &result {
+                Ok(_) => progress_reporter.event_read(position),
+                Err(ReadEventError::DataError(DataError::EndOfStream)) => {
+                    progress_reporter.finish(position)
+                }
+                Err(_) => {}
+            }
+        }
+        result
     }
 
     pub fn read_event(
         &mut self,
         registry: &mut EventKlassRegistry,
     ) -> Result<Event, ReadEventError> {
+        let result = self.read_event_with(|reader| reader.read_next_matching_event(registry));
+        self.report_progress(result)
+    }
+
+    // Same as read_event(), but at most one event is read: None is returned
+    // if the event was skipped (by the klass filter, the sampler or one of
+    // the filters) or buffered by the reorder buffer.
+    pub fn read_event_step(
+        &mut self,
+        registry: &mut EventKlassRegistry,
+    ) -> Result<Option<Event>, ReadEventError> {
+        match self.read_event_step_with(&mut |reader| reader.read_next_matching_event(registry)) {
+            Ok(None) => Ok(None),
+            result => self.report_progress(result),
+        }
+    }
+
+    fn read_next_matching_event(
+        &mut self,
+        registry: &mut EventKlassRegistry,
+    ) -> Result<Option<Event>, ReadEventError> {
+        let event = self.read_next_event(registry)?;
+        Ok(event.filter(|event| self.is_expression_matched(event, registry)))
+    }
+
+    // Same as read_event(), but the registry can be used by other threads in
+    // the meantime. The write lock is only taken to apply metadata events.
+    pub fn read_event_shared(
+        &mut self,
+        registry: &SharedEventKlassRegistry,
+    ) -> Result<Event, ReadEventError> {
+        let result = self.read_event_with(|reader| {
+            let event = reader.read_next_shared_event(registry)?;
+            Ok(event.filter(|event| reader.is_expression_matched(event, &registry.read())))
+        });
+        self.report_progress(result)
+    }
+
+    fn read_event_with<F>(&mut self, mut read_next: F) -> Result<Event, ReadEventError>
+    where
+        F: FnMut(&mut EventReader) -> Result<Option<Event>, ReadEventError>,
+    {
+        loop {
+            if let Some(event) = self.read_event_step_with(&mut read_next)? {
+                return Ok(event);
+            }
+        }
+    }
+
+    fn read_event_step_with<F>(
+        &mut self,
+        read_next: &mut F,
+    ) -> Result<Option<Event>, ReadEventError>
+    where
+        F: FnMut(&mut EventReader) -> Result<Option<Event>, ReadEventError>,
+    {
+        if let Some(event) = self
+            .reorder_buffer
+            .as_mut()
+            .and_then(ReorderBuffer::pop_ready)
+        {
+            return Ok(Some(event));
+        }
+
+        let result = self.read_filtered_event(read_next);
+        let reorder_buffer = match &mut self.reorder_buffer {
+            Some(reorder_buffer) => reorder_buffer,
+            None => return result,
+        };
+        match result {
+            Ok(Some(event)) => {
+                reorder_buffer.push(event);
+                Ok(None)
+            }
+            Ok(None) => Ok(None),
+            Err(ReadEventError::DataError(DataError::EndOfStream)) => match reorder_buffer.pop() {
+                Some(event) => Ok(Some(event)),
+                None => Err(ReadEventError::DataError(DataError::EndOfStream)),
+            },
+            Err(err) => Err(err),
+        }
+    }
+
+    // Same as read_event(), but the event is decoded into the given one,
+    // reusing the memory of its values (and of its base event). Passing the
+    // same event to consecutive calls avoids most of the per-event
+    // allocations. Readers with a filter (or a filter expression), a reorder
+    // window or flattening enabled fall back to read_event(). If reading
+    // fails, the content of the event is unspecified.
+    pub fn read_event_into(
+        &mut self,
+        registry: &mut EventKlassRegistry,
+        event: &mut Event,
+    ) -> Result<(), ReadEventError> {
+        if self.filter.is_some()
+            || self.filter_expression.is_some()
+            || self.reorder_buffer.is_some()
+            || self.flatten
+            || self.pending_event.is_some()
+        {
+            *event = self.read_event(registry)?;
+            return Ok(());
+        }
+
+        let result = self.read_next_event_into(registry, event);
+        if result.is_ok() {
+            self.last_event_end = self.get_position();
+        }
+        self.report_progress(result)
+    }
+
+    fn read_next_event_into(
+        &mut self,
+        registry: &mut EventKlassRegistry,
+        event: &mut Event,
+    ) -> Result<(), ReadEventError> {
+        loop {
+            let mut base_event = event.take_base_event();
+            match registry.get_klass_by_id(CoreEventKlassId::Base as u32) {
+                Some(klass) => {
+                    DataStructReader::new(&mut self.data_provider, registry, klass, None)
+                        .with_endianness(self.endianness)
+                        .with_max_struct_depth(self.max_struct_depth)
+                        .with_compact(self.compact)
+                        .read_event_into(&mut base_event)?
+                }
+                None => return Err(ReadEventError::MissingBaseKlass),
+            }
+            let klass_id = Self::get_klass_id(&base_event)?;
+
+            if CoreEventKlassId::is_metadata_klass(klass_id) {
+                *event = self.read_regular_event(registry, klass_id, *base_event)?;
+                return self.update_registry(registry, event);
+            }
+            if !self.is_event_selected(registry, klass_id, &base_event) {
+                self.skip_regular_event(registry, klass_id)?;
+                self.last_event_end = self.get_position();
+                continue;
+            }
+            if klass_id == CoreEventKlassId::Base as u32 {
+                *event = *base_event;
+                return Ok(());
+            }
+
+            self.check_klass_complete(registry, klass_id)?;
+            return match registry.get_klass_by_id(klass_id) {
+                Some(klass) => {
+                    DataStructReader::new(&mut self.data_provider, registry, klass, None)
+                        .with_base_event(base_event)
+                        .with_endianness(self.endianness)
+                        .with_max_struct_depth(self.max_struct_depth)
+                        .with_compact(self.compact)
+                        .with_projection(self.projection.as_ref())
+                        .read_event_into(event)
+                }
+                None => Err(self.unknown_klass(klass_id)),
+            };
+        }
+    }
+
+    // Returns None if the event was skipped or rejected by the filter.
+    fn read_filtered_event<F>(&mut self, read_next: &mut F) -> Result<Option<Event>, ReadEventError>
+    where
+        F: FnMut(&mut EventReader) -> Result<Option<Event>, ReadEventError>,
+    {
+        let event = match self.pending_event.take() {
+            Some((_, event)) => Some(event),
+            None => {
+                let event = read_next(self)?;
+                self.last_event_end = self.get_position();
+                event
+            }
+        };
+
+        Ok(event.filter(|event| match &self.filter {
+            Some(filter) => filter(event),
+            None => true,
+        }))
+    }
+
+    // Moves the reader to the first event (in stream order) with a timestamp
+    // greater than or equal to the given one; the reader can't go backwards.
+    // Metadata events before the target are still applied to the registry.
+    pub fn seek_to_timestamp(
+        &mut self,
+        registry: &mut EventKlassRegistry,
+        timestamp: u64,
+    ) -> Result<(), ReadEventError> {
+        if self.is_pending_event_after(timestamp) {
+            return Ok(());
+        }
+
+        loop {
+            let raw_event = self.read_next_captured_raw_event(registry)?;
+            let event_timestamp = raw_event
+                .get_header()
+                .get_value_u64("timestamp")
+                .unwrap_or_default();
+            if event_timestamp >= timestamp {
+                let event = self.decode_raw_event(registry, raw_event)?;
+                self.pending_event = Some((event_timestamp, event));
+                return Ok(());
+            }
+        }
+    }
+
+    // Same as seek_to_timestamp(), but payloads of events before the target
+    // are skipped without reading their headers. The index has to be built
+    // for the same stream.
+    pub fn seek_to_timestamp_with_index(
+        &mut self,
+        registry: &mut EventKlassRegistry,
+        index: &TraceIndex,
+        timestamp: u64,
+    ) -> Result<(), ReadEventError> {
+        if self.is_pending_event_after(timestamp) {
+            return Ok(());
+        }
+
+        for entry in index.get_entries() {
+            if entry.offset < self.get_position() {
+                continue;
+            }
+            if entry.timestamp >= timestamp {
+                break;
+            }
+            if CoreEventKlassId::is_metadata_klass(entry.klass_id) {
+                self.read_next_event(registry)?;
+            } else if let Err(err) = self.data_provider.skip_bytes(entry.size as usize) {
+                return Err(ReadEventError::DataError(err));
+            }
+            self.last_event_end = self.get_position();
+        }
+
+        Ok(())
+    }
+
+    fn is_pending_event_after(&mut self, timestamp: u64) -> bool {
+        match &self.pending_event {
+            Some((event_timestamp, _)) if *event_timestamp >= timestamp => true,
+            _ => {
+                self.pending_event = None;
+                false
+            }
+        }
+    }
+
+    fn decode_raw_event(
+        &self,
+        registry: &EventKlassRegistry,
+        raw_event: RawEvent,
+    ) -> Result<Event, ReadEventError> {
+        if raw_event.is_decoded() {
+            return raw_event.into_event(registry);
+        }
+
+        let data = raw_event.get_data().to_vec();
+        let mut reader = EventReader::new(DataProvider::new(Box::new(std::io::Cursor::new(data))));
+        reader.endianness = self.endianness;
+        reader.flatten = self.flatten;
+        reader.compact = self.compact;
+        reader.max_struct_depth = self.max_struct_depth;
+        reader.projection = self.projection.clone();
+        reader.read_data_event(registry)
+    }
+
+    // Reads the next event without decoding its payload; the event can be
+    // decoded later with RawEvent::decode(). Metadata events are decoded
+    // immediately so the registry is kept up to date. The event filter is
+    // not applied as it requires decoded events.
+    pub fn read_raw_event(
+        &mut self,
+        registry: &mut EventKlassRegistry,
+    ) -> Result<RawEvent, ReadEventError> {
+        let result = self.read_next_captured_raw_event(registry);
+        self.report_progress(result)
+    }
+
+    fn read_next_captured_raw_event(
+        &mut self,
+        registry: &mut EventKlassRegistry,
+    ) -> Result<RawEvent, ReadEventError> {
+        loop {
+            self.data_provider.start_capture();
+            let result = self.read_next_raw_event(registry);
+            let data = self.data_provider.finish_capture();
+
+            let result = result?;
+            self.last_event_end = self.get_position();
+            if let Some((klass_id, header, event)) = result {
+                return Ok(RawEvent::new(
+                    klass_id,
+                    header,
+                    data,
+                    self.endianness,
+                    event,
+                ));
+            }
+        }
+    }
+
+    // Consumes the next event without decoding its payload and returns its
+    // header. Metadata events are still decoded to update the registry.
+    pub fn skip_event(
+        &mut self,
+        registry: &mut EventKlassRegistry,
+    ) -> Result<Event, ReadEventError> {
+        let result = self.skip_next_event(registry);
+        self.report_progress(result)
+    }
+
+    fn skip_next_event(
+        &mut self,
+        registry: &mut EventKlassRegistry,
+    ) -> Result<Event, ReadEventError> {
+        let base_event = self.read_header(registry)?;
+        let klass_id = Self::get_klass_id(&base_event)?;
+
+        if CoreEventKlassId::is_metadata_klass(klass_id) {
+            let event = self.read_regular_event(registry, klass_id, base_event.clone())?;
+            self.update_registry(registry, &event)?;
+        } else {
+            self.skip_regular_event(registry, klass_id)?;
+        }
+
+        self.last_event_end = self.get_position();
+        Ok(base_event)
+    }
+
+    fn read_next_raw_event(
+        &mut self,
+        registry: &mut EventKlassRegistry,
+    ) -> Result<Option<(u32, Event, Option<Event>)>, ReadEventError> {
+        let base_event = self.read_header(registry)?;
+        let klass_id = Self::get_klass_id(&base_event)?;
+
+        if CoreEventKlassId::is_metadata_klass(klass_id) {
+            let header = base_event.clone();
+            let event = self.read_regular_event(registry, klass_id, base_event)?;
+            self.update_registry(registry, &event)?;
+            return Ok(Some((klass_id, header, Some(event))));
+        }
+
+        self.skip_regular_event(registry, klass_id)?;
+        if self.is_event_selected(registry, klass_id, &base_event) {
+            Ok(Some((klass_id, base_event, None)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn read_next_event(
+        &mut self,
+        registry: &mut EventKlassRegistry,
+    ) -> Result<Option<Event>, ReadEventError> {
         let base_event = self.read_header(registry)?;
+        let klass_id = Self::get_klass_id(&base_event)?;
+        let is_metadata = CoreEventKlassId::is_metadata_klass(klass_id);
 
-        let klass_id = base_event
-            .get_value_u32("type")
-            .expect("Cannot find 'type' field in base klass. Registry corrupted?");
+        if !is_metadata && !self.is_event_selected(registry, klass_id, &base_event) {
+            self.skip_regular_event(registry, klass_id)?;
+            return Ok(None);
+        }
 
         if klass_id == CoreEventKlassId::Base as u32 {
-            return Ok(base_event);
+            return Ok(Some(base_event));
         }
 
         let event = self.read_regular_event(registry, klass_id, base_event)?;
 
-        if klass_id == CoreEventKlassId::KlassInfo as u32
-            || klass_id == CoreEventKlassId::FieldInfo as u32
-        {
-            if let Err(err) = RegistryUpdater::new(registry).update_registry_from_event(&event) {
-                return Err(ReadEventError::RegistryUpdateFailed(err.to_owned()));
+        if is_metadata {
+            self.update_registry(registry, &event)?;
+        }
+
+        Ok(Some(event))
+    }
+
+    fn read_next_shared_event(
+        &mut self,
+        registry: &SharedEventKlassRegistry,
+    ) -> Result<Option<Event>, ReadEventError> {
+        let base_event = self.read_header(&registry.read())?;
+        let klass_id = Self::get_klass_id(&base_event)?;
+
+        // Metadata klasses are core klasses, so the event can be decoded
+        // before the registry is locked for writing.
+        if CoreEventKlassId::is_metadata_klass(klass_id) {
+            let event = self.read_regular_event(&registry.read(), klass_id, base_event)?;
+            self.update_registry(&mut registry.write(), &event)?;
+            return Ok(Some(event));
+        }
+
+        let registry = registry.read();
+        if !self.is_event_selected(&registry, klass_id, &base_event) {
+            self.skip_regular_event(&registry, klass_id)?;
+            return Ok(None);
+        }
+        if klass_id == CoreEventKlassId::Base as u32 {
+            return Ok(Some(base_event));
+        }
+
+        self.read_regular_event(&registry, klass_id, base_event)
+            .map(Some)
+    }
+
+    // Reads an event which is known not to be a metadata event, so the
+    // registry doesn't have to be updated.
+    pub(crate) fn read_data_event(
+        &mut self,
+        registry: &EventKlassRegistry,
+    ) -> Result<Event, ReadEventError> {
+        let base_event = self.read_header(registry)?;
+        let klass_id = Self::get_klass_id(&base_event)?;
+        if klass_id == CoreEventKlassId::Base as u32 {
+            return Ok(base_event);
+        }
+
+        self.read_regular_event(registry, klass_id, base_event)
+    }
+
+    fn get_klass_id(base_event: &Event) -> Result<u32, ReadEventError> {
+        match base_event.get_value_u32("type") {
+            Ok(klass_id) => Ok(klass_id),
+            Err(_) => Err(ReadEventError::MissingField("type".to_owned())),
+        }
+    }
+
+    fn update_registry(
+        &mut self,
+        registry: &mut EventKlassRegistry,
+        event: &Event,
+    ) -> Result<(), ReadEventError> {
+        match RegistryUpdater::new(registry).update_registry_from_event(event) {
+            Err(err) if self.strict => Err(ReadEventError::RegistryUpdateFailed(err)),
+            Err(err) => {
+                debug!(
+                    "Ignored registry update error at byte {}: {}",
+                    self.get_position(),
+                    err
+                );
+                self.ignored_error_count += 1;
+                Ok(())
             }
+            Ok(()) => Ok(()),
+        }
+    }
+
+    // Events of klasses with missing fields would be decoded with a wrong
+    // layout; the reader can only count them if it's not strict.
+    fn check_klass_complete(
+        &mut self,
+        registry: &EventKlassRegistry,
+        klass_id: u32,
+    ) -> Result<(), ReadEventError> {
+        if registry.is_klass_complete(klass_id) {
+            Ok(())
+        } else if self.strict {
+            Err(ReadEventError::IncompleteKlass(klass_id))
+        } else {
+            debug!(
+                "Reading event of incomplete klass {} at byte {}",
+                klass_id,
+                self.get_position()
+            );
+            self.ignored_error_count += 1;
+            Ok(())
+        }
+    }
+
+    fn unknown_klass(&self, klass_id: u32) -> ReadEventError {
+        debug!(
+            "Unknown klass {} in the event header ending at byte {}",
+            klass_id,
+            self.get_position()
+        );
+        ReadEventError::UnknownKlassId(klass_id)
+    }
+
+    fn is_klass_allowed(&self, registry: &EventKlassRegistry, klass_id: u32) -> bool {
+        match (&self.klass_filter, registry.get_klass_by_id(klass_id)) {
+            (Some(klass_filter), Some(klass)) => klass_filter.is_allowed(klass),
+            _ => true,
+        }
+    }
+
+    // Must not be called for metadata events.
+    fn is_event_selected(
+        &mut self,
+        registry: &EventKlassRegistry,
+        klass_id: u32,
+        header: &Event,
+    ) -> bool {
+        if !self.is_klass_allowed(registry, klass_id) {
+            return false;
+        }
+        match &mut self.sampler {
+            Some(sampler) => sampler.should_keep(klass_id, header.get_value_u64("timestamp").ok()),
+            None => true,
+        }
+    }
+
+    fn skip_regular_event(
+        &mut self,
+        registry: &EventKlassRegistry,
+        klass_id: u32,
+    ) -> Result<(), ReadEventError> {
+        if klass_id == CoreEventKlassId::Base as u32 {
+            return Ok(());
         }
 
-        Ok(event)
+        self.check_klass_complete(registry, klass_id)?;
+        match registry.get_klass_by_id(klass_id) {
+            Some(klass) => DataStructReader::new(&mut self.data_provider, registry, klass, None)
+                .with_max_struct_depth(self.max_struct_depth)
+                .skip_event(),
+            None => Err(self.unknown_klass(klass_id)),
+        }
     }
 
     fn read_regular_event(
@@ -48,19 +738,36 @@ impl EventReader {
     ) -> Result<Event, ReadEventError> {
         let klass = match registry.get_klass_by_id(klass_id) {
             Some(klass) => klass,
-            None => return Err(ReadEventError::UnknownKlassId(klass_id)),
+            None => return Err(self.unknown_klass(klass_id)),
+        };
+        self.check_klass_complete(registry, klass_id)?;
+
+        // Metadata events are always fully decoded, the registry depends on them.
+        let projection = if CoreEventKlassId::is_core_klass(klass_id) {
+            None
+        } else {
+            self.projection.as_ref()
         };
 
         DataStructReader::new(&mut self.data_provider, registry, klass, Some(base_event))
+            .with_endianness(self.endianness)
+            .with_max_struct_depth(self.max_struct_depth)
+            .with_flatten(self.flatten)
+            .with_compact(self.compact)
+            .with_projection(projection)
             .read_event()
     }
 
-    fn read_header(&mut self, registry: &mut EventKlassRegistry) -> Result<Event, ReadEventError> {
-        let base_event_klass = registry
-            .get_klass_by_id(CoreEventKlassId::Base as u32)
-            .expect("Can not find Base klass definition!");
+    fn read_header(&mut self, registry: &EventKlassRegistry) -> Result<Event, ReadEventError> {
+        let base_event_klass = match registry.get_klass_by_id(CoreEventKlassId::Base as u32) {
+            Some(klass) => klass,
+            None => return Err(ReadEventError::MissingBaseKlass),
+        };
 
         DataStructReader::new(&mut self.data_provider, registry, base_event_klass, None)
+            .with_endianness(self.endianness)
+            .with_max_struct_depth(self.max_struct_depth)
+            .with_compact(self.compact)
             .read_event()
     }
 }
@@ -68,9 +775,10 @@ impl EventReader {
 #[cfg(test)]
 pub mod tests {
     use super::*;
-    use hawktracer_parser_test_utilities::FakeDataReader;
-    use crate::event_klass::EventKlass;
     use crate::event::DataType;
+    use crate::event_klass::EventKlass;
+    use hawktracer_parser_test_utilities::stream::{TestStreamBuilder, TestValue};
+    use hawktracer_parser_test_utilities::{FakeDataReader, FakeDataWriter};
 
     #[test]
     fn read_header_should_return_valid_base_event() {
@@ -79,18 +787,117 @@ pub mod tests {
             1, 2, 0, 0, 0, 0, 0, 0, // timestamp
             2, 0, 0, 0, 0, 0, 0, 0, // id
         ];
-        let mut reg = EventKlassRegistry::new();
+        let reg = EventKlassRegistry::new();
         let data_provider = DataProvider::new(Box::new(FakeDataReader::new(data, false)));
 
-        let event = EventReader::new(data_provider)
-            .read_header(&mut reg)
+        let event = EventReader::new(data_provider).read_header(&reg).unwrap();
+
+        assert_eq!(event.get_value_u32("type").unwrap(), 1);
+        assert_eq!(event.get_value_u64("timestamp").unwrap(), 513);
+        assert_eq!(event.get_value_u64("id").unwrap(), 2);
+    }
+
+    #[test]
+    fn reader_created_from_path_should_read_file_content() {
+        let path = std::env::temp_dir().join(format!("ht-from-path-{}", std::process::id()));
+        std::fs::write(
+            &path,
+            [1, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0],
+        )
+        .unwrap();
+
+        let event = EventReader::from_path(&path)
+            .unwrap()
+            .read_event(&mut EventKlassRegistry::new())
             .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(event.get_value_u64("timestamp").unwrap(), 3);
+        assert_eq!(event.get_value_u64("id").unwrap(), 4);
+    }
+
+    #[test]
+    fn resource_usage_should_include_buffer_size() {
+        let reader = EventReader::new(DataProvider::with_buffer_size(
+            Box::new(FakeDataReader::new(vec![], false)),
+            64,
+        ));
+
+        let usage = reader.get_resource_usage(&EventKlassRegistry::new());
+        assert_eq!(usage.buffer_bytes, 64);
+        assert_eq!(usage.klass_count, 4);
+    }
+
+    #[test]
+    fn reader_created_from_non_existing_path_should_fail() {
+        assert!(EventReader::from_path("/non/existing/trace.htdump").is_err());
+    }
+
+    #[test]
+    fn read_header_should_fail_if_base_klass_is_missing() {
+        let mut reg = EventKlassRegistry::default();
+        let data_provider =
+            DataProvider::new(Box::new(FakeDataReader::new(vec![1, 0, 0, 0], false)));
+
+        assert_eq!(
+            EventReader::new(data_provider)
+                .read_event(&mut reg)
+                .unwrap_err(),
+            ReadEventError::MissingBaseKlass
+        );
+    }
+
+    #[test]
+    fn read_event_should_fail_if_base_klass_has_no_type_field() {
+        let mut reg = EventKlassRegistry::default();
+        let mut base_klass = EventKlass::new(CoreEventKlassId::Base as u32, "HT_Event".to_owned());
+        base_klass.add_field("timestamp".to_owned(), "uint64_t".to_owned(), DataType::U64);
+        reg.add_klass(base_klass);
+        let data_provider = DataProvider::new(Box::new(FakeDataReader::new(vec![1; 8], false)));
 
-        assert_eq!(event.get_value_u32(&"type").unwrap(), 1);
-        assert_eq!(event.get_value_u64(&"timestamp").unwrap(), 513);
-        assert_eq!(event.get_value_u64(&"id").unwrap(), 2);
+        assert_eq!(
+            EventReader::new(data_provider)
+                .read_event(&mut reg)
+                .unwrap_err(),
+            ReadEventError::MissingField("type".to_owned())
+        );
     }
 
+    #[test]
+    fn read_event_should_skip_klasses_rejected_by_klass_filter() {
+        let mut data = vec![
+            100, 0, 0, 0, // type
+            1, 0, 0, 0, 0, 0, 0, 0, // timestamp
+            1, 0, 0, 0, 0, 0, 0, 0, // id
+            65, 66, 67, 0, // ABC
+            45, 1, 0, 0, // 301
+        ];
+        data.extend_from_slice(&[
+            101, 0, 0, 0, // type
+            2, 0, 0, 0, 0, 0, 0, 0, // timestamp
+            2, 0, 0, 0, 0, 0, 0, 0, // id
+            7, 0, // 7
+        ]);
+        let mut reg = EventKlassRegistry::new();
+        let mut klass = EventKlass::new(100, "foo".to_owned());
+        klass.add_field("str_field".to_owned(), "char*".to_owned(), DataType::Str);
+        klass.add_field("u32_field".to_owned(), "uint32_t".to_owned(), DataType::U32);
+        reg.add_klass(klass);
+        let mut klass = EventKlass::new(101, "bar".to_owned());
+        klass.add_field("base".to_owned(), "HT_Event".to_owned(), DataType::Struct);
+        klass.add_field("u16_field".to_owned(), "uint16_t".to_owned(), DataType::U16);
+        reg.add_klass(klass);
+
+        let mut reader = EventReader::new(DataProvider::new(Box::new(FakeDataReader::new(
+            data, false,
+        ))));
+        reader.set_klass_filter(Some(KlassFilter::new().allow_name("bar")));
+
+        let event = reader.read_event(&mut reg).unwrap();
+        assert_eq!(event.get_klass_id(), 101);
+        assert_eq!(event.get_value_u16("u16_field").unwrap(), 7);
+        assert_eq!(reader.get_position(), 50);
+    }
 
     #[test]
     fn read_event_should_return_full_event() {
@@ -117,12 +924,377 @@ pub mod tests {
 
         assert_eq!(event.get_klass_id(), 100);
 
-        let base_event = event.get_value_struct(&"base").unwrap();
-        assert_eq!(base_event.get_value_u32(&"type").unwrap(), 100);
-        assert_eq!(base_event.get_value_u64(&"timestamp").unwrap(), 513);
-        assert_eq!(base_event.get_value_u64(&"id").unwrap(), 2);
+        let base_event = event.get_value_struct("base").unwrap();
+        assert_eq!(base_event.get_value_u32("type").unwrap(), 100);
+        assert_eq!(base_event.get_value_u64("timestamp").unwrap(), 513);
+        assert_eq!(base_event.get_value_u64("id").unwrap(), 2);
+
+        assert_eq!(event.get_value_string("str_field").unwrap(), "ABC");
+        assert_eq!(event.get_value_u32("u32_field").unwrap(), 301);
+
+        let field = &reg.get_klass_by_id(100).unwrap().get_fields()[1];
+        let (name, _) = event
+            .get_all_values()
+            .find(|(name, _)| &***name == "str_field")
+            .unwrap();
+        assert!(std::sync::Arc::ptr_eq(name, field.get_shared_name()));
+    }
+
+    fn make_seek_stream() -> Vec<u8> {
+        let mut builder = TestStreamBuilder::new().klass(100, "foo", &[("uint32_t", "value")]);
+        for (timestamp, value) in &[(5, 1), (3, 2), (10, 3)] {
+            builder = builder.event(100, *timestamp, &[TestValue::U32(*value)]);
+        }
+        builder
+            .klass(101, "bar", &[])
+            .event(101, 11, &[])
+            .event(100, 7, &[TestValue::U32(4)])
+            .build()
+    }
+
+    fn make_seek_reader() -> EventReader {
+        let mut reader = EventReader::new(DataProvider::new(Box::new(FakeDataReader::new(
+            make_seek_stream(),
+            false,
+        ))));
+        reader.set_endianness(Endianness::Native);
+        reader
+    }
+
+    fn check_events_after_seek(reader: &mut EventReader, reg: &mut EventKlassRegistry) {
+        let event = reader.read_event(reg).unwrap();
+        assert_eq!(event.get_value_u32("value").unwrap(), 3);
+        assert_eq!(reader.read_event(reg).unwrap().get_klass_id(), 2);
+        assert!(reg.get_klass_by_name("bar").is_some());
+        assert_eq!(reader.read_event(reg).unwrap().get_klass_id(), 101);
+        let event = reader.read_event(reg).unwrap();
+        assert_eq!(event.get_value_u32("value").unwrap(), 4);
+    }
+
+    #[test]
+    fn seek_to_timestamp_should_skip_events_before_target() {
+        let mut reg = EventKlassRegistry::new();
+        let mut reader = make_seek_reader();
+
+        reader.seek_to_timestamp(&mut reg, 8).unwrap();
+        reader.seek_to_timestamp(&mut reg, 8).unwrap();
+        assert!(reg.get_klass_by_name("foo").is_some());
+
+        check_events_after_seek(&mut reader, &mut reg);
+    }
+
+    #[test]
+    fn seek_to_timestamp_with_index_should_skip_events_before_target() {
+        let index =
+            TraceIndex::build(&mut make_seek_reader(), &mut EventKlassRegistry::new()).unwrap();
+        let mut reg = EventKlassRegistry::new();
+        let mut reader = make_seek_reader();
+
+        reader
+            .seek_to_timestamp_with_index(&mut reg, &index, 8)
+            .unwrap();
+        assert!(reg.get_klass_by_name("foo").is_some());
+        assert!(reg.get_klass_by_name("bar").is_none());
+
+        check_events_after_seek(&mut reader, &mut reg);
+    }
+
+    #[test]
+    fn shared_registry_should_be_updated_by_reader_on_another_thread() {
+        let registry = SharedEventKlassRegistry::new();
+        let thread_registry = registry.clone();
+        std::thread::spawn(move || {
+            let mut reader = make_seek_reader();
+            let mut count = 0;
+            while reader.read_event_shared(&thread_registry).is_ok() {
+                count += 1;
+            }
+            assert_eq!(count, 8);
+        })
+        .join()
+        .unwrap();
+
+        let data = make_seek_stream()[77..101].to_vec();
+        let mut reader = EventReader::new(DataProvider::new(Box::new(FakeDataReader::new(
+            data, false,
+        ))));
+        reader.set_endianness(Endianness::Native);
+
+        let event = reader.read_event_shared(&registry).unwrap();
+        assert_eq!(event.get_value_u32("value").unwrap(), 1);
+        assert!(registry.read().get_klass_by_name("bar").is_some());
+    }
+
+    #[test]
+    fn events_of_incomplete_klass_should_fail_in_strict_mode_only() {
+        // Klass "foo" declares 2 fields, but only one is defined.
+        let mut data = make_seek_stream()[..101].to_vec();
+        data[28] = 2;
+
+        for strict in &[true, false] {
+            let mut reg = EventKlassRegistry::new();
+            let mut reader = EventReader::new(DataProvider::new(Box::new(FakeDataReader::new(
+                data.clone(),
+                false,
+            ))));
+            reader.set_endianness(Endianness::Native);
+            reader.set_strict(*strict);
+
+            reader.read_event(&mut reg).unwrap();
+            reader.read_event(&mut reg).unwrap();
+            assert!(!reg.is_klass_complete(100));
+            let event = reader.read_event(&mut reg);
+            if *strict {
+                assert_eq!(event, Err(ReadEventError::IncompleteKlass(100)));
+            } else {
+                assert_eq!(event.unwrap().get_value_u32("value").unwrap(), 1);
+                assert_eq!(reader.get_ignored_error_count(), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn read_event_should_decode_described_stream() {
+        let stream = TestStreamBuilder::new()
+            .klass(100, "foo", &[("HT_Event", "base"), ("uint32_t", "value")])
+            .event(100, 5, &[TestValue::U32(7)])
+            .build();
+        let mut reader = EventReader::new(DataProvider::new(Box::new(FakeDataReader::new(
+            stream, false,
+        ))));
+        let mut reg = EventKlassRegistry::new();
+
+        for _ in 0..3 {
+            reader.read_event(&mut reg).unwrap();
+        }
+        let event = reader.read_event(&mut reg).unwrap();
+        assert_eq!(event.get_klass_id(), 100);
+        assert_eq!(event.get_timestamp(), Some(5));
+        assert_eq!(event.get_value_u32("value").unwrap(), 7);
+    }
+
+    #[test]
+    fn read_event_should_not_depend_on_read_sizes() {
+        let stream = TestStreamBuilder::new()
+            .klass(
+                100,
+                "foo",
+                &[("HT_Event", "base"), ("const char*", "label")],
+            )
+            .event(100, 5, &[TestValue::Str("bar".to_owned())])
+            .event(100, 6, &[TestValue::Str("baz".to_owned())])
+            .build();
+        let length = stream.len();
+        let mut reader = FakeDataReader::new(stream, false).with_chunk_size(3);
+        for position in (0..length).step_by(7) {
+            reader = reader.with_error_at(position, std::io::ErrorKind::Interrupted);
+        }
+        let mut reader = EventReader::new(DataProvider::with_buffer_size(Box::new(reader), 4));
+        let mut reg = EventKlassRegistry::new();
+
+        for _ in 0..3 {
+            reader.read_event(&mut reg).unwrap();
+        }
+        for (timestamp, label) in &[(5, "bar"), (6, "baz")] {
+            let event = reader.read_event(&mut reg).unwrap();
+            assert_eq!(event.get_timestamp(), Some(*timestamp));
+            assert_eq!(event.get_value::<&str>("label").unwrap(), *label);
+        }
+        assert_eq!(
+            reader.read_event(&mut reg).unwrap_err(),
+            ReadEventError::DataError(DataError::EndOfStream)
+        );
+    }
+
+    #[test]
+    fn progress_should_be_reported_while_reading() {
+        let mut builder = TestStreamBuilder::new().klass(100, "foo", &[("uint32_t", "value")]);
+        for i in 0..4 {
+            builder = builder.event(100, i, &[TestValue::U32(i as u32)]);
+        }
+        let stream = builder.build();
+        let length = stream.len() as u64;
+        let reported = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let reported_clone = reported.clone();
+        let mut reader = EventReader::new(DataProvider::new(Box::new(FakeDataReader::new(
+            stream, false,
+        ))));
+        reader.set_progress_reporter(Some(
+            ProgressReporter::new(3, move |progress| {
+                reported_clone.borrow_mut().push(*progress)
+            })
+            .with_total_bytes(length),
+        ));
+        let mut reg = EventKlassRegistry::new();
+        let mut event = Event::new(0, fnv::FnvHashMap::default());
+
+        reader.read_event(&mut reg).unwrap();
+        reader.skip_event(&mut reg).unwrap();
+        reader.read_raw_event(&mut reg).unwrap();
+        while reader.read_event_into(&mut reg, &mut event).is_ok() {}
+
+        let reported = reported.borrow();
+        assert_eq!(
+            reported.iter().map(|p| p.event_count).collect::<Vec<_>>(),
+            vec![3, 6, 6]
+        );
+        assert_eq!(reported[0].bytes_consumed, length - 3 * 24);
+        assert_eq!(reported[2].get_fraction(), Some(1.0));
+    }
+
+    #[test]
+    fn tee_should_receive_consumed_bytes() {
+        let stream = TestStreamBuilder::new()
+            .klass(
+                100,
+                "foo",
+                &[("HT_Event", "base"), ("const char*", "label")],
+            )
+            .event(100, 5, &[TestValue::Str("bar".to_owned())])
+            .build();
+        let tee = FakeDataWriter::new(false);
+        let mut reader = EventReader::new(DataProvider::with_buffer_size(
+            Box::new(FakeDataReader::new(stream.clone(), false).with_chunk_size(5)),
+            8,
+        ));
+        reader.set_tee(Some(Box::new(tee.clone())));
+        let mut reg = EventKlassRegistry::new();
+
+        reader.read_event(&mut reg).unwrap();
+        assert_eq!(tee.get_data(), stream[..reader.get_position() as usize]);
+        while reader.read_event(&mut reg).is_ok() {}
+        assert_eq!(tee.get_data(), stream);
+        assert!(reader.take_tee().is_some());
+    }
+
+    #[test]
+    fn read_event_should_use_custom_base_klass_layout() {
+        let mut reg = EventKlassRegistry::new();
+        let mut base_klass = EventKlass::new(CoreEventKlassId::Base as u32, "HT_Event".to_owned());
+        base_klass.add_field("type".to_owned(), "uint32_t".to_owned(), DataType::U32);
+        base_klass.add_field("timestamp".to_owned(), "uint32_t".to_owned(), DataType::U32);
+        base_klass.add_field("thread_id".to_owned(), "uint32_t".to_owned(), DataType::U32);
+        reg.set_base_klass(base_klass).unwrap();
+        let mut klass = EventKlass::new(100, "foo".to_owned());
+        klass.add_field("base".to_owned(), "HT_Event".to_owned(), DataType::Struct);
+        klass.add_field("value".to_owned(), "uint8_t".to_owned(), DataType::U8);
+        reg.add_klass(klass);
+
+        let data = vec![
+            100, 0, 0, 0, // type
+            5, 0, 0, 0, // timestamp
+            7, 0, 0, 0, // thread_id
+            9, // value
+        ];
+        let mut reader = EventReader::new(DataProvider::new(Box::new(FakeDataReader::new(
+            data, false,
+        ))));
+        reader.set_endianness(Endianness::Little);
+
+        let event = reader.read_event(&mut reg).unwrap();
+        assert_eq!(event.get_klass_id(), 100);
+        assert_eq!(event.get_timestamp(), Some(5));
+        assert_eq!(event.get_path_as_u64("base.thread_id").unwrap(), 7);
+        assert_eq!(event.get_value_u8("value").unwrap(), 9);
+    }
+
+    #[test]
+    fn reader_should_reorder_events_within_window() {
+        let mut data = Vec::new();
+        for timestamp in &[3u8, 1, 2, 5, 4] {
+            data.extend_from_slice(&[1, 0, 0, 0, *timestamp, 0, 0, 0, 0, 0, 0, 0]);
+            data.extend_from_slice(&[0; 8]);
+        }
+        let mut reg = EventKlassRegistry::new();
+        let mut reader = EventReader::new(DataProvider::new(Box::new(FakeDataReader::new(
+            data, false,
+        ))));
+        reader.set_reorder_window(Some(ReorderWindow::EventCount(2)));
+
+        let mut timestamps = Vec::new();
+        while let Ok(event) = reader.read_event(&mut reg) {
+            timestamps.push(event.get_value_u64("timestamp").unwrap());
+            assert!(reader.get_resource_usage(&reg).reorder_buffer_events <= 2);
+        }
+        assert_eq!(timestamps, vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn read_event_into_should_return_same_events_as_read_event() {
+        let mut expected_reg = EventKlassRegistry::new();
+        let mut expected = Vec::new();
+        let mut reader = make_seek_reader();
+        while let Ok(event) = reader.read_event(&mut expected_reg) {
+            expected.push(event);
+        }
+
+        let mut reg = EventKlassRegistry::new();
+        let mut reader = make_seek_reader();
+        let mut event = Event::new(0, fnv::FnvHashMap::default());
+        let mut events = Vec::new();
+        while reader.read_event_into(&mut reg, &mut event).is_ok() {
+            events.push(event.clone());
+        }
+
+        assert_eq!(events.len(), 8);
+        assert_eq!(events, expected);
+    }
+
+    #[test]
+    fn read_event_into_should_reuse_base_event() {
+        let mut data = Vec::new();
+        for (timestamp, value) in &[(1u8, 7u8), (2, 8)] {
+            data.extend_from_slice(&[100, 0, 0, 0, *timestamp, 0, 0, 0, 0, 0, 0, 0]);
+            data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, *value]);
+        }
+        let mut reg = EventKlassRegistry::new();
+        let mut klass = EventKlass::new(100, "foo".to_owned());
+        klass.add_field("base".to_owned(), "HT_Event".to_owned(), DataType::Struct);
+        klass.add_field("value".to_owned(), "uint8_t".to_owned(), DataType::U8);
+        reg.add_klass(klass);
+        let mut reader = EventReader::new(DataProvider::new(Box::new(FakeDataReader::new(
+            data, false,
+        ))));
+        reader.set_endianness(Endianness::Little);
+
+        let mut event = Event::new(0, fnv::FnvHashMap::default());
+        reader.read_event_into(&mut reg, &mut event).unwrap();
+        assert_eq!(event.get_timestamp(), Some(1));
+        reader.read_event_into(&mut reg, &mut event).unwrap();
+        assert_eq!(event.get_timestamp(), Some(2));
+        assert_eq!(event.get_value_u8("value").unwrap(), 8);
+        assert_eq!(event.get_value_count(), 2);
+        assert!(reader.read_event_into(&mut reg, &mut event).is_err());
+    }
+
+    #[test]
+    fn compact_reader_should_return_same_events_as_regular_reader() {
+        let mut expected_reg = EventKlassRegistry::new();
+        let mut expected = Vec::new();
+        let mut reader = make_seek_reader();
+        while let Ok(event) = reader.read_event(&mut expected_reg) {
+            expected.push(event);
+        }
+
+        let mut reg = EventKlassRegistry::new();
+        let mut reader = make_seek_reader();
+        reader.set_compact(true);
+        let mut events = Vec::new();
+        while let Ok(event) = reader.read_event(&mut reg) {
+            assert!(event.is_compact());
+            events.push(event);
+        }
+        assert_eq!(events, expected);
 
-        assert_eq!(event.get_value_string(&"str_field").unwrap(), "ABC");
-        assert_eq!(event.get_value_u32(&"u32_field").unwrap(), 301);
+        let mut reg = EventKlassRegistry::new();
+        let mut reader = make_seek_reader();
+        reader.set_compact(true);
+        let mut event = Event::new(0, fnv::FnvHashMap::default());
+        let mut events = Vec::new();
+        while reader.read_event_into(&mut reg, &mut event).is_ok() {
+            assert!(event.is_compact());
+            events.push(event.clone());
+        }
+        assert_eq!(events, expected);
     }
 }
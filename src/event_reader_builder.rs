@@ -0,0 +1,303 @@
+use crate::data_provider::DataProvider;
+use crate::data_struct_reader::Endianness;
+use crate::event::Event;
+use crate::event_reader::{EventFilter, EventReader};
+use crate::field_projection::FieldProjection;
+use crate::filter_expression::FilterExpression;
+use crate::klass_filter::KlassFilter;
+use crate::progress::ProgressReporter;
+use crate::reorder_buffer::ReorderWindow;
+use crate::sampling::EventSampler;
+
+pub struct EventReaderBuilder {
+    endianness: Endianness,
+    strict: bool,
+    max_string_length: Option<usize>,
+    buffer_size: Option<usize>,
+    flatten: bool,
+    compact: bool,
+    max_struct_depth: Option<usize>,
+    filter: Option<EventFilter>,
+    filter_expression: Option<FilterExpression>,
+    klass_filter: Option<KlassFilter>,
+    sampler: Option<EventSampler>,
+    projection: Option<FieldProjection>,
+    reorder_window: Option<ReorderWindow>,
+    progress_reporter: Option<ProgressReporter>,
+    tee: Option<Box<dyn std::io::Write>>,
+}
+
+impl Default for EventReaderBuilder {
+    fn default() -> EventReaderBuilder {
+        EventReaderBuilder::new()
+    }
+}
+
+impl EventReaderBuilder {
+    pub fn new() -> EventReaderBuilder {
+        EventReaderBuilder {
+            endianness: Endianness::Native,
+            strict: true,
+            max_string_length: None,
+            buffer_size: None,
+            flatten: false,
+            compact: false,
+            max_struct_depth: None,
+            filter: None,
+            filter_expression: None,
+            klass_filter: None,
+            sampler: None,
+            projection: None,
+            reorder_window: None,
+            progress_reporter: None,
+            tee: None,
+        }
+    }
+
+    pub fn endianness(mut self, endianness: Endianness) -> EventReaderBuilder {
+        self.endianness = endianness;
+        self
+    }
+
+    pub fn strict(mut self, strict: bool) -> EventReaderBuilder {
+        self.strict = strict;
+        self
+    }
+
+    pub fn max_string_length(mut self, max_string_length: usize) -> EventReaderBuilder {
+        self.max_string_length = Some(max_string_length);
+        self
+    }
+
+    pub fn buffer_size(mut self, buffer_size: usize) -> EventReaderBuilder {
+        self.buffer_size = Some(buffer_size);
+        self
+    }
+
+    pub fn flatten(mut self, flatten: bool) -> EventReaderBuilder {
+        self.flatten = flatten;
+        self
+    }
+
+    pub fn compact(mut self, compact: bool) -> EventReaderBuilder {
+        self.compact = compact;
+        self
+    }
+
+    pub fn max_struct_depth(mut self, max_struct_depth: usize) -> EventReaderBuilder {
+        self.max_struct_depth = Some(max_struct_depth);
+        self
+    }
+
+    pub fn filter<F>(mut self, filter: F) -> EventReaderBuilder
+    where
+        F: Fn(&Event) -> bool + 'static,
+    {
+        self.filter = Some(Box::new(filter));
+        self
+    }
+
+    pub fn filter_expression(mut self, filter_expression: FilterExpression) -> EventReaderBuilder {
+        self.filter_expression = Some(filter_expression);
+        self
+    }
+
+    pub fn klass_filter(mut self, klass_filter: KlassFilter) -> EventReaderBuilder {
+        self.klass_filter = Some(klass_filter);
+        self
+    }
+
+    pub fn sampler(mut self, sampler: EventSampler) -> EventReaderBuilder {
+        self.sampler = Some(sampler);
+        self
+    }
+
+    pub fn projection(mut self, projection: FieldProjection) -> EventReaderBuilder {
+        self.projection = Some(projection);
+        self
+    }
+
+    pub fn reorder_window(mut self, reorder_window: ReorderWindow) -> EventReaderBuilder {
+        self.reorder_window = Some(reorder_window);
+        self
+    }
+
+    pub fn progress_reporter(mut self, progress_reporter: ProgressReporter) -> EventReaderBuilder {
+        self.progress_reporter = Some(progress_reporter);
+        self
+    }
+
+    // Consumed bytes are copied to the writer, see DataProvider::set_tee().
+    pub fn tee(mut self, tee: Box<dyn std::io::Write>) -> EventReaderBuilder {
+        self.tee = Some(tee);
+        self
+    }
+
+    pub fn build(self, reader: Box<dyn std::io::Read>) -> EventReader {
+        // Without an explicit size, the buffer adapts to the stream.
+        let mut data_provider = match self.buffer_size {
+            Some(buffer_size) => DataProvider::with_buffer_size(reader, buffer_size),
+            None => DataProvider::new(reader),
+        };
+        data_provider.set_max_string_length(self.max_string_length);
+        data_provider.set_tee(self.tee);
+
+        let mut event_reader = EventReader::new(data_provider);
+        event_reader.set_endianness(self.endianness);
+        event_reader.set_strict(self.strict);
+        event_reader.set_flatten(self.flatten);
+        event_reader.set_compact(self.compact);
+        if let Some(max_struct_depth) = self.max_struct_depth {
+            event_reader.set_max_struct_depth(max_struct_depth);
+        }
+        event_reader.set_filter(self.filter);
+        event_reader.set_filter_expression(self.filter_expression);
+        event_reader.set_klass_filter(self.klass_filter);
+        event_reader.set_sampler(self.sampler);
+        event_reader.set_reorder_window(self.reorder_window);
+        event_reader.set_projection(self.projection);
+        event_reader.set_progress_reporter(self.progress_reporter);
+        event_reader
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_provider::DataError;
+    use crate::data_struct_reader::ReadEventError;
+    use crate::event::DataType;
+    use crate::event_klass::EventKlass;
+    use crate::registry::{CoreEventKlassId, EventKlassRegistry};
+    use hawktracer_parser_test_utilities::FakeDataReader;
+
+    fn make_registry() -> EventKlassRegistry {
+        let mut klass = EventKlass::new(100, "foo".to_owned());
+        klass.add_field("base".to_owned(), "HT_Event".to_owned(), DataType::Struct);
+        klass.add_field("name".to_owned(), "char*".to_owned(), DataType::Str);
+
+        let mut registry = EventKlassRegistry::new();
+        registry.add_klass(klass);
+        registry
+    }
+
+    fn make_event_data(id: u8, name: &[u8]) -> Vec<u8> {
+        let mut data = vec![
+            100, 0, 0, 0, 5, 0, 0, 0, 0, 0, 0, 0, id, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        data.extend_from_slice(name);
+        data.push(0);
+        data
+    }
+
+    #[test]
+    fn builder_should_configure_endianness() {
+        let data = vec![
+            0, 0, 0, 1, // type
+            0, 0, 0, 0, 0, 0, 1, 2, // timestamp
+            0, 0, 0, 0, 0, 0, 0, 3, // id
+        ];
+        let mut reader = EventReaderBuilder::new()
+            .endianness(Endianness::Big)
+            .build(Box::new(FakeDataReader::new(data, false)));
+
+        let event = reader.read_event(&mut EventKlassRegistry::new()).unwrap();
+        assert_eq!(event.get_value_u32("type").unwrap(), 1);
+        assert_eq!(event.get_value_u64("timestamp").unwrap(), 258);
+        assert_eq!(event.get_value_u64("id").unwrap(), 3);
+    }
+
+    #[test]
+    fn builder_should_configure_flattening_and_filter() {
+        let mut data = make_event_data(1, b"skip");
+        data.extend(make_event_data(2, b"keep"));
+        let mut reader = EventReaderBuilder::new()
+            .buffer_size(4)
+            .flatten(true)
+            .filter(|event| event.get_value_string("name").is_ok_and(|n| n == "keep"))
+            .build(Box::new(FakeDataReader::new(data, false)));
+
+        let mut registry = make_registry();
+        let event = reader.read_event(&mut registry).unwrap();
+        assert_eq!(event.get_value_u64("id").unwrap(), 2);
+        assert_eq!(event.get_value_u64("timestamp").unwrap(), 5);
+        assert!(reader.read_event(&mut registry).is_err());
+    }
+
+    #[test]
+    fn builder_should_configure_filter_expression() {
+        let mut data = make_event_data(1, b"skip");
+        data.extend(make_event_data(2, b"keep"));
+        let mut reader = EventReaderBuilder::new()
+            .filter_expression(
+                "klass == \"foo\" && name =~ \"^k\" && id > 1"
+                    .parse()
+                    .unwrap(),
+            )
+            .build(Box::new(FakeDataReader::new(data, false)));
+
+        let mut registry = make_registry();
+        let mut event = Event::new(0, fnv::FnvHashMap::default());
+        reader.read_event_into(&mut registry, &mut event).unwrap();
+        assert_eq!(event.get_value_string("name").unwrap(), "keep");
+        assert!(reader.read_event(&mut registry).is_err());
+    }
+
+    #[test]
+    fn builder_should_configure_sampler() {
+        let mut data = Vec::new();
+        for id in 0..5 {
+            data.extend(make_event_data(id, b"name"));
+        }
+        let mut reader = EventReaderBuilder::new()
+            .sampler(EventSampler::new().with_rate(2))
+            .build(Box::new(FakeDataReader::new(data, false)));
+
+        let mut registry = make_registry();
+        let mut event = Event::new(0, fnv::FnvHashMap::default());
+        let mut ids = Vec::new();
+        while reader.read_event_into(&mut registry, &mut event).is_ok() {
+            ids.push(event.find_value("id").and_then(|id| id.as_u64()).unwrap());
+        }
+        assert_eq!(ids, vec![0, 2, 4]);
+        assert_eq!(reader.get_sampler().unwrap().get_skipped_count(), 2);
+    }
+
+    #[test]
+    fn builder_should_configure_max_string_length() {
+        let mut reader = EventReaderBuilder::new()
+            .max_string_length(3)
+            .build(Box::new(FakeDataReader::new(
+                make_event_data(1, b"long"),
+                false,
+            )));
+
+        assert_eq!(
+            reader.read_event(&mut make_registry()).unwrap_err(),
+            ReadEventError::DataError(DataError::StringTooLong)
+        );
+    }
+
+    #[test]
+    fn non_strict_reader_should_ignore_registry_update_errors() {
+        let mut data = vec![CoreEventKlassId::FieldInfo as u8, 0, 0, 0];
+        data.extend_from_slice(&[0; 16]);
+        data.extend_from_slice(&[99, 0, 0, 0]); // unknown klass id
+        data.extend_from_slice(b"uint32_t\0value\0");
+        data.extend_from_slice(&[4, 0, 0, 0, 0, 0, 0, 0, 99]);
+
+        let mut strict_reader =
+            EventReaderBuilder::new().build(Box::new(FakeDataReader::new(data.clone(), false)));
+        assert!(strict_reader
+            .read_event(&mut EventKlassRegistry::new())
+            .is_err());
+
+        let mut lenient_reader = EventReaderBuilder::new()
+            .strict(false)
+            .build(Box::new(FakeDataReader::new(data, false)));
+        assert!(lenient_reader
+            .read_event(&mut EventKlassRegistry::new())
+            .is_ok());
+        assert_eq!(lenient_reader.get_ignored_error_count(), 1);
+    }
+}
@@ -0,0 +1,589 @@
+use crate::data_struct_reader::Endianness;
+use crate::event::{ArrayLength, DataType, Event, Value};
+use crate::event_klass::{EventKlass, EventKlassField};
+use crate::registry::{CoreEventKlassId, EventKlassRegistry};
+use crate::registry_updater::{MkcreflectType, OPAQUE_DATA_TYPE_ID};
+use crate::sink::EventSink;
+
+#[derive(Debug)]
+pub enum WriteEventError {
+    IoError(std::io::Error),
+    UnknownKlass(String),
+    UnknownKlassId(u32),
+    MissingField(String),
+    InvalidValue(String),
+}
+
+impl std::fmt::Display for WriteEventError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            WriteEventError::IoError(err) => write!(f, "I/O error: {}", err),
+            WriteEventError::UnknownKlass(name) => write!(f, "Unknown klass {}", name),
+            WriteEventError::UnknownKlassId(id) => write!(f, "Unknown klass id {}", id),
+            WriteEventError::MissingField(name) => write!(f, "Missing field {}", name),
+            WriteEventError::InvalidValue(name) => write!(f, "Invalid value of field {}", name),
+        }
+    }
+}
+
+impl std::error::Error for WriteEventError {}
+
+impl From<std::io::Error> for WriteEventError {
+    fn from(err: std::io::Error) -> WriteEventError {
+        WriteEventError::IoError(err)
+    }
+}
+
+// Data type identifiers used by FieldInfo events (see RegistryUpdater).
+fn get_data_type_id(data_type: DataType) -> u8 {
+    match MkcreflectType::from_data_type(data_type) {
+        Some(data_type) => data_type as u8,
+        None => OPAQUE_DATA_TYPE_ID,
+    }
+}
+
+fn get_data_type_size(data_type: DataType) -> u64 {
+    match data_type {
+        DataType::U8 | DataType::I8 => 1,
+        DataType::U16 | DataType::I16 => 2,
+        DataType::U32 | DataType::I32 => 4,
+        DataType::U64 | DataType::I64 => 8,
+        DataType::Str => std::mem::size_of::<usize>() as u64,
+        DataType::Struct => 0,
+        DataType::Bytes(size) => size as u64,
+        // Size of all elements of fixed-size arrays, see RegistryUpdater.
+        DataType::Array(element_type, ArrayLength::Fixed(count)) => {
+            (element_type.get_size() * count) as u64
+        }
+        DataType::Array(element_type, ArrayLength::Prefixed) => element_type.get_size() as u64,
+    }
+}
+
+macro_rules! encode_integer {
+    ($endianness: expr, $value: expr) => {
+        match $endianness {
+            Endianness::Little => $value.to_le_bytes().to_vec(),
+            Endianness::Big => $value.to_be_bytes().to_vec(),
+            Endianness::Native => $value.to_ne_bytes().to_vec(),
+        }
+    };
+}
+
+macro_rules! convert_integer {
+    ($value: expr, $type: ty, $getter: ident, $name: expr) => {
+        match $value
+            .$getter()
+            .and_then(|v| std::convert::TryFrom::try_from(v).ok())
+        {
+            Some(v) => {
+                let v: $type = v;
+                v
+            }
+            None => return Err(WriteEventError::InvalidValue($name.to_owned())),
+        }
+    };
+}
+
+// Serializes events to the HawkTracer binary format, so they can be read back
+// by EventReader. Klasses are described (with KlassInfo and FieldInfo events)
+// before the first event of the klass is written, unless they were already
+// written by write_klass() or write_registry(). Metadata events passed to
+// write_event() are skipped, as klass descriptions are generated from the
+// registry. Events can be either nested or flattened.
+pub struct EventWriter {
+    writer: Box<dyn std::io::Write>,
+    endianness: Endianness,
+    written_klasses: std::collections::HashSet<u32>,
+}
+
+impl EventWriter {
+    pub fn new(writer: Box<dyn std::io::Write>) -> EventWriter {
+        EventWriter {
+            writer,
+            endianness: Endianness::Native,
+            written_klasses: std::collections::HashSet::new(),
+        }
+    }
+
+    pub fn with_endianness(mut self, endianness: Endianness) -> EventWriter {
+        self.endianness = endianness;
+        self
+    }
+
+    pub fn write_registry(&mut self, registry: &EventKlassRegistry) -> Result<(), WriteEventError> {
+        for klass in registry.get_klasses() {
+            self.write_klass(klass, registry)?;
+        }
+        Ok(())
+    }
+
+    // Klasses of struct fields are written first, so the reader can resolve them.
+    pub fn write_klass(
+        &mut self,
+        klass: &EventKlass,
+        registry: &EventKlassRegistry,
+    ) -> Result<(), WriteEventError> {
+        if CoreEventKlassId::is_core_klass(klass.get_id())
+            || !self.written_klasses.insert(klass.get_id())
+        {
+            return Ok(());
+        }
+
+        for field in klass.get_fields() {
+            if *field.get_data_type() == DataType::Struct && !field.is_base_event_field() {
+                match registry.get_klass_by_name(field.get_type_name()) {
+                    Some(field_klass) => self.write_klass(field_klass, registry)?,
+                    None => {
+                        return Err(WriteEventError::UnknownKlass(field.get_type_name().clone()))
+                    }
+                }
+            }
+        }
+
+        let mut data = self.encode_metadata_header(CoreEventKlassId::KlassInfo);
+        data.extend(encode_integer!(self.endianness, klass.get_id()));
+        data.extend(encode_string(klass.get_name())?);
+        data.push(klass.get_fields().len() as u8);
+        self.writer.write_all(&data)?;
+
+        for field in klass.get_fields() {
+            let mut data = self.encode_metadata_header(CoreEventKlassId::FieldInfo);
+            data.extend(encode_integer!(self.endianness, klass.get_id()));
+            data.extend(encode_string(field.get_type_name())?);
+            data.extend(encode_string(field.get_name())?);
+            let size = field
+                .get_declared_size()
+                .unwrap_or_else(|| get_data_type_size(*field.get_data_type()));
+            data.extend(encode_integer!(self.endianness, size));
+            data.push(get_data_type_id(*field.get_data_type()));
+            self.writer.write_all(&data)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn write_event(
+        &mut self,
+        event: &Event,
+        registry: &EventKlassRegistry,
+    ) -> Result<(), WriteEventError> {
+        let klass_id = event.get_klass_id();
+        if CoreEventKlassId::is_metadata_klass(klass_id) {
+            return Ok(());
+        }
+
+        let klass = match registry.get_klass_by_id(klass_id) {
+            Some(klass) => klass,
+            None => return Err(WriteEventError::UnknownKlassId(klass_id)),
+        };
+        self.write_klass(klass, registry)?;
+
+        let base_klass = match registry.get_klass_by_id(CoreEventKlassId::Base as u32) {
+            Some(klass) => klass,
+            None => {
+                return Err(WriteEventError::UnknownKlassId(
+                    CoreEventKlassId::Base as u32,
+                ))
+            }
+        };
+        let base_event = find_base_event(event);
+        let mut data = Vec::new();
+        for field in base_klass.get_fields() {
+            match (field.get_name(), base_event.get_raw_value(field.get_name())) {
+                ("type", _) => data.extend(encode_integer!(self.endianness, klass_id)),
+                (_, Some(value)) => {
+                    self.encode_value(*field.get_data_type(), field, value, registry, &mut data)?
+                }
+                (name, None) => return Err(WriteEventError::MissingField(name.to_owned())),
+            }
+        }
+
+        if klass_id != CoreEventKlassId::Base as u32 {
+            self.encode_fields(klass, event, event, registry, &mut data)?;
+        }
+        self.writer.write_all(&data)?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), WriteEventError> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn encode_metadata_header(&self, klass_id: CoreEventKlassId) -> Vec<u8> {
+        let mut data = encode_integer!(self.endianness, klass_id as u32);
+        data.extend(encode_integer!(self.endianness, 0u64));
+        data.extend(encode_integer!(self.endianness, 0u64));
+        data
+    }
+
+    // Fields of flattened events are looked up in the top-level event.
+    fn encode_fields(
+        &self,
+        klass: &EventKlass,
+        event: &Event,
+        top_event: &Event,
+        registry: &EventKlassRegistry,
+        data: &mut Vec<u8>,
+    ) -> Result<(), WriteEventError> {
+        for field in klass.get_fields() {
+            if field.is_base_event_field() {
+                continue;
+            }
+            match event.get_raw_value(field.get_name()) {
+                Some(value) => {
+                    self.encode_value(*field.get_data_type(), field, value, registry, data)?
+                }
+                None if field.get_name() == "base"
+                    && *field.get_data_type() == DataType::Struct =>
+                {
+                    match registry.get_klass_by_name(field.get_type_name()) {
+                        Some(base_klass) => {
+                            self.encode_fields(base_klass, top_event, top_event, registry, data)?
+                        }
+                        None => {
+                            return Err(WriteEventError::UnknownKlass(
+                                field.get_type_name().clone(),
+                            ))
+                        }
+                    }
+                }
+                None => return Err(WriteEventError::MissingField(field.get_name().to_owned())),
+            }
+        }
+        Ok(())
+    }
+
+    // The data type is passed separately from the field, so array elements
+    // can be encoded as well.
+    fn encode_value(
+        &self,
+        data_type: DataType,
+        field: &EventKlassField,
+        value: &Value,
+        registry: &EventKlassRegistry,
+        data: &mut Vec<u8>,
+    ) -> Result<(), WriteEventError> {
+        let name = field.get_name();
+        let endianness = self.endianness;
+        match data_type {
+            DataType::U8 => data.extend(encode_integer!(
+                endianness,
+                convert_integer!(value, u8, as_u64, name)
+            )),
+            DataType::I8 => data.extend(encode_integer!(
+                endianness,
+                convert_integer!(value, i8, as_i64, name)
+            )),
+            DataType::U16 => data.extend(encode_integer!(
+                endianness,
+                convert_integer!(value, u16, as_u64, name)
+            )),
+            DataType::I16 => data.extend(encode_integer!(
+                endianness,
+                convert_integer!(value, i16, as_i64, name)
+            )),
+            DataType::U32 => data.extend(encode_integer!(
+                endianness,
+                convert_integer!(value, u32, as_u64, name)
+            )),
+            DataType::I32 => data.extend(encode_integer!(
+                endianness,
+                convert_integer!(value, i32, as_i64, name)
+            )),
+            DataType::U64 => data.extend(encode_integer!(
+                endianness,
+                convert_integer!(value, u64, as_u64, name)
+            )),
+            DataType::I64 => data.extend(encode_integer!(
+                endianness,
+                convert_integer!(value, i64, as_i64, name)
+            )),
+            DataType::Str => match value {
+                Value::Str(value) => data.extend(encode_string(value)?),
+                _ => return Err(WriteEventError::InvalidValue(name.to_owned())),
+            },
+            DataType::Struct => {
+                let klass = match registry.get_klass_by_name(field.get_type_name()) {
+                    Some(klass) => klass,
+                    None => {
+                        return Err(WriteEventError::UnknownKlass(field.get_type_name().clone()))
+                    }
+                };
+                match value {
+                    Value::Struct(value) => {
+                        self.encode_fields(klass, value, value, registry, data)?
+                    }
+                    _ => return Err(WriteEventError::InvalidValue(name.to_owned())),
+                }
+            }
+            DataType::Bytes(size) => match value {
+                Value::Bytes(value) if value.len() == size => data.extend_from_slice(value),
+                _ => return Err(WriteEventError::InvalidValue(name.to_owned())),
+            },
+            DataType::Array(element_type, length) => {
+                let values = match value {
+                    Value::Array(values) => values,
+                    _ => return Err(WriteEventError::InvalidValue(name.to_owned())),
+                };
+                match length {
+                    ArrayLength::Fixed(count) if count != values.len() => {
+                        return Err(WriteEventError::InvalidValue(name.to_owned()))
+                    }
+                    ArrayLength::Fixed(_) => {}
+                    ArrayLength::Prefixed => {
+                        let count: u32 = match std::convert::TryFrom::try_from(values.len()) {
+                            Ok(count) => count,
+                            Err(_) => return Err(WriteEventError::InvalidValue(name.to_owned())),
+                        };
+                        data.extend(encode_integer!(endianness, count));
+                    }
+                }
+                for value in values {
+                    self.encode_value(element_type.get_data_type(), field, value, registry, data)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl EventSink for EventWriter {
+    fn write_event(&mut self, event: &Event, registry: &EventKlassRegistry) -> std::io::Result<()> {
+        match EventWriter::write_event(self, event, registry) {
+            Ok(()) => Ok(()),
+            Err(WriteEventError::IoError(err)) => Err(err),
+            Err(err) => Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, err)),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+// Strings are zero-terminated, so they can't contain zero bytes.
+fn encode_string(value: &str) -> Result<Vec<u8>, WriteEventError> {
+    if value.contains('\0') {
+        return Err(WriteEventError::InvalidValue(value.to_owned()));
+    }
+    let mut data = value.as_bytes().to_vec();
+    data.push(0);
+    Ok(data)
+}
+
+// Header values are stored in the HT_Event struct, which is the innermost
+// "base" struct of the event (or the event itself, if it's flattened).
+fn find_base_event(event: &Event) -> &Event {
+    match event.get_raw_value("base") {
+        Some(Value::Struct(base_event)) => find_base_event(base_event),
+        _ => event,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_provider::DataProvider;
+    use crate::event::IntegerType;
+    use crate::event_reader::EventReader;
+    use crate::test_events::{make_event_with_id, make_plain_event};
+    use hawktracer_parser_test_utilities::FakeDataWriter;
+
+    fn make_registry() -> EventKlassRegistry {
+        let mut registry = EventKlassRegistry::new();
+        let mut point_klass = EventKlass::new(100, "point".to_owned());
+        point_klass.add_field("x".to_owned(), "uint8_t".to_owned(), DataType::U8);
+        registry.add_klass(point_klass);
+
+        let mut klass = EventKlass::new(101, "foo".to_owned());
+        klass.add_field("base".to_owned(), "HT_Event".to_owned(), DataType::Struct);
+        klass.add_field("label".to_owned(), "const char*".to_owned(), DataType::Str);
+        klass.add_field("value".to_owned(), "uint32_t".to_owned(), DataType::U32);
+        klass.add_field("point".to_owned(), "point".to_owned(), DataType::Struct);
+        registry.add_klass(klass);
+        registry
+    }
+
+    fn make_event(timestamp: u64, label: &str) -> Event {
+        let point = make_plain_event(100, vec![("x", Value::U8(7))]);
+        make_event_with_id(
+            101,
+            timestamp,
+            timestamp + 1,
+            vec![
+                ("label", Value::Str(label.to_owned())),
+                ("value", Value::U32(timestamp as u32 * 10)),
+                ("point", Value::Struct(Box::new(point))),
+            ],
+        )
+    }
+
+    fn read_events(data: Vec<u8>, endianness: Endianness) -> (Vec<Event>, EventKlassRegistry) {
+        let mut registry = EventKlassRegistry::new();
+        let mut reader = EventReader::new(DataProvider::new(Box::new(std::io::Cursor::new(data))));
+        reader.set_endianness(endianness);
+        let mut events = Vec::new();
+        while let Ok(event) = reader.read_event(&mut registry) {
+            if !CoreEventKlassId::is_metadata_klass(event.get_klass_id()) {
+                events.push(event);
+            }
+        }
+        (events, registry)
+    }
+
+    #[test]
+    fn written_events_should_be_read_back() {
+        let registry = make_registry();
+        let events = vec![make_event(1, "a"), make_event(2, "b")];
+        let buffer = FakeDataWriter::new(false);
+        let mut writer =
+            EventWriter::new(Box::new(buffer.clone())).with_endianness(Endianness::Big);
+        for event in &events {
+            writer.write_event(event, &registry).unwrap();
+        }
+        writer
+            .write_event(&events[0].clone().flat_event(), &registry)
+            .unwrap();
+
+        let data = buffer.get_data();
+        let (read_events, read_registry) = read_events(data, Endianness::Big);
+
+        assert_eq!(
+            read_events,
+            vec![events[0].clone(), events[1].clone(), events[0].clone()]
+        );
+        assert_eq!(
+            read_registry.get_klass_by_id(101),
+            registry.get_klass_by_id(101)
+        );
+        assert_eq!(
+            read_registry.get_klass_by_id(100),
+            registry.get_klass_by_id(100)
+        );
+    }
+
+    #[test]
+    fn opaque_fields_should_be_read_back() {
+        let mut registry = EventKlassRegistry::new();
+        let mut klass = EventKlass::new(100, "vendor".to_owned());
+        klass.add_field("base".to_owned(), "HT_Event".to_owned(), DataType::Struct);
+        klass.add_field("blob".to_owned(), "vendor_t".to_owned(), DataType::Bytes(3));
+        registry.add_klass(klass);
+
+        let mut base_values = fnv::FnvHashMap::default();
+        base_values.insert("type".to_owned(), Value::U32(100));
+        base_values.insert("timestamp".to_owned(), Value::U64(1));
+        base_values.insert("id".to_owned(), Value::U64(2));
+        let mut values = fnv::FnvHashMap::default();
+        values.insert(
+            "base".to_owned(),
+            Value::Struct(Box::new(Event::new(1, base_values))),
+        );
+        values.insert("blob".to_owned(), Value::Bytes(vec![1, 0, 255]));
+        let event = Event::new(100, values);
+
+        let buffer = FakeDataWriter::new(false);
+        let mut writer = EventWriter::new(Box::new(buffer.clone()));
+        writer.write_event(&event, &registry).unwrap();
+        let mut invalid_event = event.clone();
+        invalid_event.set_value("blob", Value::Bytes(vec![1]));
+        assert!(writer.write_event(&invalid_event, &registry).is_err());
+
+        let data = buffer.get_data();
+        let (read_events, read_registry) = read_events(data, Endianness::Native);
+        assert_eq!(read_events, vec![event]);
+        assert_eq!(
+            read_registry.get_klass_by_id(100),
+            registry.get_klass_by_id(100)
+        );
+    }
+
+    #[test]
+    fn array_fields_should_be_read_back() {
+        let mut registry = EventKlassRegistry::new();
+        let mut klass = EventKlass::new(100, "counters".to_owned());
+        klass.add_field("base".to_owned(), "HT_Event".to_owned(), DataType::Struct);
+        klass.add_field(
+            "per_core".to_owned(),
+            "uint32_t[2]".to_owned(),
+            DataType::Array(IntegerType::U32, ArrayLength::Fixed(2)),
+        );
+        klass.add_field(
+            "samples".to_owned(),
+            "uint64_t[]".to_owned(),
+            DataType::Array(IntegerType::U64, ArrayLength::Prefixed),
+        );
+        registry.add_klass(klass);
+
+        let mut base_values = fnv::FnvHashMap::default();
+        base_values.insert("type".to_owned(), Value::U32(100));
+        base_values.insert("timestamp".to_owned(), Value::U64(1));
+        base_values.insert("id".to_owned(), Value::U64(2));
+        let mut values = fnv::FnvHashMap::default();
+        values.insert(
+            "base".to_owned(),
+            Value::Struct(Box::new(Event::new(1, base_values))),
+        );
+        values.insert(
+            "per_core".to_owned(),
+            Value::Array(vec![Value::U32(5), Value::U32(6)]),
+        );
+        values.insert(
+            "samples".to_owned(),
+            Value::Array(vec![Value::U64(1), Value::U64(2), Value::U64(3)]),
+        );
+        let event = Event::new(100, values);
+
+        let buffer = FakeDataWriter::new(false);
+        let mut writer = EventWriter::new(Box::new(buffer.clone()));
+        writer.write_event(&event, &registry).unwrap();
+        let mut invalid_event = event.clone();
+        invalid_event.set_value("per_core", Value::Array(vec![Value::U32(5)]));
+        assert!(writer.write_event(&invalid_event, &registry).is_err());
+
+        let data = buffer.get_data();
+        let (read_events, read_registry) = read_events(data, Endianness::Native);
+        assert_eq!(read_events, vec![event]);
+        assert_eq!(
+            read_registry.get_klass_by_id(100),
+            registry.get_klass_by_id(100)
+        );
+    }
+
+    #[test]
+    fn klasses_should_be_written_once() {
+        let registry = make_registry();
+        let buffer = FakeDataWriter::new(false);
+        let mut writer = EventWriter::new(Box::new(buffer.clone()));
+        writer.write_registry(&registry).unwrap();
+        let registry_size = buffer.get_data().len();
+        writer.write_event(&make_event(1, "a"), &registry).unwrap();
+
+        let data = buffer.get_data();
+        assert_eq!(data.len() - registry_size, 20 + 2 + 4 + 1);
+        assert_eq!(read_events(data, Endianness::Native).0.len(), 1);
+    }
+
+    #[test]
+    fn writing_invalid_event_should_fail() {
+        let registry = make_registry();
+        let mut writer = EventWriter::new(Box::new(FakeDataWriter::new(false)));
+
+        let mut event = make_event(1, "a");
+        event.set_value("value", Value::I32(-1));
+        match writer.write_event(&event, &registry) {
+            Err(WriteEventError::InvalidValue(name)) => assert_eq!(name, "value"),
+            result => panic!("unexpected result: {:?}", result),
+        }
+
+        match writer.write_event(&Event::new(101, fnv::FnvHashMap::default()), &registry) {
+            Err(WriteEventError::MissingField(name)) => assert_eq!(name, "timestamp"),
+            result => panic!("unexpected result: {:?}", result),
+        }
+        match writer.write_event(&Event::new(999, fnv::FnvHashMap::default()), &registry) {
+            Err(WriteEventError::UnknownKlassId(999)) => {}
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+}
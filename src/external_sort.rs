@@ -0,0 +1,266 @@
+use crate::data_provider::{DataError, DataProvider};
+use crate::data_struct_reader::{Endianness, ReadEventError};
+use crate::event::Event;
+use crate::event_reader::EventReader;
+use crate::raw_event::RawEvent;
+use crate::registry::{CoreEventKlassId, EventKlassRegistry};
+use std::io::Write;
+
+static RUN_COUNTER: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+// Sorts events of traces which don't fit in memory by timestamp. Encoded
+// events are buffered until the memory limit is reached; the buffer is then
+// sorted and spilled to a temporary file (a run). Runs are merged when the
+// sorted events are read. Events with equal timestamps keep their order.
+pub struct ExternalSorter {
+    temp_dir: std::path::PathBuf,
+    max_memory: usize,
+    buffered: Vec<(u64, Vec<u8>)>,
+    buffered_bytes: usize,
+    runs: Vec<std::path::PathBuf>,
+    endianness: Endianness,
+}
+
+impl ExternalSorter {
+    pub fn new(temp_dir: &std::path::Path, max_memory: usize) -> ExternalSorter {
+        ExternalSorter {
+            temp_dir: temp_dir.to_owned(),
+            max_memory,
+            buffered: Vec::new(),
+            buffered_bytes: 0,
+            runs: Vec::new(),
+            endianness: Endianness::Native,
+        }
+    }
+
+    pub fn get_run_count(&self) -> usize {
+        self.runs.len()
+    }
+
+    // Metadata events are ignored; the registry they were applied to has to be
+    // passed to SortedEvents::read_event().
+    pub fn push(&mut self, event: RawEvent) -> std::io::Result<()> {
+        if CoreEventKlassId::is_metadata_klass(event.get_klass_id()) {
+            return Ok(());
+        }
+
+        self.endianness = event.get_endianness();
+        let timestamp = event.get_header().get_timestamp().unwrap_or_default();
+        self.buffered_bytes += event.get_data().len();
+        self.buffered.push((timestamp, event.get_data().to_vec()));
+        if self.buffered_bytes >= self.max_memory {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> std::io::Result<SortedEvents> {
+        let mut sources = Vec::new();
+        for path in &self.runs {
+            let file = std::fs::File::open(path)?;
+            sources.push(self.make_reader(Box::new(std::io::BufReader::new(file))));
+        }
+        if !self.buffered.is_empty() {
+            let data = self.take_sorted_buffer();
+            sources.push(self.make_reader(Box::new(std::io::Cursor::new(data))));
+        }
+
+        Ok(SortedEvents {
+            sources,
+            next_events: Vec::new(),
+            drained_source: None,
+            queue: std::collections::BinaryHeap::new(),
+            runs: std::mem::take(&mut self.runs),
+        })
+    }
+
+    fn make_reader(&self, reader: Box<dyn std::io::Read>) -> EventReader {
+        let mut reader = EventReader::new(DataProvider::new(reader));
+        reader.set_endianness(self.endianness);
+        reader
+    }
+
+    fn take_sorted_buffer(&mut self) -> Vec<u8> {
+        self.buffered.sort_by_key(|(timestamp, _)| *timestamp);
+        let mut data = Vec::with_capacity(self.buffered_bytes);
+        for (_, event_data) in self.buffered.drain(..) {
+            data.extend_from_slice(&event_data);
+        }
+        self.buffered_bytes = 0;
+        data
+    }
+
+    fn spill(&mut self) -> std::io::Result<()> {
+        let path = self.temp_dir.join(format!(
+            "hawktracer-sort-{}-{}.run",
+            std::process::id(),
+            RUN_COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+        ));
+        let data = self.take_sorted_buffer();
+        let mut file = std::fs::File::create(&path)?;
+        self.runs.push(path);
+        file.write_all(&data)
+    }
+}
+
+impl Drop for ExternalSorter {
+    fn drop(&mut self) {
+        for path in &self.runs {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+// Events of all the runs ordered by timestamp (taken from the "timestamp"
+// field of the event or of its base). Temporary files are removed when the
+// object is dropped.
+pub struct SortedEvents {
+    sources: Vec<EventReader>,
+    // Next event of each source; None for exhausted sources.
+    next_events: Vec<Option<Event>>,
+    // Source of the last returned event; its next event is read by the next
+    // call, so a read error doesn't lose the returned event.
+    drained_source: Option<usize>,
+    queue: std::collections::BinaryHeap<std::cmp::Reverse<(u64, usize)>>,
+    runs: Vec<std::path::PathBuf>,
+}
+
+impl SortedEvents {
+    // Returns DataError::EndOfStream when all the events were read. After a
+    // read error, the next call retries the failed source.
+    pub fn read_event(&mut self, registry: &EventKlassRegistry) -> Result<Event, ReadEventError> {
+        while self.next_events.len() < self.sources.len() {
+            let event = self.read_source(self.next_events.len(), registry)?;
+            self.next_events.push(event);
+        }
+        if let Some(index) = self.drained_source {
+            self.next_events[index] = self.read_source(index, registry)?;
+            self.drained_source = None;
+        }
+
+        let index = match self.queue.pop() {
+            Some(std::cmp::Reverse((_, index))) => index,
+            None => return Err(ReadEventError::DataError(DataError::EndOfStream)),
+        };
+        self.drained_source = Some(index);
+        match self.next_events[index].take() {
+            Some(event) => Ok(event),
+            None => Err(ReadEventError::DataError(DataError::EndOfStream)),
+        }
+    }
+
+    fn read_source(
+        &mut self,
+        index: usize,
+        registry: &EventKlassRegistry,
+    ) -> Result<Option<Event>, ReadEventError> {
+        match self.sources[index].read_data_event(registry) {
+            Ok(event) => {
+                let timestamp = event.get_timestamp().unwrap_or_default();
+                self.queue.push(std::cmp::Reverse((timestamp, index)));
+                Ok(Some(event))
+            }
+            Err(ReadEventError::DataError(DataError::EndOfStream)) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl Drop for SortedEvents {
+    fn drop(&mut self) {
+        for path in &self.runs {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hawktracer_parser_test_utilities::stream::{encode_header, TestStreamBuilder};
+
+    fn make_trace(timestamps: &[u64]) -> Vec<u8> {
+        let mut builder = TestStreamBuilder::new().klass(100, "foo", &[("HT_Event", "base")]);
+        for timestamp in timestamps {
+            builder = builder.event(100, *timestamp, &[]);
+        }
+        builder.build()
+    }
+
+    fn sort(timestamps: &[u64], max_memory: usize) -> (Vec<(u64, u64)>, usize) {
+        let temp_dir = std::env::temp_dir();
+        let mut registry = EventKlassRegistry::new();
+        let mut reader = EventReader::new(DataProvider::new(Box::new(std::io::Cursor::new(
+            make_trace(timestamps),
+        ))));
+        let mut sorter = ExternalSorter::new(&temp_dir, max_memory);
+        loop {
+            match reader.read_raw_event(&mut registry) {
+                Ok(event) => sorter.push(event).unwrap(),
+                Err(ReadEventError::DataError(DataError::EndOfStream)) => break,
+                Err(err) => panic!("{:?}", err),
+            }
+        }
+        let run_count = sorter.get_run_count();
+
+        let mut sorted = sorter.finish().unwrap();
+        let mut events = Vec::new();
+        loop {
+            match sorted.read_event(&registry) {
+                Ok(event) => events.push((event.get_timestamp().unwrap(), event.get_id().unwrap())),
+                Err(ReadEventError::DataError(DataError::EndOfStream)) => break,
+                Err(err) => panic!("{:?}", err),
+            }
+        }
+        (events, run_count)
+    }
+
+    #[test]
+    fn events_should_be_sorted_in_memory_if_limit_is_not_reached() {
+        let (events, run_count) = sort(&[5, 3, 9, 1], 1024);
+
+        assert_eq!(run_count, 0);
+        assert_eq!(events, vec![(1, 3), (3, 1), (5, 0), (9, 2)]);
+    }
+
+    #[test]
+    fn events_should_be_merged_from_spilled_runs() {
+        // Each event is 20 bytes long, so every run holds two events.
+        let (events, run_count) = sort(&[8, 4, 7, 2, 4, 9, 1], 40);
+
+        assert_eq!(run_count, 3);
+        assert_eq!(
+            events,
+            vec![(1, 6), (2, 3), (4, 1), (4, 4), (7, 2), (8, 0), (9, 5)]
+        );
+    }
+
+    #[test]
+    fn event_should_be_returned_before_error_of_its_source() {
+        let mut registry = EventKlassRegistry::new();
+        let mut reader = EventReader::new(DataProvider::new(Box::new(std::io::Cursor::new(
+            make_trace(&[]),
+        ))));
+        while reader.read_event(&mut registry).is_ok() {}
+
+        let mut data = TestStreamBuilder::new().event(100, 1, &[]).build();
+        data.extend(encode_header(999, 2, 1));
+        let sorter = ExternalSorter::new(&std::env::temp_dir(), 1024);
+        let mut sorted = SortedEvents {
+            sources: vec![sorter.make_reader(Box::new(std::io::Cursor::new(data)))],
+            next_events: Vec::new(),
+            drained_source: None,
+            queue: std::collections::BinaryHeap::new(),
+            runs: Vec::new(),
+        };
+
+        assert_eq!(
+            sorted.read_event(&registry).unwrap().get_timestamp(),
+            Some(1)
+        );
+        assert_eq!(
+            sorted.read_event(&registry).unwrap_err(),
+            ReadEventError::UnknownKlassId(999)
+        );
+    }
+}
@@ -0,0 +1,51 @@
+use crate::event_klass::EventKlass;
+
+// Set of fields which should be decoded for given klasses. Fields which are
+// not included are skipped by the reader; klasses without an entry are
+// decoded completely. Note that nested structs (e.g. "base") have to be
+// included explicitly to reach their fields.
+#[derive(Default, Clone)]
+pub struct FieldProjection {
+    klasses: fnv::FnvHashMap<String, fnv::FnvHashSet<String>>,
+}
+
+impl FieldProjection {
+    pub fn new() -> FieldProjection {
+        FieldProjection::default()
+    }
+
+    pub fn include(mut self, klass_name: &str, field_names: &[&str]) -> FieldProjection {
+        let fields = self.klasses.entry(klass_name.to_owned()).or_default();
+        for field_name in field_names {
+            fields.insert(field_name.to_string());
+        }
+        self
+    }
+
+    pub fn is_field_included(&self, klass: &EventKlass, field_name: &str) -> bool {
+        match self.klasses.get(klass.get_name()) {
+            Some(fields) => fields.contains(field_name),
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_listed_fields_of_projected_klasses_should_be_included() {
+        let projection = FieldProjection::new()
+            .include("foo", &["a"])
+            .include("foo", &["b"])
+            .include("empty", &[]);
+
+        let foo = EventKlass::new(100, "foo".to_owned());
+        assert!(projection.is_field_included(&foo, "a"));
+        assert!(projection.is_field_included(&foo, "b"));
+        assert!(!projection.is_field_included(&foo, "c"));
+        assert!(!projection.is_field_included(&EventKlass::new(101, "empty".to_owned()), "a"));
+        assert!(projection.is_field_included(&EventKlass::new(102, "bar".to_owned()), "a"));
+    }
+}
@@ -0,0 +1,761 @@
+use crate::event::{Event, Value};
+use crate::registry::EventKlassRegistry;
+
+// Predicates on events written as expressions, e.g.:
+//     klass == "my_event" && duration > 1000 && label =~ "render.*"
+// Operands of comparisons are fields (looked up in base events as well;
+// fields of struct values are separated with dots, e.g. "info.size") and
+// string or numeric literals. Two names are reserved: "klass" is the name of
+// the event klass and "klass_id" its identifier. Supported operators, from
+// the lowest precedence: ||, &&, !, comparisons (==, !=, <, <=, >, >=, and
+// =~, !~ matching strings against a pattern). A field without an operator
+// is true if the event has it. Comparisons of missing fields or values of a
+// different type are false.
+//
+// Patterns support a subset of the regular expressions syntax: literals,
+// ".", character classes ("[a-z_]", "[^0-9]"), "\d", "\w", "\s" (and
+// negations), the "*", "+" and "?" quantifiers, and the "^" and "$" anchors.
+// A pattern matches if it matches any part of the string.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilterExpression {
+    root: Expr,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilterParseError {
+    // Offset of the character where the error was found.
+    pub position: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for FilterParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for FilterParseError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Exists(Field),
+    Compare(Field, CompareOp, Literal),
+    Match(Field, Pattern, bool),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Field {
+    Klass,
+    KlassId,
+    Path(Vec<String>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CompareOp {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Literal {
+    Int(i128),
+    Float(f64),
+    Str(String),
+}
+
+// Value of a field, as compared by the expression.
+enum Operand<'a> {
+    Int(i128),
+    Str(&'a str),
+}
+
+impl FilterExpression {
+    pub fn parse(expression: &str) -> Result<FilterExpression, FilterParseError> {
+        let tokens = tokenize(expression)?;
+        let mut parser = ExprParser {
+            tokens,
+            index: 0,
+            end: expression.chars().count(),
+        };
+        let root = parser.parse_or()?;
+        match parser.tokens.get(parser.index) {
+            Some((position, token)) => Err(FilterParseError {
+                position: *position,
+                message: format!("Unexpected {:?}", token),
+            }),
+            None => Ok(FilterExpression { root }),
+        }
+    }
+
+    pub fn matches(&self, event: &Event, registry: &EventKlassRegistry) -> bool {
+        self.root.matches(event, registry)
+    }
+
+    // Iterator adaptor keeping the matching events (or references to them).
+    pub fn filter_events<'a, I>(
+        &'a self,
+        events: I,
+        registry: &'a EventKlassRegistry,
+    ) -> impl Iterator<Item = I::Item> + 'a
+    where
+        I: IntoIterator,
+        I::IntoIter: 'a,
+        I::Item: std::borrow::Borrow<Event>,
+    {
+        events
+            .into_iter()
+            .filter(move |event| self.matches(std::borrow::Borrow::borrow(event), registry))
+    }
+}
+
+impl std::str::FromStr for FilterExpression {
+    type Err = FilterParseError;
+
+    fn from_str(expression: &str) -> Result<FilterExpression, FilterParseError> {
+        FilterExpression::parse(expression)
+    }
+}
+
+impl Expr {
+    fn matches(&self, event: &Event, registry: &EventKlassRegistry) -> bool {
+        match self {
+            Expr::And(left, right) => {
+                left.matches(event, registry) && right.matches(event, registry)
+            }
+            Expr::Or(left, right) => {
+                left.matches(event, registry) || right.matches(event, registry)
+            }
+            Expr::Not(expr) => !expr.matches(event, registry),
+            Expr::Exists(field) => field.resolve(event, registry).is_some(),
+            Expr::Compare(field, op, literal) => {
+                let ordering = match (field.resolve(event, registry), literal) {
+                    (Some(Operand::Int(value)), Literal::Int(literal)) => {
+                        value.partial_cmp(literal)
+                    }
+                    (Some(Operand::Int(value)), Literal::Float(literal)) => {
+                        (value as f64).partial_cmp(literal)
+                    }
+                    (Some(Operand::Str(value)), Literal::Str(literal)) => {
+                        value.partial_cmp(literal.as_str())
+                    }
+                    _ => None,
+                };
+                match ordering {
+                    Some(ordering) => op.is_satisfied(ordering),
+                    None => false,
+                }
+            }
+            Expr::Match(field, pattern, expected) => match field.resolve(event, registry) {
+                Some(Operand::Str(value)) => pattern.is_match(value) == *expected,
+                _ => false,
+            },
+        }
+    }
+}
+
+impl Field {
+    fn from_name(name: &str) -> Field {
+        match name {
+            "klass" => Field::Klass,
+            "klass_id" => Field::KlassId,
+            _ => Field::Path(name.split('.').map(str::to_owned).collect()),
+        }
+    }
+
+    fn resolve<'a>(
+        &self,
+        event: &'a Event,
+        registry: &'a EventKlassRegistry,
+    ) -> Option<Operand<'a>> {
+        let value = match self {
+            Field::Klass => return event.get_klass_name(registry).map(Operand::Str),
+            Field::KlassId => return Some(Operand::Int(i128::from(event.get_klass_id()))),
+            Field::Path(path) => {
+                let mut value = event.find_value(&path[0])?;
+                for name in &path[1..] {
+                    value = match value {
+                        Value::Struct(event) => event.find_value(name)?,
+                        _ => return None,
+                    };
+                }
+                value
+            }
+        };
+
+        match value {
+            Value::Str(value) => Some(Operand::Str(value)),
+            value => match value.as_u64() {
+                Some(value) => Some(Operand::Int(i128::from(value))),
+                None => value.as_i64().map(|value| Operand::Int(i128::from(value))),
+            },
+        }
+    }
+}
+
+impl CompareOp {
+    fn is_satisfied(self, ordering: std::cmp::Ordering) -> bool {
+        use std::cmp::Ordering;
+        match self {
+            CompareOp::Equal => ordering == Ordering::Equal,
+            CompareOp::NotEqual => ordering != Ordering::Equal,
+            CompareOp::Less => ordering == Ordering::Less,
+            CompareOp::LessOrEqual => ordering != Ordering::Greater,
+            CompareOp::Greater => ordering == Ordering::Greater,
+            CompareOp::GreaterOrEqual => ordering != Ordering::Less,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Number(Literal),
+    Compare(CompareOp),
+    Match(bool),
+    And,
+    Or,
+    Not,
+    LeftParen,
+    RightParen,
+}
+
+fn parse_error<T>(position: usize, message: &str) -> Result<T, FilterParseError> {
+    Err(FilterParseError {
+        position,
+        message: message.to_owned(),
+    })
+}
+
+fn tokenize(expression: &str) -> Result<Vec<(usize, Token)>, FilterParseError> {
+    let chars: Vec<char> = expression.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let start = i;
+        let next = chars.get(i + 1).copied();
+        let token = match chars[i] {
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '(' => Token::LeftParen,
+            ')' => Token::RightParen,
+            '&' if next == Some('&') => Token::And,
+            '|' if next == Some('|') => Token::Or,
+            '=' if next == Some('=') => Token::Compare(CompareOp::Equal),
+            '=' if next == Some('~') => Token::Match(true),
+            '!' if next == Some('=') => Token::Compare(CompareOp::NotEqual),
+            '!' if next == Some('~') => Token::Match(false),
+            '!' => Token::Not,
+            '<' if next == Some('=') => Token::Compare(CompareOp::LessOrEqual),
+            '<' => Token::Compare(CompareOp::Less),
+            '>' if next == Some('=') => Token::Compare(CompareOp::GreaterOrEqual),
+            '>' => Token::Compare(CompareOp::Greater),
+            '"' => {
+                let mut value = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        Some('"') => break,
+                        Some('\\') if i + 1 < chars.len() => {
+                            value.push(chars[i + 1]);
+                            i += 2;
+                        }
+                        Some(c) => {
+                            value.push(*c);
+                            i += 1;
+                        }
+                        None => return parse_error(start, "Unterminated string"),
+                    }
+                }
+                Token::Str(value)
+            }
+            c if c.is_ascii_digit() || (c == '-' && next.is_some_and(|c| c.is_ascii_digit())) => {
+                i += 1;
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.') {
+                    i += 1;
+                }
+                let text: String = chars[start..i].iter().collect();
+                let literal = match (text.parse::<i128>(), text.parse::<f64>()) {
+                    (Ok(value), _) => Literal::Int(value),
+                    (_, Ok(value)) => Literal::Float(value),
+                    _ => return parse_error(start, &format!("Invalid number {}", text)),
+                };
+                tokens.push((start, Token::Number(literal)));
+                continue;
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                while i < chars.len()
+                    && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '.')
+                {
+                    i += 1;
+                }
+                tokens.push((start, Token::Ident(chars[start..i].iter().collect())));
+                continue;
+            }
+            c => return parse_error(start, &format!("Unexpected character {}", c)),
+        };
+
+        i += match token {
+            Token::LeftParen | Token::RightParen | Token::Not | Token::Str(_) => 1,
+            Token::Compare(CompareOp::Less) | Token::Compare(CompareOp::Greater) => 1,
+            _ => 2,
+        };
+        tokens.push((start, token));
+    }
+    Ok(tokens)
+}
+
+struct ExprParser {
+    tokens: Vec<(usize, Token)>,
+    index: usize,
+    // Position reported for errors at the end of the expression.
+    end: usize,
+}
+
+impl ExprParser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index).map(|(_, token)| token)
+    }
+
+    fn next(&mut self) -> Result<(usize, Token), FilterParseError> {
+        match self.tokens.get(self.index) {
+            Some(token) => {
+                self.index += 1;
+                Ok(token.clone())
+            }
+            None => parse_error(self.end, "Unexpected end of expression"),
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, FilterParseError> {
+        let mut expr = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.index += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, FilterParseError> {
+        let mut expr = self.parse_unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.index += 1;
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, FilterParseError> {
+        let (position, token) = self.next()?;
+        let field = match token {
+            Token::Not => return Ok(Expr::Not(Box::new(self.parse_unary()?))),
+            Token::LeftParen => {
+                let expr = self.parse_or()?;
+                return match self.next()? {
+                    (_, Token::RightParen) => Ok(expr),
+                    (position, _) => parse_error(position, "Expected )"),
+                };
+            }
+            Token::Ident(name) => Field::from_name(&name),
+            _ => return parse_error(position, "Expected a field name"),
+        };
+
+        match self.peek() {
+            Some(Token::Compare(op)) => {
+                let op = *op;
+                self.index += 1;
+                match self.next()? {
+                    (_, Token::Number(literal)) => Ok(Expr::Compare(field, op, literal)),
+                    (_, Token::Str(value)) => Ok(Expr::Compare(field, op, Literal::Str(value))),
+                    (position, _) => parse_error(position, "Expected a string or a number"),
+                }
+            }
+            Some(Token::Match(expected)) => {
+                let expected = *expected;
+                self.index += 1;
+                match self.next()? {
+                    (position, Token::Str(pattern)) => match Pattern::parse(&pattern) {
+                        Ok(pattern) => Ok(Expr::Match(field, pattern, expected)),
+                        Err(message) => parse_error(position, &message),
+                    },
+                    (position, _) => parse_error(position, "Expected a pattern string"),
+                }
+            }
+            _ => Ok(Expr::Exists(field)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ClassItem {
+    Range(char, char),
+    Digit,
+    Word,
+    Space,
+}
+
+impl ClassItem {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            ClassItem::Range(first, last) => *first <= c && c <= *last,
+            ClassItem::Digit => c.is_ascii_digit(),
+            ClassItem::Word => c.is_alphanumeric() || c == '_',
+            ClassItem::Space => c.is_whitespace(),
+        }
+    }
+
+    fn from_escape(c: char) -> Option<ClassItem> {
+        match c.to_ascii_lowercase() {
+            'd' => Some(ClassItem::Digit),
+            'w' => Some(ClassItem::Word),
+            's' => Some(ClassItem::Space),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Atom {
+    Char(char),
+    Any,
+    Class(Vec<ClassItem>, bool),
+}
+
+impl Atom {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Atom::Char(expected) => *expected == c,
+            Atom::Any => true,
+            Atom::Class(items, negated) => items.iter().any(|item| item.matches(c)) != *negated,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Repeat {
+    One,
+    ZeroOrOne,
+    ZeroOrMore,
+    OneOrMore,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Pattern {
+    nodes: Vec<(Atom, Repeat)>,
+    anchored_start: bool,
+    anchored_end: bool,
+}
+
+impl Pattern {
+    fn parse(pattern: &str) -> Result<Pattern, String> {
+        let chars: Vec<char> = pattern.chars().collect();
+        let mut result = Pattern {
+            nodes: Vec::new(),
+            anchored_start: chars.first() == Some(&'^'),
+            anchored_end: chars.last() == Some(&'$')
+                && (chars.len() < 2 || chars[chars.len() - 2] != '\\'),
+        };
+        let start = result.anchored_start as usize;
+        let end = std::cmp::max(chars.len() - result.anchored_end as usize, start);
+        let mut i = start;
+
+        while i < end {
+            let atom = match chars[i] {
+                '.' => Atom::Any,
+                '\\' => {
+                    i += 1;
+                    match chars.get(i) {
+                        Some(c) => match ClassItem::from_escape(*c) {
+                            Some(item) => Atom::Class(vec![item], c.is_ascii_uppercase()),
+                            None => Atom::Char(*c),
+                        },
+                        None => return Err("Pattern ends with \\".to_owned()),
+                    }
+                }
+                '[' => {
+                    let (atom, next) = Pattern::parse_class(&chars, i + 1)?;
+                    i = next;
+                    atom
+                }
+                '*' | '+' | '?' => {
+                    let repeat = match chars[i] {
+                        '*' => Repeat::ZeroOrMore,
+                        '+' => Repeat::OneOrMore,
+                        _ => Repeat::ZeroOrOne,
+                    };
+                    match result.nodes.last_mut() {
+                        Some((_, last_repeat)) if *last_repeat == Repeat::One => {
+                            *last_repeat = repeat
+                        }
+                        _ => return Err(format!("Nothing to repeat with {}", chars[i])),
+                    }
+                    i += 1;
+                    continue;
+                }
+                c @ ('(' | ')' | '|' | '{' | '}' | '^' | '$') => {
+                    return Err(format!("Unsupported pattern character {}", c))
+                }
+                c => Atom::Char(c),
+            };
+            result.nodes.push((atom, Repeat::One));
+            i += 1;
+        }
+        Ok(result)
+    }
+
+    // Returns the class and the index of its closing bracket.
+    fn parse_class(chars: &[char], mut i: usize) -> Result<(Atom, usize), String> {
+        let negated = chars.get(i) == Some(&'^');
+        i += negated as usize;
+        let mut items = Vec::new();
+        loop {
+            let c = match chars.get(i) {
+                Some(']') if !items.is_empty() => return Ok((Atom::Class(items, negated), i)),
+                Some('\\') => {
+                    i += 1;
+                    match chars.get(i) {
+                        Some(c) => match ClassItem::from_escape(*c) {
+                            Some(item) if c.is_ascii_lowercase() => {
+                                items.push(item);
+                                i += 1;
+                                continue;
+                            }
+                            Some(_) => return Err(format!("Unsupported class escape \\{}", c)),
+                            None => *c,
+                        },
+                        None => return Err("Unterminated character class".to_owned()),
+                    }
+                }
+                Some(c) => *c,
+                None => return Err("Unterminated character class".to_owned()),
+            };
+            match (chars.get(i + 1), chars.get(i + 2)) {
+                (Some('-'), Some(last)) if *last != ']' => {
+                    items.push(ClassItem::Range(c, *last));
+                    i += 3;
+                }
+                _ => {
+                    items.push(ClassItem::Range(c, c));
+                    i += 1;
+                }
+            }
+        }
+    }
+
+    fn is_match(&self, value: &str) -> bool {
+        let chars: Vec<char> = value.chars().collect();
+        let last_start = if self.anchored_start { 0 } else { chars.len() };
+        // Results of matches_at() for each (node, position), so matching
+        // takes polynomial time even for patterns like "a*a*a*b".
+        let mut memo = vec![None; self.nodes.len() * (chars.len() + 1)];
+        (0..=last_start).any(|start| self.matches_at(0, &chars, start, &mut memo))
+    }
+
+    fn matches_at(
+        &self,
+        node: usize,
+        chars: &[char],
+        position: usize,
+        memo: &mut [Option<bool>],
+    ) -> bool {
+        let (atom, repeat) = match self.nodes.get(node) {
+            Some(node) => node,
+            None => return !self.anchored_end || position == chars.len(),
+        };
+        let memo_index = node * (chars.len() + 1) + position;
+        if let Some(result) = memo[memo_index] {
+            return result;
+        }
+        let matches = |position: usize| chars.get(position).is_some_and(|c| atom.matches(*c));
+
+        let result = match repeat {
+            Repeat::One => {
+                matches(position) && self.matches_at(node + 1, chars, position + 1, memo)
+            }
+            Repeat::ZeroOrOne => {
+                (matches(position) && self.matches_at(node + 1, chars, position + 1, memo))
+                    || self.matches_at(node + 1, chars, position, memo)
+            }
+            Repeat::ZeroOrMore | Repeat::OneOrMore => {
+                let mut count = 0;
+                while matches(position + count) {
+                    count += 1;
+                }
+                let min_count = (*repeat == Repeat::OneOrMore) as usize;
+                (min_count..=count)
+                    .rev()
+                    .any(|count| self.matches_at(node + 1, chars, position + count, memo))
+            }
+        };
+        memo[memo_index] = Some(result);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event_klass::EventKlass;
+    use crate::test_events::{self, make_plain_event};
+
+    fn make_registry() -> EventKlassRegistry {
+        let mut registry = EventKlassRegistry::new();
+        registry.add_klass(EventKlass::new(100, "render_event".to_owned()));
+        registry.add_klass(EventKlass::new(101, "io_event".to_owned()));
+        registry
+    }
+
+    fn make_event(klass_id: u32, duration: u64, label: &str) -> Event {
+        let info = make_plain_event(102, vec![("depth", Value::I32(-2))]);
+        test_events::make_event(
+            klass_id,
+            10,
+            vec![
+                ("info", Value::Struct(Box::new(info))),
+                ("duration", Value::U64(duration)),
+                ("label", Value::Str(label.to_owned())),
+            ],
+        )
+    }
+
+    fn matches(expression: &str, event: &Event) -> bool {
+        FilterExpression::parse(expression)
+            .unwrap()
+            .matches(event, &make_registry())
+    }
+
+    #[test]
+    fn expression_should_match_klass_and_fields() {
+        let event = make_event(100, 1500, "render_frame");
+
+        assert!(matches(
+            "klass == \"render_event\" && duration > 1000 && label =~ \"render.*\"",
+            &event
+        ));
+        assert!(matches("klass_id == 100 && timestamp <= 10", &event));
+        assert!(matches("info.depth < 0 && info.depth >= -2.5", &event));
+        assert!(matches("duration != 1000 && label >= \"r\"", &event));
+        assert!(!matches("klass == \"io_event\" || duration < 1000", &event));
+        assert!(matches(
+            "!(klass == \"io_event\" || duration < 1000)",
+            &event
+        ));
+        assert!(matches("label !~ \"^frame\" && label", &event));
+    }
+
+    #[test]
+    fn comparisons_of_missing_or_mismatched_fields_should_be_false() {
+        let event = make_event(100, 1500, "render_frame");
+
+        assert!(!matches("missing == 1", &event));
+        assert!(!matches("missing != 1", &event));
+        assert!(!matches("missing", &event));
+        assert!(!matches("label == 1", &event));
+        assert!(!matches("duration == \"1500\"", &event));
+        assert!(!matches("duration =~ \"15\"", &event));
+        assert!(!matches("info == 1", &event));
+    }
+
+    #[test]
+    fn and_should_bind_tighter_than_or() {
+        let event = make_event(101, 10, "read");
+
+        assert!(matches(
+            "duration > 100 && label == \"x\" || klass_id == 101",
+            &event
+        ));
+        assert!(!matches(
+            "duration > 100 && (label == \"x\" || klass_id == 101)",
+            &event
+        ));
+    }
+
+    #[test]
+    fn patterns_should_support_regular_expression_subset() {
+        let cases = [
+            ("render", "pre_render_post", true),
+            ("^render", "pre_render", false),
+            ("frame$", "render_frame", true),
+            ("^$", "", true),
+            ("a.c", "abc", true),
+            ("ab*c", "ac", true),
+            ("ab+c", "ac", false),
+            ("ab?c", "abbc", false),
+            ("^[a-c_]+\\d$", "ab_c7", true),
+            ("^[^0-9]+$", "abc1", false),
+            ("\\w+\\s\\S", "frame 1", true),
+            ("a\\.b", "axb", false),
+            ("a\\.b", "a.b", true),
+            ("^.*_.*$", "a_b", true),
+        ];
+        for (pattern, value, expected) in &cases {
+            assert_eq!(
+                Pattern::parse(pattern).unwrap().is_match(value),
+                *expected,
+                "{} =~ {}",
+                value,
+                pattern
+            );
+        }
+    }
+
+    #[test]
+    fn pathological_patterns_should_match_quickly() {
+        let pattern = Pattern::parse(&format!("^{}b$", "a*".repeat(30))).unwrap();
+        let value = "a".repeat(1000);
+        assert!(!pattern.is_match(&value));
+        assert!(pattern.is_match(&format!("{}b", value)));
+        assert!(!Pattern::parse(".*.*.*.*.*x").unwrap().is_match(&value));
+    }
+
+    #[test]
+    fn invalid_expressions_should_fail_with_position() {
+        let cases = [
+            ("duration >", 10),
+            ("duration > > 1", 11),
+            ("(duration > 1", 13),
+            ("label == \"abc", 9),
+            ("duration > 1 label", 13),
+            ("label =~ \"(a|b)\"", 9),
+            ("label =~ \"*\"", 9),
+            ("duration # 1", 9),
+            ("== 1", 0),
+        ];
+        for (expression, position) in &cases {
+            assert_eq!(
+                FilterExpression::parse(expression).unwrap_err().position,
+                *position,
+                "{}",
+                expression
+            );
+        }
+    }
+
+    #[test]
+    fn filter_events_should_keep_matching_events() {
+        let registry = make_registry();
+        let events = vec![
+            make_event(100, 10, "a"),
+            make_event(101, 20, "b"),
+            make_event(100, 30, "c"),
+        ];
+        let expression: FilterExpression = "klass == \"render_event\"".parse().unwrap();
+
+        let labels: Vec<&str> = expression
+            .filter_events(&events, &registry)
+            .map(|event| event.get_value::<&str>("label").unwrap())
+            .collect();
+        assert_eq!(labels, vec!["a", "c"]);
+        assert_eq!(expression.filter_events(events, &registry).count(), 2);
+    }
+}
@@ -0,0 +1,130 @@
+use crate::data_struct_reader::Endianness;
+use crate::registry::{CoreEventKlassId, EventKlassRegistry};
+use crate::registry_updater::MkcreflectType;
+
+struct DictionaryWriter {
+    output: String,
+    entries: std::collections::HashSet<Vec<u8>>,
+}
+
+impl DictionaryWriter {
+    fn add(&mut self, name: &str, value: &[u8]) {
+        if !self.entries.insert(value.to_vec()) {
+            return;
+        }
+
+        let name: String = name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        let value: String = value
+            .iter()
+            .map(|b| match b {
+                b' '..=b'~' if *b != b'"' && *b != b'\\' => (*b as char).to_string(),
+                _ => format!("\\x{:02x}", b),
+            })
+            .collect();
+        self.output.push_str(&format!("{}=\"{}\"\n", name, value));
+    }
+}
+
+fn encode_u32(value: u32, endianness: Endianness) -> Vec<u8> {
+    match endianness {
+        Endianness::Little => value.to_le_bytes().to_vec(),
+        Endianness::Big => value.to_be_bytes().to_vec(),
+        Endianness::Native => value.to_ne_bytes().to_vec(),
+    }
+}
+
+fn encode_u64(value: u64, endianness: Endianness) -> Vec<u8> {
+    match endianness {
+        Endianness::Little => value.to_le_bytes().to_vec(),
+        Endianness::Big => value.to_be_bytes().to_vec(),
+        Endianness::Native => value.to_ne_bytes().to_vec(),
+    }
+}
+
+fn zero_terminated(value: &str) -> Vec<u8> {
+    let mut data = value.as_bytes().to_vec();
+    data.push(0);
+    data
+}
+
+// Generates a dictionary in the AFL/libFuzzer format with klass ids, klass
+// and field names, and magic values used by the HawkTracer protocol.
+pub fn generate_fuzz_dictionary(registry: &EventKlassRegistry, endianness: Endianness) -> String {
+    let mut writer = DictionaryWriter {
+        output: String::new(),
+        entries: std::collections::HashSet::new(),
+    };
+
+    for klass in registry.get_klasses() {
+        writer.add(
+            &format!("klass_id_{}", klass.get_name()),
+            &encode_u32(klass.get_id(), endianness),
+        );
+        writer.add(
+            &format!("klass_name_{}", klass.get_name()),
+            &zero_terminated(klass.get_name()),
+        );
+        for field in klass.get_fields() {
+            writer.add(
+                &format!("field_name_{}", field.get_name()),
+                &zero_terminated(field.get_name()),
+            );
+            writer.add(
+                &format!("field_type_{}", field.get_type_name()),
+                &zero_terminated(field.get_type_name()),
+            );
+        }
+    }
+
+    for klass_id in &[
+        CoreEventKlassId::Endianness,
+        CoreEventKlassId::Base,
+        CoreEventKlassId::KlassInfo,
+        CoreEventKlassId::FieldInfo,
+    ] {
+        writer.add("core_klass_id", &encode_u32(*klass_id as u32, endianness));
+    }
+    for data_type in &MkcreflectType::ALL {
+        writer.add("data_type", &[*data_type as u8]);
+    }
+    for size in &[1, 2, 4, 8] {
+        writer.add("field_size", &encode_u64(*size, endianness));
+    }
+
+    writer.output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::DataType;
+    use crate::event_klass::EventKlass;
+
+    #[test]
+    fn dictionary_should_contain_klass_and_field_entries() {
+        let mut klass = EventKlass::new(100, "my_klass".to_owned());
+        klass.add_field("value".to_owned(), "uint32_t".to_owned(), DataType::U32);
+        let mut registry = EventKlassRegistry::new();
+        registry.add_klass(klass);
+
+        let dictionary = generate_fuzz_dictionary(&registry, Endianness::Little);
+
+        assert!(dictionary.contains("klass_id_my_klass=\"d\\x00\\x00\\x00\"\n"));
+        assert!(dictionary.contains("klass_name_my_klass=\"my_klass\\x00\"\n"));
+        assert!(dictionary.contains("field_name_value=\"value\\x00\"\n"));
+        assert!(dictionary.contains("field_type_uint32_t=\"uint32_t\\x00\"\n"));
+        assert!(dictionary.contains("data_type=\"c\"\n"));
+    }
+
+    #[test]
+    fn dictionary_should_not_contain_duplicated_values() {
+        let dictionary = generate_fuzz_dictionary(&EventKlassRegistry::new(), Endianness::Big);
+
+        let count = dictionary.matches("=\"info_klass_id\\x00\"").count();
+        assert_eq!(count, 1);
+        assert!(dictionary.contains("\"\\x00\\x00\\x00\\x03\""));
+    }
+}
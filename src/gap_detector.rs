@@ -0,0 +1,151 @@
+use crate::event::{DataType, Event, Value};
+use crate::event_klass::EventKlass;
+use crate::registry::{CoreEventKlassId, EventKlassRegistry};
+
+pub const DROPPED_RANGE_KLASS_NAME: &str = "HT_DroppedRangeEvent";
+
+// Range of event ids missing in the stream. Timestamps are taken from the
+// events surrounding the gap.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DroppedRange {
+    pub first_id: u64,
+    pub last_id: u64,
+    pub start: u64,
+    pub end: u64,
+}
+
+impl DroppedRange {
+    pub fn get_count(&self) -> u64 {
+        self.last_id - self.first_id + 1
+    }
+}
+
+// Detects dropped events by looking for gaps in the sequence of event ids.
+// All the events of the stream (including metadata events) should be passed
+// to update(), as they share the same id sequence.
+#[derive(Default)]
+pub struct GapDetector {
+    last_event: Option<(u64, u64)>,
+    marker_klass_id: Option<u32>,
+    dropped_count: u64,
+}
+
+impl GapDetector {
+    pub fn new() -> GapDetector {
+        GapDetector::default()
+    }
+
+    // Registers a klass describing dropped ranges, so make_marker() can
+    // synthesize events which downstream consumers can display.
+    pub fn with_markers(mut self, registry: &mut EventKlassRegistry, klass_id: u32) -> GapDetector {
+        let mut klass = EventKlass::new(klass_id, DROPPED_RANGE_KLASS_NAME.to_owned());
+        klass.add_field("base".to_owned(), "HT_Event".to_owned(), DataType::Struct);
+        for field_name in &["first_id", "last_id", "duration"] {
+            klass.add_field(field_name.to_string(), "uint64_t".to_owned(), DataType::U64);
+        }
+        registry.add_klass(klass);
+        self.marker_klass_id = Some(klass_id);
+        self
+    }
+
+    pub fn get_dropped_count(&self) -> u64 {
+        self.dropped_count
+    }
+
+    // Events without an id, or with an id lower than the previous one, don't
+    // report any gap.
+    pub fn update(&mut self, event: &Event) -> Option<DroppedRange> {
+        let id = event.get_id()?;
+        let timestamp = event.get_timestamp().unwrap_or_default();
+        let previous = self.last_event.replace((id, timestamp));
+
+        match previous {
+            Some((last_id, last_timestamp)) if id > last_id.saturating_add(1) => {
+                let range = DroppedRange {
+                    first_id: last_id + 1,
+                    last_id: id - 1,
+                    start: last_timestamp,
+                    end: timestamp.max(last_timestamp),
+                };
+                self.dropped_count += range.get_count();
+                Some(range)
+            }
+            _ => None,
+        }
+    }
+
+    // Returns None if markers were not enabled with with_markers().
+    pub fn make_marker(&self, range: &DroppedRange) -> Option<Event> {
+        let mut base_values = fnv::FnvHashMap::default();
+        base_values.insert("type".to_owned(), Value::U32(self.marker_klass_id?));
+        base_values.insert("timestamp".to_owned(), Value::U64(range.start));
+        base_values.insert("id".to_owned(), Value::U64(range.first_id));
+
+        let mut values = fnv::FnvHashMap::default();
+        values.insert(
+            "base".to_owned(),
+            Value::Struct(Box::new(Event::new(
+                CoreEventKlassId::Base as u32,
+                base_values,
+            ))),
+        );
+        values.insert("first_id".to_owned(), Value::U64(range.first_id));
+        values.insert("last_id".to_owned(), Value::U64(range.last_id));
+        values.insert("duration".to_owned(), Value::U64(range.end - range.start));
+        Some(Event::new(self.marker_klass_id?, values))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_events;
+
+    fn make_event(id: u64, timestamp: u64) -> Event {
+        test_events::make_event_with_id(100, timestamp, id, vec![])
+    }
+
+    #[test]
+    fn id_gaps_should_be_reported_as_dropped_ranges() {
+        let mut detector = GapDetector::new();
+
+        assert_eq!(detector.update(&make_event(1, 10)), None);
+        assert_eq!(detector.update(&make_event(2, 15)), None);
+        assert_eq!(
+            detector.update(&make_event(6, 40)),
+            Some(DroppedRange {
+                first_id: 3,
+                last_id: 5,
+                start: 15,
+                end: 40,
+            })
+        );
+        assert_eq!(detector.update(&make_event(7, 41)), None);
+        assert_eq!(detector.update(&make_event(3, 42)), None);
+        assert_eq!(detector.get_dropped_count(), 3);
+    }
+
+    #[test]
+    fn markers_should_be_synthesized_only_when_enabled() {
+        let range = DroppedRange {
+            first_id: 3,
+            last_id: 5,
+            start: 15,
+            end: 40,
+        };
+        assert_eq!(GapDetector::new().make_marker(&range), None);
+
+        let mut registry = EventKlassRegistry::new();
+        let detector = GapDetector::new().with_markers(&mut registry, 500);
+        let marker = detector.make_marker(&range).unwrap();
+
+        assert_eq!(marker.get_klass_id(), 500);
+        assert_eq!(
+            registry.get_klass_by_id(500).unwrap().get_name(),
+            DROPPED_RANGE_KLASS_NAME
+        );
+        assert_eq!(marker.get_value_u64("last_id").unwrap(), 5);
+        assert_eq!(marker.get_value_u64("duration").unwrap(), 25);
+        assert_eq!(marker.get_timestamp(), Some(15));
+    }
+}
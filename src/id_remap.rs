@@ -0,0 +1,101 @@
+use crate::event::{Event, Storage, Value};
+use crate::registry::CoreEventKlassId;
+
+// Klass ids changed by EventKlassRegistry::merge(). Ids which are not in
+// the table are kept.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct IdRemapTable {
+    ids: fnv::FnvHashMap<u32, u32>,
+}
+
+impl IdRemapTable {
+    pub fn new() -> IdRemapTable {
+        IdRemapTable::default()
+    }
+
+    pub(crate) fn insert(&mut self, from: u32, to: u32) {
+        if from != to {
+            self.ids.insert(from, to);
+        }
+    }
+
+    pub fn get_klass_id(&self, klass_id: u32) -> u32 {
+        self.ids.get(&klass_id).copied().unwrap_or(klass_id)
+    }
+
+    pub fn get_remapped_count(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    // Rewrites the klass ids of the event and its nested structs, and the
+    // "type" field of base events (or of flattened events). The layout of
+    // the event is kept.
+    pub fn remap_event(&self, event: Event) -> Event {
+        if self.is_empty() {
+            return event;
+        }
+
+        let klass_id = event.get_klass_id();
+        let mut values = Storage::new(event.is_compact());
+        for (name, value) in event.into_values() {
+            let value = match value {
+                Value::Struct(event) => Value::Struct(Box::new(self.remap_event(*event))),
+                Value::U32(id)
+                    if &*name == "type"
+                        && (klass_id == CoreEventKlassId::Base as u32 || id == klass_id) =>
+                {
+                    Value::U32(self.get_klass_id(id))
+                }
+                value => value,
+            };
+            values.insert(name, value);
+        }
+
+        Event::from_storage(self.get_klass_id(klass_id), values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_events;
+
+    fn make_event(klass_id: u32) -> Event {
+        test_events::make_event(klass_id, 5, vec![("type", Value::U32(100))])
+    }
+
+    #[test]
+    fn remap_event_should_rewrite_klass_ids() {
+        let mut table = IdRemapTable::new();
+        table.insert(100, 200);
+        table.insert(101, 101);
+        assert_eq!(table.get_remapped_count(), 1);
+
+        let event = table.remap_event(make_event(100));
+        assert_eq!(event.get_klass_id(), 200);
+        assert_eq!(event.get_path_as_u64("base.type").unwrap(), 200);
+        assert_eq!(event.get_value_u32("type").unwrap(), 200);
+
+        let event = table.remap_event(make_event(101).compact());
+        assert!(event.is_compact());
+        assert_eq!(event.get_klass_id(), 101);
+        assert_eq!(event.get_path_as_u64("base.type").unwrap(), 101);
+        // Not the klass id of the event, so it's a regular field.
+        assert_eq!(event.get_value_u32("type").unwrap(), 100);
+    }
+
+    #[test]
+    fn remap_event_should_rewrite_flattened_events() {
+        let mut table = IdRemapTable::new();
+        table.insert(100, 200);
+
+        let event = table.remap_event(make_event(100).flat_event());
+        assert_eq!(event.get_klass_id(), 200);
+        assert_eq!(event.get_value_u32("type").unwrap(), 200);
+        assert_eq!(event.get_value_u64("timestamp").unwrap(), 5);
+    }
+}
@@ -0,0 +1,172 @@
+use crate::data_provider::DataError;
+use crate::data_struct_reader::ReadEventError;
+use crate::event_reader::EventReader;
+use crate::registry::EventKlassRegistry;
+
+const INDEX_MAGIC: &[u8; 4] = b"HTIX";
+const INDEX_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexEntry {
+    pub offset: u64,
+    pub size: u64,
+    pub klass_id: u32,
+    pub timestamp: u64,
+}
+
+// Byte offsets, klass ids and timestamps of all the events of a trace, in
+// the order they appear in the stream. Payloads are skipped while the index
+// is built, only headers and metadata events are decoded.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TraceIndex {
+    entries: Vec<IndexEntry>,
+}
+
+fn read_u32(reader: &mut dyn std::io::Read) -> std::io::Result<u32> {
+    let mut buffer = [0u8; 4];
+    reader.read_exact(&mut buffer)?;
+    Ok(u32::from_le_bytes(buffer))
+}
+
+fn read_u64(reader: &mut dyn std::io::Read) -> std::io::Result<u64> {
+    let mut buffer = [0u8; 8];
+    reader.read_exact(&mut buffer)?;
+    Ok(u64::from_le_bytes(buffer))
+}
+
+impl TraceIndex {
+    pub fn new(entries: Vec<IndexEntry>) -> TraceIndex {
+        TraceIndex { entries }
+    }
+
+    // Indexes events from the current position of the reader to the end of
+    // the stream. Offsets are positions of the reader.
+    pub fn build(
+        reader: &mut EventReader,
+        registry: &mut EventKlassRegistry,
+    ) -> Result<TraceIndex, ReadEventError> {
+        let mut entries = Vec::new();
+
+        loop {
+            let offset = reader.get_position();
+            let header = match reader.skip_event(registry) {
+                Ok(header) => header,
+                Err(ReadEventError::DataError(DataError::EndOfStream)) => break,
+                Err(err) => return Err(err),
+            };
+
+            entries.push(IndexEntry {
+                offset,
+                size: reader.get_position() - offset,
+                klass_id: header.get_value_u32("type").unwrap_or_default(),
+                timestamp: header.get_value_u64("timestamp").unwrap_or_default(),
+            });
+        }
+
+        Ok(TraceIndex { entries })
+    }
+
+    pub fn get_entries(&self) -> &[IndexEntry] {
+        &self.entries
+    }
+
+    pub fn write(&self, writer: &mut dyn std::io::Write) -> std::io::Result<()> {
+        writer.write_all(INDEX_MAGIC)?;
+        writer.write_all(&INDEX_VERSION.to_le_bytes())?;
+        writer.write_all(&(self.entries.len() as u64).to_le_bytes())?;
+        for entry in &self.entries {
+            writer.write_all(&entry.offset.to_le_bytes())?;
+            writer.write_all(&entry.size.to_le_bytes())?;
+            writer.write_all(&entry.klass_id.to_le_bytes())?;
+            writer.write_all(&entry.timestamp.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub fn read(reader: &mut dyn std::io::Read) -> std::io::Result<TraceIndex> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != INDEX_MAGIC || read_u32(reader)? != INDEX_VERSION {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Invalid trace index header",
+            ));
+        }
+
+        let count = read_u64(reader)?;
+        let mut entries = Vec::new();
+        for _ in 0..count {
+            entries.push(IndexEntry {
+                offset: read_u64(reader)?,
+                size: read_u64(reader)?,
+                klass_id: read_u32(reader)?,
+                timestamp: read_u64(reader)?,
+            });
+        }
+
+        Ok(TraceIndex { entries })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_provider::DataProvider;
+    use hawktracer_parser_test_utilities::FakeDataReader;
+
+    fn make_index() -> TraceIndex {
+        let mut data = vec![
+            2, 0, 0, 0, // type
+            1, 0, 0, 0, 0, 0, 0, 0, // timestamp
+            1, 0, 0, 0, 0, 0, 0, 0, // id
+            100, 0, 0, 0, // info_klass_id
+            102, 111, 111, 0, // foo
+            0, // field_count
+        ];
+        data.extend_from_slice(&[100, 0, 0, 0, 5, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0]);
+        data.extend_from_slice(&[1, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0]);
+
+        let mut reader = EventReader::new(DataProvider::new(Box::new(FakeDataReader::new(
+            data, false,
+        ))));
+        TraceIndex::build(&mut reader, &mut EventKlassRegistry::new()).unwrap()
+    }
+
+    #[test]
+    fn index_should_contain_all_events() {
+        assert_eq!(
+            make_index().get_entries(),
+            &[
+                IndexEntry {
+                    offset: 0,
+                    size: 29,
+                    klass_id: 2,
+                    timestamp: 1
+                },
+                IndexEntry {
+                    offset: 29,
+                    size: 20,
+                    klass_id: 100,
+                    timestamp: 5
+                },
+                IndexEntry {
+                    offset: 49,
+                    size: 20,
+                    klass_id: 1,
+                    timestamp: 3
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn index_should_be_restored_from_serialized_data() {
+        let index = make_index();
+        let mut data = Vec::new();
+        index.write(&mut data).unwrap();
+
+        assert_eq!(TraceIndex::read(&mut &data[..]).unwrap(), index);
+        assert!(TraceIndex::read(&mut &data[1..]).is_err());
+        assert!(TraceIndex::read(&mut &data[..data.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,437 @@
+use crate::event::{ArrayLength, DataType, Event, Value};
+use crate::event_klass::EventKlass;
+use crate::registry::EventKlassRegistry;
+use crate::sink::EventSink;
+
+pub(crate) fn escape_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn write_value(
+    writer: &mut dyn std::io::Write,
+    value: &Value,
+    registry: &EventKlassRegistry,
+) -> std::io::Result<()> {
+    match value {
+        Value::Str(v) => write!(writer, "{}", escape_string(v)),
+        Value::Struct(v) => write_event(writer, v, registry),
+        // Opaque bytes are written as an array of numbers.
+        Value::Bytes(v) => {
+            let bytes: Vec<String> = v.iter().map(|byte| byte.to_string()).collect();
+            write!(writer, "[{}]", bytes.join(","))
+        }
+        Value::Array(v) => {
+            write!(writer, "[")?;
+            for (i, value) in v.iter().enumerate() {
+                if i > 0 {
+                    write!(writer, ",")?;
+                }
+                write_value(writer, value, registry)?;
+            }
+            write!(writer, "]")
+        }
+        value => write!(writer, "{}", value),
+    }
+}
+
+// Writes the event as a JSON object. The "klass" member holds the name of
+// the klass (omitted if the klass is unknown); fields are written in the
+// order of Event::get_ordered_values(), structs as nested objects.
+pub fn write_event(
+    writer: &mut dyn std::io::Write,
+    event: &Event,
+    registry: &EventKlassRegistry,
+) -> std::io::Result<()> {
+    let klass = registry.get_klass_by_id(event.get_klass_id());
+
+    write!(writer, "{{")?;
+    let mut separator = "";
+    if let Some(klass) = klass {
+        write!(writer, "\"klass\":{}", escape_string(klass.get_name()))?;
+        separator = ",";
+    }
+    for (name, value) in event.get_ordered_values(registry) {
+        write!(writer, "{}{}:", separator, escape_string(name))?;
+        write_value(writer, value, registry)?;
+        separator = ",";
+    }
+    write!(writer, "}}")
+}
+
+// Writes each event, flattened, as a JSON object in a separate line (the
+// JSON Lines format).
+pub struct JsonLinesWriter {
+    writer: Box<dyn std::io::Write>,
+}
+
+impl JsonLinesWriter {
+    pub fn new(writer: Box<dyn std::io::Write>) -> JsonLinesWriter {
+        JsonLinesWriter { writer }
+    }
+}
+
+impl EventSink for JsonLinesWriter {
+    fn write_event(&mut self, event: &Event, registry: &EventKlassRegistry) -> std::io::Result<()> {
+        write_event(&mut self.writer, &event.clone().flat_event(), registry)?;
+        writeln!(self.writer)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+// Reference to the definition of the klass; the name is escaped as a JSON
+// pointer token and percent-encoded, as the pointer is a URI fragment.
+fn get_definition_ref(klass_name: &str) -> String {
+    let mut fragment = String::from("#/definitions/");
+    for byte in klass_name.replace('~', "~0").replace('/', "~1").bytes() {
+        match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                fragment.push(byte as char)
+            }
+            byte => fragment.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    format!("{{\"$ref\":{}}}", escape_string(&fragment))
+}
+
+fn get_type_schema(data_type: DataType, type_name: &str, registry: &EventKlassRegistry) -> String {
+    let integer = |minimum: i128, maximum: i128| {
+        format!(
+            "{{\"type\":\"integer\",\"minimum\":{},\"maximum\":{}}}",
+            minimum, maximum
+        )
+    };
+    match data_type {
+        DataType::U8 => integer(0, u8::MAX.into()),
+        DataType::I8 => integer(i8::MIN.into(), i8::MAX.into()),
+        DataType::U16 => integer(0, u16::MAX.into()),
+        DataType::I16 => integer(i16::MIN.into(), i16::MAX.into()),
+        DataType::U32 => integer(0, u32::MAX.into()),
+        DataType::I32 => integer(i32::MIN.into(), i32::MAX.into()),
+        DataType::U64 => integer(0, u64::MAX.into()),
+        DataType::I64 => integer(i64::MIN.into(), i64::MAX.into()),
+        DataType::Str => "{\"type\":\"string\"}".to_owned(),
+        DataType::Struct => match registry.get_klass_by_name(type_name) {
+            Some(_) => get_definition_ref(type_name),
+            None => "{\"type\":\"object\"}".to_owned(),
+        },
+        DataType::Bytes(size) => format!(
+            "{{\"type\":\"array\",\"items\":{},\"minItems\":{},\"maxItems\":{}}}",
+            integer(0, u8::MAX.into()),
+            size,
+            size
+        ),
+        DataType::Array(element_type, ArrayLength::Fixed(count)) => format!(
+            "{{\"type\":\"array\",\"items\":{},\"minItems\":{},\"maxItems\":{}}}",
+            get_type_schema(element_type.get_data_type(), type_name, registry),
+            count,
+            count
+        ),
+        DataType::Array(element_type, ArrayLength::Prefixed) => format!(
+            "{{\"type\":\"array\",\"items\":{}}}",
+            get_type_schema(element_type.get_data_type(), type_name, registry)
+        ),
+    }
+}
+
+// Adds (name, schema) of the fields, with fields of the base klass merged
+// into the list as Event::flat_event() does, when flatten is set.
+fn add_field_schemas(
+    klass: &EventKlass,
+    registry: &EventKlassRegistry,
+    flatten: bool,
+    visited: &mut Vec<u32>,
+    schemas: &mut Vec<(String, String)>,
+) {
+    visited.push(klass.get_id());
+    let mut base_klass = None;
+    for field in klass.get_fields() {
+        if flatten && field.get_name() == "base" && *field.get_data_type() == DataType::Struct {
+            base_klass = registry.get_klass_by_name(field.get_type_name());
+            if base_klass.is_some() {
+                continue;
+            }
+        }
+        let schema = get_type_schema(*field.get_data_type(), field.get_type_name(), registry);
+        // Values of base events replace values of fields with the same name.
+        match schemas
+            .iter_mut()
+            .find(|(name, _)| name == field.get_name())
+        {
+            Some(entry) => entry.1 = schema,
+            None => schemas.push((field.get_name().to_owned(), schema)),
+        }
+    }
+    if let Some(base_klass) = base_klass {
+        if !visited.contains(&base_klass.get_id()) {
+            add_field_schemas(base_klass, registry, flatten, visited, schemas);
+        }
+    }
+}
+
+fn get_klass_schema(klass: &EventKlass, registry: &EventKlassRegistry, flatten: bool) -> String {
+    let mut schemas = Vec::new();
+    add_field_schemas(klass, registry, flatten, &mut Vec::new(), &mut schemas);
+
+    let mut properties = vec![format!(
+        "\"klass\":{{\"const\":{}}}",
+        escape_string(klass.get_name())
+    )];
+    let mut required = vec![escape_string("klass")];
+    for (name, schema) in schemas {
+        properties.push(format!("{}:{}", escape_string(&name), schema));
+        required.push(escape_string(&name));
+    }
+    format!(
+        "{{\"type\":\"object\",\"properties\":{{{}}},\"required\":[{}]}}",
+        properties.join(","),
+        required.join(",")
+    )
+}
+
+// Generates a JSON Schema (draft-07) of events written by JsonLinesWriter
+// for klasses of the registry: fields of base events are merged into the
+// event, while other struct fields are nested objects (written by
+// write_event()), described in "definitions". Note that 64-bit integers
+// exceed the range which some JSON parsers represent exactly.
+pub fn generate_schema(registry: &EventKlassRegistry) -> String {
+    let klasses = registry.get_klasses();
+    let events: Vec<String> = klasses
+        .iter()
+        .map(|klass| get_klass_schema(klass, registry, true))
+        .collect();
+    let definitions: Vec<String> = klasses
+        .iter()
+        .map(|klass| {
+            format!(
+                "{}:{}",
+                escape_string(klass.get_name()),
+                get_klass_schema(klass, registry, false)
+            )
+        })
+        .collect();
+
+    format!(
+        "{{\"$schema\":\"http://json-schema.org/draft-07/schema#\",\
+         \"title\":\"HawkTracer event\",\"oneOf\":[{}],\"definitions\":{{{}}}}}",
+        events.join(","),
+        definitions.join(",")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::IntegerType;
+    use crate::registry::CoreEventKlassId;
+    use hawktracer_parser_test_utilities::FakeDataWriter;
+
+    fn make_registry() -> EventKlassRegistry {
+        let mut registry = EventKlassRegistry::new();
+        let mut klass = EventKlass::new(100, "foo".to_owned());
+        klass.add_field("base".to_owned(), "HT_Event".to_owned(), DataType::Struct);
+        klass.add_field("name".to_owned(), "const char*".to_owned(), DataType::Str);
+        klass.add_field("value".to_owned(), "int8_t".to_owned(), DataType::I8);
+        registry.add_klass(klass);
+        registry
+    }
+
+    #[test]
+    fn event_should_be_written_as_json_object() {
+        let mut base_values = fnv::FnvHashMap::default();
+        base_values.insert("timestamp".to_owned(), Value::U64(10));
+        let mut values = fnv::FnvHashMap::default();
+        values.insert(
+            "base".to_owned(),
+            Value::Struct(Box::new(Event::new(
+                CoreEventKlassId::Base as u32,
+                base_values,
+            ))),
+        );
+        values.insert("value".to_owned(), Value::I8(-3));
+        values.insert("name".to_owned(), Value::Str("a\"b\n".to_owned()));
+        values.insert("extra".to_owned(), Value::U32(1));
+
+        let mut output = Vec::new();
+        write_event(&mut output, &Event::new(100, values), &make_registry()).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{\"klass\":\"foo\",\"base\":{\"klass\":\"HT_Event\",\"timestamp\":10},\
+             \"name\":\"a\\\"b\\n\",\"value\":-3,\"extra\":1}"
+        );
+    }
+
+    #[test]
+    fn bytes_should_be_written_as_array() {
+        let mut output = Vec::new();
+        write_value(
+            &mut output,
+            &Value::Bytes(vec![0, 7, 255]),
+            &EventKlassRegistry::new(),
+        )
+        .unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "[0,7,255]");
+    }
+
+    #[test]
+    fn arrays_should_be_written_as_arrays() {
+        let mut output = Vec::new();
+        write_value(
+            &mut output,
+            &Value::Array(vec![Value::I8(-1), Value::I8(2)]),
+            &EventKlassRegistry::new(),
+        )
+        .unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "[-1,2]");
+    }
+
+    #[test]
+    fn json_lines_writer_should_write_flat_events() {
+        let make_event = |timestamp| {
+            let mut base_values = fnv::FnvHashMap::default();
+            base_values.insert("timestamp".to_owned(), Value::U64(timestamp));
+            let mut values = fnv::FnvHashMap::default();
+            values.insert(
+                "base".to_owned(),
+                Value::Struct(Box::new(Event::new(
+                    CoreEventKlassId::Base as u32,
+                    base_values,
+                ))),
+            );
+            values.insert("value".to_owned(), Value::I8(1));
+            Event::new(100, values)
+        };
+        let buffer = FakeDataWriter::new(false);
+        let registry = make_registry();
+        let mut writer = JsonLinesWriter::new(Box::new(buffer.clone()));
+        writer.write_event(&make_event(1), &registry).unwrap();
+        writer.write_event(&make_event(2), &registry).unwrap();
+        writer.flush().unwrap();
+
+        assert_eq!(
+            String::from_utf8(buffer.get_data()).unwrap(),
+            "{\"klass\":\"foo\",\"timestamp\":1,\"value\":1}\n\
+             {\"klass\":\"foo\",\"timestamp\":2,\"value\":1}\n"
+        );
+    }
+
+    #[test]
+    fn schema_should_describe_flattened_events() {
+        let schema = generate_schema(&make_registry());
+
+        assert!(schema.starts_with("{\"$schema\":"));
+        assert!(schema.contains(
+            "{\"type\":\"object\",\"properties\":{\"klass\":{\"const\":\"foo\"},\
+             \"name\":{\"type\":\"string\"},\
+             \"value\":{\"type\":\"integer\",\"minimum\":-128,\"maximum\":127},\
+             \"type\":{\"type\":\"integer\",\"minimum\":0,\"maximum\":4294967295},\
+             \"timestamp\":{\"type\":\"integer\",\"minimum\":0,\"maximum\":18446744073709551615},\
+             \"id\":{\"type\":\"integer\",\"minimum\":0,\"maximum\":18446744073709551615}},\
+             \"required\":[\"klass\",\"name\",\"value\",\"type\",\"timestamp\",\"id\"]}"
+        ));
+        assert!(schema.contains(
+            "\"foo\":{\"type\":\"object\",\"properties\":{\"klass\":{\"const\":\"foo\"},\
+             \"base\":{\"$ref\":\"#/definitions/HT_Event\"},"
+        ));
+        assert!(schema.contains("\"HT_Event\":{"));
+    }
+
+    #[test]
+    fn struct_references_should_be_escaped() {
+        assert_eq!(
+            get_definition_ref("a/b~c \"d\""),
+            "{\"$ref\":\"#/definitions/a~1b~0c%20%22d%22\"}"
+        );
+    }
+
+    #[test]
+    fn json_lines_output_should_match_schema() {
+        let mut registry = make_registry();
+        let mut point_klass = EventKlass::new(101, "point/2d \"xy\"".to_owned());
+        point_klass.add_field("x".to_owned(), "uint8_t".to_owned(), DataType::U8);
+        registry.add_klass(point_klass);
+        let mut klass = EventKlass::new(102, "bar".to_owned());
+        klass.add_field("base".to_owned(), "HT_Event".to_owned(), DataType::Struct);
+        klass.add_field(
+            "point".to_owned(),
+            "point/2d \"xy\"".to_owned(),
+            DataType::Struct,
+        );
+        klass.add_field(
+            "values".to_owned(),
+            "uint16_t[]".to_owned(),
+            DataType::Array(IntegerType::U16, ArrayLength::Prefixed),
+        );
+        registry.add_klass(klass);
+
+        let make_base = |klass_id: u32, timestamp: u64| {
+            let mut values = fnv::FnvHashMap::default();
+            values.insert("type".to_owned(), Value::U32(klass_id));
+            values.insert("timestamp".to_owned(), Value::U64(timestamp));
+            values.insert("id".to_owned(), Value::U64(timestamp));
+            Value::Struct(Box::new(Event::new(CoreEventKlassId::Base as u32, values)))
+        };
+        let mut foo_values = fnv::FnvHashMap::default();
+        foo_values.insert("base".to_owned(), make_base(100, 1));
+        foo_values.insert("name".to_owned(), Value::Str("a".to_owned()));
+        foo_values.insert("value".to_owned(), Value::I8(-1));
+        let mut point_values = fnv::FnvHashMap::default();
+        point_values.insert("x".to_owned(), Value::U8(3));
+        let mut bar_values = fnv::FnvHashMap::default();
+        bar_values.insert("base".to_owned(), make_base(102, 2));
+        bar_values.insert(
+            "point".to_owned(),
+            Value::Struct(Box::new(Event::new(101, point_values))),
+        );
+        bar_values.insert(
+            "values".to_owned(),
+            Value::Array(vec![Value::U16(1), Value::U16(2)]),
+        );
+
+        let output = FakeDataWriter::new(false);
+        let mut writer = JsonLinesWriter::new(Box::new(output.clone()));
+        writer
+            .write_event(&Event::new(100, foo_values), &registry)
+            .unwrap();
+        writer
+            .write_event(&Event::new(102, bar_values), &registry)
+            .unwrap();
+
+        let schema: serde_json::Value = serde_json::from_str(&generate_schema(&registry)).unwrap();
+        let validator = jsonschema::validator_for(&schema).unwrap();
+        let output = String::from_utf8(output.get_data()).unwrap();
+        assert_eq!(output.lines().count(), 2);
+        for line in output.lines() {
+            let event: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert!(validator.is_valid(&event), "{}", line);
+        }
+
+        let unflattened: serde_json::Value =
+            serde_json::from_str("{\"klass\":\"bar\",\"base\":{},\"values\":[]}").unwrap();
+        assert!(!validator.is_valid(&unflattened));
+    }
+
+    #[test]
+    fn control_characters_should_be_escaped() {
+        assert_eq!(escape_string("a\u{1}\\"), "\"a\\u0001\\\\\"");
+    }
+}
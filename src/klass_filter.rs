@@ -0,0 +1,43 @@
+use crate::event_klass::EventKlass;
+
+// Allowlist of klasses the reader should decode. Events of other klasses
+// are skipped without being materialized.
+#[derive(Default, Clone)]
+pub struct KlassFilter {
+    ids: fnv::FnvHashSet<u32>,
+    names: fnv::FnvHashSet<String>,
+}
+
+impl KlassFilter {
+    pub fn new() -> KlassFilter {
+        KlassFilter::default()
+    }
+
+    pub fn allow_id(mut self, klass_id: u32) -> KlassFilter {
+        self.ids.insert(klass_id);
+        self
+    }
+
+    pub fn allow_name(mut self, klass_name: &str) -> KlassFilter {
+        self.names.insert(klass_name.to_owned());
+        self
+    }
+
+    pub fn is_allowed(&self, klass: &EventKlass) -> bool {
+        self.ids.contains(&klass.get_id()) || self.names.contains(klass.get_name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter_should_allow_klasses_by_id_and_name() {
+        let filter = KlassFilter::new().allow_id(5).allow_name("foo");
+
+        assert!(filter.is_allowed(&EventKlass::new(5, "bar".to_owned())));
+        assert!(filter.is_allowed(&EventKlass::new(6, "foo".to_owned())));
+        assert!(!filter.is_allowed(&EventKlass::new(7, "bar".to_owned())));
+    }
+}
@@ -0,0 +1,141 @@
+use crate::event::{Event, Value};
+use crate::label_table::LabelTable;
+use crate::registry::EventKlassRegistry;
+
+// Resolves numeric labels to strings using string mapping events. Mapping
+// events have to be passed to update() before the events using the labels;
+// HawkTracer emits them before the first use of a label.
+#[derive(Default)]
+pub struct LabelResolver {
+    table: LabelTable,
+    rewrite_fields: Vec<String>,
+}
+
+impl LabelResolver {
+    pub fn new() -> LabelResolver {
+        LabelResolver::default()
+    }
+
+    // Fields (of the event itself, not of its base) which process() replaces
+    // with resolved strings. Note the klass in the registry still describes
+    // them as integers.
+    pub fn with_rewrite_fields(mut self, field_names: &[&str]) -> LabelResolver {
+        self.rewrite_fields = field_names.iter().map(|name| name.to_string()).collect();
+        self
+    }
+
+    // Returns true if the event was a string mapping event.
+    pub fn update(&mut self, event: &Event, registry: &EventKlassRegistry) -> bool {
+        self.table.update(event, registry)
+    }
+
+    pub fn resolve(&self, label_id: u64) -> Option<&str> {
+        self.table.get_label(label_id).map(|label| label.as_str())
+    }
+
+    pub fn get_label_count(&self) -> usize {
+        self.table.get_label_count()
+    }
+
+    // Labels seen so far, with the number of their uses.
+    pub fn get_table(&self) -> &LabelTable {
+        &self.table
+    }
+
+    // Updates the mapping and rewrites the configured fields of the event.
+    // Fields with unknown labels are left unchanged.
+    pub fn process(&mut self, event: &mut Event, registry: &EventKlassRegistry) {
+        if self.update(event, registry) {
+            return;
+        }
+
+        for field_name in &self.rewrite_fields {
+            let label_id = match event.get_raw_value(field_name).and_then(Value::as_u64) {
+                Some(id) => id,
+                None => continue,
+            };
+            if let Some(label) = self.table.get_label(label_id) {
+                event.set_value(field_name, Value::Str(label.clone()));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::DataType;
+    use crate::event_klass::EventKlass;
+    use crate::label_table::{LabelEntry, STRING_MAPPING_KLASS_NAME};
+    use crate::test_events::make_plain_event;
+
+    fn make_registry() -> EventKlassRegistry {
+        let mut registry = EventKlassRegistry::new();
+        let mut klass = EventKlass::new(10, STRING_MAPPING_KLASS_NAME.to_owned());
+        klass.add_field(
+            "identifier".to_owned(),
+            "uint64_t".to_owned(),
+            DataType::U64,
+        );
+        klass.add_field("label".to_owned(), "const char*".to_owned(), DataType::Str);
+        registry.add_klass(klass);
+        registry
+    }
+
+    fn mapping(id: u64, label: &str) -> Event {
+        make_plain_event(
+            10,
+            vec![
+                ("identifier", Value::U64(id)),
+                ("label", Value::Str(label.to_owned())),
+            ],
+        )
+    }
+
+    #[test]
+    fn mapping_events_should_define_labels() {
+        let registry = make_registry();
+        let mut resolver = LabelResolver::new();
+
+        assert!(resolver.update(&mapping(5, "render"), &registry));
+        assert!(!resolver.update(
+            &make_plain_event(11, vec![("label", Value::U64(5))]),
+            &registry
+        ));
+
+        assert_eq!(resolver.resolve(5), Some("render"));
+        assert_eq!(resolver.resolve(6), None);
+        assert_eq!(resolver.get_label_count(), 1);
+    }
+
+    #[test]
+    fn process_should_rewrite_only_resolved_fields() {
+        let registry = make_registry();
+        let mut resolver = LabelResolver::new().with_rewrite_fields(&["label", "parent"]);
+        let mut mapping_event = mapping(5, "render");
+        resolver.process(&mut mapping_event, &registry);
+        assert_eq!(mapping_event, mapping(5, "render"));
+
+        let mut event = make_plain_event(
+            11,
+            vec![
+                ("label", Value::U32(5)),
+                ("parent", Value::U64(7)),
+                ("other", Value::U64(5)),
+            ],
+        );
+        resolver.process(&mut event, &registry);
+
+        assert_eq!(event.get_value_string("label").unwrap(), "render");
+        assert_eq!(event.get_value_u64("parent").unwrap(), 7);
+        assert_eq!(event.get_value_u64("other").unwrap(), 5);
+        assert_eq!(
+            resolver.get_table().get_entries()[0],
+            &LabelEntry {
+                id: 5,
+                label: Some("render".to_owned()),
+                count: 1
+            }
+        );
+    }
+}
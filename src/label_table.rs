@@ -0,0 +1,207 @@
+use crate::event::{Event, Value};
+use crate::registry::EventKlassRegistry;
+use std::hash::Hasher;
+
+pub use crate::well_known::STRING_MAPPING_KLASS_NAME;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct LabelEntry {
+    pub id: u64,
+    pub label: Option<String>,
+    pub count: u64,
+}
+
+// Deduplicated table of labels used in a trace. Numeric labels are resolved
+// through string mapping events; string labels are identified by the hash of
+// their content, so the same string always gets the same id.
+#[derive(Default)]
+pub struct LabelTable {
+    entries: std::collections::HashMap<u64, LabelEntry, fnv::FnvBuildHasher>,
+}
+
+impl LabelTable {
+    pub fn new() -> LabelTable {
+        LabelTable::default()
+    }
+
+    pub fn content_id(label: &str) -> u64 {
+        let mut hasher = fnv::FnvHasher::default();
+        hasher.write(label.as_bytes());
+        hasher.finish()
+    }
+
+    // Returns true if the event was a string mapping event.
+    pub fn update(&mut self, event: &Event, registry: &EventKlassRegistry) -> bool {
+        let is_mapping = registry
+            .get_klass_by_id(event.get_klass_id())
+            .is_some_and(|klass| klass.get_name() == STRING_MAPPING_KLASS_NAME);
+
+        if is_mapping {
+            if let (Ok(id), Ok(label)) = (
+                event.get_value_as_u64("identifier"),
+                event.get_value_string("label"),
+            ) {
+                self.get_entry(id).label = Some(label.clone());
+            }
+            return true;
+        }
+
+        match event.get_raw_value("label") {
+            Some(Value::Str(label)) => {
+                let entry = self.get_entry(LabelTable::content_id(label));
+                entry.label.get_or_insert_with(|| label.clone());
+                entry.count += 1;
+            }
+            Some(value) => {
+                if let Some(id) = value.as_u64() {
+                    self.get_entry(id).count += 1;
+                }
+            }
+            None => {}
+        }
+        false
+    }
+
+    pub fn get_label(&self, id: u64) -> Option<&String> {
+        self.entries.get(&id).and_then(|entry| entry.label.as_ref())
+    }
+
+    // Number of labels with a known string.
+    pub fn get_label_count(&self) -> usize {
+        self.entries
+            .values()
+            .filter(|entry| entry.label.is_some())
+            .count()
+    }
+
+    pub fn get_entries(&self) -> Vec<&LabelEntry> {
+        let mut entries: Vec<&LabelEntry> = self.entries.values().collect();
+        entries.sort_by_key(|entry| entry.id);
+        entries
+    }
+
+    pub fn write_csv(&self, writer: &mut dyn std::io::Write) -> std::io::Result<()> {
+        writeln!(writer, "id,label,count")?;
+        for entry in self.get_entries() {
+            let label = entry.label.as_deref().unwrap_or("");
+            if label.contains([',', '"', '\n']) {
+                writeln!(
+                    writer,
+                    "{},\"{}\",{}",
+                    entry.id,
+                    label.replace('"', "\"\""),
+                    entry.count
+                )?;
+            } else {
+                writeln!(writer, "{},{},{}", entry.id, label, entry.count)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn get_entry(&mut self, id: u64) -> &mut LabelEntry {
+        self.entries.entry(id).or_insert(LabelEntry {
+            id,
+            label: None,
+            count: 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::DataType;
+    use crate::event_klass::EventKlass;
+    use crate::test_events::make_plain_event;
+
+    fn make_registry() -> EventKlassRegistry {
+        let mut registry = EventKlassRegistry::new();
+        let mut klass = EventKlass::new(10, STRING_MAPPING_KLASS_NAME.to_owned());
+        klass.add_field(
+            "identifier".to_owned(),
+            "uint64_t".to_owned(),
+            DataType::U64,
+        );
+        klass.add_field("label".to_owned(), "const char*".to_owned(), DataType::Str);
+        registry.add_klass(klass);
+        registry
+    }
+
+    #[test]
+    fn numeric_labels_should_be_resolved_and_counted() {
+        let registry = make_registry();
+        let mut table = LabelTable::new();
+        table.update(
+            &make_plain_event(11, vec![("label", Value::U32(5))]),
+            &registry,
+        );
+        table.update(
+            &make_plain_event(
+                10,
+                vec![
+                    ("identifier", Value::U64(5)),
+                    ("label", Value::Str("render".to_owned())),
+                ],
+            ),
+            &registry,
+        );
+        table.update(
+            &make_plain_event(11, vec![("label", Value::U64(5))]),
+            &registry,
+        );
+
+        assert_eq!(
+            table.get_entries(),
+            vec![&LabelEntry {
+                id: 5,
+                label: Some("render".to_owned()),
+                count: 2
+            }]
+        );
+    }
+
+    #[test]
+    fn string_labels_should_be_deduplicated_by_content() {
+        let registry = make_registry();
+        let mut table = LabelTable::new();
+        for label in &["a", "b", "a"] {
+            table.update(
+                &make_plain_event(12, vec![("label", Value::Str(label.to_string()))]),
+                &registry,
+            );
+        }
+
+        assert_eq!(table.get_entries().len(), 2);
+        let id = LabelTable::content_id("a");
+        assert_eq!(table.get_label(id).unwrap(), "a");
+        assert_eq!(table.entries[&id].count, 2);
+    }
+
+    #[test]
+    fn write_csv_should_escape_labels() {
+        let registry = make_registry();
+        let mut table = LabelTable::new();
+        table.update(
+            &make_plain_event(
+                10,
+                vec![
+                    ("identifier", Value::U64(1)),
+                    ("label", Value::Str("a,\"b\"".to_owned())),
+                ],
+            ),
+            &registry,
+        );
+        table.update(
+            &make_plain_event(11, vec![("label", Value::U64(2))]),
+            &registry,
+        );
+
+        let mut output = Vec::new();
+        table.write_csv(&mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "id,label,count\n1,\"a,\"\"b\"\"\",0\n2,,1\n"
+        );
+    }
+}
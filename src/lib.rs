@@ -1,15 +1,103 @@
+#[macro_use]
+mod diagnostics;
+
 pub mod registry;
 pub use crate::registry::CoreEventKlassId;
 pub use crate::registry::EventKlassRegistry;
 pub mod event_reader;
+pub use crate::data_struct_reader::Endianness;
 pub use crate::data_struct_reader::ReadEventError;
 pub use crate::event_reader::EventReader;
+pub mod event_reader_builder;
+pub use crate::event_reader_builder::EventReaderBuilder;
 pub mod event;
 pub use crate::event::DataType;
 pub use crate::event::Event;
 pub use crate::event::Value;
+pub mod event_diff;
+pub mod event_writer;
+pub use crate::event_writer::EventWriter;
+pub mod analysis;
+#[cfg(feature = "async")]
+pub mod async_sink;
+pub mod benchmark;
+pub mod checkpoint;
+pub use crate::checkpoint::Checkpoint;
+pub mod codegen;
+pub mod csv_writer;
+pub use crate::csv_writer::CsvWriter;
 pub mod data_provider;
+pub mod demux;
+pub use crate::demux::Demux;
+pub mod external_sort;
+pub mod field_projection;
+pub use crate::field_projection::FieldProjection;
+pub mod filter_expression;
+pub use crate::filter_expression::FilterExpression;
+pub mod fuzz_dictionary;
+pub mod gap_detector;
+pub use crate::gap_detector::GapDetector;
+pub mod id_remap;
+pub use crate::id_remap::IdRemapTable;
+pub mod index;
+pub use crate::index::TraceIndex;
+pub mod json;
+pub use crate::json::JsonLinesWriter;
+pub mod label_resolver;
+pub use crate::label_resolver::LabelResolver;
+pub mod label_table;
+pub mod merged_reader;
+pub use crate::merged_reader::MergedReader;
+#[cfg(feature = "msgpack")]
+pub mod msgpack;
+#[cfg(feature = "parallel")]
+pub mod parallel;
+pub mod parser;
+pub use crate::parser::Parser;
+pub mod pipeline;
+pub use crate::pipeline::{process, Pipeline, PipelineConfig, RunReport, RunStatus, StepStatus};
+pub mod pipelined_reader;
+pub mod progress;
+pub use crate::progress::{Progress, ProgressReporter};
+pub mod quota;
+pub use crate::quota::Quota;
+pub mod raw_event;
+pub use crate::raw_event::RawEvent;
+pub mod reorder_buffer;
+pub use crate::reorder_buffer::ReorderWindow;
+pub mod resource_usage;
+pub mod rotated_files_reader;
+pub mod sampling;
+pub mod shared_registry;
+pub use crate::shared_registry::SharedEventKlassRegistry;
+pub mod sink;
+pub use crate::sink::EventSink;
+pub mod spans;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_writer;
+pub mod string_cardinality;
+pub mod thread_splitter;
+pub use crate::thread_splitter::ThreadSplitter;
+pub mod transcoder;
+pub use crate::transcoder::Transcoder;
+#[cfg(feature = "unstable")]
+pub mod version_converter;
+#[cfg(feature = "unstable")]
+pub use crate::version_converter::VersionConverter;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "wasm")]
+pub use crate::wasm::WasmParser;
+pub mod well_known;
+#[cfg(feature = "zstd")]
+pub mod zstd_seekable;
+
 pub mod event_klass;
+pub mod klass_filter;
+pub use crate::klass_filter::KlassFilter;
 
 mod data_struct_reader;
 mod registry_updater;
+#[cfg(test)]
+mod test_events;
+pub use crate::registry_updater::RegistryUpdateError;
@@ -0,0 +1,146 @@
+use crate::data_provider::DataError;
+use crate::data_struct_reader::ReadEventError;
+use crate::event::Event;
+use crate::event_reader::EventReader;
+use crate::registry::EventKlassRegistry;
+
+struct Source {
+    reader: EventReader,
+    registry: EventKlassRegistry,
+    next_event: Option<Event>,
+}
+
+// Merges events from multiple streams (e.g. one per process) into a single
+// sequence ordered by timestamp. Each stream has its own registry. Events
+// of a single stream are expected to be ordered.
+pub struct MergedReader {
+    sources: Vec<Source>,
+    queue: std::collections::BinaryHeap<std::cmp::Reverse<(u64, usize)>>,
+    // Sources which next event has to be read before the queue is used.
+    // A source which fails is not read again.
+    pending_sources: Vec<usize>,
+}
+
+impl MergedReader {
+    pub fn new(readers: Vec<EventReader>) -> MergedReader {
+        MergedReader {
+            pending_sources: (0..readers.len()).collect(),
+            sources: readers
+                .into_iter()
+                .map(|reader| Source {
+                    reader,
+                    registry: EventKlassRegistry::new(),
+                    next_event: None,
+                })
+                .collect(),
+            queue: std::collections::BinaryHeap::new(),
+        }
+    }
+
+    pub fn get_source_count(&self) -> usize {
+        self.sources.len()
+    }
+
+    pub fn get_registry(&self, source: usize) -> Option<&EventKlassRegistry> {
+        self.sources.get(source).map(|source| &source.registry)
+    }
+
+    // Returns the index of the source and the event. EndOfStream is
+    // reported once all the sources are exhausted.
+    pub fn read_event(&mut self) -> Result<(usize, Event), ReadEventError> {
+        while let Some(source) = self.pending_sources.pop() {
+            self.fetch_event(source)?;
+        }
+
+        let source = match self.queue.pop() {
+            Some(std::cmp::Reverse((_, source))) => source,
+            None => return Err(ReadEventError::DataError(DataError::EndOfStream)),
+        };
+        self.pending_sources.push(source);
+
+        match self.sources[source].next_event.take() {
+            Some(event) => Ok((source, event)),
+            None => Err(ReadEventError::DataError(DataError::EndOfStream)),
+        }
+    }
+
+    fn fetch_event(&mut self, index: usize) -> Result<(), ReadEventError> {
+        let source = &mut self.sources[index];
+        match source.reader.read_event(&mut source.registry) {
+            Ok(event) => {
+                self.queue.push(std::cmp::Reverse((
+                    event.get_timestamp().unwrap_or_default(),
+                    index,
+                )));
+                source.next_event = Some(event);
+                Ok(())
+            }
+            Err(ReadEventError::DataError(DataError::EndOfStream)) => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_provider::DataProvider;
+    use hawktracer_parser_test_utilities::FakeDataReader;
+
+    fn make_reader(timestamps: &[u8]) -> EventReader {
+        let mut data = Vec::new();
+        for timestamp in timestamps {
+            data.extend_from_slice(&[1, 0, 0, 0, *timestamp, 0, 0, 0, 0, 0, 0, 0]);
+            data.extend_from_slice(&[0; 8]);
+        }
+        EventReader::new(DataProvider::new(Box::new(FakeDataReader::new(
+            data, false,
+        ))))
+    }
+
+    #[test]
+    fn merged_reader_should_order_events_by_timestamp() {
+        let mut reader = MergedReader::new(vec![
+            make_reader(&[1, 4, 9]),
+            make_reader(&[]),
+            make_reader(&[2, 3, 10]),
+        ]);
+        assert_eq!(reader.get_source_count(), 3);
+        assert!(reader.get_registry(2).is_some());
+
+        let mut events = Vec::new();
+        loop {
+            match reader.read_event() {
+                Ok((source, event)) => {
+                    events.push((source, event.get_value_u64("timestamp").unwrap()))
+                }
+                Err(err) => {
+                    assert_eq!(err, ReadEventError::DataError(DataError::EndOfStream));
+                    break;
+                }
+            }
+        }
+
+        assert_eq!(
+            events,
+            vec![(0, 1), (2, 2), (2, 3), (0, 4), (0, 9), (2, 10)]
+        );
+    }
+
+    #[test]
+    fn merged_reader_should_report_source_errors() {
+        let mut data = vec![1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        data.extend_from_slice(&[99, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let failing_reader = EventReader::new(DataProvider::new(Box::new(FakeDataReader::new(
+            data, false,
+        ))));
+        let mut reader = MergedReader::new(vec![failing_reader, make_reader(&[3])]);
+
+        assert_eq!(reader.read_event().unwrap().0, 0);
+        assert_eq!(
+            reader.read_event().unwrap_err(),
+            ReadEventError::UnknownKlassId(99)
+        );
+        assert_eq!(reader.read_event().unwrap().0, 1);
+    }
+}
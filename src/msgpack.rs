@@ -0,0 +1,215 @@
+use crate::event::{Event, Value};
+use crate::registry::EventKlassRegistry;
+use crate::sink::EventSink;
+
+fn write_string(writer: &mut dyn std::io::Write, value: &str) -> std::io::Result<()> {
+    let length = value.len();
+    if length < 32 {
+        writer.write_all(&[0xa0 | length as u8])?;
+    } else if length <= u8::MAX as usize {
+        writer.write_all(&[0xd9, length as u8])?;
+    } else if length <= u16::MAX as usize {
+        writer.write_all(&[0xda])?;
+        writer.write_all(&(length as u16).to_be_bytes())?;
+    } else {
+        writer.write_all(&[0xdb])?;
+        writer.write_all(&(length as u32).to_be_bytes())?;
+    }
+    writer.write_all(value.as_bytes())
+}
+
+fn write_binary(writer: &mut dyn std::io::Write, value: &[u8]) -> std::io::Result<()> {
+    let length = value.len();
+    if length <= u8::MAX as usize {
+        writer.write_all(&[0xc4, length as u8])?;
+    } else if length <= u16::MAX as usize {
+        writer.write_all(&[0xc5])?;
+        writer.write_all(&(length as u16).to_be_bytes())?;
+    } else {
+        writer.write_all(&[0xc6])?;
+        writer.write_all(&(length as u32).to_be_bytes())?;
+    }
+    writer.write_all(value)
+}
+
+fn write_array_length(writer: &mut dyn std::io::Write, length: usize) -> std::io::Result<()> {
+    if length < 16 {
+        writer.write_all(&[0x90 | length as u8])
+    } else if length <= u16::MAX as usize {
+        writer.write_all(&[0xdc])?;
+        writer.write_all(&(length as u16).to_be_bytes())
+    } else {
+        writer.write_all(&[0xdd])?;
+        writer.write_all(&(length as u32).to_be_bytes())
+    }
+}
+
+fn write_map_length(writer: &mut dyn std::io::Write, length: usize) -> std::io::Result<()> {
+    if length < 16 {
+        writer.write_all(&[0x80 | length as u8])
+    } else if length <= u16::MAX as usize {
+        writer.write_all(&[0xde])?;
+        writer.write_all(&(length as u16).to_be_bytes())
+    } else {
+        writer.write_all(&[0xdf])?;
+        writer.write_all(&(length as u32).to_be_bytes())
+    }
+}
+
+fn write_unsigned(writer: &mut dyn std::io::Write, value: u64) -> std::io::Result<()> {
+    if value < 0x80 {
+        writer.write_all(&[value as u8])
+    } else if value <= u64::from(u8::MAX) {
+        writer.write_all(&[0xcc, value as u8])
+    } else if value <= u64::from(u16::MAX) {
+        writer.write_all(&[0xcd])?;
+        writer.write_all(&(value as u16).to_be_bytes())
+    } else if value <= u64::from(u32::MAX) {
+        writer.write_all(&[0xce])?;
+        writer.write_all(&(value as u32).to_be_bytes())
+    } else {
+        writer.write_all(&[0xcf])?;
+        writer.write_all(&value.to_be_bytes())
+    }
+}
+
+fn write_signed(writer: &mut dyn std::io::Write, value: i64) -> std::io::Result<()> {
+    if value >= 0 {
+        write_unsigned(writer, value as u64)
+    } else if value >= -32 {
+        writer.write_all(&[value as u8])
+    } else if value >= i64::from(i8::MIN) {
+        writer.write_all(&[0xd0, value as u8])
+    } else if value >= i64::from(i16::MIN) {
+        writer.write_all(&[0xd1])?;
+        writer.write_all(&(value as i16).to_be_bytes())
+    } else if value >= i64::from(i32::MIN) {
+        writer.write_all(&[0xd2])?;
+        writer.write_all(&(value as i32).to_be_bytes())
+    } else {
+        writer.write_all(&[0xd3])?;
+        writer.write_all(&value.to_be_bytes())
+    }
+}
+
+// Writes the event as a MessagePack map, with the same structure as the JSON
+// encoding (see json::write_event()). Integers use the smallest encoding
+// which can represent the value.
+pub fn write_event(
+    writer: &mut dyn std::io::Write,
+    event: &Event,
+    registry: &EventKlassRegistry,
+) -> std::io::Result<()> {
+    let klass = registry.get_klass_by_id(event.get_klass_id());
+    let values = event.get_ordered_values(registry);
+    let length = values.len() + klass.map_or(0, |_| 1);
+    write_map_length(writer, length)?;
+
+    if let Some(klass) = klass {
+        write_string(writer, "klass")?;
+        write_string(writer, klass.get_name())?;
+    }
+    for (name, value) in values {
+        write_string(writer, name)?;
+        write_value(writer, value, registry)?;
+    }
+    Ok(())
+}
+
+fn write_value(
+    writer: &mut dyn std::io::Write,
+    value: &Value,
+    registry: &EventKlassRegistry,
+) -> std::io::Result<()> {
+    match value {
+        Value::Str(value) => write_string(writer, value),
+        Value::Struct(value) => write_event(writer, value, registry),
+        Value::Bytes(value) => write_binary(writer, value),
+        Value::Array(values) => {
+            write_array_length(writer, values.len())?;
+            for value in values {
+                write_value(writer, value, registry)?;
+            }
+            Ok(())
+        }
+        value => match value.as_i64() {
+            Some(value) => write_signed(writer, value),
+            None => write_unsigned(writer, value.as_u64().unwrap_or_default()),
+        },
+    }
+}
+
+// Writes flattened events as a stream of MessagePack maps.
+pub struct MessagePackWriter {
+    writer: Box<dyn std::io::Write>,
+}
+
+impl MessagePackWriter {
+    pub fn new(writer: Box<dyn std::io::Write>) -> MessagePackWriter {
+        MessagePackWriter { writer }
+    }
+}
+
+impl EventSink for MessagePackWriter {
+    fn write_event(&mut self, event: &Event, registry: &EventKlassRegistry) -> std::io::Result<()> {
+        write_event(&mut self.writer, &event.clone().flat_event(), registry)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::DataType;
+    use crate::event_klass::EventKlass;
+
+    fn encode<F: FnOnce(&mut Vec<u8>) -> std::io::Result<()>>(write: F) -> Vec<u8> {
+        let mut output = Vec::new();
+        write(&mut output).unwrap();
+        output
+    }
+
+    #[test]
+    fn integers_should_use_smallest_encoding() {
+        assert_eq!(encode(|w| write_unsigned(w, 5)), vec![5]);
+        assert_eq!(encode(|w| write_unsigned(w, 200)), vec![0xcc, 200]);
+        assert_eq!(encode(|w| write_unsigned(w, 300)), vec![0xcd, 1, 44]);
+        assert_eq!(
+            encode(|w| write_unsigned(w, u64::MAX)),
+            vec![0xcf, 255, 255, 255, 255, 255, 255, 255, 255]
+        );
+        assert_eq!(encode(|w| write_signed(w, -1)), vec![0xff]);
+        assert_eq!(encode(|w| write_signed(w, -33)), vec![0xd0, 0xdf]);
+        assert_eq!(encode(|w| write_signed(w, -200)), vec![0xd1, 0xff, 0x38]);
+    }
+
+    #[test]
+    fn strings_should_have_length_prefix() {
+        assert_eq!(encode(|w| write_string(w, "ab")), vec![0xa2, b'a', b'b']);
+        let long = "x".repeat(40);
+        assert_eq!(encode(|w| write_string(w, &long))[..2], [0xd9, 40]);
+        let longer = "x".repeat(300);
+        assert_eq!(encode(|w| write_string(w, &longer))[..3], [0xda, 1, 44]);
+    }
+
+    #[test]
+    fn event_should_be_encoded_as_map() {
+        let mut registry = EventKlassRegistry::new();
+        let mut klass = EventKlass::new(100, "foo".to_owned());
+        klass.add_field("value".to_owned(), "uint64_t".to_owned(), DataType::U64);
+        registry.add_klass(klass);
+        let mut values = fnv::FnvHashMap::default();
+        values.insert("value".to_owned(), Value::U64(u64::MAX));
+        let event = Event::new(100, values);
+
+        let mut expected = vec![0x82, 0xa5];
+        expected.extend_from_slice(b"klass");
+        expected.extend_from_slice(&[0xa3, b'f', b'o', b'o', 0xa5]);
+        expected.extend_from_slice(b"value");
+        expected.extend_from_slice(&[0xcf, 255, 255, 255, 255, 255, 255, 255, 255]);
+        assert_eq!(encode(|w| write_event(w, &event, &registry)), expected);
+    }
+}
@@ -0,0 +1,115 @@
+use crate::data_provider::DataProvider;
+use crate::data_struct_reader::{Endianness, ReadEventError};
+use crate::event::Event;
+use crate::event_reader::EventReader;
+use crate::index::{IndexEntry, TraceIndex};
+use crate::registry::{CoreEventKlassId, EventKlassRegistry};
+use rayon::prelude::*;
+
+fn make_reader(data: Box<dyn std::io::Read>, endianness: Endianness) -> EventReader {
+    let mut reader = EventReader::new(DataProvider::new(data));
+    reader.set_endianness(endianness);
+    reader
+}
+
+fn decode_entries(
+    data: &[u8],
+    entries: &[IndexEntry],
+    endianness: Endianness,
+    registry: &EventKlassRegistry,
+) -> Result<Vec<Event>, ReadEventError> {
+    let mut bytes = Vec::with_capacity(entries.iter().map(|entry| entry.size as usize).sum());
+    for entry in entries {
+        let offset = entry.offset as usize;
+        bytes.extend_from_slice(&data[offset..offset + entry.size as usize]);
+    }
+
+    let mut reader = make_reader(Box::new(std::io::Cursor::new(bytes)), endianness);
+    entries
+        .iter()
+        .map(|_| reader.read_data_event(registry))
+        .collect()
+}
+
+// Decodes all the events of a trace loaded into memory (e.g. with
+// std::fs::read()) on multiple threads. Metadata events are applied to the
+// registry in a sequential pass first, and are not included in the result.
+// Events are returned in timestamp order.
+pub fn decode_parallel(
+    data: std::sync::Arc<[u8]>,
+    endianness: Endianness,
+    registry: &mut EventKlassRegistry,
+) -> Result<Vec<Event>, ReadEventError> {
+    let mut reader = make_reader(Box::new(std::io::Cursor::new(data.clone())), endianness);
+    let mut entries: Vec<IndexEntry> = TraceIndex::build(&mut reader, registry)?
+        .get_entries()
+        .iter()
+        .filter(|entry| !CoreEventKlassId::is_metadata_klass(entry.klass_id))
+        .cloned()
+        .collect();
+    entries.sort_by_key(|entry| entry.timestamp);
+
+    let registry: &EventKlassRegistry = registry;
+    let chunk_size = std::cmp::max(1, entries.len() / (rayon::current_num_threads() * 4));
+    let chunks: Vec<Vec<Event>> = entries
+        .par_chunks(chunk_size)
+        .map(|chunk| decode_entries(&data, chunk, endianness, registry))
+        .collect::<Result<_, _>>()?;
+
+    Ok(chunks.into_iter().flatten().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hawktracer_parser_test_utilities::stream::{encode_header, TestStreamBuilder, TestValue};
+
+    fn make_trace() -> Vec<u8> {
+        let mut builder = TestStreamBuilder::new().klass(
+            100,
+            "foo",
+            &[("HT_Event", "base"), ("const char*", "name")],
+        );
+        for i in 0..100u64 {
+            // Timestamps are intentionally out of order.
+            let name = TestValue::Str(format!("event-{}", i));
+            builder = builder.event(100, (i * 37) % 100, &[name]);
+        }
+        builder.build()
+    }
+
+    #[test]
+    fn parallel_decoding_should_return_events_in_timestamp_order() {
+        let mut registry = EventKlassRegistry::new();
+        let events =
+            decode_parallel(make_trace().into(), Endianness::Native, &mut registry).unwrap();
+
+        assert_eq!(events.len(), 100);
+        assert!(registry.get_klass_by_name("foo").is_some());
+        for (timestamp, event) in events.iter().enumerate() {
+            let base = event.get_value_struct("base").unwrap();
+            assert_eq!(base.get_value_u64("timestamp").unwrap(), timestamp as u64);
+            let i = base.get_value_u64("id").unwrap();
+            assert_eq!(
+                event.get_value_string("name").unwrap(),
+                &format!("event-{}", i)
+            );
+        }
+    }
+
+    #[test]
+    fn parallel_decoding_should_fail_for_unknown_klass() {
+        let mut data = make_trace();
+        data.extend(encode_header(999, 0, 0));
+
+        assert_eq!(
+            decode_parallel(
+                data.into(),
+                Endianness::Native,
+                &mut EventKlassRegistry::new()
+            )
+            .unwrap_err(),
+            ReadEventError::UnknownKlassId(999)
+        );
+    }
+}
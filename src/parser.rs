@@ -0,0 +1,281 @@
+use crate::data_provider::{DataError, DataProvider};
+use crate::data_struct_reader::ReadEventError;
+use crate::event::Event;
+use crate::event_reader::EventReader;
+use crate::quota::{Quota, QuotaMetrics, QuotaTracker, QuotaViolation};
+use crate::registry::EventKlassRegistry;
+use crate::registry_updater::RegistryUpdateError;
+use crate::resource_usage::ResourceUsage;
+
+// Pending bytes are shared with the reader decoding them instead of being
+// copied for every chunk.
+struct PendingBytes(std::sync::Arc<Vec<u8>>);
+
+impl AsRef<[u8]> for PendingBytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+// Push-based parser: bytes are fed in arbitrary chunks and only fully
+// received events are returned. Incomplete events stay buffered until
+// the rest of their bytes arrive.
+#[derive(Default)]
+pub struct Parser {
+    registry: EventKlassRegistry,
+    pending: std::sync::Arc<Vec<u8>>,
+    // Error which occurred after some events of the chunk were decoded.
+    error: Option<ReadEventError>,
+    quota: Option<QuotaTracker>,
+}
+
+impl Parser {
+    pub fn new() -> Parser {
+        Parser::with_registry(EventKlassRegistry::new())
+    }
+
+    pub fn with_registry(registry: EventKlassRegistry) -> Parser {
+        Parser {
+            registry,
+            pending: std::sync::Arc::default(),
+            error: None,
+            quota: None,
+        }
+    }
+
+    // The klass count limit is enforced by the registry, so metadata events
+    // exceeding it fail as any other error of the chunk.
+    pub fn with_quota(mut self, quota: Quota) -> Parser {
+        self.registry.set_max_klass_count(quota.max_klass_count);
+        self.quota = Some(QuotaTracker::new(quota));
+        self
+    }
+
+    pub fn get_quota_metrics(&self) -> Option<&QuotaMetrics> {
+        self.quota.as_ref().map(|quota| quota.get_metrics())
+    }
+
+    pub fn get_registry(&self) -> &EventKlassRegistry {
+        &self.registry
+    }
+
+    pub fn get_pending_size(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn get_resource_usage(&self) -> ResourceUsage {
+        let mut usage = ResourceUsage::from_registry(&self.registry);
+        usage.pending_bytes = self.pending.capacity();
+        usage
+    }
+
+    // Drops the buffered bytes (and a pending error), e.g. before feeding a
+    // stream from the beginning of an event after a reconnection. Klasses
+    // stay registered.
+    pub fn reset(&mut self) {
+        self.pending = std::sync::Arc::default();
+        self.error = None;
+    }
+
+    // If decoding fails after some events of the chunk were already decoded,
+    // those events are returned and the error is reported by the next call
+    // (feeding an empty slice is enough to get it). The boundary of the next
+    // event isn't known after an error, so the buffered bytes are dropped;
+    // next chunks are decoded as if they started a new event.
+    // If the quota limits buffered events, the rest of the chunk stays
+    // buffered and is decoded by the next call.
+    pub fn feed(&mut self, data: &[u8]) -> Result<Vec<Event>, ReadEventError> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        if let Some(quota) = &mut self.quota {
+            quota
+                .check(data.len(), self.pending.len(), std::time::Instant::now())
+                .map_err(ReadEventError::QuotaExceeded)?;
+        }
+        std::sync::Arc::make_mut(&mut self.pending).extend_from_slice(data);
+
+        let max_event_count = self
+            .quota
+            .as_ref()
+            .and_then(|quota| quota.get_quota().max_buffered_events)
+            .unwrap_or(usize::MAX);
+        let mut events = Vec::new();
+        let mut consumed = 0;
+        let mut reader = EventReader::new(DataProvider::new(Box::new(std::io::Cursor::new(
+            PendingBytes(self.pending.clone()),
+        ))));
+
+        let error = loop {
+            if events.len() >= max_event_count {
+                break None;
+            }
+            match reader.read_event(&mut self.registry) {
+                Ok(event) => {
+                    consumed = reader.get_position() as usize;
+                    events.push(event);
+                }
+                Err(ReadEventError::DataError(DataError::EndOfStream)) => break None,
+                Err(ReadEventError::RegistryUpdateFailed(
+                    RegistryUpdateError::TooManyKlasses { limit },
+                )) => {
+                    if let Some(quota) = &mut self.quota {
+                        quota.add_violation();
+                    }
+                    break Some(ReadEventError::QuotaExceeded(QuotaViolation::KlassCount(
+                        limit,
+                    )));
+                }
+                Err(err) => break Some(err),
+            }
+        };
+
+        drop(reader);
+
+        let pending = std::sync::Arc::make_mut(&mut self.pending);
+        match error {
+            Some(err) => {
+                pending.clear();
+                if events.is_empty() {
+                    return Err(err);
+                }
+                self.error = Some(err);
+            }
+            None => {
+                pending.drain(..consumed);
+            }
+        }
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hawktracer_parser_test_utilities::stream::{encode_header, TestStreamBuilder, TestValue};
+
+    fn make_stream() -> Vec<u8> {
+        TestStreamBuilder::new()
+            .klass(100, "foo", &[("uint32_t", "value")])
+            .event(100, 3, &[TestValue::U32(301)])
+            .build()
+    }
+
+    #[test]
+    fn feed_whole_stream_should_return_all_events() {
+        let mut parser = Parser::new();
+        let events = parser.feed(&make_stream()).unwrap();
+
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[2].get_klass_id(), 100);
+        assert_eq!(events[2].get_value_u32("value").unwrap(), 301);
+        assert_eq!(parser.get_pending_size(), 0);
+        assert!(parser.get_registry().get_klass_by_name("foo").is_some());
+    }
+
+    #[test]
+    fn feed_byte_by_byte_should_buffer_partial_events() {
+        let mut parser = Parser::new();
+        let mut events = Vec::new();
+        for byte in make_stream() {
+            events.extend(parser.feed(&[byte]).unwrap());
+        }
+
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[2].get_value_u32("value").unwrap(), 301);
+        assert_eq!(parser.get_pending_size(), 0);
+    }
+
+    #[test]
+    fn resource_usage_should_report_buffered_bytes() {
+        let mut parser = Parser::new();
+        let data = make_stream();
+        parser.feed(&data[..20]).unwrap();
+
+        let usage = parser.get_resource_usage();
+        assert!(usage.pending_bytes >= 20);
+        assert_eq!(usage.klass_count, 4);
+
+        parser.feed(&data[20..]).unwrap();
+        assert_eq!(parser.get_resource_usage().klass_count, 5);
+    }
+
+    #[test]
+    fn feed_should_reject_data_exceeding_quota() {
+        let data = make_stream();
+        let mut parser = Parser::new().with_quota(Quota {
+            max_klass_count: Some(4),
+            max_buffered_bytes: Some(40),
+            ..Quota::default()
+        });
+
+        assert_eq!(
+            parser.feed(&data[..41]).unwrap_err(),
+            ReadEventError::QuotaExceeded(QuotaViolation::BufferedBytes(40))
+        );
+        assert_eq!(
+            parser.feed(&data[..33]).unwrap_err(),
+            ReadEventError::QuotaExceeded(QuotaViolation::KlassCount(4))
+        );
+        assert_eq!(parser.get_registry().get_klass_count(), 4);
+
+        let metrics = parser.get_quota_metrics().unwrap();
+        assert_eq!(metrics.accepted_bytes, 33);
+        assert_eq!(metrics.rejected_bytes, 41);
+        assert_eq!(metrics.violation_count, 2);
+    }
+
+    #[test]
+    fn feed_should_keep_events_exceeding_quota_buffered() {
+        let data = make_stream();
+        let mut parser = Parser::new().with_quota(Quota {
+            max_buffered_events: Some(2),
+            ..Quota::default()
+        });
+
+        assert_eq!(parser.feed(&data).unwrap().len(), 2);
+        assert!(parser.get_pending_size() > 0);
+        let events = parser.feed(&[]).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].get_value_u32("value").unwrap(), 301);
+        assert_eq!(parser.get_pending_size(), 0);
+    }
+
+    #[test]
+    fn feed_should_report_error_after_returning_decoded_events() {
+        let mut data = make_stream();
+        data.extend(encode_header(999, 4, 4));
+
+        let mut parser = Parser::new();
+        assert_eq!(parser.feed(&data).unwrap().len(), 3);
+        assert_eq!(
+            parser.feed(&[]).unwrap_err(),
+            ReadEventError::UnknownKlassId(999)
+        );
+        assert_eq!(parser.get_pending_size(), 0);
+    }
+
+    #[test]
+    fn feed_should_continue_after_error() {
+        let stream = make_stream();
+        let mut parser = Parser::new();
+        assert_eq!(parser.feed(&stream).unwrap().len(), 3);
+
+        let mut data = encode_header(999, 4, 4);
+        data.extend_from_slice(&[1, 2, 3]);
+        assert_eq!(
+            parser.feed(&data).unwrap_err(),
+            ReadEventError::UnknownKlassId(999)
+        );
+        assert_eq!(parser.get_pending_size(), 0);
+
+        parser.feed(&stream[..stream.len() - 30]).unwrap();
+        parser.reset();
+        let event = TestStreamBuilder::new()
+            .event(100, 5, &[TestValue::U32(7)])
+            .build();
+        let events = parser.feed(&event).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].get_value_u32("value").unwrap(), 7);
+    }
+}
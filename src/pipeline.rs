@@ -0,0 +1,960 @@
+use crate::data_provider::DataError;
+use crate::data_struct_reader::ReadEventError;
+use crate::demux::Demux;
+use crate::event_reader::EventReader;
+use crate::event_reader_builder::EventReaderBuilder;
+use crate::registry::EventKlassRegistry;
+use crate::sink::EventSink;
+
+#[derive(Debug)]
+pub enum PipelineError {
+    Config(String),
+    IOError(std::io::Error),
+    ReadEventError(ReadEventError),
+}
+
+impl std::fmt::Display for PipelineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PipelineError::Config(message) => write!(f, "Invalid configuration: {}", message),
+            PipelineError::IOError(err) => write!(f, "I/O error: {}", err),
+            PipelineError::ReadEventError(err) => write!(f, "Cannot read event: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for PipelineError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PipelineError::IOError(err) => Some(err),
+            PipelineError::ReadEventError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RunStatus {
+    Success,
+    // All the events were processed, but some errors were recovered from.
+    PartialSuccess,
+    Failure,
+}
+
+impl RunStatus {
+    pub fn from_result(result: &Result<RunReport, PipelineError>) -> RunStatus {
+        match result {
+            Ok(report) => report.get_status(),
+            Err(_) => RunStatus::Failure,
+        }
+    }
+
+    // 2 is left for usage errors of command line tools.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            RunStatus::Success => 0,
+            RunStatus::Failure => 1,
+            RunStatus::PartialSuccess => 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RunReport {
+    pub event_count: u64,
+    // Metadata errors ignored by a non-strict reader.
+    pub ignored_error_count: u64,
+    // Size of an incomplete event at the end of the stream.
+    pub truncated_bytes: u64,
+}
+
+impl RunReport {
+    // Collects the errors of a reader which reached the end of the stream.
+    pub fn update_from_reader(&mut self, reader: &EventReader) {
+        self.truncated_bytes = reader.get_position() - reader.get_last_event_end();
+        self.ignored_error_count = reader.get_ignored_error_count();
+    }
+
+    pub fn get_status(&self) -> RunStatus {
+        if self.ignored_error_count > 0 || self.truncated_bytes > 0 {
+            RunStatus::PartialSuccess
+        } else {
+            RunStatus::Success
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StepStatus {
+    // The buffer was refilled, or an event was passed to the sink or
+    // skipped; more may follow.
+    Running,
+    Finished(RunReport),
+}
+
+// Reads all the events from the source and passes them to the sink.
+pub struct Pipeline {
+    reader: EventReader,
+    registry: EventKlassRegistry,
+    sink: Box<dyn EventSink>,
+    report: RunReport,
+    finished: bool,
+}
+
+impl Pipeline {
+    pub fn new(reader: EventReader, sink: Box<dyn EventSink>) -> Pipeline {
+        Pipeline {
+            reader,
+            registry: EventKlassRegistry::new(),
+            sink,
+            report: RunReport::default(),
+            finished: false,
+        }
+    }
+
+    pub fn get_registry(&self) -> &EventKlassRegistry {
+        &self.registry
+    }
+
+    pub fn run(&mut self) -> Result<RunReport, PipelineError> {
+        loop {
+            if let StepStatus::Finished(report) = self.step()? {
+                return Ok(report);
+            }
+        }
+    }
+
+    // Either refills the buffer of the reader or processes (or skips) at
+    // most one event, and returns, so the pipeline can be driven from an
+    // existing loop (e.g. a frame loop of a game engine) without threads.
+    // An event crossing the end of the buffer still refills it while being
+    // decoded. Once finished, subsequent calls return the same report.
+    pub fn step(&mut self) -> Result<StepStatus, PipelineError> {
+        if self.finished {
+            return Ok(StepStatus::Finished(self.report.clone()));
+        }
+
+        match self.reader.fill_buffer() {
+            Ok(true) => return Ok(StepStatus::Running),
+            // The end of the stream is handled by the reader, which may
+            // still have buffered events.
+            Ok(false) | Err(ReadEventError::DataError(DataError::EndOfStream)) => {}
+            Err(err) => return Err(PipelineError::ReadEventError(err)),
+        }
+
+        match self.reader.read_event_step(&mut self.registry) {
+            Ok(Some(event)) => {
+                if let Err(err) = self.sink.write_event(&event, &self.registry) {
+                    return Err(PipelineError::IOError(err));
+                }
+                self.report.event_count += 1;
+                Ok(StepStatus::Running)
+            }
+            Ok(None) => Ok(StepStatus::Running),
+            Err(ReadEventError::DataError(DataError::EndOfStream)) => {
+                self.report.update_from_reader(&self.reader);
+                if let Err(err) = self.sink.flush() {
+                    return Err(PipelineError::IOError(err));
+                }
+                self.finished = true;
+                Ok(StepStatus::Finished(self.report.clone()))
+            }
+            Err(err) => Err(PipelineError::ReadEventError(err)),
+        }
+    }
+}
+
+pub type WriterSinkConstructor = Box<dyn FnOnce(Box<dyn std::io::Write>) -> Box<dyn EventSink>>;
+
+struct Output {
+    name: String,
+    klass_names: Vec<String>,
+    constructor: WriterSinkConstructor,
+}
+
+// Configuration of an in-memory pipeline run by process(). Each output is
+// a sink writing to a buffer returned in the Outputs.
+#[derive(Default)]
+pub struct PipelineConfig {
+    reader_builder: EventReaderBuilder,
+    outputs: Vec<Output>,
+}
+
+impl PipelineConfig {
+    pub fn new() -> PipelineConfig {
+        PipelineConfig::default()
+    }
+
+    pub fn reader(mut self, reader_builder: EventReaderBuilder) -> PipelineConfig {
+        self.reader_builder = reader_builder;
+        self
+    }
+
+    // Events of listed klasses are routed to the output; if the list is
+    // empty, the output receives events without any other route.
+    pub fn output(
+        mut self,
+        name: &str,
+        klass_names: &[&str],
+        constructor: WriterSinkConstructor,
+    ) -> PipelineConfig {
+        self.outputs.push(Output {
+            name: name.to_owned(),
+            klass_names: klass_names.iter().map(|name| name.to_string()).collect(),
+            constructor,
+        });
+        self
+    }
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct SharedBuffer(pub(crate) std::rc::Rc<std::cell::RefCell<Vec<u8>>>);
+
+impl std::io::Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Outputs {
+    report: RunReport,
+    buffers: std::collections::HashMap<String, Vec<u8>>,
+}
+
+impl Outputs {
+    pub fn get_report(&self) -> &RunReport {
+        &self.report
+    }
+
+    pub fn get_output(&self, name: &str) -> Option<&[u8]> {
+        self.buffers.get(name).map(|buffer| buffer.as_slice())
+    }
+}
+
+// Runs the pipeline on a trace stored in memory; doesn't access filesystem.
+pub fn process(data: Vec<u8>, config: PipelineConfig) -> Result<Outputs, PipelineError> {
+    let mut demux = Demux::new();
+    let mut buffers = Vec::new();
+    for output in config.outputs {
+        let buffer = SharedBuffer::default();
+        let sink = (output.constructor)(Box::new(buffer.clone()));
+        let klass_names: Vec<&str> = output
+            .klass_names
+            .iter()
+            .map(|name| name.as_str())
+            .collect();
+        demux = if klass_names.is_empty() {
+            demux.default_route(sink)
+        } else {
+            demux.route(&klass_names, sink)
+        };
+        buffers.push((output.name, buffer));
+    }
+
+    let reader = config
+        .reader_builder
+        .build(Box::new(std::io::Cursor::new(data)));
+    let report = Pipeline::new(reader, Box::new(demux)).run()?;
+
+    Ok(Outputs {
+        report,
+        buffers: buffers
+            .into_iter()
+            .map(|(name, buffer)| (name, buffer.0.take()))
+            .collect(),
+    })
+}
+
+#[cfg(feature = "config")]
+pub type SinkConstructor = Box<dyn Fn(&toml::Value) -> Result<Box<dyn EventSink>, String>>;
+
+#[cfg(feature = "config")]
+pub type TransformConstructor =
+    Box<dyn Fn(&toml::Value) -> Result<crate::transcoder::EventTransform, String>>;
+
+// Sink and transform types which can be referenced from the configuration,
+// with constructors taking the `options` table of the sink or transform.
+// The built-in types are registered by new():
+//   - "csv" and "json-lines" sinks, writing to `path` (standard output if
+//     missing); csv takes `klass` to write fields of a single klass,
+//   - "resolve-labels" transform, rewriting the label ids of `fields`
+//     (default: ["label"]) to strings,
+//   - "sample" transform, keeping every `rate`-th event, and events whose
+//     `field` (default: "duration") exceeds the `percentile` (default: 0.99).
+#[cfg(feature = "config")]
+pub struct SinkFactory {
+    constructors: std::collections::HashMap<String, SinkConstructor>,
+    transform_constructors: std::collections::HashMap<String, TransformConstructor>,
+}
+
+#[cfg(feature = "config")]
+impl Default for SinkFactory {
+    fn default() -> SinkFactory {
+        SinkFactory {
+            constructors: std::collections::HashMap::new(),
+            transform_constructors: std::collections::HashMap::new(),
+        }
+        .register("csv", Box::new(create_csv_sink))
+        .register("json-lines", Box::new(create_json_lines_sink))
+        .register_transform("resolve-labels", Box::new(create_label_resolver))
+        .register_transform("sample", Box::new(create_sampler))
+    }
+}
+
+#[cfg(feature = "config")]
+impl SinkFactory {
+    pub fn new() -> SinkFactory {
+        SinkFactory::default()
+    }
+
+    // Replaces the sink type of the same name, including built-in ones.
+    pub fn register(mut self, sink_type: &str, constructor: SinkConstructor) -> SinkFactory {
+        self.constructors.insert(sink_type.to_owned(), constructor);
+        self
+    }
+
+    pub fn register_transform(
+        mut self,
+        transform_type: &str,
+        constructor: TransformConstructor,
+    ) -> SinkFactory {
+        self.transform_constructors
+            .insert(transform_type.to_owned(), constructor);
+        self
+    }
+
+    fn create(
+        &self,
+        sink_type: &str,
+        options: &toml::Value,
+    ) -> Result<Box<dyn EventSink>, PipelineError> {
+        match self.constructors.get(sink_type) {
+            Some(constructor) => constructor(options).map_err(PipelineError::Config),
+            None => Err(PipelineError::Config(format!(
+                "Unknown sink type: {}",
+                sink_type
+            ))),
+        }
+    }
+
+    fn create_transform(
+        &self,
+        transform_type: &str,
+        options: &toml::Value,
+    ) -> Result<crate::transcoder::EventTransform, PipelineError> {
+        match self.transform_constructors.get(transform_type) {
+            Some(constructor) => constructor(options).map_err(PipelineError::Config),
+            None => Err(PipelineError::Config(format!(
+                "Unknown transform type: {}",
+                transform_type
+            ))),
+        }
+    }
+}
+
+#[cfg(feature = "config")]
+fn create_output(options: &toml::Value) -> Result<Box<dyn std::io::Write>, String> {
+    match options.get("path") {
+        Some(toml::Value::String(path)) => match std::fs::File::create(path) {
+            Ok(file) => Ok(Box::new(std::io::BufWriter::new(file))),
+            Err(err) => Err(format!("Cannot create {}: {}", path, err)),
+        },
+        Some(_) => Err("'path' must be a string".to_owned()),
+        None => Ok(Box::new(std::io::BufWriter::new(std::io::stdout()))),
+    }
+}
+
+#[cfg(feature = "config")]
+fn create_csv_sink(options: &toml::Value) -> Result<Box<dyn EventSink>, String> {
+    use crate::csv_writer::{CsvColumns, CsvWriter};
+
+    let columns = match options.get("klass") {
+        Some(toml::Value::String(klass_name)) => CsvColumns::Klass(klass_name.clone()),
+        Some(_) => return Err("'klass' must be a string".to_owned()),
+        None => CsvColumns::Union,
+    };
+    Ok(Box::new(CsvWriter::new(create_output(options)?, columns)))
+}
+
+#[cfg(feature = "config")]
+fn create_json_lines_sink(options: &toml::Value) -> Result<Box<dyn EventSink>, String> {
+    Ok(Box::new(crate::json::JsonLinesWriter::new(create_output(
+        options,
+    )?)))
+}
+
+#[cfg(feature = "config")]
+fn create_label_resolver(
+    options: &toml::Value,
+) -> Result<crate::transcoder::EventTransform, String> {
+    let fields = match options.get("fields") {
+        Some(fields) => get_string_list(Some(fields), "fields")
+            .map_err(|_| "'fields' must be a list of strings".to_owned())?,
+        None => vec!["label".to_owned()],
+    };
+    let fields: Vec<&str> = fields.iter().map(|field| field.as_str()).collect();
+    let mut resolver = crate::label_resolver::LabelResolver::new().with_rewrite_fields(&fields);
+    Ok(Box::new(move |event, registry| {
+        resolver.process(event, registry);
+        true
+    }))
+}
+
+#[cfg(feature = "config")]
+fn create_sampler(options: &toml::Value) -> Result<crate::transcoder::EventTransform, String> {
+    let rate = match options.get("rate") {
+        Some(rate) => rate
+            .as_integer()
+            .filter(|rate| *rate > 0)
+            .ok_or_else(|| "'rate' must be a positive integer".to_owned())?,
+        None => return Err("Missing 'rate'".to_owned()),
+    };
+    let field = match options.get("field") {
+        Some(field) => field
+            .as_str()
+            .ok_or_else(|| "'field' must be a string".to_owned())?,
+        None => "duration",
+    };
+    let percentile = match options.get("percentile") {
+        Some(percentile) => percentile
+            .as_float()
+            .filter(|percentile| *percentile > 0.0 && *percentile < 1.0)
+            .ok_or_else(|| "'percentile' must be a number between 0 and 1".to_owned())?,
+        None => 0.99,
+    };
+    let mut sampler = crate::sampling::OutlierSampler::new(rate as u64, field, percentile);
+    Ok(Box::new(move |event, _registry| sampler.should_keep(event)))
+}
+
+// Applies the transforms to all the events except metadata events, before
+// passing them to the sink.
+#[cfg(feature = "config")]
+struct TransformSink {
+    transforms: Vec<crate::transcoder::EventTransform>,
+    sink: Box<dyn EventSink>,
+}
+
+#[cfg(feature = "config")]
+impl EventSink for TransformSink {
+    fn write_event(
+        &mut self,
+        event: &crate::event::Event,
+        registry: &EventKlassRegistry,
+    ) -> std::io::Result<()> {
+        if crate::registry::CoreEventKlassId::is_metadata_klass(event.get_klass_id()) {
+            return self.sink.write_event(event, registry);
+        }
+        let mut event = event.clone();
+        for transform in &mut self.transforms {
+            if !transform(&mut event, registry) {
+                return Ok(());
+            }
+        }
+        self.sink.write_event(&event, registry)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.sink.flush()
+    }
+}
+
+#[cfg(feature = "config")]
+fn get_string_list(value: Option<&toml::Value>, name: &str) -> Result<Vec<String>, PipelineError> {
+    let invalid = || PipelineError::Config(format!("'{}' must be a list of strings", name));
+    match value {
+        Some(toml::Value::Array(items)) => items
+            .iter()
+            .map(|item| item.as_str().map(|s| s.to_owned()).ok_or_else(invalid))
+            .collect(),
+        Some(_) => Err(invalid()),
+        None => Ok(Vec::new()),
+    }
+}
+
+// Example configuration:
+//
+//   [source]
+//   path = "trace.htdump"
+//   endianness = "little"   # optional: little, big, native
+//   buffer_size = 4096      # optional
+//   flatten = true          # optional
+//   strict = false          # optional
+//
+//   [filter]
+//   klasses = ["HT_CallstackIntEvent"]
+//
+//   [projection]
+//   HT_CallstackIntEvent = ["base", "label", "duration"]
+//
+//   [[transforms]]                      # applied in order, before the sinks
+//   type = "resolve-labels"             # registered in the SinkFactory
+//   options = { fields = ["label"] }    # optional, passed to the constructor
+//
+//   [[sinks]]
+//   type = "csv"                        # registered in the SinkFactory
+//   klasses = ["HT_CallstackIntEvent"]  # optional, default route if missing
+//   options = { path = "out.csv" }      # optional, passed to the constructor
+#[cfg(feature = "config")]
+impl Pipeline {
+    pub fn from_config(config: &str, factory: &SinkFactory) -> Result<Pipeline, PipelineError> {
+        use crate::data_struct_reader::Endianness;
+
+        let config: toml::Value = config
+            .parse()
+            .map_err(|err: toml::de::Error| PipelineError::Config(err.to_string()))?;
+        let invalid = |name: &str| PipelineError::Config(format!("Invalid value of '{}'", name));
+
+        let source = config
+            .get("source")
+            .ok_or_else(|| PipelineError::Config("Missing [source] section".to_owned()))?;
+        let path = source
+            .get("path")
+            .and_then(|path| path.as_str())
+            .ok_or_else(|| invalid("source.path"))?;
+
+        let mut builder = EventReaderBuilder::new();
+        if let Some(endianness) = source.get("endianness") {
+            builder = builder.endianness(match endianness.as_str() {
+                Some("little") => Endianness::Little,
+                Some("big") => Endianness::Big,
+                Some("native") => Endianness::Native,
+                _ => return Err(invalid("source.endianness")),
+            });
+        }
+        if let Some(buffer_size) = source.get("buffer_size") {
+            let buffer_size = buffer_size
+                .as_integer()
+                .filter(|size| *size > 0)
+                .ok_or_else(|| invalid("source.buffer_size"))?;
+            builder = builder.buffer_size(buffer_size as usize);
+        }
+        if let Some(flatten) = source.get("flatten") {
+            builder = builder.flatten(flatten.as_bool().ok_or_else(|| invalid("source.flatten"))?);
+        }
+        if let Some(strict) = source.get("strict") {
+            builder = builder.strict(strict.as_bool().ok_or_else(|| invalid("source.strict"))?);
+        }
+
+        if let Some(filter) = config.get("filter") {
+            let mut klass_filter = crate::klass_filter::KlassFilter::new();
+            for klass_name in get_string_list(filter.get("klasses"), "filter.klasses")? {
+                klass_filter = klass_filter.allow_name(&klass_name);
+            }
+            builder = builder.klass_filter(klass_filter);
+        }
+
+        if let Some(projection_config) = config.get("projection") {
+            let table = projection_config
+                .as_table()
+                .ok_or_else(|| invalid("projection"))?;
+            let mut projection = crate::field_projection::FieldProjection::new();
+            for (klass_name, fields) in table {
+                let fields = get_string_list(Some(fields), "projection")?;
+                let fields: Vec<&str> = fields.iter().map(|field| field.as_str()).collect();
+                projection = projection.include(klass_name, &fields);
+            }
+            builder = builder.projection(projection);
+        }
+
+        let empty_options = toml::Value::Table(toml::value::Table::new());
+        let mut transforms = Vec::new();
+        let transform_configs = match config.get("transforms") {
+            Some(toml::Value::Array(transforms)) => transforms.clone(),
+            Some(_) => return Err(invalid("transforms")),
+            None => Vec::new(),
+        };
+        for transform_config in &transform_configs {
+            let transform_type = transform_config
+                .get("type")
+                .and_then(|transform_type| transform_type.as_str())
+                .ok_or_else(|| invalid("transforms.type"))?;
+            let options = transform_config.get("options").unwrap_or(&empty_options);
+            transforms.push(factory.create_transform(transform_type, options)?);
+        }
+
+        let mut demux = Demux::new();
+        let sinks = match config.get("sinks") {
+            Some(toml::Value::Array(sinks)) => sinks.clone(),
+            Some(_) => return Err(invalid("sinks")),
+            None => Vec::new(),
+        };
+        for sink_config in &sinks {
+            let sink_type = sink_config
+                .get("type")
+                .and_then(|sink_type| sink_type.as_str())
+                .ok_or_else(|| invalid("sinks.type"))?;
+            let options = sink_config.get("options").unwrap_or(&empty_options);
+            let sink = factory.create(sink_type, options)?;
+
+            demux = match sink_config.get("klasses") {
+                Some(klasses) => {
+                    let klasses = get_string_list(Some(klasses), "sinks.klasses")?;
+                    let klasses: Vec<&str> = klasses.iter().map(|name| name.as_str()).collect();
+                    demux.route(&klasses, sink)
+                }
+                None => demux.default_route(sink),
+            };
+        }
+
+        let sink: Box<dyn EventSink> = if transforms.is_empty() {
+            Box::new(demux)
+        } else {
+            Box::new(TransformSink {
+                transforms,
+                sink: Box::new(demux),
+            })
+        };
+        let file = std::fs::File::open(path).map_err(PipelineError::IOError)?;
+        let reader = builder.build(Box::new(std::io::BufReader::new(file)));
+        Ok(Pipeline::new(reader, sink))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_provider::DataProvider;
+    use crate::event::Event;
+    use hawktracer_parser_test_utilities::FakeDataReader;
+
+    struct CountingSink {
+        count: std::rc::Rc<std::cell::Cell<u64>>,
+    }
+
+    impl EventSink for CountingSink {
+        fn write_event(
+            &mut self,
+            _event: &Event,
+            _registry: &EventKlassRegistry,
+        ) -> std::io::Result<()> {
+            self.count.set(self.count.get() + 1);
+            Ok(())
+        }
+    }
+
+    fn make_trace() -> Vec<u8> {
+        let mut data = vec![
+            2, 0, 0, 0, // type
+            1, 0, 0, 0, 0, 0, 0, 0, // timestamp
+            1, 0, 0, 0, 0, 0, 0, 0, // id
+            100, 0, 0, 0, // info_klass_id
+            102, 111, 111, 0, // foo
+            0, // field_count
+        ];
+        data.extend_from_slice(&[100, 0, 0, 0, 5, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0]);
+        data
+    }
+
+    #[test]
+    fn pipeline_should_pass_all_events_to_sink() {
+        let count = std::rc::Rc::new(std::cell::Cell::new(0));
+        let reader = EventReader::new(DataProvider::new(Box::new(FakeDataReader::new(
+            make_trace(),
+            false,
+        ))));
+        let mut pipeline = Pipeline::new(
+            reader,
+            Box::new(CountingSink {
+                count: count.clone(),
+            }),
+        );
+
+        let result = pipeline.run();
+        assert_eq!(RunStatus::from_result(&result), RunStatus::Success);
+        assert_eq!(result.unwrap().event_count, 2);
+        assert_eq!(count.get(), 2);
+        assert!(pipeline.get_registry().get_klass_by_name("foo").is_some());
+    }
+
+    #[test]
+    fn pipeline_should_report_partial_success_for_truncated_stream() {
+        let mut data = make_trace();
+        data.extend_from_slice(&[100, 0, 0, 0, 5]);
+        let reader = EventReader::new(DataProvider::new(Box::new(FakeDataReader::new(
+            data, false,
+        ))));
+        let count = std::rc::Rc::new(std::cell::Cell::new(0));
+        let mut pipeline = Pipeline::new(reader, Box::new(CountingSink { count }));
+
+        let result = pipeline.run();
+        assert_eq!(RunStatus::from_result(&result).exit_code(), 3);
+        assert_eq!(
+            result.unwrap(),
+            RunReport {
+                event_count: 2,
+                ignored_error_count: 0,
+                truncated_bytes: 5,
+            }
+        );
+    }
+
+    #[test]
+    fn pipeline_should_process_one_event_per_step() {
+        let count = std::rc::Rc::new(std::cell::Cell::new(0));
+        let reader = EventReader::new(DataProvider::new(Box::new(FakeDataReader::new(
+            make_trace(),
+            false,
+        ))));
+        let mut pipeline = Pipeline::new(
+            reader,
+            Box::new(CountingSink {
+                count: count.clone(),
+            }),
+        );
+
+        // The first step only fills the buffer.
+        assert_eq!(pipeline.step().unwrap(), StepStatus::Running);
+        assert_eq!(count.get(), 0);
+        assert_eq!(pipeline.step().unwrap(), StepStatus::Running);
+        assert_eq!(count.get(), 1);
+        assert_eq!(pipeline.step().unwrap(), StepStatus::Running);
+        assert_eq!(count.get(), 2);
+
+        let expected_report = RunReport {
+            event_count: 2,
+            ignored_error_count: 0,
+            truncated_bytes: 0,
+        };
+        assert_eq!(
+            pipeline.step().unwrap(),
+            StepStatus::Finished(expected_report.clone())
+        );
+        assert_eq!(
+            pipeline.step().unwrap(),
+            StepStatus::Finished(expected_report)
+        );
+    }
+
+    #[test]
+    fn pipeline_should_return_after_each_skipped_event() {
+        let mut data = make_trace();
+        for timestamp in 6..10u64 {
+            data.extend_from_slice(&[100, 0, 0, 0]);
+            data.extend_from_slice(&timestamp.to_ne_bytes());
+            data.extend_from_slice(&timestamp.to_ne_bytes());
+        }
+        let mut reader = EventReader::new(DataProvider::new(Box::new(FakeDataReader::new(
+            data, false,
+        ))));
+        reader.set_filter(Some(Box::new(|event| event.get_klass_id() != 100)));
+        let count = std::rc::Rc::new(std::cell::Cell::new(0));
+        let mut pipeline = Pipeline::new(
+            reader,
+            Box::new(CountingSink {
+                count: count.clone(),
+            }),
+        );
+
+        let mut steps = 0;
+        while let StepStatus::Running = pipeline.step().unwrap() {
+            steps += 1;
+        }
+        // One refill and one step per event.
+        assert_eq!(steps, 7);
+        assert_eq!(count.get(), 1);
+    }
+
+    #[test]
+    fn pipeline_should_not_report_skipped_events_as_truncated() {
+        let make_reader = |data| {
+            let mut reader = EventReader::new(DataProvider::new(Box::new(FakeDataReader::new(
+                data, false,
+            ))));
+            reader.set_filter(Some(Box::new(|event: &Event| event.get_klass_id() != 100)));
+            reader
+        };
+        let count = std::rc::Rc::new(std::cell::Cell::new(0));
+
+        let result = Pipeline::new(
+            make_reader(make_trace()),
+            Box::new(CountingSink {
+                count: count.clone(),
+            }),
+        )
+        .run();
+        assert_eq!(RunStatus::from_result(&result), RunStatus::Success);
+        assert_eq!(result.unwrap().event_count, 1);
+
+        let mut data = make_trace();
+        data.extend_from_slice(&[100, 0, 0, 0, 7]);
+        let result = Pipeline::new(make_reader(data), Box::new(CountingSink { count })).run();
+        assert_eq!(
+            result.unwrap(),
+            RunReport {
+                event_count: 1,
+                ignored_error_count: 0,
+                truncated_bytes: 5,
+            }
+        );
+    }
+
+    #[test]
+    fn pipeline_should_fail_for_unknown_klass() {
+        let mut data = make_trace();
+        data.extend_from_slice(&[200, 0, 0, 0, 5, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0]);
+        let reader = EventReader::new(DataProvider::new(Box::new(FakeDataReader::new(
+            data, false,
+        ))));
+        let count = std::rc::Rc::new(std::cell::Cell::new(0));
+        let mut pipeline = Pipeline::new(reader, Box::new(CountingSink { count }));
+
+        let result = pipeline.run();
+        assert_eq!(RunStatus::from_result(&result), RunStatus::Failure);
+        assert_eq!(RunStatus::from_result(&result).exit_code(), 1);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Cannot read event: Unknown klass id 200"
+        );
+    }
+
+    struct KlassIdWriter {
+        writer: Box<dyn std::io::Write>,
+    }
+
+    impl EventSink for KlassIdWriter {
+        fn write_event(
+            &mut self,
+            event: &Event,
+            _registry: &EventKlassRegistry,
+        ) -> std::io::Result<()> {
+            writeln!(self.writer, "{}", event.get_klass_id())
+        }
+    }
+
+    #[test]
+    fn process_should_return_outputs_in_memory() {
+        let config = PipelineConfig::new()
+            .reader(EventReaderBuilder::new().buffer_size(8))
+            .output(
+                "foo",
+                &["foo"],
+                Box::new(|writer| Box::new(KlassIdWriter { writer })),
+            )
+            .output(
+                "other",
+                &[],
+                Box::new(|writer| Box::new(KlassIdWriter { writer })),
+            );
+
+        let outputs = process(make_trace(), config).unwrap();
+
+        assert_eq!(outputs.get_report().event_count, 2);
+        assert_eq!(outputs.get_output("foo").unwrap(), b"100\n");
+        assert_eq!(outputs.get_output("other").unwrap(), b"2\n");
+        assert!(outputs.get_output("bar").is_none());
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn pipeline_should_be_created_from_config() {
+        let path = std::env::temp_dir().join(format!("ht-pipeline-{}", std::process::id()));
+        std::fs::write(&path, make_trace()).unwrap();
+
+        let foo_count = std::rc::Rc::new(std::cell::Cell::new(0));
+        let other_count = std::rc::Rc::new(std::cell::Cell::new(0));
+        let (foo, other) = (foo_count.clone(), other_count.clone());
+        let factory = SinkFactory::new().register(
+            "counter",
+            Box::new(move |options| {
+                let count = match options.get("name").and_then(|name| name.as_str()) {
+                    Some("foo") => foo.clone(),
+                    _ => other.clone(),
+                };
+                Ok(Box::new(CountingSink { count }))
+            }),
+        );
+
+        let config = format!(
+            r#"
+            [source]
+            path = "{}"
+            buffer_size = 16
+            endianness = "native"
+
+            [[sinks]]
+            type = "counter"
+            klasses = ["foo"]
+            options = {{ name = "foo" }}
+
+            [[sinks]]
+            type = "counter"
+            "#,
+            path.display()
+        );
+        let mut pipeline = Pipeline::from_config(&config, &factory).unwrap();
+        let result = pipeline.run();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(result.unwrap().event_count, 2);
+        assert_eq!(foo_count.get(), 1);
+        assert_eq!(other_count.get(), 1);
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn config_should_support_builtin_sinks_and_transforms() {
+        let id = std::process::id();
+        let path = std::env::temp_dir().join(format!("ht-pipeline-builtin-{}", id));
+        let output_path = std::env::temp_dir().join(format!("ht-pipeline-builtin-{}.json", id));
+        let mut data = make_trace();
+        for timestamp in 6..9u64 {
+            data.extend_from_slice(&[100, 0, 0, 0]);
+            data.extend_from_slice(&timestamp.to_ne_bytes());
+            data.extend_from_slice(&timestamp.to_ne_bytes());
+        }
+        std::fs::write(&path, data).unwrap();
+
+        let config = format!(
+            r#"
+            [source]
+            path = "{}"
+
+            [[transforms]]
+            type = "sample"
+            options = {{ rate = 2 }}
+
+            [[sinks]]
+            type = "json-lines"
+            klasses = ["foo"]
+            options = {{ path = "{}" }}
+            "#,
+            path.display(),
+            output_path.display()
+        );
+        let mut pipeline = Pipeline::from_config(&config, &SinkFactory::new()).unwrap();
+        let result = pipeline.run();
+        let output = std::fs::read_to_string(&output_path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&output_path).unwrap();
+
+        assert_eq!(result.unwrap().event_count, 5);
+        // Every second of the four foo events is kept.
+        assert_eq!(output, "{\"klass\":\"foo\"}\n".repeat(2));
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn invalid_config_should_be_rejected() {
+        let factory = SinkFactory::new();
+        for config in &[
+            "[source",
+            "[filter]",
+            "[source]\npath = 1",
+            "[source]\npath = \"a\"\nendianness = \"middle\"",
+            "[source]\npath = \"a\"\n[[sinks]]\ntype = \"unknown\"",
+            "[source]\npath = \"a\"\n[[transforms]]\ntype = \"unknown\"",
+            "[source]\npath = \"a\"\n[[transforms]]\ntype = \"sample\"",
+        ] {
+            match Pipeline::from_config(config, &factory) {
+                Err(PipelineError::Config(_)) => {}
+                _ => panic!("config should be rejected: {}", config),
+            }
+        }
+    }
+}
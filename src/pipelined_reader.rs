@@ -0,0 +1,148 @@
+// Reader which performs I/O on a separate thread, so the data is loaded while
+// the previous chunks are decoded. Chunks are passed through a bounded
+// channel and their buffers are sent back to the I/O thread for reuse.
+//
+// Usage: EventReader::new(DataProvider::new(Box::new(PipelinedReader::new(file))))
+pub struct PipelinedReader {
+    receiver: std::sync::mpsc::Receiver<std::io::Result<Vec<u8>>>,
+    recycler: std::sync::mpsc::SyncSender<Vec<u8>>,
+    chunk: Vec<u8>,
+    offset: usize,
+    finished: bool,
+}
+
+impl PipelinedReader {
+    pub fn new<R: std::io::Read + Send + 'static>(reader: R) -> PipelinedReader {
+        PipelinedReader::with_chunks(reader, 64 * 1024, 4)
+    }
+
+    pub fn with_chunks<R: std::io::Read + Send + 'static>(
+        reader: R,
+        chunk_size: usize,
+        chunk_count: usize,
+    ) -> PipelinedReader {
+        let chunk_count = std::cmp::max(chunk_count, 1);
+        let (sender, receiver) = std::sync::mpsc::sync_channel(chunk_count);
+        let (recycler, recycled) = std::sync::mpsc::sync_channel(chunk_count);
+
+        std::thread::spawn(move || {
+            PipelinedReader::run_io(reader, std::cmp::max(chunk_size, 1), sender, recycled)
+        });
+
+        PipelinedReader {
+            receiver,
+            recycler,
+            chunk: Vec::new(),
+            offset: 0,
+            finished: false,
+        }
+    }
+
+    // An empty chunk marks the end of the stream.
+    fn run_io<R: std::io::Read>(
+        mut reader: R,
+        chunk_size: usize,
+        sender: std::sync::mpsc::SyncSender<std::io::Result<Vec<u8>>>,
+        recycled: std::sync::mpsc::Receiver<Vec<u8>>,
+    ) {
+        loop {
+            let mut buffer = recycled.try_recv().unwrap_or_default();
+            buffer.resize(chunk_size, 0);
+
+            let chunk = match reader.read(&mut buffer) {
+                Ok(size) => {
+                    buffer.truncate(size);
+                    Ok(buffer)
+                }
+                Err(ref err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(err) => Err(err),
+            };
+
+            let last = match &chunk {
+                Ok(buffer) => buffer.is_empty(),
+                Err(_) => true,
+            };
+            if sender.send(chunk).is_err() || last {
+                break;
+            }
+        }
+    }
+
+    fn next_chunk(&mut self) -> std::io::Result<()> {
+        match self.receiver.recv() {
+            Ok(Ok(chunk)) => {
+                self.finished = chunk.is_empty();
+                let used = std::mem::replace(&mut self.chunk, chunk);
+                self.offset = 0;
+                let _ = self.recycler.try_send(used);
+                Ok(())
+            }
+            Ok(Err(err)) => {
+                self.finished = true;
+                Err(err)
+            }
+            Err(_) => {
+                self.finished = true;
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::io::Read for PipelinedReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.offset == self.chunk.len() {
+            if self.finished {
+                return Ok(0);
+            }
+            self.next_chunk()?;
+        }
+
+        let size = std::cmp::min(buf.len(), self.chunk.len() - self.offset);
+        buf[..size].copy_from_slice(&self.chunk[self.offset..self.offset + size]);
+        self.offset += size;
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hawktracer_parser_test_utilities::FakeDataReader;
+    use std::io::Read;
+
+    #[test]
+    fn pipelined_reader_should_return_all_data() {
+        let data: Vec<u8> = (0..1000).map(|v| (v % 256) as u8).collect();
+        let mut reader =
+            PipelinedReader::with_chunks(FakeDataReader::new(data.clone(), false), 7, 2);
+
+        let mut output = Vec::new();
+        reader.read_to_end(&mut output).unwrap();
+
+        assert_eq!(output, data);
+        assert_eq!(reader.read(&mut [0u8; 4]).unwrap(), 0);
+    }
+
+    #[test]
+    fn pipelined_reader_should_forward_errors() {
+        let mut reader = PipelinedReader::new(FakeDataReader::new(vec![1, 2], true));
+
+        assert!(reader.read(&mut [0u8; 4]).is_err());
+        assert_eq!(reader.read(&mut [0u8; 4]).unwrap(), 0);
+    }
+
+    #[test]
+    fn events_should_be_decoded_from_pipelined_reader() {
+        let data = vec![1, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0];
+        let reader = PipelinedReader::with_chunks(FakeDataReader::new(data, false), 3, 1);
+        let mut event_reader = crate::event_reader::EventReader::new(
+            crate::data_provider::DataProvider::new(Box::new(reader)),
+        );
+
+        let event = event_reader
+            .read_event(&mut crate::registry::EventKlassRegistry::new())
+            .unwrap();
+        assert_eq!(event.get_value_u64("timestamp").unwrap(), 3);
+    }
+}
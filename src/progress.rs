@@ -0,0 +1,115 @@
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Progress {
+    pub bytes_consumed: u64,
+    // Size of the stream, if known (e.g. the size of the trace file).
+    pub total_bytes: Option<u64>,
+    pub event_count: u64,
+}
+
+impl Progress {
+    // Returns a value between 0 and 1, or None if the total size is unknown.
+    pub fn get_fraction(&self) -> Option<f64> {
+        match self.total_bytes {
+            Some(0) => Some(1.0),
+            Some(total_bytes) => Some((self.bytes_consumed as f64 / total_bytes as f64).min(1.0)),
+            None => None,
+        }
+    }
+}
+
+pub type ProgressCallback = Box<dyn FnMut(&Progress)>;
+
+// Calls the callback every interval events returned by the reader, and once
+// more when the end of the stream is reached.
+pub struct ProgressReporter {
+    callback: ProgressCallback,
+    interval: u64,
+    progress: Progress,
+    finished: bool,
+}
+
+impl ProgressReporter {
+    pub fn new<F>(interval: u64, callback: F) -> ProgressReporter
+    where
+        F: FnMut(&Progress) + 'static,
+    {
+        ProgressReporter {
+            callback: Box::new(callback),
+            interval: std::cmp::max(interval, 1),
+            progress: Progress::default(),
+            finished: false,
+        }
+    }
+
+    pub fn with_total_bytes(mut self, total_bytes: u64) -> ProgressReporter {
+        self.progress.total_bytes = Some(total_bytes);
+        self
+    }
+
+    pub fn get_progress(&self) -> &Progress {
+        &self.progress
+    }
+
+    pub(crate) fn event_read(&mut self, position: u64) {
+        self.progress.event_count += 1;
+        self.progress.bytes_consumed = position;
+        if self.progress.event_count % self.interval == 0 {
+            (self.callback)(&self.progress);
+        }
+    }
+
+    pub(crate) fn finish(&mut self, position: u64) {
+        if !self.finished {
+            self.finished = true;
+            self.progress.bytes_consumed = position;
+            (self.callback)(&self.progress);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn callback_should_be_called_every_interval_events() {
+        let reported = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let reported_clone = reported.clone();
+        let mut reporter = ProgressReporter::new(2, move |progress: &Progress| {
+            reported_clone.borrow_mut().push(*progress)
+        })
+        .with_total_bytes(100);
+
+        for position in &[10, 20, 30, 40, 50] {
+            reporter.event_read(*position);
+        }
+        reporter.finish(60);
+        reporter.finish(60);
+
+        let counts: Vec<(u64, u64)> = reported
+            .borrow()
+            .iter()
+            .map(|progress| (progress.event_count, progress.bytes_consumed))
+            .collect();
+        assert_eq!(counts, vec![(2, 20), (4, 40), (5, 60)]);
+        assert_eq!(reporter.get_progress().get_fraction(), Some(0.6));
+    }
+
+    #[test]
+    fn fraction_should_be_unknown_without_total_size() {
+        let progress = Progress {
+            bytes_consumed: 10,
+            total_bytes: None,
+            event_count: 1,
+        };
+        assert_eq!(progress.get_fraction(), None);
+        assert_eq!(
+            Progress {
+                total_bytes: Some(0),
+                ..progress
+            }
+            .get_fraction(),
+            Some(1.0)
+        );
+    }
+}
@@ -0,0 +1,178 @@
+// Limits of resources a single stream (e.g. a client connection of a shared
+// collector) may use. Limits which are None are not enforced.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Quota {
+    pub max_bytes_per_second: Option<u64>,
+    // Klasses of the registry (including the core ones); checked when a
+    // metadata event defines a new klass.
+    pub max_klass_count: Option<usize>,
+    // Bytes buffered by the parser, including the chunk being fed.
+    pub max_buffered_bytes: Option<usize>,
+    // Events decoded by a single feed; the rest of the data stays buffered
+    // until the next one.
+    pub max_buffered_events: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QuotaViolation {
+    ByteRate(u64),
+    KlassCount(usize),
+    BufferedBytes(usize),
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QuotaMetrics {
+    pub accepted_bytes: u64,
+    pub rejected_bytes: u64,
+    pub violation_count: u64,
+}
+
+// Enforces a quota. The byte rate is measured in one second windows; a
+// chunk larger than the limit is accepted at the start of a window, so it
+// doesn't stall the stream.
+pub struct QuotaTracker {
+    quota: Quota,
+    window_start: Option<std::time::Instant>,
+    window_bytes: u64,
+    metrics: QuotaMetrics,
+}
+
+impl QuotaTracker {
+    pub fn new(quota: Quota) -> QuotaTracker {
+        QuotaTracker {
+            quota,
+            window_start: None,
+            window_bytes: 0,
+            metrics: QuotaMetrics::default(),
+        }
+    }
+
+    pub fn get_quota(&self) -> &Quota {
+        &self.quota
+    }
+
+    pub fn get_metrics(&self) -> &QuotaMetrics {
+        &self.metrics
+    }
+
+    // Checks whether a chunk of data can be accepted given the current state
+    // of the consumer; rejected chunks are not counted to the byte rate.
+    pub fn check(
+        &mut self,
+        size: usize,
+        buffered_bytes: usize,
+        now: std::time::Instant,
+    ) -> Result<(), QuotaViolation> {
+        let result = self.check_limits(size, buffered_bytes, now);
+        match result {
+            Ok(()) => {
+                self.window_bytes += size as u64;
+                self.metrics.accepted_bytes += size as u64;
+            }
+            Err(_) => {
+                self.metrics.rejected_bytes += size as u64;
+                self.metrics.violation_count += 1;
+            }
+        }
+        result
+    }
+
+    // Counts a violation detected while decoding an accepted chunk.
+    pub fn add_violation(&mut self) {
+        self.metrics.violation_count += 1;
+    }
+
+    fn check_limits(
+        &mut self,
+        size: usize,
+        buffered_bytes: usize,
+        now: std::time::Instant,
+    ) -> Result<(), QuotaViolation> {
+        if let Some(limit) = self.quota.max_buffered_bytes {
+            if buffered_bytes + size > limit {
+                return Err(QuotaViolation::BufferedBytes(limit));
+            }
+        }
+        if let Some(limit) = self.quota.max_bytes_per_second {
+            let window_start = *self.window_start.get_or_insert(now);
+            if now.duration_since(window_start) >= std::time::Duration::from_secs(1) {
+                self.window_start = Some(now);
+                self.window_bytes = 0;
+            }
+            if self.window_bytes > 0 && self.window_bytes + size as u64 > limit {
+                return Err(QuotaViolation::ByteRate(limit));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn byte_rate_should_be_limited_per_second() {
+        let mut tracker = QuotaTracker::new(Quota {
+            max_bytes_per_second: Some(100),
+            ..Quota::default()
+        });
+        let start = std::time::Instant::now();
+
+        assert_eq!(tracker.check(60, 0, start), Ok(()));
+        assert_eq!(
+            tracker.check(60, 0, start + std::time::Duration::from_millis(500)),
+            Err(QuotaViolation::ByteRate(100))
+        );
+        assert_eq!(
+            tracker.check(40, 0, start + std::time::Duration::from_millis(900)),
+            Ok(())
+        );
+        assert_eq!(
+            tracker.check(100, 0, start + std::time::Duration::from_secs(1)),
+            Ok(())
+        );
+        assert_eq!(
+            tracker.get_metrics(),
+            &QuotaMetrics {
+                accepted_bytes: 200,
+                rejected_bytes: 60,
+                violation_count: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn chunk_larger_than_byte_rate_should_be_accepted_in_new_window() {
+        let mut tracker = QuotaTracker::new(Quota {
+            max_bytes_per_second: Some(100),
+            ..Quota::default()
+        });
+        let start = std::time::Instant::now();
+
+        assert_eq!(tracker.check(250, 0, start), Ok(()));
+        assert_eq!(
+            tracker.check(1, 0, start + std::time::Duration::from_millis(500)),
+            Err(QuotaViolation::ByteRate(100))
+        );
+        assert_eq!(
+            tracker.check(250, 0, start + std::time::Duration::from_secs(1)),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn buffered_bytes_should_be_limited() {
+        let mut tracker = QuotaTracker::new(Quota {
+            max_buffered_bytes: Some(50),
+            ..Quota::default()
+        });
+        let now = std::time::Instant::now();
+
+        assert_eq!(tracker.check(20, 30, now), Ok(()));
+        assert_eq!(
+            tracker.check(21, 30, now),
+            Err(QuotaViolation::BufferedBytes(50))
+        );
+    }
+}
@@ -0,0 +1,174 @@
+use crate::data_provider::DataProvider;
+use crate::data_struct_reader::{Endianness, ReadEventError};
+use crate::event::Event;
+use crate::event_reader::EventReader;
+use crate::registry::EventKlassRegistry;
+
+// Event with undecoded payload. Only the header is decoded when the event
+// is read; the rest of the fields are decoded on the first call to decode().
+pub struct RawEvent {
+    klass_id: u32,
+    header: Event,
+    data: Vec<u8>,
+    endianness: Endianness,
+    event: Option<Event>,
+}
+
+impl RawEvent {
+    pub(crate) fn new(
+        klass_id: u32,
+        header: Event,
+        data: Vec<u8>,
+        endianness: Endianness,
+        event: Option<Event>,
+    ) -> RawEvent {
+        RawEvent {
+            klass_id,
+            header,
+            data,
+            endianness,
+            event,
+        }
+    }
+
+    pub fn get_klass_id(&self) -> u32 {
+        self.klass_id
+    }
+
+    // Base event (HT_Event) of the event.
+    pub fn get_header(&self) -> &Event {
+        &self.header
+    }
+
+    // Encoded event, including the header.
+    pub fn get_data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn get_endianness(&self) -> Endianness {
+        self.endianness
+    }
+
+    pub fn is_decoded(&self) -> bool {
+        self.event.is_some()
+    }
+
+    pub fn decode(&mut self, registry: &EventKlassRegistry) -> Result<&Event, ReadEventError> {
+        let event = match self.event.take() {
+            Some(event) => event,
+            None => self.decode_data(registry)?,
+        };
+
+        Ok(self.event.insert(event))
+    }
+
+    pub fn into_event(mut self, registry: &EventKlassRegistry) -> Result<Event, ReadEventError> {
+        match self.event.take() {
+            Some(event) => Ok(event),
+            None => self.decode_data(registry),
+        }
+    }
+
+    fn decode_data(&self, registry: &EventKlassRegistry) -> Result<Event, ReadEventError> {
+        let mut reader = EventReader::new(DataProvider::new(Box::new(std::io::Cursor::new(
+            self.data.clone(),
+        ))));
+        reader.set_endianness(self.endianness);
+        reader.read_data_event(registry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data_provider::DataProvider;
+    use crate::event::DataType;
+    use crate::event_klass::EventKlass;
+    use crate::event_reader::EventReader;
+    use crate::registry::EventKlassRegistry;
+    use hawktracer_parser_test_utilities::FakeDataReader;
+
+    fn make_reader(data: Vec<u8>) -> EventReader {
+        EventReader::new(DataProvider::new(Box::new(FakeDataReader::new(
+            data, false,
+        ))))
+    }
+
+    fn make_registry() -> EventKlassRegistry {
+        let mut registry = EventKlassRegistry::new();
+        let mut klass = EventKlass::new(100, "foo".to_owned());
+        klass.add_field("base".to_owned(), "HT_Event".to_owned(), DataType::Struct);
+        klass.add_field("str_field".to_owned(), "char*".to_owned(), DataType::Str);
+        klass.add_field("u32_field".to_owned(), "uint32_t".to_owned(), DataType::U32);
+        registry.add_klass(klass);
+        registry
+    }
+
+    #[test]
+    fn raw_event_should_be_decoded_on_demand() {
+        let data = vec![
+            100, 0, 0, 0, // type
+            1, 2, 0, 0, 0, 0, 0, 0, // timestamp
+            2, 0, 0, 0, 0, 0, 0, 0, // id
+            65, 66, 67, 0, // ABC
+            45, 1, 0, 0, // 301
+        ];
+        let mut registry = make_registry();
+        let mut reader = make_reader(data.clone());
+
+        let mut raw_event = reader.read_raw_event(&mut registry).unwrap();
+        assert_eq!(reader.get_position(), 28);
+        assert_eq!(raw_event.get_klass_id(), 100);
+        assert_eq!(
+            raw_event.get_header().get_value_u64("timestamp").unwrap(),
+            513
+        );
+        assert_eq!(raw_event.get_data(), &data[..]);
+        assert!(!raw_event.is_decoded());
+
+        let event = raw_event.decode(&registry).unwrap();
+        assert_eq!(event.get_value_string("str_field").unwrap(), "ABC");
+        assert_eq!(event.get_value_u32("u32_field").unwrap(), 301);
+        assert!(raw_event.is_decoded());
+
+        let event = raw_event.into_event(&registry).unwrap();
+        assert_eq!(
+            event
+                .get_value_struct("base")
+                .unwrap()
+                .get_value_u64("id")
+                .unwrap(),
+            2
+        );
+    }
+
+    #[test]
+    fn raw_metadata_events_should_update_registry() {
+        let mut data = vec![
+            2, 0, 0, 0, // type
+            1, 0, 0, 0, 0, 0, 0, 0, // timestamp
+            1, 0, 0, 0, 0, 0, 0, 0, // id
+            100, 0, 0, 0, // info_klass_id
+            102, 111, 111, 0, // foo
+            0, // field_count
+        ];
+        data.extend_from_slice(&[
+            100, 0, 0, 0, // type
+            2, 0, 0, 0, 0, 0, 0, 0, // timestamp
+            2, 0, 0, 0, 0, 0, 0, 0, // id
+        ]);
+        let mut registry = EventKlassRegistry::new();
+        let mut reader = make_reader(data);
+
+        let raw_event = reader.read_raw_event(&mut registry).unwrap();
+        assert!(raw_event.is_decoded());
+        assert_eq!(
+            raw_event.get_header().get_value_u64("timestamp").unwrap(),
+            1
+        );
+        assert!(registry.get_klass_by_name("foo").is_some());
+
+        let raw_event = reader.read_raw_event(&mut registry).unwrap();
+        assert_eq!(raw_event.get_klass_id(), 100);
+        assert_eq!(raw_event.get_data().len(), 20);
+    }
+}
@@ -1,5 +1,7 @@
 use crate::event::DataType;
-use crate::event_klass::EventKlass;
+use crate::event_klass::{EventKlass, EventKlassField};
+use crate::id_remap::IdRemapTable;
+use crate::registry_updater::RegistryUpdateError;
 
 #[derive(Copy, Clone)]
 pub enum CoreEventKlassId {
@@ -19,17 +21,74 @@ impl CoreEventKlassId {
         }
         false
     }
+
+    // Klasses of events describing other klasses (the registry is built from them).
+    pub fn is_metadata_klass(klass_id: u32) -> bool {
+        klass_id == CoreEventKlassId::KlassInfo as u32
+            || klass_id == CoreEventKlassId::FieldInfo as u32
+    }
+}
+
+// Definitions added to the registry by metadata events of the stream.
+pub enum RegistryChange<'a> {
+    KlassAdded(&'a EventKlass),
+    FieldAdded(&'a EventKlass, &'a EventKlassField),
 }
 
+// Observers are called while the registry is being updated by a reader, so
+// they shouldn't block (e.g. they can forward changes through a channel).
+pub type RegistryObserver = Box<dyn Fn(&RegistryChange) + Send + Sync>;
+
+// What happens when a metadata event defines a klass which is already in
+// the registry, e.g. because the traced process was restarted with a
+// different build.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum RedefinitionPolicy {
+    // The first definition is kept and later ones are ignored.
+    #[default]
+    Keep,
+    // The metadata event fails to update the registry.
+    Error,
+    // The new definition replaces the previous one.
+    Replace,
+    // Same as Replace, but previous definitions are kept and can be looked
+    // up by their generation.
+    Version,
+}
+
+// Klass which has a different number of fields than declared by its
+// KlassInfo event, e.g. because the metadata was truncated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldCountMismatch {
+    pub klass_id: u32,
+    pub declared: usize,
+    pub actual: usize,
+}
+
+// Klasses are looked up for every decoded event, so the map uses the
+// FNV hasher as Event values do. Names are indexed as well, since struct
+// fields reference their klass by name.
 #[derive(Default)]
 pub struct EventKlassRegistry {
-    klasses: std::collections::HashMap<u32, EventKlass>,
+    klasses: fnv::FnvHashMap<u32, EventKlass>,
+    klass_ids: fnv::FnvHashMap<String, u32>,
+    observers: Vec<RegistryObserver>,
+    redefinition_policy: RedefinitionPolicy,
+    previous_klasses: fnv::FnvHashMap<u32, Vec<EventKlass>>,
+    declared_field_counts: fnv::FnvHashMap<u32, usize>,
+    max_klass_count: Option<usize>,
 }
 
 impl EventKlassRegistry {
     pub fn new() -> EventKlassRegistry {
         let mut reg = EventKlassRegistry {
-            klasses: std::collections::HashMap::new(),
+            klasses: fnv::FnvHashMap::default(),
+            klass_ids: fnv::FnvHashMap::default(),
+            observers: Vec::new(),
+            redefinition_policy: RedefinitionPolicy::default(),
+            previous_klasses: fnv::FnvHashMap::default(),
+            declared_field_counts: fnv::FnvHashMap::default(),
+            max_klass_count: None,
         };
         reg.create_core_klasses();
         reg
@@ -45,7 +104,7 @@ impl EventKlassRegistry {
         for (name, type_name, data_type) in fields {
             klass.add_field(name.to_string(), type_name.to_string(), *data_type);
         }
-        self.klasses.insert(klass.get_id(), klass);
+        self.add_klass(klass);
     }
 
     fn create_core_klasses(&mut self) {
@@ -88,8 +147,161 @@ impl EventKlassRegistry {
         );
     }
 
+    // If several klasses share a name, the name refers to the first one added.
     pub fn add_klass(&mut self, klass: EventKlass) {
-        self.klasses.entry(klass.get_id()).or_insert(klass);
+        if let std::collections::hash_map::Entry::Vacant(entry) = self.klasses.entry(klass.get_id())
+        {
+            self.klass_ids
+                .entry(klass.get_name().clone())
+                .or_insert_with(|| klass.get_id());
+            entry.insert(klass);
+        }
+    }
+
+    // Adds a klass defined by a metadata event according to the redefinition
+    // policy. Returns false if the definition was ignored.
+    pub(crate) fn define_klass(&mut self, klass: EventKlass) -> Result<bool, RegistryUpdateError> {
+        let id = klass.get_id();
+        if !self.klasses.contains_key(&id) {
+            if let Some(limit) = self.max_klass_count {
+                if self.klasses.len() >= limit {
+                    return Err(RegistryUpdateError::TooManyKlasses { limit });
+                }
+            }
+            self.add_klass(klass);
+            return Ok(true);
+        }
+
+        match self.redefinition_policy {
+            RedefinitionPolicy::Keep => return Ok(false),
+            RedefinitionPolicy::Error => {
+                return Err(RegistryUpdateError::KlassAlreadyDefined { id })
+            }
+            RedefinitionPolicy::Replace | RedefinitionPolicy::Version => {}
+        }
+
+        let name = klass.get_name().clone();
+        let previous = match self.klasses.insert(id, klass) {
+            Some(previous) => previous,
+            None => return Ok(true),
+        };
+        if self.klass_ids.get(previous.get_name()) == Some(&id) {
+            self.klass_ids.remove(previous.get_name());
+        }
+        self.klass_ids.entry(name).or_insert(id);
+
+        if self.redefinition_policy == RedefinitionPolicy::Version {
+            self.previous_klasses.entry(id).or_default().push(previous);
+        }
+        Ok(true)
+    }
+
+    pub(crate) fn set_declared_field_count(&mut self, klass_id: u32, field_count: Option<usize>) {
+        match field_count {
+            Some(field_count) => self.declared_field_counts.insert(klass_id, field_count),
+            None => self.declared_field_counts.remove(&klass_id),
+        };
+    }
+
+    // Number of fields declared by the KlassInfo event of the klass.
+    pub fn get_declared_field_count(&self, klass_id: u32) -> Option<usize> {
+        self.declared_field_counts.get(&klass_id).copied()
+    }
+
+    // Klasses without a declared field count are always complete.
+    pub fn is_klass_complete(&self, klass_id: u32) -> bool {
+        match (
+            self.get_declared_field_count(klass_id),
+            self.get_klass_by_id(klass_id),
+        ) {
+            (Some(declared), Some(klass)) => klass.get_fields().len() == declared,
+            _ => true,
+        }
+    }
+
+    pub fn validate_field_counts(&self) -> Vec<FieldCountMismatch> {
+        let mut mismatches: Vec<FieldCountMismatch> = self
+            .declared_field_counts
+            .iter()
+            .filter_map(|(klass_id, declared)| {
+                let actual = self.get_klass_by_id(*klass_id)?.get_fields().len();
+                if actual == *declared {
+                    return None;
+                }
+                Some(FieldCountMismatch {
+                    klass_id: *klass_id,
+                    declared: *declared,
+                    actual,
+                })
+            })
+            .collect();
+        mismatches.sort_by_key(|mismatch| mismatch.klass_id);
+        mismatches
+    }
+
+    pub fn set_redefinition_policy(&mut self, policy: RedefinitionPolicy) {
+        self.redefinition_policy = policy;
+    }
+
+    pub fn get_redefinition_policy(&self) -> RedefinitionPolicy {
+        self.redefinition_policy
+    }
+
+    // Limits the number of klasses (including the core ones) metadata
+    // events can define; klasses added directly are not limited.
+    pub fn set_max_klass_count(&mut self, max_klass_count: Option<usize>) {
+        self.max_klass_count = max_klass_count;
+    }
+
+    pub fn get_max_klass_count(&self) -> Option<usize> {
+        self.max_klass_count
+    }
+
+    // The first definition of a klass has generation 0; only redefinitions
+    // made with RedefinitionPolicy::Version increment it.
+    pub fn get_klass_generation(&self, id: u32) -> Option<usize> {
+        self.klasses.get(&id).map(|_| {
+            self.previous_klasses
+                .get(&id)
+                .map_or(0, |previous| previous.len())
+        })
+    }
+
+    pub fn get_klass_by_generation(&self, id: u32, generation: usize) -> Option<&EventKlass> {
+        match self.previous_klasses.get(&id) {
+            Some(previous) if generation < previous.len() => previous.get(generation),
+            _ if Some(generation) == self.get_klass_generation(id) => self.get_klass_by_id(id),
+            _ => None,
+        }
+    }
+
+    // Customized tracers can use a different event header (e.g. with 32-bit
+    // timestamps or an additional thread id). The base klass has to be set
+    // before any event is read, and it needs a u32 "type" field. Struct fields
+    // refer to the base klass by its name, so it shouldn't be changed.
+    pub fn set_base_klass(&mut self, klass: EventKlass) -> Result<(), RegistryUpdateError> {
+        let id = CoreEventKlassId::Base as u32;
+        if klass.get_id() != id {
+            return Err(RegistryUpdateError::InvalidBaseKlassId { id: klass.get_id() });
+        }
+        match klass
+            .get_fields()
+            .iter()
+            .find(|field| field.get_name() == "type")
+        {
+            Some(field) if *field.get_data_type() == DataType::U32 => {}
+            _ => return Err(RegistryUpdateError::InvalidBaseTypeField),
+        }
+
+        if let Some(previous) = self.klasses.insert(id, klass) {
+            if self.klass_ids.get(previous.get_name()) == Some(&id) {
+                self.klass_ids.remove(previous.get_name());
+            }
+        }
+        if let Some(klass) = self.klasses.get(&id) {
+            self.klass_ids.insert(klass.get_name().clone(), id);
+        }
+        Ok(())
     }
 
     pub fn get_klass_by_id(&self, id: u32) -> Option<&EventKlass> {
@@ -100,13 +312,103 @@ impl EventKlassRegistry {
         self.klasses.get_mut(&id)
     }
 
+    pub fn add_observer(&mut self, observer: RegistryObserver) {
+        self.observers.push(observer);
+    }
+
+    pub(crate) fn notify_observers(&self, change: &RegistryChange) {
+        for observer in &self.observers {
+            observer(change);
+        }
+    }
+
+    pub fn get_klass_count(&self) -> usize {
+        self.klasses.len()
+    }
+
+    pub fn get_klasses(&self) -> Vec<&EventKlass> {
+        let mut klasses: Vec<&EventKlass> = self.klasses.values().collect();
+        klasses.sort_by_key(|klass| klass.get_id());
+        klasses
+    }
+
     pub fn get_klass_by_name(&self, name: &str) -> Option<&EventKlass> {
-        for (_, klass) in self.klasses.iter() {
-            if klass.get_name() == name {
-                return Some(klass);
+        self.klass_ids.get(name).and_then(|id| self.klasses.get(id))
+    }
+
+    // Adds klasses of another registry (e.g. of a trace of another process).
+    // Klasses with the same name and fields as existing ones are shared,
+    // others which ids are already taken get new ids; events of the other
+    // registry have to be rewritten with the returned table. Struct fields
+    // refer to klasses by name, so the merge fails (and the registry is left
+    // unchanged) if a klass has the name of an existing klass with different
+    // fields.
+    pub fn merge(
+        &mut self,
+        other: &EventKlassRegistry,
+    ) -> Result<IdRemapTable, RegistryUpdateError> {
+        let klasses: Vec<&EventKlass> = other
+            .get_klasses()
+            .into_iter()
+            .filter(|klass| !CoreEventKlassId::is_core_klass(klass.get_id()))
+            .collect();
+
+        for klass in &klasses {
+            let mut existing = self
+                .klasses
+                .values()
+                .filter(|existing| existing.get_name() == klass.get_name())
+                .peekable();
+            if existing.peek().is_some()
+                && existing.all(|existing| existing.get_fields() != klass.get_fields())
+            {
+                return Err(RegistryUpdateError::KlassNameConflict {
+                    name: klass.get_name().clone(),
+                });
+            }
+        }
+
+        let mut table = IdRemapTable::new();
+        for klass in klasses {
+            let id = klass.get_id();
+            let existing = self.klasses.values().find(|existing| {
+                existing.get_name() == klass.get_name()
+                    && existing.get_fields() == klass.get_fields()
+            });
+            if let Some(existing) = existing {
+                table.insert(id, existing.get_id());
+                continue;
             }
+
+            // The first free id following the original one.
+            let new_id = (id..=u32::MAX)
+                .chain(0..id)
+                .find(|new_id| !self.klasses.contains_key(new_id))
+                .unwrap_or(id);
+            self.add_klass(klass.clone().with_id(new_id));
+            table.insert(id, new_id);
+        }
+        Ok(table)
+    }
+
+    // Core klasses are not exported, they're always part of the registry.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        let klasses: Vec<&EventKlass> = self
+            .get_klasses()
+            .into_iter()
+            .filter(|klass| !CoreEventKlassId::is_core_klass(klass.get_id()))
+            .collect();
+        serde_json::to_string_pretty(&klasses).unwrap_or_default()
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn from_json(json: &str) -> Result<EventKlassRegistry, serde_json::Error> {
+        let mut registry = EventKlassRegistry::new();
+        for klass in serde_json::from_str::<Vec<EventKlass>>(json)? {
+            registry.add_klass(klass);
         }
-        None
+        Ok(registry)
     }
 }
 
@@ -132,6 +434,230 @@ mod tests {
         assert!(registry.get_klass_by_id_mut(klass_id).is_some());
     }
 
+    #[test]
+    fn get_klass_by_name_should_return_first_klass_added_with_the_name() {
+        let mut registry = EventKlassRegistry::new();
+        registry.add_klass(EventKlass::new(99, String::from("test_name")));
+        registry.add_klass(EventKlass::new(98, String::from("test_name")));
+        registry.add_klass(EventKlass::new(99, String::from("other_name")));
+
+        assert_eq!(
+            registry.get_klass_by_name("test_name").unwrap().get_id(),
+            99
+        );
+        assert!(registry.get_klass_by_name("other_name").is_none());
+        assert_eq!(registry.get_klass_by_name("HT_Event").unwrap().get_id(), 1);
+    }
+
+    fn define_klasses(policy: RedefinitionPolicy) -> (EventKlassRegistry, Vec<bool>) {
+        let mut registry = EventKlassRegistry::new();
+        registry.set_redefinition_policy(policy);
+        let mut results = Vec::new();
+        for name in &["foo", "bar", "baz"] {
+            results.push(
+                registry
+                    .define_klass(EventKlass::new(99, name.to_string()))
+                    .unwrap(),
+            );
+        }
+        (registry, results)
+    }
+
+    #[test]
+    fn redefinition_should_be_ignored_by_default() {
+        let (registry, results) = define_klasses(RedefinitionPolicy::default());
+
+        assert_eq!(results, vec![true, false, false]);
+        assert_eq!(registry.get_klass_by_id(99).unwrap().get_name(), "foo");
+        assert_eq!(registry.get_klass_generation(99), Some(0));
+    }
+
+    #[test]
+    fn redefinition_should_fail_with_error_policy() {
+        let mut registry = EventKlassRegistry::new();
+        registry.set_redefinition_policy(RedefinitionPolicy::Error);
+
+        assert!(registry
+            .define_klass(EventKlass::new(99, "foo".to_owned()))
+            .is_ok());
+        assert_eq!(
+            registry.define_klass(EventKlass::new(99, "foo".to_owned())),
+            Err(RegistryUpdateError::KlassAlreadyDefined { id: 99 })
+        );
+        assert_eq!(registry.get_klass_by_id(99).unwrap().get_name(), "foo");
+    }
+
+    #[test]
+    fn redefinition_should_replace_klass_with_replace_policy() {
+        let (registry, results) = define_klasses(RedefinitionPolicy::Replace);
+
+        assert_eq!(results, vec![true, true, true]);
+        assert_eq!(registry.get_klass_by_id(99).unwrap().get_name(), "baz");
+        assert!(registry.get_klass_by_name("foo").is_none());
+        assert_eq!(registry.get_klass_by_name("baz").unwrap().get_id(), 99);
+        assert_eq!(registry.get_klass_generation(99), Some(0));
+        assert!(registry.get_klass_by_generation(99, 1).is_none());
+    }
+
+    #[test]
+    fn define_klass_should_fail_if_klass_count_limit_is_reached() {
+        let mut registry = EventKlassRegistry::new();
+        registry.set_max_klass_count(Some(5));
+
+        assert_eq!(
+            registry.define_klass(EventKlass::new(99, "foo".to_owned())),
+            Ok(true)
+        );
+        assert_eq!(
+            registry.define_klass(EventKlass::new(99, "bar".to_owned())),
+            Ok(false)
+        );
+        assert_eq!(
+            registry.define_klass(EventKlass::new(100, "bar".to_owned())),
+            Err(RegistryUpdateError::TooManyKlasses { limit: 5 })
+        );
+        assert_eq!(registry.get_klass_count(), 5);
+    }
+
+    #[test]
+    fn redefinition_should_keep_previous_klasses_with_version_policy() {
+        let (registry, _) = define_klasses(RedefinitionPolicy::Version);
+
+        assert_eq!(registry.get_klass_generation(99), Some(2));
+        assert_eq!(registry.get_klass_generation(98), None);
+        for (generation, name) in ["foo", "bar", "baz"].iter().enumerate() {
+            let klass = registry.get_klass_by_generation(99, generation).unwrap();
+            assert_eq!(klass.get_name(), name);
+        }
+        assert!(registry.get_klass_by_generation(99, 3).is_none());
+        assert_eq!(registry.get_klass_by_id(99).unwrap().get_name(), "baz");
+    }
+
+    fn make_klass(id: u32, name: &str, field_type: DataType) -> EventKlass {
+        let mut klass = EventKlass::new(id, name.to_owned());
+        klass.add_field("value".to_owned(), "uint32_t".to_owned(), field_type);
+        klass
+    }
+
+    #[test]
+    fn merge_should_remap_colliding_klass_ids() {
+        let mut registry = EventKlassRegistry::new();
+        registry.add_klass(make_klass(100, "foo", DataType::U32));
+        registry.add_klass(make_klass(101, "bar", DataType::U32));
+
+        let mut other = EventKlassRegistry::new();
+        other.add_klass(make_klass(100, "bar", DataType::U32));
+        other.add_klass(make_klass(101, "baz", DataType::U32));
+        other.add_klass(make_klass(102, "foo", DataType::U32));
+        other.add_klass(make_klass(200, "qux", DataType::U32));
+
+        let table = registry.merge(&other).unwrap();
+
+        assert_eq!(table.get_klass_id(100), 101);
+        assert_eq!(table.get_klass_id(101), 102);
+        assert_eq!(table.get_klass_id(102), 100);
+        assert_eq!(table.get_klass_id(200), 200);
+        assert_eq!(table.get_klass_id(1), 1);
+        assert_eq!(table.get_remapped_count(), 3);
+
+        assert_eq!(registry.get_klass_count(), 8);
+        assert_eq!(registry.get_klass_by_id(102).unwrap().get_name(), "baz");
+        assert_eq!(registry.get_klass_by_name("baz").unwrap().get_id(), 102);
+
+        assert_eq!(registry.merge(&other).unwrap(), table);
+        assert_eq!(registry.get_klass_count(), 8);
+    }
+
+    #[test]
+    fn merge_should_fail_for_klass_name_with_different_fields() {
+        let mut registry = EventKlassRegistry::new();
+        registry.add_klass(make_klass(100, "foo", DataType::U32));
+
+        let mut other = EventKlassRegistry::new();
+        other.add_klass(make_klass(100, "bar", DataType::U32));
+        other.add_klass(make_klass(101, "foo", DataType::U8));
+
+        assert_eq!(
+            registry.merge(&other),
+            Err(RegistryUpdateError::KlassNameConflict {
+                name: "foo".to_owned()
+            })
+        );
+        assert_eq!(registry.get_klass_count(), 5);
+        assert!(registry.get_klass_by_name("bar").is_none());
+    }
+
+    #[test]
+    fn set_base_klass_should_replace_base_klass() {
+        let mut registry = EventKlassRegistry::new();
+        let mut klass = EventKlass::new(CoreEventKlassId::Base as u32, "HT_Event".to_owned());
+        klass.add_field("type".to_owned(), "uint32_t".to_owned(), DataType::U32);
+        klass.add_field("timestamp".to_owned(), "uint32_t".to_owned(), DataType::U32);
+
+        assert!(registry.set_base_klass(klass).is_ok());
+
+        let base_klass = registry.get_klass_by_name("HT_Event").unwrap();
+        assert_eq!(base_klass.get_fields().len(), 2);
+        assert_eq!(*base_klass.get_fields()[1].get_data_type(), DataType::U32);
+    }
+
+    #[test]
+    fn set_base_klass_should_fail_for_invalid_klass() {
+        let mut registry = EventKlassRegistry::new();
+        let mut klass = EventKlass::new(99, "HT_Event".to_owned());
+        klass.add_field("type".to_owned(), "uint32_t".to_owned(), DataType::U32);
+        assert_eq!(
+            registry.set_base_klass(klass),
+            Err(RegistryUpdateError::InvalidBaseKlassId { id: 99 })
+        );
+
+        let mut klass = EventKlass::new(CoreEventKlassId::Base as u32, "HT_Event".to_owned());
+        klass.add_field("type".to_owned(), "uint64_t".to_owned(), DataType::U64);
+        assert_eq!(
+            registry.set_base_klass(klass),
+            Err(RegistryUpdateError::InvalidBaseTypeField)
+        );
+
+        assert_eq!(
+            registry
+                .get_klass_by_id(CoreEventKlassId::Base as u32)
+                .unwrap()
+                .get_fields()
+                .len(),
+            3
+        );
+    }
+
+    #[test]
+    fn validate_field_counts_should_report_incomplete_klasses() {
+        let mut registry = EventKlassRegistry::new();
+        registry.add_klass(EventKlass::new(99, String::from("foo")));
+        registry.add_klass(EventKlass::new(100, String::from("bar")));
+        registry.set_declared_field_count(99, Some(1));
+        registry.set_declared_field_count(100, Some(0));
+        registry.set_declared_field_count(101, Some(3));
+
+        assert!(!registry.is_klass_complete(99));
+        assert!(registry.is_klass_complete(100));
+        assert!(registry.is_klass_complete(1));
+        assert_eq!(
+            registry.validate_field_counts(),
+            vec![FieldCountMismatch {
+                klass_id: 99,
+                declared: 1,
+                actual: 0
+            }]
+        );
+
+        registry.get_klass_by_id_mut(99).unwrap().add_field(
+            "a".to_owned(),
+            "uint8_t".to_owned(),
+            DataType::U8,
+        );
+        assert!(registry.is_klass_complete(99));
+        assert!(registry.validate_field_counts().is_empty());
+    }
+
     #[test]
     fn get_klass_by_name_should_be_none_if_not_exists() {
         let registry = EventKlassRegistry::new();
@@ -139,6 +665,15 @@ mod tests {
         assert!(registry.get_klass_by_name("test").is_none());
     }
 
+    #[test]
+    fn get_klasses_should_return_klasses_sorted_by_id() {
+        let mut registry = EventKlassRegistry::new();
+        registry.add_klass(EventKlass::new(99, String::from("test_name")));
+
+        let ids: Vec<u32> = registry.get_klasses().iter().map(|k| k.get_id()).collect();
+        assert_eq!(ids, vec![0, 1, 2, 3, 99]);
+    }
+
     #[test]
     fn check_core_event_klasses() {
         for i in 1..4 {
@@ -146,5 +681,27 @@ mod tests {
         }
         assert!(!CoreEventKlassId::is_core_klass(5));
         assert!(!CoreEventKlassId::is_core_klass(99));
+
+        assert!(CoreEventKlassId::is_metadata_klass(2));
+        assert!(CoreEventKlassId::is_metadata_klass(3));
+        assert!(!CoreEventKlassId::is_metadata_klass(1));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn registry_exported_to_json_should_be_imported() {
+        let mut registry = EventKlassRegistry::new();
+        let mut klass = EventKlass::new(99, String::from("test_name"));
+        klass.add_field("base".to_owned(), "HT_Event".to_owned(), DataType::Struct);
+        klass.add_field("value".to_owned(), "uint32_t".to_owned(), DataType::U32);
+        registry.add_klass(klass);
+
+        let json = registry.to_json();
+        let imported = EventKlassRegistry::from_json(&json).unwrap();
+
+        assert_eq!(imported.get_klass_count(), registry.get_klass_count());
+        assert_eq!(imported.get_klass_by_id(99), registry.get_klass_by_id(99));
+        assert!(!json.contains("HT_EventKlassInfoEvent"));
+        assert!(EventKlassRegistry::from_json("[{\"id\": 5}]").is_err());
     }
 }
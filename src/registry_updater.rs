@@ -1,8 +1,211 @@
-use crate::event::DataType;
 use crate::event::Event;
-use crate::event_klass::EventKlass;
+use crate::event::{ArrayLength, DataType, IntegerType};
+use crate::event_klass::{EventKlass, EventKlassField};
 use crate::registry::CoreEventKlassId;
-use crate::registry::EventKlassRegistry;
+use crate::registry::{EventKlassRegistry, RegistryChange};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RegistryUpdateError {
+    // Event is neither a KlassInfo nor a FieldInfo event.
+    UnexpectedKlass { id: u32 },
+    MissingField { name: String },
+    // Unknown data type identifier of a field without a size.
+    UnknownDataType { value: u8 },
+    // Size of the field doesn't fit its data type.
+    InvalidFieldSize { name: String, size: u64 },
+    KlassNotFound { id: u32 },
+    // Only with RedefinitionPolicy::Error.
+    KlassAlreadyDefined { id: u32 },
+    TooManyFields { id: u32, declared: usize },
+    // Limit set by EventKlassRegistry::set_max_klass_count().
+    TooManyKlasses { limit: usize },
+    // Klass of a merged registry has the name of a klass with other fields.
+    KlassNameConflict { name: String },
+    // Returned by EventKlassRegistry::set_base_klass().
+    InvalidBaseKlassId { id: u32 },
+    InvalidBaseTypeField,
+}
+
+impl std::fmt::Display for RegistryUpdateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RegistryUpdateError::UnexpectedKlass { id } => {
+                write!(f, "Klass id {} is neither KlassInfo nor FieldInfo", id)
+            }
+            RegistryUpdateError::MissingField { name } => write!(f, "Missing field {}", name),
+            RegistryUpdateError::UnknownDataType { value } => {
+                write!(f, "Unknown data type {}", value)
+            }
+            RegistryUpdateError::InvalidFieldSize { name, size } => {
+                write!(f, "Invalid size {} of field {}", size, name)
+            }
+            RegistryUpdateError::KlassNotFound { id } => write!(f, "Cannot find klass {}", id),
+            RegistryUpdateError::KlassAlreadyDefined { id } => {
+                write!(f, "Klass {} is already defined", id)
+            }
+            RegistryUpdateError::TooManyFields { id, declared } => {
+                write!(
+                    f,
+                    "Klass {} has more fields than declared ({})",
+                    id, declared
+                )
+            }
+            RegistryUpdateError::TooManyKlasses { limit } => {
+                write!(f, "Registry can't have more than {} klasses", limit)
+            }
+            RegistryUpdateError::KlassNameConflict { name } => {
+                write!(f, "Klass {} is already defined with different fields", name)
+            }
+            RegistryUpdateError::InvalidBaseKlassId { id } => {
+                write!(f, "Invalid base klass id {}", id)
+            }
+            RegistryUpdateError::InvalidBaseTypeField => {
+                write!(f, "Base klass must have type field of uint32_t type")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RegistryUpdateError {}
+
+fn missing_field(name: &str) -> RegistryUpdateError {
+    RegistryUpdateError::MissingField {
+        name: name.to_owned(),
+    }
+}
+
+// Data type identifier written for opaque fields. Any identifier unknown to
+// the updater is read back as DataType::Bytes.
+pub(crate) const OPAQUE_DATA_TYPE_ID: u8 = 0;
+
+// Data types of fields defined by FieldInfo events (MKCREFLECT_Types, and
+// the unsigned integer type added by HawkTracer).
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub(crate) enum MkcreflectType {
+    Struct = 1,
+    String = 2,
+    Integer = 3,
+    Float = 4,
+    Double = 5,
+    Pointer = 6,
+    UnsignedInteger = 99,
+}
+
+impl MkcreflectType {
+    pub(crate) const ALL: [MkcreflectType; 7] = [
+        MkcreflectType::Struct,
+        MkcreflectType::String,
+        MkcreflectType::Integer,
+        MkcreflectType::Float,
+        MkcreflectType::Double,
+        MkcreflectType::Pointer,
+        MkcreflectType::UnsignedInteger,
+    ];
+
+    pub(crate) fn from_id(id: u8) -> Option<MkcreflectType> {
+        MkcreflectType::ALL
+            .iter()
+            .find(|data_type| **data_type as u8 == id)
+            .copied()
+    }
+
+    // Identifier written for fields of the given type; opaque fields use
+    // OPAQUE_DATA_TYPE_ID.
+    pub(crate) fn from_data_type(data_type: DataType) -> Option<MkcreflectType> {
+        let integer_type = match data_type {
+            DataType::Struct => return Some(MkcreflectType::Struct),
+            DataType::Str => return Some(MkcreflectType::String),
+            DataType::Bytes(_) => return None,
+            DataType::Array(element_type, _) => element_type,
+            data_type => IntegerType::from_data_type(data_type)?,
+        };
+        match integer_type {
+            IntegerType::I8 | IntegerType::I16 | IntegerType::I32 | IntegerType::I64 => {
+                Some(MkcreflectType::Integer)
+            }
+            _ => Some(MkcreflectType::UnsignedInteger),
+        }
+    }
+
+    // Floating point values are carried as opaque bytes as there's no data
+    // type for them.
+    fn get_data_type(self, type_name: &str, size: u64) -> Option<DataType> {
+        match self {
+            MkcreflectType::Struct => Some(DataType::Struct),
+            MkcreflectType::String => Some(DataType::Str),
+            MkcreflectType::Integer => get_integer_type(type_name, size, true),
+            MkcreflectType::UnsignedInteger => get_integer_type(type_name, size, false),
+            MkcreflectType::Pointer => match size {
+                4 => Some(DataType::U32),
+                8 => Some(DataType::U64),
+                _ => None,
+            },
+            _ => get_opaque_type(size),
+        }
+    }
+}
+
+fn get_opaque_type(size: u64) -> Option<DataType> {
+    std::convert::TryFrom::try_from(size)
+        .ok()
+        .map(DataType::Bytes)
+}
+
+fn get_sized_integer_type(size: u64, signed: bool) -> Option<IntegerType> {
+    match (size, signed) {
+        (1, false) => Some(IntegerType::U8),
+        (1, true) => Some(IntegerType::I8),
+        (2, false) => Some(IntegerType::U16),
+        (2, true) => Some(IntegerType::I16),
+        (4, false) => Some(IntegerType::U32),
+        (4, true) => Some(IntegerType::I32),
+        (8, false) => Some(IntegerType::U64),
+        (8, true) => Some(IntegerType::I64),
+        _ => None,
+    }
+}
+
+// Integer arrays are described by the element type name followed by the
+// element count in brackets, e.g. "uint32_t[4]" (the size is the size of the
+// whole array), or by empty brackets for arrays prefixed by their count,
+// e.g. "uint32_t[]" (the size is the size of an element).
+fn get_array_type(type_name: &str, size: u64, signed: bool) -> Option<DataType> {
+    let start = type_name.rfind('[')?;
+    let (element_size, length) = match type_name[start + 1..type_name.len() - 1].trim() {
+        "" => (size, ArrayLength::Prefixed),
+        count => match count.parse::<u64>() {
+            Ok(count) if count > 0 && size % count == 0 => {
+                let fixed_count = std::convert::TryFrom::try_from(count).ok()?;
+                (size / count, ArrayLength::Fixed(fixed_count))
+            }
+            _ => return None,
+        },
+    };
+    get_sized_integer_type(element_size, signed)
+        .map(|element_type| DataType::Array(element_type, length))
+}
+
+// The type name is used if it's consistent with the size, as the data type
+// identifier may not match the actual signedness. Otherwise the integer
+// type is derived from the size.
+fn get_integer_type(type_name: &str, size: u64, signed: bool) -> Option<DataType> {
+    let named_type = DataType::from_type_name(type_name).filter(|data_type| {
+        let named_size = match data_type {
+            DataType::Array(element_type, ArrayLength::Prefixed) => Some(element_type.get_size()),
+            DataType::Array(_, ArrayLength::Fixed(_)) => data_type.get_fixed_size(),
+            data_type => IntegerType::from_data_type(*data_type).map(IntegerType::get_size),
+        };
+        named_size.map(|named_size| named_size as u64) == Some(size)
+    });
+    if named_type.is_some() {
+        return named_type;
+    }
+
+    if type_name.ends_with(']') {
+        return get_array_type(type_name, size, signed);
+    }
+    get_sized_integer_type(size, signed).map(IntegerType::get_data_type)
+}
 
 pub struct RegistryUpdater<'a> {
     registry: &'a mut EventKlassRegistry,
@@ -13,18 +216,18 @@ impl<'a> RegistryUpdater<'a> {
         RegistryUpdater { registry }
     }
 
-    pub fn update_registry_from_event(&mut self, event: &Event) -> Result<(), &'static str> {
+    pub fn update_registry_from_event(&mut self, event: &Event) -> Result<(), RegistryUpdateError> {
         match event.get_klass_id() {
-            x if x == CoreEventKlassId::KlassInfo as u32 => self.add_new_klass(&event),
-            x if x == CoreEventKlassId::FieldInfo as u32 => self.add_klass_field(&event),
-            _ => Err("Klass id is neither KlassInfo nor FieldInfo"),
+            x if x == CoreEventKlassId::KlassInfo as u32 => self.add_new_klass(event),
+            x if x == CoreEventKlassId::FieldInfo as u32 => self.add_klass_field(event),
+            id => Err(RegistryUpdateError::UnexpectedKlass { id }),
         }
     }
 
-    fn add_new_klass(&mut self, event: &Event) -> Result<(), &'static str> {
+    fn add_new_klass(&mut self, event: &Event) -> Result<(), RegistryUpdateError> {
         let klass_id = match event.get_value_u32("info_klass_id") {
             Ok(value) => value,
-            Err(_) => return Err("Cannot read klass id"),
+            Err(_) => return Err(missing_field("info_klass_id")),
         };
 
         if CoreEventKlassId::is_core_klass(klass_id) {
@@ -33,18 +236,36 @@ impl<'a> RegistryUpdater<'a> {
 
         let klass_name = match event.get_value_string("event_klass_name") {
             Ok(value) => value.clone(),
-            Err(_) => return Err("Cannot read klass name"),
+            Err(_) => return Err(missing_field("event_klass_name")),
         };
 
+        if !self
+            .registry
+            .define_klass(EventKlass::new(klass_id, klass_name.clone()))?
+        {
+            debug!(
+                "Ignored redefinition of klass {} ({})",
+                klass_id, klass_name
+            );
+            return Ok(());
+        }
+        debug!("Registered klass {} ({})", klass_id, klass_name);
+        // Redefined klasses don't inherit the field count of the previous
+        // definition.
+        let field_count = event.get_value_u8("field_count").ok();
         self.registry
-            .add_klass(EventKlass::new(klass_id, klass_name));
+            .set_declared_field_count(klass_id, field_count.map(usize::from));
+        if let Some(klass) = self.registry.get_klass_by_id(klass_id) {
+            self.registry
+                .notify_observers(&RegistryChange::KlassAdded(klass));
+        }
         Ok(())
     }
 
-    fn add_klass_field(&mut self, event: &Event) -> Result<(), &'static str> {
+    fn add_klass_field(&mut self, event: &Event) -> Result<(), RegistryUpdateError> {
         let klass_id = match event.get_value_u32("info_klass_id") {
             Ok(value) => value,
-            Err(_) => return Err("Cannot read klass id"),
+            Err(_) => return Err(missing_field("info_klass_id")),
         };
 
         if CoreEventKlassId::is_core_klass(klass_id) {
@@ -53,43 +274,85 @@ impl<'a> RegistryUpdater<'a> {
 
         let field_name = match event.get_value_string("field_name") {
             Ok(value) => value.clone(),
-            Err(_) => return Err("Cannot read field name"),
+            Err(_) => return Err(missing_field("field_name")),
         };
 
         let type_name = match event.get_value_string("field_type") {
             Ok(value) => value.clone(),
-            Err(_) => return Err("Cannot read field type"),
+            Err(_) => return Err(missing_field("field_type")),
         };
 
-        let data_type = match event.get_value_u8("data_type") {
-            Ok(value) => match value {
-                1 => DataType::Struct,
-                2 => DataType::Str,
-                6 => DataType::U64, // TODO it's a pointer!
-                99 => {
-                    if let Ok(size) = event.get_value_u64("size") {
-                        match size {
-                            1 => DataType::U8,
-                            4 => DataType::U32,
-                            8 => DataType::U64,
-                            _ => return Err("Invalid size of integer type"),
-                        }
-                    } else {
-                        return Err("Cannot read field size");
+        let size = event.get_value_u64("size").ok();
+        let data_type_id = match event.get_value_u8("data_type") {
+            Ok(value) => value,
+            Err(_) => return Err(missing_field("data_type")),
+        };
+        let data_type = match (MkcreflectType::from_id(data_type_id), size) {
+            (Some(MkcreflectType::Struct), _) => DataType::Struct,
+            (Some(MkcreflectType::String), _) => DataType::Str,
+            (Some(_), None) => return Err(missing_field("size")),
+            (None, None) => {
+                return Err(RegistryUpdateError::UnknownDataType {
+                    value: data_type_id,
+                })
+            }
+            (data_type, Some(size)) => {
+                let data_type = match data_type {
+                    Some(data_type) => data_type.get_data_type(&type_name, size),
+                    // Fields of unknown types are carried as opaque bytes.
+                    None => get_opaque_type(size),
+                };
+                match data_type {
+                    Some(data_type) => data_type,
+                    None => {
+                        return Err(RegistryUpdateError::InvalidFieldSize {
+                            name: field_name,
+                            size,
+                        })
                     }
                 }
-                _ => return Err("Invalid data type"),
-            },
-            Err(_) => return Err("Cannot read field data type"),
+            }
         };
 
-        match self.registry.get_klass_by_id_mut(klass_id) {
+        let mut field = EventKlassField::new(field_name, type_name, data_type);
+        if let Some(size) = size {
+            field = field.with_declared_size(size);
+        }
+
+        let declared_field_count = self.registry.get_declared_field_count(klass_id);
+        let field_count = match self.registry.get_klass_by_id_mut(klass_id) {
             Some(klass) => {
-                klass.add_field(field_name, type_name, data_type);
-                Ok(())
+                let field_count = klass.get_fields().len();
+                let is_new_field = klass
+                    .get_fields()
+                    .iter()
+                    .all(|existing_field| existing_field.get_name() != field.get_name());
+                if is_new_field && declared_field_count == Some(field_count) {
+                    return Err(RegistryUpdateError::TooManyFields {
+                        id: klass_id,
+                        declared: field_count,
+                    });
+                }
+                klass.add_klass_field(field);
+                field_count
+            }
+            None => return Err(RegistryUpdateError::KlassNotFound { id: klass_id }),
+        };
+
+        if let Some(klass) = self.registry.get_klass_by_id(klass_id) {
+            if let Some(field) = klass.get_fields().get(field_count) {
+                trace!(
+                    "Added field {} ({:?}) to klass {} ({})",
+                    field.get_name(),
+                    field.get_data_type(),
+                    klass_id,
+                    klass.get_name()
+                );
+                self.registry
+                    .notify_observers(&RegistryChange::FieldAdded(klass, field));
             }
-            None => Err("Cannot find klass"),
         }
+        Ok(())
     }
 }
 
@@ -103,19 +366,16 @@ mod tests {
         name: Option<&str>,
         field_count: Option<u8>,
     ) -> Event {
-        let mut values = std::collections::HashMap::new();
+        let mut values = fnv::FnvHashMap::default();
 
-        if id.is_some() {
-            values.insert("info_klass_id".to_string(), Value::U32(id.unwrap()));
+        if let Some(id) = id {
+            values.insert("info_klass_id".to_string(), Value::U32(id));
         }
-        if name.is_some() {
-            values.insert(
-                "event_klass_name".to_string(),
-                Value::Str(name.unwrap().to_string()),
-            );
+        if let Some(name) = name {
+            values.insert("event_klass_name".to_string(), Value::Str(name.to_string()));
         }
-        if field_count.is_some() {
-            values.insert("field_count".to_string(), Value::U8(field_count.unwrap()));
+        if let Some(field_count) = field_count {
+            values.insert("field_count".to_string(), Value::U8(field_count));
         }
 
         Event::new(CoreEventKlassId::KlassInfo as u32, values)
@@ -128,28 +388,22 @@ mod tests {
         size: Option<u64>,
         data_type: Option<u8>,
     ) -> Event {
-        let mut values = std::collections::HashMap::new();
+        let mut values = fnv::FnvHashMap::default();
 
-        if klass_id.is_some() {
-            values.insert("info_klass_id".to_string(), Value::U32(klass_id.unwrap()));
+        if let Some(klass_id) = klass_id {
+            values.insert("info_klass_id".to_string(), Value::U32(klass_id));
         }
-        if field_type.is_some() {
-            values.insert(
-                "field_type".to_string(),
-                Value::Str(field_type.unwrap().to_string()),
-            );
+        if let Some(field_type) = field_type {
+            values.insert("field_type".to_string(), Value::Str(field_type.to_string()));
         }
-        if field_name.is_some() {
-            values.insert(
-                "field_name".to_string(),
-                Value::Str(field_name.unwrap().to_string()),
-            );
+        if let Some(field_name) = field_name {
+            values.insert("field_name".to_string(), Value::Str(field_name.to_string()));
         }
-        if size.is_some() {
-            values.insert("size".to_string(), Value::U64(size.unwrap()));
+        if let Some(size) = size {
+            values.insert("size".to_string(), Value::U64(size));
         }
-        if data_type.is_some() {
-            values.insert("data_type".to_string(), Value::U8(data_type.unwrap()));
+        if let Some(data_type) = data_type {
+            values.insert("data_type".to_string(), Value::U8(data_type));
         }
 
         Event::new(CoreEventKlassId::FieldInfo as u32, values)
@@ -159,9 +413,12 @@ mod tests {
     fn should_fail_if_event_is_not_field_or_klass_info_event() {
         let mut registry = EventKlassRegistry::new();
         let mut updater = RegistryUpdater::new(&mut registry);
-        let event = Event::new(99, std::collections::HashMap::new());
+        let event = Event::new(99, fnv::FnvHashMap::default());
 
-        assert!(updater.update_registry_from_event(&event).is_err());
+        assert_eq!(
+            updater.update_registry_from_event(&event),
+            Err(RegistryUpdateError::UnexpectedKlass { id: 99 })
+        );
     }
 
     #[test]
@@ -179,6 +436,39 @@ mod tests {
         assert!(registry.get_klass_by_name("name").is_some());
     }
 
+    #[test]
+    fn add_field_beyond_declared_field_count_should_fail() {
+        let mut registry = EventKlassRegistry::new();
+
+        {
+            let mut updater = RegistryUpdater::new(&mut registry);
+            updater
+                .update_registry_from_event(&make_klass_info_event(Some(99), Some("name"), Some(1)))
+                .unwrap();
+            let field =
+                make_field_info_event(Some(99), Some("uint8_t"), Some("a"), Some(1), Some(3));
+            updater.update_registry_from_event(&field).unwrap();
+            // Duplicated fields are still ignored.
+            updater.update_registry_from_event(&field).unwrap();
+            assert_eq!(
+                updater.update_registry_from_event(&make_field_info_event(
+                    Some(99),
+                    Some("uint8_t"),
+                    Some("b"),
+                    Some(1),
+                    Some(3)
+                )),
+                Err(RegistryUpdateError::TooManyFields {
+                    id: 99,
+                    declared: 1
+                })
+            );
+        }
+
+        assert_eq!(registry.get_declared_field_count(99), Some(1));
+        assert!(registry.is_klass_complete(99));
+    }
+
     #[test]
     fn add_new_klass_to_registry_if_some_fields_are_missing_should_fail() {
         let mut registry = EventKlassRegistry::new();
@@ -225,7 +515,10 @@ mod tests {
         {
             let mut updater = RegistryUpdater::new(&mut registry);
             let event = make_field_info_event(Some(99), Some("t"), Some("n"), Some(4), Some(99));
-            assert!(updater.update_registry_from_event(&event).is_err());
+            assert_eq!(
+                updater.update_registry_from_event(&event),
+                Err(RegistryUpdateError::KlassNotFound { id: 99 })
+            );
         }
     }
 
@@ -237,51 +530,58 @@ mod tests {
             .update_registry_from_event(&make_klass_info_event(Some(99), Some("name"), Some(10)))
             .is_ok());
 
-        assert!(updater
-            .update_registry_from_event(&make_field_info_event(
-                None,
-                Some("t"),
-                Some("n"),
-                Some(4),
-                Some(1)
-            ))
-            .is_err());
-        assert!(updater
-            .update_registry_from_event(&make_field_info_event(
-                Some(99),
-                None,
-                Some("n"),
-                Some(4),
-                Some(2)
-            ))
-            .is_err());
-        assert!(updater
-            .update_registry_from_event(&make_field_info_event(
-                Some(99),
-                Some("u"),
-                None,
-                Some(4),
-                Some(6)
-            ))
-            .is_err());
-        assert!(updater
-            .update_registry_from_event(&make_field_info_event(
+        for (event, name) in &[
+            (
+                make_field_info_event(None, Some("t"), Some("n"), Some(4), Some(1)),
+                "info_klass_id",
+            ),
+            (
+                make_field_info_event(Some(99), None, Some("n"), Some(4), Some(2)),
+                "field_type",
+            ),
+            (
+                make_field_info_event(Some(99), Some("u"), None, Some(4), Some(6)),
+                "field_name",
+            ),
+            (
+                make_field_info_event(Some(99), Some("v"), Some("n"), None, Some(99)),
+                "size",
+            ),
+            (
+                make_field_info_event(Some(99), Some("w"), Some("n"), Some(99), None),
+                "data_type",
+            ),
+        ] {
+            assert_eq!(
+                updater.update_registry_from_event(event),
+                Err(RegistryUpdateError::MissingField {
+                    name: name.to_string()
+                })
+            );
+        }
+        assert_eq!(
+            updater.update_registry_from_event(&make_field_info_event(
                 Some(99),
-                Some("v"),
+                Some("x"),
                 Some("n"),
                 None,
-                Some(99)
-            ))
-            .is_err());
-        assert!(updater
-            .update_registry_from_event(&make_field_info_event(
+                Some(42)
+            )),
+            Err(RegistryUpdateError::UnknownDataType { value: 42 })
+        );
+        assert_eq!(
+            updater.update_registry_from_event(&make_field_info_event(
                 Some(99),
-                Some("w"),
+                Some("void*"),
                 Some("n"),
-                Some(99),
-                None
-            ))
-            .is_err());
+                Some(2),
+                Some(6)
+            )),
+            Err(RegistryUpdateError::InvalidFieldSize {
+                name: "n".to_owned(),
+                size: 2
+            })
+        );
     }
 
     #[test]
@@ -323,9 +623,217 @@ mod tests {
                 Some(99),
                 Some("t"),
                 Some("n"),
+                Some(10),
+                Some(99)
+            ))
+            .is_err());
+    }
+
+    #[test]
+    fn add_array_field_should_add_array_type() {
+        let mut registry = EventKlassRegistry::new();
+        let mut updater = RegistryUpdater::new(&mut registry);
+        assert!(updater
+            .update_registry_from_event(&make_klass_info_event(Some(99), Some("name"), Some(2)))
+            .is_ok());
+        for (type_name, size) in &[("uint32_t[4]", 16), ("uint16_t[]", 2)] {
+            assert!(updater
+                .update_registry_from_event(&make_field_info_event(
+                    Some(99),
+                    Some(type_name),
+                    Some(type_name),
+                    Some(*size),
+                    Some(99)
+                ))
+                .is_ok());
+        }
+        for (type_name, size) in &[("uint32_t[3]", 16), ("uint32_t[x]", 16), ("uint8_t[]", 3)] {
+            assert!(updater
+                .update_registry_from_event(&make_field_info_event(
+                    Some(99),
+                    Some(type_name),
+                    Some(type_name),
+                    Some(*size),
+                    Some(99)
+                ))
+                .is_err());
+        }
+
+        let fields = registry.get_klass_by_id(99).unwrap().get_fields();
+        assert_eq!(
+            *fields[0].get_data_type(),
+            DataType::Array(IntegerType::U32, ArrayLength::Fixed(4))
+        );
+        assert_eq!(
+            *fields[1].get_data_type(),
+            DataType::Array(IntegerType::U16, ArrayLength::Prefixed)
+        );
+        assert_eq!(fields.len(), 2);
+    }
+
+    #[test]
+    fn add_field_of_unknown_type_should_add_opaque_field() {
+        let mut registry = EventKlassRegistry::new();
+        let mut updater = RegistryUpdater::new(&mut registry);
+        assert!(updater
+            .update_registry_from_event(&make_klass_info_event(Some(99), Some("name"), Some(1)))
+            .is_ok());
+        assert!(updater
+            .update_registry_from_event(&make_field_info_event(
                 Some(99),
-                Some(10)
+                Some("vendor_t"),
+                Some("n"),
+                Some(16),
+                Some(42)
+            ))
+            .is_ok());
+
+        let field = &registry.get_klass_by_id(99).unwrap().get_fields()[0];
+        assert_eq!(*field.get_data_type(), DataType::Bytes(16));
+        assert_eq!(field.get_declared_size(), Some(16));
+    }
+
+    #[test]
+    fn observers_should_be_notified_about_new_klasses_and_fields_only() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut registry = EventKlassRegistry::new();
+        registry.add_observer(Box::new(move |change| {
+            let description = match change {
+                RegistryChange::KlassAdded(klass) => klass.get_name().clone(),
+                RegistryChange::FieldAdded(klass, field) => {
+                    format!("{}.{}", klass.get_name(), field.get_name())
+                }
+            };
+            sender.send(description).unwrap();
+        }));
+
+        let mut updater = RegistryUpdater::new(&mut registry);
+        let field_event =
+            make_field_info_event(Some(99), Some("uint32_t"), Some("n"), Some(4), Some(99));
+        for event in &[
+            make_klass_info_event(Some(99), Some("name"), Some(1)),
+            make_klass_info_event(Some(99), Some("other"), Some(1)),
+            make_klass_info_event(Some(1), Some("HT_Event"), Some(3)),
+            field_event.clone(),
+            field_event,
+        ] {
+            assert!(updater.update_registry_from_event(event).is_ok());
+        }
+
+        let changes: Vec<String> = receiver.try_iter().collect();
+        assert_eq!(changes, vec!["name", "name.n"]);
+    }
+
+    #[test]
+    fn add_integer_field_should_use_type_name_consistent_with_size() {
+        let mut registry = EventKlassRegistry::new();
+        let mut updater = RegistryUpdater::new(&mut registry);
+        assert!(updater
+            .update_registry_from_event(&make_klass_info_event(Some(99), Some("name"), Some(4)))
+            .is_ok());
+        for (type_name, field_name, size) in &[
+            ("int32_t", "a", 4),
+            ("uint16_t", "b", 2),
+            ("int8_t[3]", "c", 3),
+            ("int64_t", "d", 1),
+        ] {
+            assert!(updater
+                .update_registry_from_event(&make_field_info_event(
+                    Some(99),
+                    Some(type_name),
+                    Some(field_name),
+                    Some(*size),
+                    Some(99)
+                ))
+                .is_ok());
+        }
+
+        let data_types: Vec<DataType> = registry
+            .get_klass_by_id(99)
+            .unwrap()
+            .get_fields()
+            .iter()
+            .map(|field| *field.get_data_type())
+            .collect();
+        assert_eq!(
+            data_types,
+            vec![
+                DataType::I32,
+                DataType::U16,
+                DataType::Array(IntegerType::I8, ArrayLength::Fixed(3)),
+                DataType::U8
+            ]
+        );
+    }
+
+    #[test]
+    fn add_field_should_map_mkcreflect_types_by_size() {
+        let mut registry = EventKlassRegistry::new();
+        let mut updater = RegistryUpdater::new(&mut registry);
+        assert!(updater
+            .update_registry_from_event(&make_klass_info_event(Some(99), Some("name"), Some(6)))
+            .is_ok());
+        for (field_name, type_name, size, data_type) in &[
+            ("a", "short", 2, MkcreflectType::Integer),
+            ("b", "uint8_t[4]", 4, MkcreflectType::Integer),
+            ("c", "float", 4, MkcreflectType::Float),
+            ("d", "double", 8, MkcreflectType::Double),
+            ("e", "void*", 4, MkcreflectType::Pointer),
+            ("f", "uint16_t", 2, MkcreflectType::UnsignedInteger),
+        ] {
+            assert!(updater
+                .update_registry_from_event(&make_field_info_event(
+                    Some(99),
+                    Some(type_name),
+                    Some(field_name),
+                    Some(*size),
+                    Some(*data_type as u8)
+                ))
+                .is_ok());
+        }
+        assert!(updater
+            .update_registry_from_event(&make_field_info_event(
+                Some(99),
+                Some("void*"),
+                Some("g"),
+                Some(2),
+                Some(MkcreflectType::Pointer as u8)
             ))
             .is_err());
+
+        let data_types: Vec<DataType> = registry
+            .get_klass_by_id(99)
+            .unwrap()
+            .get_fields()
+            .iter()
+            .map(|field| *field.get_data_type())
+            .collect();
+        assert_eq!(
+            data_types,
+            vec![
+                DataType::I16,
+                DataType::Array(IntegerType::U8, ArrayLength::Fixed(4)),
+                DataType::Bytes(4),
+                DataType::Bytes(8),
+                DataType::U32,
+                DataType::U16
+            ]
+        );
+    }
+
+    #[test]
+    fn mkcreflect_type_should_be_found_by_id() {
+        for data_type in &MkcreflectType::ALL {
+            assert_eq!(MkcreflectType::from_id(*data_type as u8), Some(*data_type));
+        }
+        assert_eq!(MkcreflectType::from_id(OPAQUE_DATA_TYPE_ID), None);
+        assert_eq!(
+            MkcreflectType::from_data_type(DataType::Array(
+                IntegerType::I32,
+                ArrayLength::Prefixed
+            )),
+            Some(MkcreflectType::Integer)
+        );
+        assert_eq!(MkcreflectType::from_data_type(DataType::Bytes(3)), None);
     }
 }
@@ -0,0 +1,150 @@
+use crate::event::Event;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReorderWindow {
+    // Maximum difference between timestamps of out-of-order events.
+    Duration(u64),
+    // Maximum number of events an event can be delayed by.
+    EventCount(usize),
+}
+
+struct Entry {
+    timestamp: u64,
+    sequence: u64,
+    event: Event,
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Entry) -> bool {
+        (self.timestamp, self.sequence) == (other.timestamp, other.sequence)
+    }
+}
+
+impl Eq for Entry {}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Entry) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Reversed, so BinaryHeap pops the oldest entry first.
+impl Ord for Entry {
+    fn cmp(&self, other: &Entry) -> std::cmp::Ordering {
+        (other.timestamp, other.sequence).cmp(&(self.timestamp, self.sequence))
+    }
+}
+
+// Buffers events so they can be released in timestamp order. Events with
+// equal timestamps keep their original order. Events delayed by more than
+// the window are released as soon as possible, so the order is not
+// guaranteed for them.
+pub struct ReorderBuffer {
+    window: ReorderWindow,
+    entries: std::collections::BinaryHeap<Entry>,
+    sequence: u64,
+    newest_timestamp: u64,
+}
+
+impl ReorderBuffer {
+    pub fn new(window: ReorderWindow) -> ReorderBuffer {
+        ReorderBuffer {
+            window,
+            entries: std::collections::BinaryHeap::new(),
+            sequence: 0,
+            newest_timestamp: 0,
+        }
+    }
+
+    pub fn get_size(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn push(&mut self, event: Event) {
+        let timestamp = event.get_timestamp().unwrap_or_default();
+        self.newest_timestamp = std::cmp::max(self.newest_timestamp, timestamp);
+        self.entries.push(Entry {
+            timestamp,
+            sequence: self.sequence,
+            event,
+        });
+        self.sequence += 1;
+    }
+
+    // Returns the oldest event if no event pushed later can precede it.
+    pub fn pop_ready(&mut self) -> Option<Event> {
+        let ready = match (self.window, self.entries.peek()) {
+            (ReorderWindow::Duration(duration), Some(entry)) => {
+                self.newest_timestamp.saturating_sub(entry.timestamp) > duration
+            }
+            (ReorderWindow::EventCount(count), Some(_)) => self.entries.len() > count,
+            (_, None) => false,
+        };
+
+        if ready {
+            self.pop()
+        } else {
+            None
+        }
+    }
+
+    // Returns the oldest event regardless of the window (e.g. at the end of
+    // the stream).
+    pub fn pop(&mut self) -> Option<Event> {
+        self.entries.pop().map(|entry| entry.event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::Value;
+    use crate::test_events::make_plain_event;
+
+    fn make_event(timestamp: u64, id: u64) -> Event {
+        make_plain_event(
+            1,
+            vec![("timestamp", Value::U64(timestamp)), ("id", Value::U64(id))],
+        )
+    }
+
+    fn reorder(window: ReorderWindow, timestamps: &[u64]) -> Vec<(u64, u64)> {
+        let mut buffer = ReorderBuffer::new(window);
+        let mut output = Vec::new();
+        for (id, timestamp) in timestamps.iter().enumerate() {
+            buffer.push(make_event(*timestamp, id as u64));
+            while let Some(event) = buffer.pop_ready() {
+                output.push(event);
+            }
+        }
+        while let Some(event) = buffer.pop() {
+            output.push(event);
+        }
+
+        output
+            .iter()
+            .map(|event| {
+                (
+                    event.get_value_u64("timestamp").unwrap(),
+                    event.get_value_u64("id").unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn events_should_be_sorted_within_event_count_window() {
+        assert_eq!(
+            reorder(ReorderWindow::EventCount(2), &[3, 1, 2, 2, 6, 4, 5]),
+            vec![(1, 1), (2, 2), (2, 3), (3, 0), (4, 5), (5, 6), (6, 4)]
+        );
+    }
+
+    #[test]
+    fn events_should_be_sorted_within_duration_window() {
+        assert_eq!(
+            reorder(ReorderWindow::Duration(10), &[100, 95, 105, 120, 111, 90]),
+            vec![(95, 1), (100, 0), (105, 2), (90, 5), (111, 4), (120, 3)]
+        );
+    }
+}
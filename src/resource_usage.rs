@@ -0,0 +1,60 @@
+use crate::registry::EventKlassRegistry;
+
+// Snapshot of sizes of the internal containers of a reader or a parser.
+// Long-running consumers can poll it to detect unbounded growth caused by
+// pathological streams.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ResourceUsage {
+    pub klass_count: usize,
+    pub field_count: usize,
+    pub registry_bytes: usize,
+    pub buffer_bytes: usize,
+    pub pending_bytes: usize,
+    pub reorder_buffer_events: usize,
+}
+
+impl ResourceUsage {
+    pub fn from_registry(registry: &EventKlassRegistry) -> ResourceUsage {
+        let mut usage = ResourceUsage::default();
+        for klass in registry.get_klasses() {
+            usage.klass_count += 1;
+            usage.registry_bytes += std::mem::size_of_val(klass) + klass.get_name().len();
+            for field in klass.get_fields() {
+                usage.field_count += 1;
+                usage.registry_bytes += std::mem::size_of_val(field)
+                    + field.get_name().len()
+                    + field.get_type_name().len();
+            }
+        }
+        usage
+    }
+
+    pub fn get_total_bytes(&self) -> usize {
+        self.registry_bytes + self.buffer_bytes + self.pending_bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::DataType;
+    use crate::event_klass::EventKlass;
+
+    #[test]
+    fn usage_should_count_registry_entries() {
+        let mut registry = EventKlassRegistry::new();
+        let core_usage = ResourceUsage::from_registry(&registry);
+        assert_eq!(core_usage.klass_count, 4);
+        assert_eq!(core_usage.field_count, 12);
+
+        let mut klass = EventKlass::new(99, "foo".to_owned());
+        klass.add_field("bar".to_owned(), "uint8_t".to_owned(), DataType::U8);
+        registry.add_klass(klass);
+
+        let usage = ResourceUsage::from_registry(&registry);
+        assert_eq!(usage.klass_count, 5);
+        assert_eq!(usage.field_count, 13);
+        assert!(usage.registry_bytes > core_usage.registry_bytes);
+        assert_eq!(usage.get_total_bytes(), usage.registry_bytes);
+    }
+}
@@ -0,0 +1,122 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+// Reads a sequence of rotated trace files (trace.0, trace.1, ...) as one
+// continuous stream, so an event split between two files is decoded correctly
+// and the registry built from earlier files applies to the later ones.
+pub struct RotatedFilesReader {
+    paths: std::vec::IntoIter<PathBuf>,
+    current: Option<std::fs::File>,
+}
+
+impl RotatedFilesReader {
+    pub fn new(paths: Vec<PathBuf>) -> RotatedFilesReader {
+        RotatedFilesReader {
+            paths: paths.into_iter(),
+            current: None,
+        }
+    }
+
+    pub fn from_base_path(base_path: &Path) -> std::io::Result<RotatedFilesReader> {
+        let paths = RotatedFilesReader::discover(base_path);
+        if paths.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("No rotated files found for {}", base_path.display()),
+            ));
+        }
+        Ok(RotatedFilesReader::new(paths))
+    }
+
+    pub fn discover(base_path: &Path) -> Vec<PathBuf> {
+        let mut paths = Vec::new();
+        loop {
+            let mut path = base_path.as_os_str().to_owned();
+            path.push(format!(".{}", paths.len()));
+            let path = PathBuf::from(path);
+            if !path.is_file() {
+                return paths;
+            }
+            paths.push(path);
+        }
+    }
+}
+
+impl Read for RotatedFilesReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            if let Some(file) = &mut self.current {
+                let size = file.read(buf)?;
+                if size > 0 || buf.is_empty() {
+                    return Ok(size);
+                }
+            }
+
+            match self.paths.next() {
+                Some(path) => self.current = Some(std::fs::File::open(path)?),
+                None => return Ok(0),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_files(name: &str, chunks: &[&[u8]]) -> PathBuf {
+        let base_path = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+        for (i, chunk) in chunks.iter().enumerate() {
+            let mut path = base_path.as_os_str().to_owned();
+            path.push(format!(".{}", i));
+            std::fs::write(path, chunk).unwrap();
+        }
+        base_path
+    }
+
+    #[test]
+    fn discover_should_find_consecutive_files() {
+        let base_path = make_files("ht-discover", &[b"a", b"b", b"c"]);
+
+        let paths = RotatedFilesReader::discover(&base_path);
+        assert_eq!(paths.len(), 3);
+        assert!(paths[2].to_string_lossy().ends_with(".2"));
+    }
+
+    #[test]
+    fn reader_should_concatenate_files() {
+        let base_path = make_files("ht-concat", &[&[1, 2], &[], &[3, 4, 5]]);
+        let mut reader = RotatedFilesReader::from_base_path(&base_path).unwrap();
+
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data, vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn event_split_between_files_should_be_decoded() {
+        let base_path = make_files(
+            "ht-split",
+            &[
+                &[1, 0, 0, 0, 7, 0, 0, 0],
+                &[0, 0, 0, 0, 9, 0, 0, 0, 0, 0, 0, 0],
+            ],
+        );
+        let reader = RotatedFilesReader::from_base_path(&base_path).unwrap();
+        let mut event_reader = crate::event_reader::EventReader::new(
+            crate::data_provider::DataProvider::new(Box::new(reader)),
+        );
+
+        let event = event_reader
+            .read_event(&mut crate::registry::EventKlassRegistry::new())
+            .unwrap();
+        assert_eq!(event.get_value_u64("timestamp").unwrap(), 7);
+        assert_eq!(event.get_value_u64("id").unwrap(), 9);
+    }
+
+    #[test]
+    fn missing_files_should_fail() {
+        let base_path = std::env::temp_dir().join("ht-non-existing-rotated-trace");
+        assert!(RotatedFilesReader::from_base_path(&base_path).is_err());
+    }
+}
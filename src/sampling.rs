@@ -0,0 +1,346 @@
+use crate::event::{Event, Value};
+
+// Online quantile estimation using the P-square algorithm (Jain & Chlamtac),
+// so the threshold can be computed without storing the observed values.
+pub struct QuantileEstimator {
+    quantile: f64,
+    heights: [f64; 5],
+    positions: [f64; 5],
+    desired_positions: [f64; 5],
+    increments: [f64; 5],
+    count: usize,
+}
+
+impl QuantileEstimator {
+    pub fn new(quantile: f64) -> QuantileEstimator {
+        QuantileEstimator {
+            quantile,
+            heights: [0.0; 5],
+            positions: [0.0, 1.0, 2.0, 3.0, 4.0],
+            desired_positions: [
+                0.0,
+                2.0 * quantile,
+                4.0 * quantile,
+                2.0 + 2.0 * quantile,
+                4.0,
+            ],
+            increments: [0.0, quantile / 2.0, quantile, (1.0 + quantile) / 2.0, 1.0],
+            count: 0,
+        }
+    }
+
+    pub fn get_count(&self) -> usize {
+        self.count
+    }
+
+    pub fn estimate(&self) -> Option<f64> {
+        match self.count {
+            0 => None,
+            c if c < 5 => {
+                let mut values = self.heights[..c].to_vec();
+                values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+                let index = ((c - 1) as f64 * self.quantile).round() as usize;
+                Some(values[index])
+            }
+            _ => Some(self.heights[2]),
+        }
+    }
+
+    pub fn add(&mut self, value: f64) {
+        if self.count < 5 {
+            self.heights[self.count] = value;
+            self.count += 1;
+            if self.count == 5 {
+                self.heights
+                    .sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+            }
+            return;
+        }
+        self.count += 1;
+
+        let cell = if value < self.heights[0] {
+            self.heights[0] = value;
+            0
+        } else if value >= self.heights[4] {
+            self.heights[4] = value;
+            3
+        } else {
+            (0..4).find(|&i| value < self.heights[i + 1]).unwrap_or(3)
+        };
+
+        for position in &mut self.positions[cell + 1..] {
+            *position += 1.0;
+        }
+        for (desired, increment) in self.desired_positions.iter_mut().zip(&self.increments) {
+            *desired += increment;
+        }
+
+        for i in 1..4 {
+            let delta = self.desired_positions[i] - self.positions[i];
+            if (delta >= 1.0 && self.positions[i + 1] - self.positions[i] > 1.0)
+                || (delta <= -1.0 && self.positions[i - 1] - self.positions[i] < -1.0)
+            {
+                let sign = delta.signum();
+                let height = self.parabolic(i, sign);
+                self.heights[i] = if self.heights[i - 1] < height && height < self.heights[i + 1] {
+                    height
+                } else {
+                    self.linear(i, sign)
+                };
+                self.positions[i] += sign;
+            }
+        }
+    }
+
+    fn parabolic(&self, i: usize, d: f64) -> f64 {
+        let (q, n) = (&self.heights, &self.positions);
+        q[i] + d / (n[i + 1] - n[i - 1])
+            * ((n[i] - n[i - 1] + d) * (q[i + 1] - q[i]) / (n[i + 1] - n[i])
+                + (n[i + 1] - n[i] - d) * (q[i] - q[i - 1]) / (n[i] - n[i - 1]))
+    }
+
+    fn linear(&self, i: usize, d: f64) -> f64 {
+        let j = if d > 0.0 { i + 1 } else { i - 1 };
+        self.heights[i]
+            + d * (self.heights[j] - self.heights[i]) / (self.positions[j] - self.positions[i])
+    }
+}
+
+// Keeps every N-th event, plus any event whose importance field (e.g. duration)
+// exceeds the given percentile of the values observed so far.
+pub struct OutlierSampler {
+    rate: u64,
+    field: String,
+    estimator: QuantileEstimator,
+    counter: u64,
+}
+
+impl OutlierSampler {
+    pub fn new(rate: u64, field: &str, percentile: f64) -> OutlierSampler {
+        OutlierSampler {
+            rate: std::cmp::max(rate, 1),
+            field: field.to_owned(),
+            estimator: QuantileEstimator::new(percentile),
+            counter: 0,
+        }
+    }
+
+    pub fn get_threshold(&self) -> Option<f64> {
+        self.estimator.estimate()
+    }
+
+    pub fn should_keep(&mut self, event: &Event) -> bool {
+        let sampled = self.counter % self.rate == 0;
+        self.counter += 1;
+
+        let value = match event.find_value(&self.field).and_then(Value::as_f64) {
+            Some(value) => value,
+            None => return sampled,
+        };
+
+        let is_outlier = self.estimator.get_count() >= 5
+            && self
+                .estimator
+                .estimate()
+                .is_some_and(|threshold| value > threshold);
+        self.estimator.add(value);
+
+        sampled || is_outlier
+    }
+}
+
+const NANOSECONDS_PER_SECOND: u64 = 1_000_000_000;
+
+#[derive(Default)]
+struct SamplingState {
+    counter: u64,
+    // Second of the trace (from the event timestamps) and the number of
+    // events kept in it.
+    second: u64,
+    kept_in_second: u64,
+}
+
+// Decides which events the reader decodes, based on their headers only, so
+// rejected events are skipped without decoding their payloads. Events can be
+// decimated (1 in N events is kept) and capped to a number of events per
+// second of the trace; both can be applied to each klass separately.
+// Metadata events are never sampled out.
+pub struct EventSampler {
+    rate: u64,
+    max_events_per_second: Option<u64>,
+    per_klass: bool,
+    states: fnv::FnvHashMap<u32, SamplingState>,
+    skipped_count: u64,
+}
+
+impl Default for EventSampler {
+    fn default() -> EventSampler {
+        EventSampler::new()
+    }
+}
+
+impl EventSampler {
+    pub fn new() -> EventSampler {
+        EventSampler {
+            rate: 1,
+            max_events_per_second: None,
+            per_klass: false,
+            states: fnv::FnvHashMap::default(),
+            skipped_count: 0,
+        }
+    }
+
+    // Keeps the first of every rate events.
+    pub fn with_rate(mut self, rate: u64) -> EventSampler {
+        self.rate = std::cmp::max(rate, 1);
+        self
+    }
+
+    // Events without a timestamp are not limited.
+    pub fn with_max_events_per_second(mut self, max_events_per_second: u64) -> EventSampler {
+        self.max_events_per_second = Some(max_events_per_second);
+        self
+    }
+
+    pub fn with_per_klass(mut self, per_klass: bool) -> EventSampler {
+        self.per_klass = per_klass;
+        self
+    }
+
+    pub fn get_skipped_count(&self) -> u64 {
+        self.skipped_count
+    }
+
+    pub fn should_keep(&mut self, klass_id: u32, timestamp: Option<u64>) -> bool {
+        let key = if self.per_klass { klass_id } else { 0 };
+        let state = self.states.entry(key).or_default();
+
+        let mut keep = state.counter % self.rate == 0;
+        state.counter += 1;
+        if let (true, Some(max_events_per_second), Some(timestamp)) =
+            (keep, self.max_events_per_second, timestamp)
+        {
+            let second = timestamp / NANOSECONDS_PER_SECOND;
+            if second != state.second {
+                state.second = second;
+                state.kept_in_second = 0;
+            }
+            keep = state.kept_in_second < max_events_per_second;
+            state.kept_in_second += keep as u64;
+        }
+
+        if !keep {
+            self.skipped_count += 1;
+        }
+        keep
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_events;
+    use fnv::FnvHashMap;
+
+    fn make_event(duration: u64) -> Event {
+        test_events::make_event(100, 0, vec![("duration", Value::U64(duration))])
+    }
+
+    #[test]
+    fn estimator_should_approximate_percentile() {
+        let mut estimator = QuantileEstimator::new(0.9);
+        for i in 0..1000 {
+            estimator.add(f64::from((i * 7919) % 1000));
+        }
+
+        let estimate = estimator.estimate().unwrap();
+        assert!(estimate > 850.0 && estimate < 950.0);
+    }
+
+    #[test]
+    fn estimator_should_handle_few_values() {
+        let mut estimator = QuantileEstimator::new(0.5);
+        assert!(estimator.estimate().is_none());
+
+        estimator.add(3.0);
+        estimator.add(1.0);
+        estimator.add(2.0);
+        assert_eq!(estimator.estimate(), Some(2.0));
+    }
+
+    #[test]
+    fn sampler_should_keep_every_nth_event_and_outliers() {
+        let mut sampler = OutlierSampler::new(10, "duration", 0.95);
+        let mut kept = Vec::new();
+        for i in 0..100 {
+            let duration = if i == 55 { 100_000 } else { 100 + i % 5 };
+            if sampler.should_keep(&make_event(duration)) {
+                kept.push(i);
+            }
+        }
+
+        assert!(kept.contains(&55));
+        for i in (0..100).step_by(10) {
+            assert!(kept.contains(&i));
+        }
+        assert!(kept.len() < 30);
+    }
+
+    #[test]
+    fn sampler_should_use_rate_for_events_without_field() {
+        let mut sampler = OutlierSampler::new(2, "duration", 0.5);
+        let event = Event::new(5, FnvHashMap::default());
+
+        let kept = (0..10).filter(|_| sampler.should_keep(&event)).count();
+        assert_eq!(kept, 5);
+    }
+
+    #[test]
+    fn event_sampler_should_keep_one_in_n_events() {
+        let mut sampler = EventSampler::new().with_rate(3);
+        let kept: Vec<u32> = (0..7)
+            .filter(|i| sampler.should_keep(100 + i % 2, None))
+            .collect();
+        assert_eq!(kept, vec![0, 3, 6]);
+        assert_eq!(sampler.get_skipped_count(), 4);
+    }
+
+    #[test]
+    fn event_sampler_should_sample_klasses_separately() {
+        let mut sampler = EventSampler::new().with_rate(2).with_per_klass(true);
+        let kept: Vec<u32> = (0..8)
+            .filter(|i| sampler.should_keep(100 + i % 2, None))
+            .collect();
+        assert_eq!(kept, vec![0, 1, 4, 5]);
+    }
+
+    #[test]
+    fn event_sampler_should_cap_events_per_second() {
+        let mut sampler = EventSampler::new().with_max_events_per_second(2);
+        let timestamps = [
+            0,
+            1,
+            2,
+            NANOSECONDS_PER_SECOND,
+            NANOSECONDS_PER_SECOND + 1,
+            NANOSECONDS_PER_SECOND + 2,
+            5 * NANOSECONDS_PER_SECOND,
+        ];
+        let kept: Vec<u64> = timestamps
+            .iter()
+            .copied()
+            .filter(|timestamp| sampler.should_keep(100, Some(*timestamp)))
+            .collect();
+        assert_eq!(
+            kept,
+            vec![
+                0,
+                1,
+                NANOSECONDS_PER_SECOND,
+                NANOSECONDS_PER_SECOND + 1,
+                5 * NANOSECONDS_PER_SECOND
+            ]
+        );
+        assert!(sampler.should_keep(100, None));
+    }
+}
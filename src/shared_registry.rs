@@ -0,0 +1,62 @@
+use crate::registry::EventKlassRegistry;
+
+// Registry which can be shared between threads, e.g. by readers of several
+// streams of the same process, or by an indexing and a decoding pass.
+// Clones refer to the same registry. Metadata events are rare, so readers
+// mostly take the read lock; see EventReader::read_event_shared().
+#[derive(Clone, Default)]
+pub struct SharedEventKlassRegistry {
+    registry: std::sync::Arc<std::sync::RwLock<EventKlassRegistry>>,
+}
+
+impl SharedEventKlassRegistry {
+    pub fn new() -> SharedEventKlassRegistry {
+        SharedEventKlassRegistry::from_registry(EventKlassRegistry::new())
+    }
+
+    pub fn from_registry(registry: EventKlassRegistry) -> SharedEventKlassRegistry {
+        SharedEventKlassRegistry {
+            registry: std::sync::Arc::new(std::sync::RwLock::new(registry)),
+        }
+    }
+
+    // A panic while the lock was held can't leave the registry in an
+    // inconsistent state (klasses are inserted at once), so poisoning is
+    // ignored.
+    pub fn read(&self) -> std::sync::RwLockReadGuard<'_, EventKlassRegistry> {
+        self.registry
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    pub fn write(&self) -> std::sync::RwLockWriteGuard<'_, EventKlassRegistry> {
+        self.registry
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event_klass::EventKlass;
+
+    #[test]
+    fn clones_should_share_the_registry_between_threads() {
+        let registry = SharedEventKlassRegistry::new();
+        let writer = registry.clone();
+
+        std::thread::spawn(move || {
+            writer
+                .write()
+                .add_klass(EventKlass::new(99, "foo".to_owned()))
+        })
+        .join()
+        .unwrap();
+
+        assert_eq!(
+            registry.read().get_klass_by_name("foo").unwrap().get_id(),
+            99
+        );
+    }
+}
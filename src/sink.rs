@@ -0,0 +1,11 @@
+use crate::event::Event;
+use crate::registry::EventKlassRegistry;
+
+// Destination of decoded events (e.g. an exporter writing them to a file).
+pub trait EventSink {
+    fn write_event(&mut self, event: &Event, registry: &EventKlassRegistry) -> std::io::Result<()>;
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,333 @@
+use crate::event::{Event, Value};
+use crate::registry::EventKlassRegistry;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    pub label: String,
+    pub thread_id: u64,
+    pub start: u64,
+    pub duration: u64,
+}
+
+// Defines how end markers are matched with begin markers.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PairingStrategy {
+    // Most recent begin marker with the same label on the same thread.
+    LabelAndThread,
+    // Begin marker with the same value of the given field.
+    IdField(String),
+    // Most recent begin marker on the same thread, regardless of the label.
+    Nesting,
+}
+
+#[derive(Hash, PartialEq, Eq)]
+enum PairKey {
+    Thread(u64),
+    LabelAndThread(String, u64),
+    Id(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MarkerKind {
+    Begin,
+    End,
+}
+
+// Begin marker which was never closed, or end marker without a matching
+// begin marker.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnmatchedMarker {
+    pub kind: MarkerKind,
+    pub label: String,
+    pub thread_id: u64,
+    pub timestamp: u64,
+}
+
+struct Marker {
+    label: String,
+    thread_id: u64,
+    timestamp: u64,
+}
+
+// Builds spans from events. Events with a duration field are converted to
+// spans directly, events of begin/end marker klasses are paired according
+// to the strategy.
+pub struct SpanBuilder {
+    strategy: PairingStrategy,
+    begin_klass: Option<String>,
+    end_klass: Option<String>,
+    open_markers: std::collections::HashMap<PairKey, Vec<Marker>>,
+    unmatched_end_markers: Vec<UnmatchedMarker>,
+}
+
+pub(crate) fn find_u64(event: &Event, name: &str) -> Option<u64> {
+    event.find_value(name)?.as_u64()
+}
+
+pub(crate) fn find_string(event: &Event, name: &str) -> Option<String> {
+    match event.find_value(name)? {
+        Value::Str(v) => Some(v.clone()),
+        Value::Struct(_) => None,
+        value => Some(value.to_string()),
+    }
+}
+
+impl SpanBuilder {
+    pub fn new(strategy: PairingStrategy) -> SpanBuilder {
+        SpanBuilder {
+            strategy,
+            begin_klass: None,
+            end_klass: None,
+            open_markers: std::collections::HashMap::new(),
+            unmatched_end_markers: Vec::new(),
+        }
+    }
+
+    pub fn with_markers(mut self, begin_klass: &str, end_klass: &str) -> SpanBuilder {
+        self.begin_klass = Some(begin_klass.to_owned());
+        self.end_klass = Some(end_klass.to_owned());
+        self
+    }
+
+    pub fn update(&mut self, event: &Event, registry: &EventKlassRegistry) -> Option<Span> {
+        let klass_name = registry.get_klass_by_id(event.get_klass_id())?.get_name();
+        let timestamp = event.get_timestamp()?;
+        let thread_id = find_u64(event, "thread_id").unwrap_or_default();
+
+        if Some(klass_name) == self.begin_klass.as_ref() {
+            let marker = Marker {
+                label: find_string(event, "label").unwrap_or_default(),
+                thread_id,
+                timestamp,
+            };
+            let key = self.get_key(event, &marker.label, thread_id)?;
+            self.open_markers.entry(key).or_default().push(marker);
+            None
+        } else if Some(klass_name) == self.end_klass.as_ref() {
+            let label = find_string(event, "label").unwrap_or_default();
+            let key = self.get_key(event, &label, thread_id)?;
+            let marker = match self.open_markers.get_mut(&key) {
+                Some(markers) => {
+                    let marker = markers.pop();
+                    if markers.is_empty() {
+                        self.open_markers.remove(&key);
+                    }
+                    marker
+                }
+                None => None,
+            };
+            match marker {
+                Some(marker) => Some(Span {
+                    label: marker.label,
+                    thread_id: marker.thread_id,
+                    start: marker.timestamp,
+                    duration: timestamp.saturating_sub(marker.timestamp),
+                }),
+                None => {
+                    self.unmatched_end_markers.push(UnmatchedMarker {
+                        kind: MarkerKind::End,
+                        label,
+                        thread_id,
+                        timestamp,
+                    });
+                    None
+                }
+            }
+        } else {
+            Some(Span {
+                label: find_string(event, "label")?,
+                thread_id,
+                start: timestamp,
+                duration: find_u64(event, "duration")?,
+            })
+        }
+    }
+
+    pub fn get_open_marker_count(&self) -> usize {
+        self.open_markers
+            .values()
+            .map(|markers| markers.len())
+            .sum()
+    }
+
+    pub fn get_unmatched_end_markers(&self) -> &[UnmatchedMarker] {
+        &self.unmatched_end_markers
+    }
+
+    // Should be called at the end of the stream; returns all the markers
+    // which couldn't be paired, ordered by timestamp.
+    pub fn finish(self) -> Vec<UnmatchedMarker> {
+        let mut unmatched = self.unmatched_end_markers;
+        for markers in self.open_markers.into_values() {
+            unmatched.extend(markers.into_iter().map(|marker| UnmatchedMarker {
+                kind: MarkerKind::Begin,
+                label: marker.label,
+                thread_id: marker.thread_id,
+                timestamp: marker.timestamp,
+            }));
+        }
+        unmatched.sort_by_key(|marker| marker.timestamp);
+        unmatched
+    }
+
+    fn get_key(&self, event: &Event, label: &str, thread_id: u64) -> Option<PairKey> {
+        match &self.strategy {
+            PairingStrategy::LabelAndThread => {
+                Some(PairKey::LabelAndThread(label.to_owned(), thread_id))
+            }
+            PairingStrategy::IdField(field) => Some(PairKey::Id(find_string(event, field)?)),
+            PairingStrategy::Nesting => Some(PairKey::Thread(thread_id)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::DataType;
+    use crate::event_klass::EventKlass;
+    use crate::test_events::make_event;
+
+    fn make_registry() -> EventKlassRegistry {
+        let mut registry = EventKlassRegistry::new();
+        for (id, name) in &[(100, "begin"), (101, "end"), (102, "callstack")] {
+            let mut klass = EventKlass::new(*id, name.to_string());
+            klass.add_field("base".to_owned(), "HT_Event".to_owned(), DataType::Struct);
+            registry.add_klass(klass);
+        }
+        registry
+    }
+
+    fn marker(klass_id: u32, timestamp: u64, label: &str, thread_id: u32) -> Event {
+        make_event(
+            klass_id,
+            timestamp,
+            vec![
+                ("label", Value::Str(label.to_owned())),
+                ("thread_id", Value::U32(thread_id)),
+            ],
+        )
+    }
+
+    fn span(label: &str, thread_id: u64, start: u64, duration: u64) -> Option<Span> {
+        Some(Span {
+            label: label.to_owned(),
+            thread_id,
+            start,
+            duration,
+        })
+    }
+
+    #[test]
+    fn duration_events_should_be_converted_directly() {
+        let registry = make_registry();
+        let mut builder = SpanBuilder::new(PairingStrategy::Nesting);
+        let event = make_event(
+            102,
+            10,
+            vec![
+                ("label", Value::U64(5)),
+                ("duration", Value::U64(7)),
+                ("thread_id", Value::U32(2)),
+            ],
+        );
+
+        assert_eq!(builder.update(&event, &registry), span("5", 2, 10, 7));
+    }
+
+    #[test]
+    fn label_and_thread_strategy_should_match_same_label_on_same_thread() {
+        let registry = make_registry();
+        let mut builder =
+            SpanBuilder::new(PairingStrategy::LabelAndThread).with_markers("begin", "end");
+
+        assert_eq!(builder.update(&marker(100, 1, "a", 1), &registry), None);
+        assert_eq!(builder.update(&marker(100, 2, "b", 1), &registry), None);
+        assert_eq!(builder.update(&marker(100, 3, "a", 2), &registry), None);
+        assert_eq!(
+            builder.update(&marker(101, 5, "a", 1), &registry),
+            span("a", 1, 1, 4)
+        );
+        assert_eq!(
+            builder.update(&marker(101, 6, "b", 1), &registry),
+            span("b", 1, 2, 4)
+        );
+        assert_eq!(builder.update(&marker(101, 9, "c", 2), &registry), None);
+    }
+
+    #[test]
+    fn unmatched_markers_should_be_reported() {
+        let registry = make_registry();
+        let mut builder =
+            SpanBuilder::new(PairingStrategy::LabelAndThread).with_markers("begin", "end");
+
+        builder.update(&marker(100, 1, "a", 1), &registry);
+        builder.update(&marker(100, 2, "b", 1), &registry);
+        builder.update(&marker(101, 3, "b", 2), &registry);
+        builder.update(&marker(101, 4, "b", 1), &registry);
+
+        assert_eq!(builder.get_open_marker_count(), 1);
+        assert_eq!(builder.get_unmatched_end_markers().len(), 1);
+        assert_eq!(
+            builder.finish(),
+            vec![
+                UnmatchedMarker {
+                    kind: MarkerKind::Begin,
+                    label: "a".to_owned(),
+                    thread_id: 1,
+                    timestamp: 1,
+                },
+                UnmatchedMarker {
+                    kind: MarkerKind::End,
+                    label: "b".to_owned(),
+                    thread_id: 2,
+                    timestamp: 3,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn nesting_strategy_should_close_most_recent_scope() {
+        let registry = make_registry();
+        let mut builder = SpanBuilder::new(PairingStrategy::Nesting).with_markers("begin", "end");
+
+        builder.update(&marker(100, 1, "outer", 1), &registry);
+        builder.update(&marker(100, 2, "inner", 1), &registry);
+        assert_eq!(
+            builder.update(&marker(101, 3, "", 1), &registry),
+            span("inner", 1, 2, 1)
+        );
+        assert_eq!(
+            builder.update(&marker(101, 4, "", 1), &registry),
+            span("outer", 1, 1, 3)
+        );
+    }
+
+    #[test]
+    fn id_field_strategy_should_match_by_field_value() {
+        let registry = make_registry();
+        let mut builder = SpanBuilder::new(PairingStrategy::IdField("span_id".to_owned()))
+            .with_markers("begin", "end");
+        let begin = |timestamp, id| {
+            make_event(
+                100,
+                timestamp,
+                vec![
+                    ("label", Value::Str(format!("op{}", id))),
+                    ("span_id", Value::U64(id)),
+                    ("thread_id", Value::U32(1)),
+                ],
+            )
+        };
+        let end = |timestamp, id| make_event(101, timestamp, vec![("span_id", Value::U64(id))]);
+
+        builder.update(&begin(1, 7), &registry);
+        builder.update(&begin(2, 8), &registry);
+        assert_eq!(builder.update(&end(10, 7), &registry), span("op7", 1, 1, 9));
+        assert_eq!(
+            builder.update(&end(12, 8), &registry),
+            span("op8", 1, 2, 10)
+        );
+    }
+}
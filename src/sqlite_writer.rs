@@ -0,0 +1,252 @@
+use crate::csv_writer::add_flat_field_names;
+use crate::event::{Event, Value};
+use crate::registry::{CoreEventKlassId, EventKlassRegistry};
+use crate::sink::EventSink;
+
+struct Table {
+    name: String,
+    columns: Vec<String>,
+    insert_query: String,
+}
+
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn to_io_error(err: rusqlite::Error) -> std::io::Error {
+    std::io::Error::other(err)
+}
+
+// Writes events to a SQLite database, one table per klass (named after the
+// klass) with a column per field of the flattened event. Integers are
+// stored as INTEGER, unless they don't fit in i64 (they're stored as TEXT
+// then). Events are inserted in transactions of batch_size events; the last
+// transaction is committed by flush(). Events of core klasses and of klasses
+// without fields are skipped.
+pub struct SqliteWriter {
+    connection: rusqlite::Connection,
+    batch_size: usize,
+    tables: std::collections::HashMap<u32, Table>,
+    pending_count: usize,
+}
+
+impl SqliteWriter {
+    pub fn new(connection: rusqlite::Connection) -> SqliteWriter {
+        SqliteWriter {
+            connection,
+            batch_size: 10000,
+            tables: std::collections::HashMap::new(),
+            pending_count: 0,
+        }
+    }
+
+    pub fn open<P: AsRef<std::path::Path>>(path: P) -> rusqlite::Result<SqliteWriter> {
+        Ok(SqliteWriter::new(rusqlite::Connection::open(path)?))
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> SqliteWriter {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    pub fn get_connection(&self) -> &rusqlite::Connection {
+        &self.connection
+    }
+
+    fn create_table(
+        &self,
+        klass_id: u32,
+        registry: &EventKlassRegistry,
+    ) -> rusqlite::Result<Option<Table>> {
+        let klass = match registry.get_klass_by_id(klass_id) {
+            Some(klass) => klass,
+            None => return Ok(None),
+        };
+        let mut columns = Vec::new();
+        add_flat_field_names(klass, registry, &mut columns);
+        if columns.is_empty() {
+            return Ok(None);
+        }
+
+        let name = klass.get_name().clone();
+        let quoted_name = quote_identifier(&name);
+        let column_names: Vec<String> = columns
+            .iter()
+            .map(|column| quote_identifier(column))
+            .collect();
+        self.connection.execute_batch(&format!(
+            "CREATE TABLE IF NOT EXISTS {} ({})",
+            quoted_name,
+            column_names.join(", ")
+        ))?;
+        let placeholders: Vec<String> = (1..=columns.len()).map(|i| format!("?{}", i)).collect();
+        let insert_query = format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            quoted_name,
+            column_names.join(", "),
+            placeholders.join(", ")
+        );
+        Ok(Some(Table {
+            name,
+            columns,
+            insert_query,
+        }))
+    }
+
+    fn insert(&mut self, event: &Event, registry: &EventKlassRegistry) -> rusqlite::Result<()> {
+        let klass_id = event.get_klass_id();
+        if !self.tables.contains_key(&klass_id) {
+            match self.create_table(klass_id, registry)? {
+                Some(table) => self.tables.insert(klass_id, table),
+                None => return Ok(()),
+            };
+        }
+        if self.pending_count == 0 {
+            self.connection.execute_batch("BEGIN")?;
+        }
+
+        let table = &self.tables[&klass_id];
+        let event = event.clone().flat_event();
+        let values: Vec<rusqlite::types::Value> = table
+            .columns
+            .iter()
+            .map(|column| match event.get_raw_value(column) {
+                Some(Value::Str(value)) => rusqlite::types::Value::Text(value.clone()),
+                Some(Value::Bytes(value)) => rusqlite::types::Value::Blob(value.clone()),
+                Some(Value::Struct(_)) | None => rusqlite::types::Value::Null,
+                Some(value) => match value.as_i64() {
+                    Some(value) => rusqlite::types::Value::Integer(value),
+                    None => rusqlite::types::Value::Text(value.to_string()),
+                },
+            })
+            .collect();
+        self.connection
+            .prepare_cached(&table.insert_query)?
+            .execute(rusqlite::params_from_iter(values))?;
+
+        self.pending_count += 1;
+        if self.pending_count >= self.batch_size {
+            self.commit()?;
+        }
+        Ok(())
+    }
+
+    fn commit(&mut self) -> rusqlite::Result<()> {
+        if self.pending_count > 0 {
+            self.connection.execute_batch("COMMIT")?;
+            self.pending_count = 0;
+        }
+        Ok(())
+    }
+
+    pub fn get_table_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self
+            .tables
+            .values()
+            .map(|table| table.name.as_str())
+            .collect();
+        names.sort_unstable();
+        names
+    }
+}
+
+impl EventSink for SqliteWriter {
+    fn write_event(&mut self, event: &Event, registry: &EventKlassRegistry) -> std::io::Result<()> {
+        if CoreEventKlassId::is_core_klass(event.get_klass_id()) {
+            return Ok(());
+        }
+        self.insert(event, registry).map_err(to_io_error)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.commit().map_err(to_io_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::DataType;
+    use crate::event_klass::EventKlass;
+    use crate::test_events;
+
+    fn make_registry() -> EventKlassRegistry {
+        let mut registry = EventKlassRegistry::new();
+        let mut klass = EventKlass::new(100, "my \"klass\"".to_owned());
+        klass.add_field("base".to_owned(), "HT_Event".to_owned(), DataType::Struct);
+        klass.add_field("label".to_owned(), "const char*".to_owned(), DataType::Str);
+        klass.add_field("value".to_owned(), "uint64_t".to_owned(), DataType::U64);
+        registry.add_klass(klass);
+        registry
+    }
+
+    fn make_event(timestamp: u64, label: &str, value: u64) -> Event {
+        test_events::make_event(
+            100,
+            timestamp,
+            vec![
+                ("label", Value::Str(label.to_owned())),
+                ("value", Value::U64(value)),
+            ],
+        )
+    }
+
+    #[test]
+    fn events_should_be_inserted_into_klass_tables() {
+        let registry = make_registry();
+        let mut writer =
+            SqliteWriter::new(rusqlite::Connection::open_in_memory().unwrap()).with_batch_size(2);
+        for (timestamp, label, value) in &[(1, "a", 5), (2, "b", u64::MAX), (3, "c", 7)] {
+            writer
+                .write_event(&make_event(*timestamp, label, *value), &registry)
+                .unwrap();
+        }
+        writer
+            .write_event(
+                &Event::new(
+                    CoreEventKlassId::Endianness as u32,
+                    fnv::FnvHashMap::default(),
+                ),
+                &registry,
+            )
+            .unwrap();
+        writer.flush().unwrap();
+
+        assert_eq!(writer.get_table_names(), vec!["my \"klass\""]);
+        let connection = writer.get_connection();
+        let mut statement = connection
+            .prepare("SELECT timestamp, label, value FROM \"my \"\"klass\"\"\" ORDER BY timestamp")
+            .unwrap();
+        let rows: Vec<(i64, String, rusqlite::types::Value)> = statement
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .map(|row| row.unwrap())
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                (1, "a".to_owned(), rusqlite::types::Value::Integer(5)),
+                (
+                    2,
+                    "b".to_owned(),
+                    rusqlite::types::Value::Text(u64::MAX.to_string())
+                ),
+                (3, "c".to_owned(), rusqlite::types::Value::Integer(7)),
+            ]
+        );
+        assert!(connection.is_autocommit());
+    }
+
+    #[test]
+    fn klasses_without_fields_should_be_skipped() {
+        let mut registry = EventKlassRegistry::new();
+        registry.add_klass(EventKlass::new(101, "empty".to_owned()));
+        let mut writer = SqliteWriter::new(rusqlite::Connection::open_in_memory().unwrap());
+        writer
+            .write_event(&Event::new(101, fnv::FnvHashMap::default()), &registry)
+            .unwrap();
+        writer.flush().unwrap();
+
+        assert!(writer.get_table_names().is_empty());
+    }
+}
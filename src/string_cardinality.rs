@@ -0,0 +1,241 @@
+use crate::event::{Event, Value};
+use crate::registry::EventKlassRegistry;
+use std::hash::Hasher;
+
+const HLL_PRECISION: u32 = 12;
+const HLL_REGISTER_COUNT: usize = 1 << HLL_PRECISION;
+
+// HyperLogLog estimator used for distinct counts once the exact value
+// counts grow beyond the configured limit.
+struct DistinctCounter {
+    registers: Vec<u8>,
+}
+
+impl DistinctCounter {
+    fn new() -> DistinctCounter {
+        DistinctCounter {
+            registers: vec![0; HLL_REGISTER_COUNT],
+        }
+    }
+
+    fn add(&mut self, value: &str) {
+        let mut hasher = fnv::FnvHasher::default();
+        hasher.write(value.as_bytes());
+        // FNV has a weak avalanche in the upper bits, mix them before use.
+        let mut hash = hasher.finish();
+        hash ^= hash >> 33;
+        hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+        hash ^= hash >> 33;
+
+        let index = (hash >> (64 - HLL_PRECISION)) as usize;
+        let rank = ((hash << HLL_PRECISION) | (1 << (HLL_PRECISION - 1))).leading_zeros() + 1;
+        self.registers[index] = std::cmp::max(self.registers[index], rank as u8);
+    }
+
+    fn estimate(&self) -> u64 {
+        let count = HLL_REGISTER_COUNT as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / count);
+        let sum: f64 = self
+            .registers
+            .iter()
+            .map(|register| 2f64.powi(-i32::from(*register)))
+            .sum();
+        let estimate = alpha * count * count / sum;
+
+        let zeros = self
+            .registers
+            .iter()
+            .filter(|register| **register == 0)
+            .count();
+        if estimate <= 2.5 * count && zeros > 0 {
+            (count * (count / zeros as f64).ln()).round() as u64
+        } else {
+            estimate.round() as u64
+        }
+    }
+}
+
+struct FieldStats {
+    total_count: u64,
+    values: std::collections::HashMap<String, u64, fnv::FnvBuildHasher>,
+    overflow: bool,
+    distinct_counter: DistinctCounter,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldCardinality {
+    pub klass_name: String,
+    pub field_name: String,
+    pub total_count: u64,
+    pub distinct_count: u64,
+    // Distinct count is estimated and top values only include values seen
+    // before the limit of tracked values was reached.
+    pub approximate: bool,
+    pub top_values: Vec<(String, u64)>,
+}
+
+// Collects distinct-value statistics of string fields for each klass.
+pub struct StringCardinality {
+    fields: std::collections::HashMap<(String, String), FieldStats>,
+    max_tracked_values: usize,
+}
+
+impl Default for StringCardinality {
+    fn default() -> StringCardinality {
+        StringCardinality::new()
+    }
+}
+
+impl StringCardinality {
+    pub fn new() -> StringCardinality {
+        StringCardinality::with_max_tracked_values(10000)
+    }
+
+    pub fn with_max_tracked_values(max_tracked_values: usize) -> StringCardinality {
+        StringCardinality {
+            fields: std::collections::HashMap::new(),
+            max_tracked_values,
+        }
+    }
+
+    pub fn update(&mut self, event: &Event, registry: &EventKlassRegistry) {
+        let klass_name = match registry.get_klass_by_id(event.get_klass_id()) {
+            Some(klass) => klass.get_name(),
+            None => return,
+        };
+
+        for (field_name, value) in event.get_all_values() {
+            if let Value::Str(value) = value {
+                let stats = self
+                    .fields
+                    .entry((klass_name.clone(), field_name.to_string()))
+                    .or_insert_with(|| FieldStats {
+                        total_count: 0,
+                        values: Default::default(),
+                        overflow: false,
+                        distinct_counter: DistinctCounter::new(),
+                    });
+                StringCardinality::update_stats(stats, value, self.max_tracked_values);
+            }
+        }
+    }
+
+    fn update_stats(stats: &mut FieldStats, value: &str, max_tracked_values: usize) {
+        stats.total_count += 1;
+        stats.distinct_counter.add(value);
+
+        if let Some(count) = stats.values.get_mut(value) {
+            *count += 1;
+        } else if stats.values.len() < max_tracked_values {
+            stats.values.insert(value.to_owned(), 1);
+        } else {
+            stats.overflow = true;
+        }
+    }
+
+    // Statistics of all string fields, the fields with the highest number of
+    // distinct values first.
+    pub fn get_report(&self, top_value_count: usize) -> Vec<FieldCardinality> {
+        let mut report: Vec<FieldCardinality> = self
+            .fields
+            .iter()
+            .map(|((klass_name, field_name), stats)| {
+                let mut top_values: Vec<(String, u64)> = stats
+                    .values
+                    .iter()
+                    .map(|(value, count)| (value.clone(), *count))
+                    .collect();
+                top_values.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+                top_values.truncate(top_value_count);
+
+                let distinct_count = if stats.overflow {
+                    std::cmp::max(stats.distinct_counter.estimate(), stats.values.len() as u64)
+                } else {
+                    stats.values.len() as u64
+                };
+
+                FieldCardinality {
+                    klass_name: klass_name.clone(),
+                    field_name: field_name.clone(),
+                    total_count: stats.total_count,
+                    distinct_count,
+                    approximate: stats.overflow,
+                    top_values,
+                }
+            })
+            .collect();
+
+        report.sort_by(|a, b| {
+            b.distinct_count
+                .cmp(&a.distinct_count)
+                .then_with(|| a.klass_name.cmp(&b.klass_name))
+                .then_with(|| a.field_name.cmp(&b.field_name))
+        });
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::DataType;
+    use crate::event_klass::EventKlass;
+    use crate::test_events::make_plain_event;
+
+    fn make_registry() -> EventKlassRegistry {
+        let mut registry = EventKlassRegistry::new();
+        let mut klass = EventKlass::new(100, "foo".to_owned());
+        klass.add_field("label".to_owned(), "const char*".to_owned(), DataType::Str);
+        klass.add_field("value".to_owned(), "uint32_t".to_owned(), DataType::U32);
+        registry.add_klass(klass);
+        registry
+    }
+
+    fn make_event(label: &str) -> Event {
+        make_plain_event(
+            100,
+            vec![
+                ("label", Value::Str(label.to_owned())),
+                ("value", Value::U32(1)),
+            ],
+        )
+    }
+
+    #[test]
+    fn report_should_contain_exact_counts_and_top_values() {
+        let registry = make_registry();
+        let mut cardinality = StringCardinality::new();
+        for label in &["a", "b", "a", "c", "a", "b"] {
+            cardinality.update(&make_event(label), &registry);
+        }
+
+        let report = cardinality.get_report(2);
+        assert_eq!(
+            report,
+            vec![FieldCardinality {
+                klass_name: "foo".to_owned(),
+                field_name: "label".to_owned(),
+                total_count: 6,
+                distinct_count: 3,
+                approximate: false,
+                top_values: vec![("a".to_owned(), 3), ("b".to_owned(), 2)],
+            }]
+        );
+    }
+
+    #[test]
+    fn distinct_count_should_be_estimated_above_tracked_values_limit() {
+        let registry = make_registry();
+        let mut cardinality = StringCardinality::with_max_tracked_values(100);
+        for i in 0..20000 {
+            cardinality.update(&make_event(&format!("label-{}", i % 10000)), &registry);
+        }
+
+        let report = cardinality.get_report(1);
+        assert!(report[0].approximate);
+        assert_eq!(report[0].total_count, 20000);
+        assert_eq!(report[0].top_values[0].1, 2);
+        let error = (report[0].distinct_count as f64 - 10000.0).abs() / 10000.0;
+        assert!(error < 0.05, "estimate: {}", report[0].distinct_count);
+    }
+}
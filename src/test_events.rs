@@ -0,0 +1,38 @@
+// Events for unit tests. They're not in hawktracer_parser_test_utilities,
+// as unit tests are built against their own copy of the crate, so Event
+// types of the two crates wouldn't match.
+use crate::event::{Event, Value};
+use crate::registry::CoreEventKlassId;
+
+// Event without a base event.
+pub(crate) fn make_plain_event(klass_id: u32, fields: Vec<(&str, Value)>) -> Event {
+    let values = fields
+        .into_iter()
+        .map(|(name, value)| (name.to_owned(), value))
+        .collect();
+    Event::new(klass_id, values)
+}
+
+// Event with a base event (HT_Event) having the type, timestamp and id
+// (equal to the timestamp) fields.
+pub(crate) fn make_event(klass_id: u32, timestamp: u64, fields: Vec<(&str, Value)>) -> Event {
+    make_event_with_id(klass_id, timestamp, timestamp, fields)
+}
+
+pub(crate) fn make_event_with_id(
+    klass_id: u32,
+    timestamp: u64,
+    id: u64,
+    mut fields: Vec<(&str, Value)>,
+) -> Event {
+    let base_event = make_plain_event(
+        CoreEventKlassId::Base as u32,
+        vec![
+            ("type", Value::U32(klass_id)),
+            ("timestamp", Value::U64(timestamp)),
+            ("id", Value::U64(id)),
+        ],
+    );
+    fields.push(("base", Value::Struct(Box::new(base_event))));
+    make_plain_event(klass_id, fields)
+}
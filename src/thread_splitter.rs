@@ -0,0 +1,143 @@
+use crate::event::Event;
+use crate::registry::EventKlassRegistry;
+use crate::sink::EventSink;
+use crate::spans::find_u64;
+
+// Groups events by the thread they were emitted from (the "thread_id" field
+// of the event or of its base struct). Events without a thread id are kept
+// in a separate queue. Events of each thread are kept in the order they
+// were added.
+#[derive(Default)]
+pub struct ThreadSplitter {
+    threads: std::collections::BTreeMap<u64, std::collections::VecDeque<Event>>,
+    unassigned: std::collections::VecDeque<Event>,
+}
+
+impl ThreadSplitter {
+    pub fn new() -> ThreadSplitter {
+        ThreadSplitter::default()
+    }
+
+    pub fn push(&mut self, event: Event) {
+        match find_u64(&event, "thread_id") {
+            Some(thread_id) => self.threads.entry(thread_id).or_default().push_back(event),
+            None => self.unassigned.push_back(event),
+        }
+    }
+
+    pub fn get_thread_ids(&self) -> Vec<u64> {
+        self.threads.keys().cloned().collect()
+    }
+
+    pub fn get_event_count(&self, thread_id: u64) -> usize {
+        match self.threads.get(&thread_id) {
+            Some(events) => events.len(),
+            None => 0,
+        }
+    }
+
+    pub fn iter_thread(&self, thread_id: u64) -> impl Iterator<Item = &Event> {
+        self.threads.get(&thread_id).into_iter().flatten()
+    }
+
+    pub fn pop_event(&mut self, thread_id: u64) -> Option<Event> {
+        let events = self.threads.get_mut(&thread_id)?;
+        let event = events.pop_front();
+        if events.is_empty() {
+            self.threads.remove(&thread_id);
+        }
+        event
+    }
+
+    pub fn take_thread(&mut self, thread_id: u64) -> impl Iterator<Item = Event> {
+        self.threads.remove(&thread_id).into_iter().flatten()
+    }
+
+    pub fn take_unassigned(&mut self) -> impl Iterator<Item = Event> {
+        std::mem::take(&mut self.unassigned).into_iter()
+    }
+
+    pub fn into_threads(self) -> impl Iterator<Item = (u64, Vec<Event>)> {
+        self.threads
+            .into_iter()
+            .map(|(thread_id, events)| (thread_id, events.into_iter().collect()))
+    }
+}
+
+impl EventSink for ThreadSplitter {
+    fn write_event(
+        &mut self,
+        event: &Event,
+        _registry: &EventKlassRegistry,
+    ) -> std::io::Result<()> {
+        self.push(event.clone());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::Value;
+    use crate::test_events;
+
+    fn make_event(timestamp: u64, thread_id: Option<u32>) -> Event {
+        let fields = match thread_id {
+            Some(thread_id) => vec![("thread_id", Value::U32(thread_id))],
+            None => vec![],
+        };
+        test_events::make_event(100, timestamp, fields)
+    }
+
+    fn timestamps<'a>(events: impl Iterator<Item = &'a Event>) -> Vec<u64> {
+        events.map(|event| event.get_timestamp().unwrap()).collect()
+    }
+
+    #[test]
+    fn events_should_be_grouped_by_thread_id() {
+        let mut splitter = ThreadSplitter::new();
+        splitter.push(make_event(1, Some(7)));
+        splitter.push(make_event(2, Some(3)));
+        splitter.push(make_event(3, Some(7)));
+        splitter.push(make_event(4, None));
+
+        assert_eq!(splitter.get_thread_ids(), vec![3, 7]);
+        assert_eq!(splitter.get_event_count(7), 2);
+        assert_eq!(splitter.get_event_count(5), 0);
+        assert_eq!(timestamps(splitter.iter_thread(7)), vec![1, 3]);
+        assert_eq!(timestamps(splitter.iter_thread(5)), Vec::<u64>::new());
+
+        let unassigned: Vec<Event> = splitter.take_unassigned().collect();
+        assert_eq!(timestamps(unassigned.iter()), vec![4]);
+    }
+
+    #[test]
+    fn consumed_threads_should_be_removed() {
+        let mut splitter = ThreadSplitter::new();
+        splitter.push(make_event(1, Some(1)));
+        splitter.push(make_event(2, Some(2)));
+        splitter.push(make_event(3, Some(2)));
+
+        assert!(splitter.pop_event(1).is_some());
+        assert!(splitter.pop_event(1).is_none());
+        assert_eq!(splitter.take_thread(2).count(), 2);
+        assert!(splitter.get_thread_ids().is_empty());
+    }
+
+    #[test]
+    fn into_threads_should_return_events_in_insertion_order() {
+        let mut splitter = ThreadSplitter::new();
+        let registry = EventKlassRegistry::new();
+        splitter
+            .write_event(&make_event(5, Some(1)), &registry)
+            .unwrap();
+        splitter
+            .write_event(&make_event(2, Some(1)), &registry)
+            .unwrap();
+
+        let threads: Vec<(u64, Vec<Event>)> = splitter.into_threads().collect();
+        assert_eq!(threads.len(), 1);
+        assert_eq!(threads[0].0, 1);
+        assert_eq!(timestamps(threads[0].1.iter()), vec![5, 2]);
+    }
+}
@@ -0,0 +1,404 @@
+use crate::data_provider::DataError;
+use crate::data_struct_reader::ReadEventError;
+use crate::event::{Event, Value};
+use crate::event_reader::EventReader;
+use crate::event_writer::EventWriter;
+use crate::json::escape_string;
+use crate::pipeline::{PipelineError, RunReport};
+use crate::registry::{CoreEventKlassId, EventKlassRegistry};
+use crate::sink::EventSink;
+
+// Modifies the event before it's written; returning false drops the event.
+pub type EventTransform = Box<dyn FnMut(&mut Event, &EventKlassRegistry) -> bool>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppliedTransform {
+    pub name: String,
+    pub removed_count: u64,
+}
+
+// Records what the transcoder did to the trace, so the output can be audited
+// against the original one.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TranscodeManifest {
+    pub written_count: u64,
+    // Removed events per klass name (or klass id, if the klass is unknown).
+    pub removed_per_klass: std::collections::BTreeMap<String, u64>,
+    pub dropped_klasses: std::collections::BTreeSet<String>,
+    pub time_range: Option<(u64, u64)>,
+    // First and last timestamp of the events cut before and after the time range.
+    pub cut_before: Option<(u64, u64)>,
+    pub cut_after: Option<(u64, u64)>,
+    pub transforms: Vec<AppliedTransform>,
+}
+
+impl TranscodeManifest {
+    pub fn get_removed_count(&self) -> u64 {
+        self.removed_per_klass.values().sum()
+    }
+
+    pub fn write_json(&self, writer: &mut dyn std::io::Write) -> std::io::Result<()> {
+        let removed: Vec<String> = self
+            .removed_per_klass
+            .iter()
+            .map(|(name, count)| format!("{}:{}", escape_string(name), count))
+            .collect();
+        let dropped: Vec<String> = self
+            .dropped_klasses
+            .iter()
+            .map(|name| escape_string(name))
+            .collect();
+        let transforms: Vec<String> = self
+            .transforms
+            .iter()
+            .map(|transform| {
+                format!(
+                    "{{\"name\":{},\"removed_events\":{}}}",
+                    escape_string(&transform.name),
+                    transform.removed_count
+                )
+            })
+            .collect();
+        let time_range = match self.time_range {
+            Some((start, end)) => format!(
+                "{{\"start\":{},\"end\":{},\"cut_before\":{},\"cut_after\":{}}}",
+                start,
+                end,
+                Self::format_cut_range(self.cut_before),
+                Self::format_cut_range(self.cut_after)
+            ),
+            None => "null".to_owned(),
+        };
+        writeln!(
+            writer,
+            "{{\"written_events\":{},\"removed_events\":{{{}}},\"dropped_klasses\":[{}],\"time_range\":{},\"transforms\":[{}]}}",
+            self.written_count,
+            removed.join(","),
+            dropped.join(","),
+            time_range,
+            transforms.join(",")
+        )
+    }
+
+    fn format_cut_range(range: Option<(u64, u64)>) -> String {
+        match range {
+            Some((first, last)) => format!("{{\"first\":{},\"last\":{}}}", first, last),
+            None => "null".to_owned(),
+        }
+    }
+
+    fn extend_cut_range(range: &mut Option<(u64, u64)>, timestamp: u64) {
+        *range = Some(match *range {
+            Some((first, last)) => (first.min(timestamp), last.max(timestamp)),
+            None => (timestamp, timestamp),
+        });
+    }
+}
+
+// Rewrites a trace: events are filtered and transformed before being written
+// by the EventWriter. Only klasses of written events end up in the output.
+pub struct Transcoder {
+    writer: EventWriter,
+    transforms: Vec<EventTransform>,
+    manifest: TranscodeManifest,
+}
+
+impl Transcoder {
+    pub fn new(writer: EventWriter) -> Transcoder {
+        Transcoder {
+            writer,
+            transforms: Vec::new(),
+            manifest: TranscodeManifest::default(),
+        }
+    }
+
+    pub fn drop_klass(mut self, klass_name: &str) -> Transcoder {
+        self.manifest.dropped_klasses.insert(klass_name.to_owned());
+        self
+    }
+
+    // Keeps events with timestamps in [start, end); events without a
+    // timestamp are kept as well.
+    pub fn with_time_range(mut self, start: u64, end: u64) -> Transcoder {
+        self.manifest.time_range = Some((start, end));
+        self
+    }
+
+    // Transforms are applied in the order they were added; the name
+    // identifies the transform in the manifest.
+    pub fn with_transform(mut self, name: &str, transform: EventTransform) -> Transcoder {
+        self.transforms.push(transform);
+        self.manifest.transforms.push(AppliedTransform {
+            name: name.to_owned(),
+            removed_count: 0,
+        });
+        self
+    }
+
+    // Replaces values of the top-level field in all events having it.
+    pub fn rewrite_field(
+        self,
+        field_name: &str,
+        mut rewrite: Box<dyn FnMut(&Value) -> Value>,
+    ) -> Transcoder {
+        let name = format!("rewrite_field({})", field_name);
+        let field_name = field_name.to_owned();
+        self.with_transform(
+            &name,
+            Box::new(move |event, _| {
+                if let Some(value) = event.get_raw_value(&field_name) {
+                    let value = rewrite(value);
+                    event.set_value(&field_name, value);
+                }
+                true
+            }),
+        )
+    }
+
+    // Counts of (non-metadata) events are summed over all the runs.
+    pub fn get_written_count(&self) -> u64 {
+        self.manifest.written_count
+    }
+
+    pub fn get_dropped_count(&self) -> u64 {
+        self.manifest.get_removed_count()
+    }
+
+    pub fn get_manifest(&self) -> &TranscodeManifest {
+        &self.manifest
+    }
+
+    // Transcodes all the events of the reader; the event count of the
+    // report is the number of events written in this run.
+    pub fn run(&mut self, mut reader: EventReader) -> Result<RunReport, PipelineError> {
+        let mut registry = EventKlassRegistry::new();
+        let mut report = RunReport::default();
+        let written_count = self.manifest.written_count;
+        loop {
+            match reader.read_event(&mut registry) {
+                Ok(event) => self
+                    .write_event(&event, &registry)
+                    .map_err(PipelineError::IOError)?,
+                Err(ReadEventError::DataError(DataError::EndOfStream)) => break,
+                Err(err) => return Err(PipelineError::ReadEventError(err)),
+            }
+        }
+
+        self.flush().map_err(PipelineError::IOError)?;
+        report.update_from_reader(&reader);
+        report.event_count = self.manifest.written_count - written_count;
+        Ok(report)
+    }
+
+    // Returns false (and records the reason in the manifest) if the event
+    // is removed by the klass or time range filters.
+    fn keep_event(&mut self, event: &Event) -> bool {
+        if let Some((start, end)) = self.manifest.time_range {
+            if let Some(timestamp) = event.get_timestamp() {
+                if timestamp < start {
+                    TranscodeManifest::extend_cut_range(&mut self.manifest.cut_before, timestamp);
+                    return false;
+                }
+                if timestamp >= end {
+                    TranscodeManifest::extend_cut_range(&mut self.manifest.cut_after, timestamp);
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    fn record_removed(&mut self, klass_name: String) {
+        *self
+            .manifest
+            .removed_per_klass
+            .entry(klass_name)
+            .or_insert(0) += 1;
+    }
+}
+
+impl EventSink for Transcoder {
+    fn write_event(&mut self, event: &Event, registry: &EventKlassRegistry) -> std::io::Result<()> {
+        if CoreEventKlassId::is_metadata_klass(event.get_klass_id()) {
+            return Ok(());
+        }
+        let klass_name = match registry.get_klass_by_id(event.get_klass_id()) {
+            Some(klass) => klass.get_name().clone(),
+            None => event.get_klass_id().to_string(),
+        };
+        if self.manifest.dropped_klasses.contains(&klass_name) || !self.keep_event(event) {
+            self.record_removed(klass_name);
+            return Ok(());
+        }
+        if self.transforms.is_empty() {
+            EventSink::write_event(&mut self.writer, event, registry)?;
+            self.manifest.written_count += 1;
+            return Ok(());
+        }
+
+        let mut event = event.clone();
+        for (index, transform) in self.transforms.iter_mut().enumerate() {
+            if !transform(&mut event, registry) {
+                self.manifest.transforms[index].removed_count += 1;
+                self.record_removed(klass_name);
+                return Ok(());
+            }
+        }
+        EventSink::write_event(&mut self.writer, &event, registry)?;
+        self.manifest.written_count += 1;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        EventSink::flush(&mut self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_provider::DataProvider;
+    use crate::event::DataType;
+    use crate::event_klass::EventKlass;
+    use crate::pipeline::RunStatus;
+    use crate::test_events;
+    use hawktracer_parser_test_utilities::FakeDataWriter;
+
+    fn make_registry() -> EventKlassRegistry {
+        let mut registry = EventKlassRegistry::new();
+        for (klass_id, name) in &[(100, "foo"), (101, "bar")] {
+            let mut klass = EventKlass::new(*klass_id, name.to_string());
+            klass.add_field("base".to_owned(), "HT_Event".to_owned(), DataType::Struct);
+            klass.add_field("label".to_owned(), "const char*".to_owned(), DataType::Str);
+            registry.add_klass(klass);
+        }
+        registry
+    }
+
+    fn make_event(klass_id: u32, timestamp: u64, label: &str) -> Event {
+        test_events::make_event(
+            klass_id,
+            timestamp,
+            vec![("label", Value::Str(label.to_owned()))],
+        )
+    }
+
+    fn make_reader(events: &[Event]) -> EventReader {
+        let buffer = FakeDataWriter::new(false);
+        let mut writer = EventWriter::new(Box::new(buffer.clone()));
+        let registry = make_registry();
+        for event in events {
+            writer.write_event(event, &registry).unwrap();
+        }
+        let data = buffer.get_data();
+        EventReader::new(DataProvider::new(Box::new(std::io::Cursor::new(data))))
+    }
+
+    fn read_all(data: Vec<u8>) -> (Vec<Event>, EventKlassRegistry) {
+        let mut registry = EventKlassRegistry::new();
+        let mut reader = EventReader::new(DataProvider::new(Box::new(std::io::Cursor::new(data))));
+        let mut events = Vec::new();
+        while let Ok(event) = reader.read_event(&mut registry) {
+            if !CoreEventKlassId::is_metadata_klass(event.get_klass_id()) {
+                events.push(event);
+            }
+        }
+        (events, registry)
+    }
+
+    #[test]
+    fn transcoder_should_filter_and_rewrite_events() {
+        let reader = make_reader(&[
+            make_event(100, 1, "a"),
+            make_event(101, 2, "b"),
+            make_event(100, 3, "secret"),
+            make_event(100, 4, "c"),
+            make_event(100, 9, "d"),
+        ]);
+        let buffer = FakeDataWriter::new(false);
+        let mut transcoder = Transcoder::new(EventWriter::new(Box::new(buffer.clone())))
+            .drop_klass("bar")
+            .with_time_range(2, 9)
+            .with_transform(
+                "drop_secrets",
+                Box::new(|event, _| event.get_value_string("label").unwrap() != "secret"),
+            )
+            .rewrite_field(
+                "label",
+                Box::new(|value| match value {
+                    Value::Str(label) => Value::Str(format!("{}!", label)),
+                    value => value.clone(),
+                }),
+            );
+
+        let report = transcoder.run(reader).unwrap();
+        assert_eq!(report.event_count, 1);
+        assert_eq!(report.get_status(), RunStatus::Success);
+        assert_eq!(transcoder.get_written_count(), 1);
+        assert_eq!(transcoder.get_dropped_count(), 4);
+
+        let (events, registry) = read_all(buffer.get_data());
+        assert_eq!(events, vec![make_event(100, 4, "c!")]);
+        assert!(registry.get_klass_by_name("foo").is_some());
+        assert!(registry.get_klass_by_name("bar").is_none());
+
+        let manifest = transcoder.get_manifest();
+        assert_eq!(manifest.removed_per_klass.get("foo"), Some(&3));
+        assert_eq!(manifest.removed_per_klass.get("bar"), Some(&1));
+        assert_eq!(manifest.cut_before, Some((1, 1)));
+        assert_eq!(manifest.cut_after, Some((9, 9)));
+        assert_eq!(
+            manifest.transforms,
+            vec![
+                AppliedTransform {
+                    name: "drop_secrets".to_owned(),
+                    removed_count: 1
+                },
+                AppliedTransform {
+                    name: "rewrite_field(label)".to_owned(),
+                    removed_count: 0
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn manifest_should_be_written_as_json() {
+        let reader = make_reader(&[
+            make_event(100, 1, "a"),
+            make_event(101, 2, "b"),
+            make_event(100, 5, "c"),
+        ]);
+        let mut transcoder = Transcoder::new(EventWriter::new(Box::new(std::io::sink())))
+            .drop_klass("bar")
+            .with_time_range(3, 10)
+            .with_transform("keep_all", Box::new(|_, _| true));
+        transcoder.run(reader).unwrap();
+
+        let mut output = Vec::new();
+        transcoder.get_manifest().write_json(&mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{\"written_events\":1,\"removed_events\":{\"bar\":1,\"foo\":1},\
+             \"dropped_klasses\":[\"bar\"],\"time_range\":{\"start\":3,\"end\":10,\
+             \"cut_before\":{\"first\":1,\"last\":1},\"cut_after\":null},\
+             \"transforms\":[{\"name\":\"keep_all\",\"removed_events\":0}]}\n"
+        );
+    }
+
+    #[test]
+    fn transcoder_without_filters_should_copy_trace() {
+        let events = vec![make_event(100, 1, "a"), make_event(101, 2, "b")];
+        let buffer = FakeDataWriter::new(false);
+        let mut transcoder = Transcoder::new(EventWriter::new(Box::new(buffer.clone())));
+        let registry = make_registry();
+        for event in &events {
+            transcoder.write_event(event, &registry).unwrap();
+        }
+        transcoder.flush().unwrap();
+
+        assert_eq!(transcoder.get_written_count(), 2);
+        assert_eq!(transcoder.get_dropped_count(), 0);
+        assert_eq!(read_all(buffer.get_data()).0, events);
+    }
+}
@@ -0,0 +1,233 @@
+use crate::data_provider::DataError;
+use crate::data_struct_reader::ReadEventError;
+use crate::event::{DataType, Event, Value};
+use crate::event_klass::EventKlass;
+use crate::event_reader::EventReader;
+use crate::event_writer::EventWriter;
+use crate::pipeline::{PipelineError, RunReport};
+use crate::registry::{CoreEventKlassId, EventKlassRegistry};
+use crate::sink::EventSink;
+
+// Rewrites traces recorded with another base event layout (e.g. by older
+// clients, or customized ones using 32-bit timestamps) with the current core
+// klass layout, so they can be read without overriding the base klass.
+// Header fields missing in the old layout are written as 0, and fields which
+// are not part of the current layout are dropped.
+pub struct VersionConverter {
+    writer: EventWriter,
+    base_klass: EventKlass,
+    // Klasses of the converted traces, with the current base klass.
+    registry: EventKlassRegistry,
+    converted_count: u64,
+}
+
+impl VersionConverter {
+    // The base klass describes the header of events of the converted traces
+    // (see EventKlassRegistry::set_base_klass()).
+    pub fn new(writer: EventWriter, base_klass: EventKlass) -> VersionConverter {
+        VersionConverter {
+            writer,
+            base_klass,
+            registry: EventKlassRegistry::new(),
+            converted_count: 0,
+        }
+    }
+
+    // Counts of (non-metadata) events are summed over all the runs.
+    pub fn get_converted_count(&self) -> u64 {
+        self.converted_count
+    }
+
+    // Converts all the events of the reader; the event count of the report
+    // is the number of events written in this run.
+    pub fn run(&mut self, mut reader: EventReader) -> Result<RunReport, PipelineError> {
+        let mut registry = EventKlassRegistry::new();
+        registry
+            .set_base_klass(self.base_klass.clone())
+            .map_err(|err| PipelineError::Config(err.to_string()))?;
+        let mut report = RunReport::default();
+        let converted_count = self.converted_count;
+        loop {
+            match reader.read_event(&mut registry) {
+                Ok(event) => self
+                    .write_event(&event, &registry)
+                    .map_err(PipelineError::IOError)?,
+                Err(ReadEventError::DataError(DataError::EndOfStream)) => break,
+                Err(err) => return Err(PipelineError::ReadEventError(err)),
+            }
+        }
+
+        self.flush().map_err(PipelineError::IOError)?;
+        report.update_from_reader(&reader);
+        report.event_count = self.converted_count - converted_count;
+        Ok(report)
+    }
+
+    // Klasses of struct fields are copied as well, since the writer
+    // describes them before the klass.
+    fn add_klass(&mut self, klass_id: u32, source: &EventKlassRegistry) {
+        if CoreEventKlassId::is_core_klass(klass_id)
+            || self.registry.get_klass_by_id(klass_id).is_some()
+        {
+            return;
+        }
+        let klass = match source.get_klass_by_id(klass_id) {
+            Some(klass) => klass,
+            None => return,
+        };
+        self.registry.add_klass(klass.clone());
+
+        for field in klass.get_fields() {
+            if *field.get_data_type() == DataType::Struct && !field.is_base_event_field() {
+                if let Some(field_klass) = source.get_klass_by_name(field.get_type_name()) {
+                    self.add_klass(field_klass.get_id(), source);
+                }
+            }
+        }
+    }
+}
+
+// Header values are stored in the innermost base struct of the event (or in
+// the event itself, if it's flattened).
+fn add_missing_header_fields(event: &mut Event, base_klass: &EventKlass) {
+    match event.take_value("base") {
+        Some(Value::Struct(mut base_event)) => {
+            add_missing_header_fields(&mut base_event, base_klass);
+            event.set_value("base", Value::Struct(base_event));
+        }
+        value => {
+            if let Some(value) = value {
+                event.set_value("base", value);
+            }
+            for field in base_klass.get_fields() {
+                if field.get_name() != "type" && event.get_raw_value(field.get_name()).is_none() {
+                    event.set_value(field.get_name(), Value::U64(0));
+                }
+            }
+        }
+    }
+}
+
+impl EventSink for VersionConverter {
+    fn write_event(&mut self, event: &Event, registry: &EventKlassRegistry) -> std::io::Result<()> {
+        if CoreEventKlassId::is_metadata_klass(event.get_klass_id()) {
+            return Ok(());
+        }
+        self.add_klass(event.get_klass_id(), registry);
+
+        let mut event = event.clone();
+        if let Some(base_klass) = self.registry.get_klass_by_id(CoreEventKlassId::Base as u32) {
+            add_missing_header_fields(&mut event, base_klass);
+        }
+        EventSink::write_event(&mut self.writer, &event, &self.registry)?;
+        self.converted_count += 1;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        EventSink::flush(&mut self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_provider::DataProvider;
+    use crate::data_struct_reader::Endianness;
+    use hawktracer_parser_test_utilities::FakeDataWriter;
+
+    fn make_base_klass() -> EventKlass {
+        let mut klass = EventKlass::new(CoreEventKlassId::Base as u32, "HT_Event".to_owned());
+        klass.add_field("type".to_owned(), "uint32_t".to_owned(), DataType::U32);
+        klass.add_field("timestamp".to_owned(), "uint32_t".to_owned(), DataType::U32);
+        klass.add_field("thread_id".to_owned(), "uint32_t".to_owned(), DataType::U32);
+        klass
+    }
+
+    // Event with the header of make_base_klass() (little endian).
+    fn encode_event(klass_id: u32, timestamp: u32, payload: &[u8]) -> Vec<u8> {
+        let mut data = klass_id.to_le_bytes().to_vec();
+        data.extend_from_slice(&timestamp.to_le_bytes());
+        data.extend_from_slice(&7u32.to_le_bytes());
+        data.extend_from_slice(payload);
+        data
+    }
+
+    fn encode_field_info(type_name: &str, name: &str, size: u64, data_type: u8) -> Vec<u8> {
+        let mut payload = 100u32.to_le_bytes().to_vec();
+        for value in &[type_name, name] {
+            payload.extend_from_slice(value.as_bytes());
+            payload.push(0);
+        }
+        payload.extend_from_slice(&size.to_le_bytes());
+        payload.push(data_type);
+        encode_event(CoreEventKlassId::FieldInfo as u32, 0, &payload)
+    }
+
+    fn make_old_trace() -> Vec<u8> {
+        let mut klass_info = 100u32.to_le_bytes().to_vec();
+        klass_info.extend_from_slice(b"foo\0");
+        klass_info.push(2);
+
+        let mut data = encode_event(CoreEventKlassId::KlassInfo as u32, 0, &klass_info);
+        data.extend(encode_field_info("HT_Event", "base", 0, 1));
+        data.extend(encode_field_info("uint8_t", "value", 1, 99));
+        data.extend(encode_event(100, 5, &[9]));
+        data.extend(encode_event(100, 6, &[10]));
+        data
+    }
+
+    #[test]
+    fn events_should_be_rewritten_with_current_base_layout() {
+        let output = FakeDataWriter::new(false);
+        let mut converter = VersionConverter::new(
+            EventWriter::new(Box::new(output.clone())).with_endianness(Endianness::Little),
+            make_base_klass(),
+        );
+        let mut reader = EventReader::new(DataProvider::new(Box::new(std::io::Cursor::new(
+            make_old_trace(),
+        ))));
+        reader.set_endianness(Endianness::Little);
+
+        let report = converter.run(reader).unwrap();
+        assert_eq!(report.event_count, 2);
+        assert_eq!(converter.get_converted_count(), 2);
+
+        let mut registry = EventKlassRegistry::new();
+        let mut reader = EventReader::new(DataProvider::new(Box::new(std::io::Cursor::new(
+            output.get_data(),
+        ))));
+        reader.set_endianness(Endianness::Little);
+        let mut events = Vec::new();
+        while let Ok(event) = reader.read_event(&mut registry) {
+            if !CoreEventKlassId::is_metadata_klass(event.get_klass_id()) {
+                events.push(event);
+            }
+        }
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].get_klass_id(), 100);
+        assert_eq!(events[0].get_timestamp(), Some(5));
+        assert_eq!(events[0].get_id(), Some(0));
+        assert_eq!(events[0].get_value_u8("value").unwrap(), 9);
+        assert!(events[0].get_path("base.thread_id").is_none());
+        assert_eq!(events[1].get_timestamp(), Some(6));
+        assert_eq!(events[1].get_value_u8("value").unwrap(), 10);
+    }
+
+    #[test]
+    fn run_should_fail_for_invalid_base_klass() {
+        let mut converter = VersionConverter::new(
+            EventWriter::new(Box::new(FakeDataWriter::new(false))),
+            EventKlass::new(99, "HT_Event".to_owned()),
+        );
+        let reader = EventReader::new(DataProvider::new(Box::new(
+            std::io::Cursor::new(Vec::new()),
+        )));
+
+        assert!(matches!(
+            converter.run(reader),
+            Err(PipelineError::Config(_))
+        ));
+    }
+}
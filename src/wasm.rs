@@ -0,0 +1,95 @@
+use crate::event::Event;
+use crate::parser::Parser;
+use crate::registry::EventKlassRegistry;
+use wasm_bindgen::prelude::*;
+
+// Encodes events, flattened, as a JSON array of objects (see
+// json::write_event()). Note that JavaScript numbers can't exactly
+// represent 64-bit integers above 2^53.
+fn events_to_json(events: &[Event], registry: &EventKlassRegistry) -> String {
+    let mut json = Vec::new();
+    json.push(b'[');
+    for (i, event) in events.iter().enumerate() {
+        if i > 0 {
+            json.push(b',');
+        }
+        // Writing to a vector can't fail.
+        let _ = crate::json::write_event(&mut json, &event.clone().flat_event(), registry);
+    }
+    json.push(b']');
+    String::from_utf8_lossy(&json).into_owned()
+}
+
+// Wrapper of the push parser for browser-based viewers: chunks of the
+// stream (e.g. from a fetch() body reader) are fed as they arrive.
+// Quotas are not supported as std::time::Instant is not available in
+// wasm32-unknown-unknown.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct WasmParser {
+    parser: Parser,
+}
+
+#[wasm_bindgen]
+impl WasmParser {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> WasmParser {
+        WasmParser::default()
+    }
+
+    // Returns an array of the events fully received so far; decoding errors
+    // are thrown as strings (see Parser::feed()).
+    pub fn feed(&mut self, bytes: &[u8]) -> Result<JsValue, JsValue> {
+        let events = self
+            .parser
+            .feed(bytes)
+            .map_err(|err| JsValue::from_str(&err.to_string()))?;
+        js_sys::JSON::parse(&events_to_json(&events, self.parser.get_registry()))
+    }
+
+    #[wasm_bindgen(js_name = pendingSize)]
+    pub fn get_pending_size(&self) -> usize {
+        self.parser.get_pending_size()
+    }
+
+    // See Parser::reset().
+    pub fn reset(&mut self) {
+        self.parser.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{DataType, Value};
+    use crate::event_klass::EventKlass;
+    use crate::registry::CoreEventKlassId;
+
+    #[test]
+    fn events_should_be_encoded_as_json_array() {
+        let mut registry = EventKlassRegistry::new();
+        let mut klass = EventKlass::new(100, "foo".to_owned());
+        klass.add_field("base".to_owned(), "HT_Event".to_owned(), DataType::Struct);
+        klass.add_field("value".to_owned(), "uint32_t".to_owned(), DataType::U32);
+        registry.add_klass(klass);
+
+        let mut base_values = fnv::FnvHashMap::default();
+        base_values.insert("type".to_owned(), Value::U32(100));
+        let mut values = fnv::FnvHashMap::default();
+        values.insert(
+            "base".to_owned(),
+            Value::Struct(Box::new(Event::new(
+                CoreEventKlassId::Base as u32,
+                base_values,
+            ))),
+        );
+        values.insert("value".to_owned(), Value::U32(5));
+        let event = Event::new(100, values);
+
+        assert_eq!(events_to_json(&[], &registry), "[]");
+        assert_eq!(
+            events_to_json(&[event.clone(), event], &registry),
+            "[{\"klass\":\"foo\",\"type\":100,\"value\":5},{\"klass\":\"foo\",\"type\":100,\"value\":5}]"
+        );
+    }
+}
@@ -0,0 +1,244 @@
+use crate::event::{Event, Value};
+use crate::registry::EventKlassRegistry;
+
+// Names of the standard (non-core) klasses defined by HawkTracer.
+pub const CALLSTACK_BASE_KLASS_NAME: &str = "HT_CallstackBaseEvent";
+pub const CALLSTACK_INT_KLASS_NAME: &str = "HT_CallstackIntEvent";
+pub const CALLSTACK_STRING_KLASS_NAME: &str = "HT_CallstackStringEvent";
+pub const STRING_MAPPING_KLASS_NAME: &str = "HT_StringMappingEvent";
+pub const SYSTEM_INFO_KLASS_NAME: &str = "HT_SystemInfoEvent";
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum WellKnownKlass {
+    CallstackBase,
+    CallstackInt,
+    CallstackString,
+    StringMapping,
+    SystemInfo,
+}
+
+impl WellKnownKlass {
+    pub fn get_name(self) -> &'static str {
+        match self {
+            WellKnownKlass::CallstackBase => CALLSTACK_BASE_KLASS_NAME,
+            WellKnownKlass::CallstackInt => CALLSTACK_INT_KLASS_NAME,
+            WellKnownKlass::CallstackString => CALLSTACK_STRING_KLASS_NAME,
+            WellKnownKlass::StringMapping => STRING_MAPPING_KLASS_NAME,
+            WellKnownKlass::SystemInfo => SYSTEM_INFO_KLASS_NAME,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<WellKnownKlass> {
+        match name {
+            CALLSTACK_BASE_KLASS_NAME => Some(WellKnownKlass::CallstackBase),
+            CALLSTACK_INT_KLASS_NAME => Some(WellKnownKlass::CallstackInt),
+            CALLSTACK_STRING_KLASS_NAME => Some(WellKnownKlass::CallstackString),
+            STRING_MAPPING_KLASS_NAME => Some(WellKnownKlass::StringMapping),
+            SYSTEM_INFO_KLASS_NAME => Some(WellKnownKlass::SystemInfo),
+            _ => None,
+        }
+    }
+
+    pub fn from_event(event: &Event, registry: &EventKlassRegistry) -> Option<WellKnownKlass> {
+        WellKnownKlass::from_name(event.get_klass_name(registry)?)
+    }
+}
+
+pub fn is_callstack_event(event: &Event, registry: &EventKlassRegistry) -> bool {
+    matches!(
+        WellKnownKlass::from_event(event, registry),
+        Some(WellKnownKlass::CallstackBase)
+            | Some(WellKnownKlass::CallstackInt)
+            | Some(WellKnownKlass::CallstackString)
+    )
+}
+
+pub fn is_string_mapping_event(event: &Event, registry: &EventKlassRegistry) -> bool {
+    WellKnownKlass::from_event(event, registry) == Some(WellKnownKlass::StringMapping)
+}
+
+pub fn is_system_info_event(event: &Event, registry: &EventKlassRegistry) -> bool {
+    WellKnownKlass::from_event(event, registry) == Some(WellKnownKlass::SystemInfo)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CallstackLabel<'a> {
+    // Identifier resolved through string mapping events.
+    Int(u64),
+    Str(&'a str),
+}
+
+// Fields are looked up in base events as well, so both nested and
+// flattened events are supported.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallstackEvent<'a> {
+    pub timestamp: u64,
+    pub duration: u64,
+    pub thread_id: u64,
+    // None for HT_CallstackBaseEvent events.
+    pub label: Option<CallstackLabel<'a>>,
+}
+
+impl<'a> CallstackEvent<'a> {
+    // Returns None if the event is not a callstack event or some of its
+    // fields are missing.
+    pub fn from_event(
+        event: &'a Event,
+        registry: &EventKlassRegistry,
+    ) -> Option<CallstackEvent<'a>> {
+        let label = match WellKnownKlass::from_event(event, registry)? {
+            WellKnownKlass::CallstackBase => None,
+            WellKnownKlass::CallstackInt => {
+                Some(CallstackLabel::Int(event.find_value("label")?.as_u64()?))
+            }
+            WellKnownKlass::CallstackString => match event.find_value("label")? {
+                Value::Str(label) => Some(CallstackLabel::Str(label)),
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        Some(CallstackEvent {
+            timestamp: event.get_timestamp()?,
+            duration: event.find_value("duration")?.as_u64()?,
+            thread_id: event.find_value("thread_id")?.as_u64()?,
+            label,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StringMappingEvent<'a> {
+    pub identifier: u64,
+    pub label: &'a str,
+}
+
+impl<'a> StringMappingEvent<'a> {
+    pub fn from_event(
+        event: &'a Event,
+        registry: &EventKlassRegistry,
+    ) -> Option<StringMappingEvent<'a>> {
+        if !is_string_mapping_event(event, registry) {
+            return None;
+        }
+
+        match (
+            event.find_value("identifier")?.as_u64(),
+            event.find_value("label")?,
+        ) {
+            (Some(identifier), Value::Str(label)) => Some(StringMappingEvent { identifier, label }),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::DataType;
+    use crate::event_klass::EventKlass;
+    use crate::registry::CoreEventKlassId;
+
+    fn make_registry() -> EventKlassRegistry {
+        let mut registry = EventKlassRegistry::new();
+        for (klass_id, name) in &[
+            (100, CALLSTACK_INT_KLASS_NAME),
+            (101, CALLSTACK_STRING_KLASS_NAME),
+            (102, STRING_MAPPING_KLASS_NAME),
+            (103, "foo"),
+        ] {
+            let mut klass = EventKlass::new(*klass_id, name.to_string());
+            klass.add_field("base".to_owned(), "HT_Event".to_owned(), DataType::Struct);
+            registry.add_klass(klass);
+        }
+        registry
+    }
+
+    fn make_callstack_event(klass_id: u32, label: Value) -> Event {
+        let mut base_values = fnv::FnvHashMap::default();
+        base_values.insert("type".to_owned(), Value::U32(klass_id));
+        base_values.insert("timestamp".to_owned(), Value::U64(10));
+        let mut callstack_base_values = fnv::FnvHashMap::default();
+        callstack_base_values.insert(
+            "base".to_owned(),
+            Value::Struct(Box::new(Event::new(
+                CoreEventKlassId::Base as u32,
+                base_values,
+            ))),
+        );
+        callstack_base_values.insert("duration".to_owned(), Value::U64(5));
+        callstack_base_values.insert("thread_id".to_owned(), Value::U32(2));
+        let mut values = fnv::FnvHashMap::default();
+        values.insert(
+            "base".to_owned(),
+            Value::Struct(Box::new(Event::new(99, callstack_base_values))),
+        );
+        values.insert("label".to_owned(), label);
+        Event::new(klass_id, values)
+    }
+
+    #[test]
+    fn well_known_klass_should_be_found_by_name() {
+        for klass in &[
+            WellKnownKlass::CallstackBase,
+            WellKnownKlass::CallstackInt,
+            WellKnownKlass::CallstackString,
+            WellKnownKlass::StringMapping,
+            WellKnownKlass::SystemInfo,
+        ] {
+            assert_eq!(WellKnownKlass::from_name(klass.get_name()), Some(*klass));
+        }
+        assert_eq!(WellKnownKlass::from_name("HT_Event"), None);
+    }
+
+    #[test]
+    fn callstack_events_should_be_recognized() {
+        let registry = make_registry();
+        let int_event = make_callstack_event(100, Value::U64(7));
+        let string_event = make_callstack_event(101, Value::Str("foo".to_owned()));
+
+        assert!(is_callstack_event(&int_event, &registry));
+        assert!(!is_callstack_event(
+            &make_callstack_event(103, Value::U64(7)),
+            &registry
+        ));
+        assert_eq!(
+            CallstackEvent::from_event(&int_event, &registry),
+            Some(CallstackEvent {
+                timestamp: 10,
+                duration: 5,
+                thread_id: 2,
+                label: Some(CallstackLabel::Int(7)),
+            })
+        );
+        assert_eq!(
+            CallstackEvent::from_event(&string_event.clone().flat_event(), &registry)
+                .unwrap()
+                .label,
+            Some(CallstackLabel::Str("foo"))
+        );
+        assert!(
+            CallstackEvent::from_event(&make_callstack_event(101, Value::U64(7)), &registry)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn string_mapping_events_should_be_recognized() {
+        let registry = make_registry();
+        let mut values = fnv::FnvHashMap::default();
+        values.insert("identifier".to_owned(), Value::U64(7));
+        values.insert("label".to_owned(), Value::Str("foo".to_owned()));
+        let event = Event::new(102, values);
+
+        assert!(is_string_mapping_event(&event, &registry));
+        assert!(!is_system_info_event(&event, &registry));
+        assert_eq!(
+            StringMappingEvent::from_event(&event, &registry),
+            Some(StringMappingEvent {
+                identifier: 7,
+                label: "foo"
+            })
+        );
+    }
+}
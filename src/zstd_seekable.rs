@@ -0,0 +1,423 @@
+use crate::data_provider::{DataError, DataProvider};
+use crate::data_struct_reader::{Endianness, ReadEventError};
+use crate::event::Event;
+use crate::event_reader::EventReader;
+use crate::index::IndexEntry;
+use crate::registry::EventKlassRegistry;
+
+// Zstd seekable format: the data is compressed in independent zstd frames,
+// followed by a skippable frame with the seek table (compressed and
+// decompressed size of each frame) and a footer:
+// https://github.com/facebook/zstd/blob/dev/contrib/seekable_format/zstd_seekable_compression_format.md
+const SKIPPABLE_FRAME_MAGIC: u32 = 0x184D_2A5E;
+const SEEKABLE_MAGIC: u32 = 0x8F92_EAB1;
+const SKIPPABLE_HEADER_SIZE: u64 = 8;
+const FOOTER_SIZE: u64 = 9;
+const CHECKSUM_FLAG: u8 = 0x80;
+const RESERVED_FLAGS: u8 = 0x7C;
+
+pub const DEFAULT_FRAME_SIZE: usize = 256 * 1024;
+
+fn invalid_data(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message.to_owned())
+}
+
+fn read_u32(data: &[u8]) -> u32 {
+    u32::from_le_bytes([data[0], data[1], data[2], data[3]])
+}
+
+// Compresses the data written to it in frames of (at most) frame size
+// decompressed bytes. The seek table is written by finish(); when the writer
+// is dropped without calling finish(), it's written on drop and errors are
+// ignored.
+pub struct SeekableWriter<W: std::io::Write> {
+    writer: Option<W>,
+    frame: Vec<u8>,
+    frame_size: usize,
+    level: i32,
+    frame_sizes: Vec<(u32, u32)>,
+}
+
+impl<W: std::io::Write> SeekableWriter<W> {
+    pub fn new(writer: W) -> SeekableWriter<W> {
+        SeekableWriter {
+            writer: Some(writer),
+            frame: Vec::new(),
+            frame_size: DEFAULT_FRAME_SIZE,
+            level: zstd::DEFAULT_COMPRESSION_LEVEL,
+            frame_sizes: Vec::new(),
+        }
+    }
+
+    // Smaller frames make seeking cheaper, but compress worse.
+    pub fn with_frame_size(mut self, frame_size: usize) -> SeekableWriter<W> {
+        self.frame_size = std::cmp::max(1, std::cmp::min(frame_size, u32::MAX as usize));
+        self
+    }
+
+    pub fn with_level(mut self, level: i32) -> SeekableWriter<W> {
+        self.level = level;
+        self
+    }
+
+    pub fn get_frame_count(&self) -> usize {
+        self.frame_sizes.len()
+    }
+
+    pub fn finish(mut self) -> std::io::Result<W> {
+        self.write_seek_table()?;
+        Ok(self.writer.take().unwrap())
+    }
+
+    // The writer is only taken by finish(), which consumes self.
+    fn get_writer(&mut self) -> &mut W {
+        self.writer.as_mut().unwrap()
+    }
+
+    fn write_frame(&mut self) -> std::io::Result<()> {
+        if self.frame.is_empty() {
+            return Ok(());
+        }
+        let compressed = zstd::bulk::compress(&self.frame, self.level)?;
+        if compressed.len() > u32::MAX as usize {
+            return Err(invalid_data("Compressed frame is too big"));
+        }
+        self.get_writer().write_all(&compressed)?;
+        self.frame_sizes
+            .push((compressed.len() as u32, self.frame.len() as u32));
+        self.frame.clear();
+        Ok(())
+    }
+
+    fn write_seek_table(&mut self) -> std::io::Result<()> {
+        self.write_frame()?;
+        let frame_count = self.frame_sizes.len() as u32;
+        let mut table = Vec::with_capacity(self.frame_sizes.len() * 8 + 17);
+        table.extend_from_slice(&SKIPPABLE_FRAME_MAGIC.to_le_bytes());
+        table.extend_from_slice(&(frame_count * 8 + FOOTER_SIZE as u32).to_le_bytes());
+        for (compressed_size, decompressed_size) in &self.frame_sizes {
+            table.extend_from_slice(&compressed_size.to_le_bytes());
+            table.extend_from_slice(&decompressed_size.to_le_bytes());
+        }
+        table.extend_from_slice(&frame_count.to_le_bytes());
+        table.push(0);
+        table.extend_from_slice(&SEEKABLE_MAGIC.to_le_bytes());
+
+        let writer = self.get_writer();
+        writer.write_all(&table)?;
+        writer.flush()
+    }
+}
+
+impl<W: std::io::Write> std::io::Write for SeekableWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let size = std::cmp::min(buf.len(), self.frame_size - self.frame.len());
+        self.frame.extend_from_slice(&buf[..size]);
+        if self.frame.len() == self.frame_size {
+            self.write_frame()?;
+        }
+        Ok(size)
+    }
+
+    // Doesn't end the current frame, so flushing often doesn't produce
+    // tiny frames.
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.get_writer().flush()
+    }
+}
+
+impl<W: std::io::Write> Drop for SeekableWriter<W> {
+    fn drop(&mut self) {
+        if self.writer.is_some() {
+            let _ = self.write_seek_table();
+        }
+    }
+}
+
+struct FrameEntry {
+    compressed_offset: u64,
+    compressed_size: u32,
+    decompressed_offset: u64,
+    decompressed_size: u32,
+}
+
+// Reads the decompressed data; seeking only decompresses the frame
+// containing the new position. Positions are offsets in the decompressed
+// data, so they match offsets of the TraceIndex built for the stream.
+pub struct SeekableReader<R: std::io::Read + std::io::Seek> {
+    reader: R,
+    frames: Vec<FrameEntry>,
+    decompressed_size: u64,
+    position: u64,
+    current_frame: Option<(usize, Vec<u8>)>,
+}
+
+impl<R: std::io::Read + std::io::Seek> SeekableReader<R> {
+    pub fn new(mut reader: R) -> std::io::Result<SeekableReader<R>> {
+        let stream_size = reader.seek(std::io::SeekFrom::End(0))?;
+        if stream_size < SKIPPABLE_HEADER_SIZE + FOOTER_SIZE {
+            return Err(invalid_data("Stream is too short for the seek table"));
+        }
+        let mut footer = [0u8; FOOTER_SIZE as usize];
+        reader.seek(std::io::SeekFrom::End(-(FOOTER_SIZE as i64)))?;
+        reader.read_exact(&mut footer)?;
+        if read_u32(&footer[5..]) != SEEKABLE_MAGIC {
+            return Err(invalid_data("Invalid seekable format magic number"));
+        }
+        let descriptor = footer[4];
+        if descriptor & RESERVED_FLAGS != 0 {
+            return Err(invalid_data("Reserved seek table flags are set"));
+        }
+
+        let entry_size = if descriptor & CHECKSUM_FLAG != 0 {
+            12
+        } else {
+            8
+        };
+        let frame_count = read_u32(&footer) as u64;
+        let table_size = SKIPPABLE_HEADER_SIZE + frame_count * entry_size + FOOTER_SIZE;
+        if table_size > stream_size {
+            return Err(invalid_data("Seek table is bigger than the stream"));
+        }
+        let mut table = vec![0u8; (table_size - FOOTER_SIZE) as usize];
+        reader.seek(std::io::SeekFrom::End(-(table_size as i64)))?;
+        reader.read_exact(&mut table)?;
+        if read_u32(&table) != SKIPPABLE_FRAME_MAGIC
+            || read_u32(&table[4..]) as u64 != table_size - SKIPPABLE_HEADER_SIZE
+        {
+            return Err(invalid_data("Invalid seek table frame header"));
+        }
+
+        let mut frames = Vec::with_capacity(frame_count as usize);
+        let mut compressed_offset = 0;
+        let mut decompressed_offset = 0;
+        for entry in table[SKIPPABLE_HEADER_SIZE as usize..].chunks(entry_size as usize) {
+            let frame = FrameEntry {
+                compressed_offset,
+                compressed_size: read_u32(entry),
+                decompressed_offset,
+                decompressed_size: read_u32(&entry[4..]),
+            };
+            compressed_offset += frame.compressed_size as u64;
+            decompressed_offset += frame.decompressed_size as u64;
+            frames.push(frame);
+        }
+        if compressed_offset + table_size > stream_size {
+            return Err(invalid_data("Seek table doesn't match the stream size"));
+        }
+
+        Ok(SeekableReader {
+            reader,
+            frames,
+            decompressed_size: decompressed_offset,
+            position: 0,
+            current_frame: None,
+        })
+    }
+
+    pub fn get_frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn get_decompressed_size(&self) -> u64 {
+        self.decompressed_size
+    }
+
+    fn load_frame(&mut self, frame_index: usize) -> std::io::Result<()> {
+        if let Some((index, _)) = &self.current_frame {
+            if *index == frame_index {
+                return Ok(());
+            }
+        }
+
+        let frame = &self.frames[frame_index];
+        let mut compressed = vec![0u8; frame.compressed_size as usize];
+        self.reader
+            .seek(std::io::SeekFrom::Start(frame.compressed_offset))?;
+        self.reader.read_exact(&mut compressed)?;
+        let data = zstd::bulk::decompress(&compressed, frame.decompressed_size as usize)?;
+        if data.len() != frame.decompressed_size as usize {
+            return Err(invalid_data("Frame size doesn't match the seek table"));
+        }
+        self.current_frame = Some((frame_index, data));
+        Ok(())
+    }
+}
+
+impl<R: std::io::Read + std::io::Seek> std::io::Read for SeekableReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.position >= self.decompressed_size || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let frame_index = self
+            .frames
+            .partition_point(|frame| frame.decompressed_offset <= self.position)
+            - 1;
+        self.load_frame(frame_index)?;
+        let offset = (self.position - self.frames[frame_index].decompressed_offset) as usize;
+        let data = &self.current_frame.as_ref().unwrap().1[offset..];
+        let size = std::cmp::min(data.len(), buf.len());
+        buf[..size].copy_from_slice(&data[..size]);
+        self.position += size as u64;
+        Ok(size)
+    }
+}
+
+impl<R: std::io::Read + std::io::Seek> std::io::Seek for SeekableReader<R> {
+    fn seek(&mut self, position: std::io::SeekFrom) -> std::io::Result<u64> {
+        let position = match position {
+            std::io::SeekFrom::Start(offset) => Some(offset),
+            std::io::SeekFrom::End(offset) => self.decompressed_size.checked_add_signed(offset),
+            std::io::SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        match position {
+            Some(position) => {
+                self.position = position;
+                Ok(position)
+            }
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+// Decodes the events of the index entries (which can't be metadata events)
+// without decompressing frames not containing them. Klasses of the events
+// have to be in the registry already, e.g. from building the index.
+pub fn read_indexed_events<R: std::io::Read + std::io::Seek>(
+    reader: &mut SeekableReader<R>,
+    entries: &[IndexEntry],
+    endianness: Endianness,
+    registry: &EventKlassRegistry,
+) -> Result<Vec<Event>, ReadEventError> {
+    let mut events = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let mut data = vec![0u8; entry.size as usize];
+        std::io::Seek::seek(reader, std::io::SeekFrom::Start(entry.offset))
+            .and_then(|_| std::io::Read::read_exact(reader, &mut data))
+            .map_err(|err| ReadEventError::DataError(DataError::IOError(err)))?;
+
+        let mut event_reader =
+            EventReader::new(DataProvider::new(Box::new(std::io::Cursor::new(data))));
+        event_reader.set_endianness(endianness);
+        events.push(event_reader.read_data_event(registry)?);
+    }
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::TraceIndex;
+    use hawktracer_parser_test_utilities::stream::{TestStreamBuilder, TestValue};
+    use std::io::{Read, Seek, Write};
+
+    fn make_trace() -> Vec<u8> {
+        let mut builder = TestStreamBuilder::new().klass(100, "foo", &[("uint32_t", "value")]);
+        for value in 0..50 {
+            builder = builder.event(100, value as u64, &[TestValue::U32(value)]);
+        }
+        builder.build()
+    }
+
+    fn compress(data: &[u8], frame_size: usize) -> Vec<u8> {
+        let mut writer = SeekableWriter::new(Vec::new()).with_frame_size(frame_size);
+        writer.write_all(data).unwrap();
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn compressed_data_should_be_read_back() {
+        let data = make_trace();
+        let compressed = compress(&data, 100);
+
+        let mut reader = SeekableReader::new(std::io::Cursor::new(compressed.clone())).unwrap();
+        assert_eq!(reader.get_frame_count(), data.len().div_ceil(100));
+        assert_eq!(reader.get_decompressed_size(), data.len() as u64);
+        let mut decompressed = Vec::new();
+        reader.read_to_end(&mut decompressed).unwrap();
+        assert_eq!(decompressed, data);
+
+        // Frames are regular zstd frames, the seek table is skipped.
+        assert_eq!(zstd::stream::decode_all(&compressed[..]).unwrap(), data);
+    }
+
+    #[test]
+    fn reader_should_seek_into_any_frame() {
+        let data: Vec<u8> = (0..1000).map(|value| value as u8).collect();
+        let mut reader = SeekableReader::new(std::io::Cursor::new(compress(&data, 64))).unwrap();
+
+        let mut buffer = [0u8; 100];
+        reader.seek(std::io::SeekFrom::Start(500)).unwrap();
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(&buffer[..], &data[500..600]);
+
+        reader.seek(std::io::SeekFrom::Current(-300)).unwrap();
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(&buffer[..], &data[300..400]);
+
+        reader.seek(std::io::SeekFrom::End(-10)).unwrap();
+        assert_eq!(reader.read(&mut buffer).unwrap(), 10);
+        assert_eq!(&buffer[..10], &data[990..]);
+        assert_eq!(reader.read(&mut buffer).unwrap(), 0);
+        assert!(reader.seek(std::io::SeekFrom::Current(-2000)).is_err());
+    }
+
+    #[test]
+    fn dropped_writer_should_write_seek_table() {
+        let mut compressed = Vec::new();
+        {
+            let mut writer = SeekableWriter::new(&mut compressed);
+            writer.write_all(b"data").unwrap();
+        }
+
+        let mut reader = SeekableReader::new(std::io::Cursor::new(compressed)).unwrap();
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"data");
+    }
+
+    #[test]
+    fn invalid_seek_table_should_be_rejected() {
+        let compressed = compress(&make_trace(), 100);
+        let mut truncated = compressed.clone();
+        truncated.pop();
+        assert!(SeekableReader::new(std::io::Cursor::new(truncated)).is_err());
+
+        let mut corrupted = compressed.clone();
+        let frame_count_offset = corrupted.len() - FOOTER_SIZE as usize;
+        corrupted[frame_count_offset] += 1;
+        assert!(SeekableReader::new(std::io::Cursor::new(corrupted)).is_err());
+
+        let unseekable = zstd::bulk::compress(&make_trace(), 0).unwrap();
+        assert!(SeekableReader::new(std::io::Cursor::new(unseekable)).is_err());
+    }
+
+    #[test]
+    fn indexed_events_should_be_read_from_compressed_trace() {
+        let compressed = compress(&make_trace(), 128);
+        let mut registry = EventKlassRegistry::new();
+        let reader = SeekableReader::new(std::io::Cursor::new(compressed.clone())).unwrap();
+        let mut event_reader = EventReader::new(DataProvider::new(Box::new(reader)));
+        let index = TraceIndex::build(&mut event_reader, &mut registry).unwrap();
+
+        let entries: Vec<IndexEntry> = index
+            .get_entries()
+            .iter()
+            .filter(|entry| entry.klass_id == 100 && entry.timestamp % 10 == 7)
+            .cloned()
+            .collect();
+        let mut reader = SeekableReader::new(std::io::Cursor::new(compressed)).unwrap();
+        let events =
+            read_indexed_events(&mut reader, &entries, Endianness::Native, &registry).unwrap();
+
+        let values: Vec<u32> = events
+            .iter()
+            .map(|event| event.get_value_u32("value").unwrap())
+            .collect();
+        assert_eq!(values, vec![7, 17, 27, 37, 47]);
+    }
+}
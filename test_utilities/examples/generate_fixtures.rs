@@ -0,0 +1,208 @@
+// Regenerates the trace fixtures with `cargo run --example generate_fixtures`
+// (on a little-endian machine). The streams follow the layout written by the
+// HawkTracer client library: the endianness event, definitions of the core
+// and standard klasses (using the client's type names), then the events.
+use hawktracer_parser_test_utilities::stream::{
+    encode_header, EventPayload, FieldDefinition, KlassDefinition, TestStreamBuilder, TestValue,
+};
+
+const ENDIANNESS_KLASS_ID: u32 = 0;
+const CALLSTACK_INT_KLASS_ID: u32 = 5;
+const CALLSTACK_STRING_KLASS_ID: u32 = 6;
+const STRING_MAPPING_KLASS_ID: u32 = 7;
+
+fn field(type_name: &str, name: &str, size: u64, data_type: u8) -> FieldDefinition {
+    FieldDefinition {
+        type_name: type_name.to_owned(),
+        name: name.to_owned(),
+        size,
+        data_type,
+    }
+}
+
+fn klass(id: u32, name: &str, fields: Vec<FieldDefinition>) -> KlassDefinition {
+    KlassDefinition {
+        id,
+        name: name.to_owned(),
+        field_count: fields.len() as u8,
+        fields,
+    }
+}
+
+fn klasses() -> Vec<KlassDefinition> {
+    let base = || field("HT_Event", "base", 24, 1);
+    vec![
+        klass(
+            1,
+            "HT_Event",
+            vec![
+                field("HT_EventKlassId", "type", 4, 99),
+                field("HT_TimestampNs", "timestamp", 8, 99),
+                field("HT_EventId", "id", 8, 99),
+            ],
+        ),
+        klass(
+            ENDIANNESS_KLASS_ID,
+            "HT_EndiannessInfoEvent",
+            vec![base(), field("uint8_t", "endianness", 1, 99)],
+        ),
+        klass(
+            2,
+            "HT_EventKlassInfoEvent",
+            vec![
+                base(),
+                field("HT_EventKlassId", "info_klass_id", 4, 99),
+                field("const char*", "event_klass_name", 8, 2),
+                field("uint8_t", "field_count", 1, 99),
+            ],
+        ),
+        klass(
+            3,
+            "HT_EventKlassFieldInfoEvent",
+            vec![
+                base(),
+                field("HT_EventKlassId", "info_klass_id", 4, 99),
+                field("const char*", "field_type", 8, 2),
+                field("const char*", "field_name", 8, 2),
+                field("uint64_t", "size", 8, 99),
+                field("uint8_t", "data_type", 1, 99),
+            ],
+        ),
+        klass(
+            4,
+            "HT_CallstackBaseEvent",
+            vec![
+                base(),
+                field("HT_DurationNs", "duration", 8, 99),
+                field("HT_ThreadId", "thread_id", 4, 99),
+            ],
+        ),
+        klass(
+            CALLSTACK_INT_KLASS_ID,
+            "HT_CallstackIntEvent",
+            vec![
+                field("HT_CallstackBaseEvent", "base", 40, 1),
+                field("uint64_t", "label", 8, 99),
+            ],
+        ),
+        klass(
+            CALLSTACK_STRING_KLASS_ID,
+            "HT_CallstackStringEvent",
+            vec![
+                field("HT_CallstackBaseEvent", "base", 40, 1),
+                field("const char*", "label", 8, 2),
+            ],
+        ),
+        klass(
+            STRING_MAPPING_KLASS_ID,
+            "HT_StringMappingEvent",
+            vec![
+                base(),
+                field("uint64_t", "identifier", 8, 99),
+                field("const char*", "label", 8, 2),
+            ],
+        ),
+    ]
+}
+
+fn start_stream() -> TestStreamBuilder {
+    let mut builder = TestStreamBuilder::new().raw_event(&EventPayload {
+        klass_id: ENDIANNESS_KLASS_ID,
+        timestamp: 0,
+        id: 0,
+        data: vec![0],
+    });
+    for klass in klasses() {
+        builder = builder.klass_definition(&klass);
+    }
+    builder
+}
+
+fn callstack_values(duration: u64, thread_id: u32, label: TestValue) -> Vec<TestValue> {
+    vec![TestValue::U64(duration), TestValue::U32(thread_id), label]
+}
+
+// Ten sequential string callstack events of a single thread.
+fn make_small() -> Vec<u8> {
+    let mut builder = start_stream();
+    for i in 0..10u64 {
+        let label = TestValue::Str(format!("task_{}", i % 3));
+        builder = builder.event(
+            CALLSTACK_STRING_KLASS_ID,
+            1000 + i * 100,
+            &callstack_values(80, 1, label),
+        );
+    }
+    builder.build()
+}
+
+// Nested int callstack events of four threads with labels defined by string
+// mapping events. Events of a callstack are written when they finish, so
+// children come before their parents.
+fn make_medium() -> Vec<u8> {
+    let labels = ["main_loop", "update", "physics", "render", "submit"];
+    let mut builder = start_stream();
+    for (identifier, label) in labels.iter().enumerate() {
+        builder = builder.event(
+            STRING_MAPPING_KLASS_ID,
+            0,
+            &[
+                TestValue::U64(identifier as u64),
+                TestValue::Str(label.to_string()),
+            ],
+        );
+    }
+    for frame in 0..100u64 {
+        for thread_id in 1..=4u32 {
+            let start = 1_000_000 + frame * 16_000 + thread_id as u64;
+            for (offset, duration, label) in &[
+                (100, 2000, 2),
+                (50, 2150, 1),
+                (5300, 1000, 4),
+                (5200, 1300, 3),
+                (0, 15_000, 0),
+            ] {
+                builder = builder.event(
+                    CALLSTACK_INT_KLASS_ID,
+                    start + offset,
+                    &callstack_values(*duration, thread_id, TestValue::U64(*label)),
+                );
+            }
+        }
+    }
+    builder.build()
+}
+
+// The small trace with an event of an undefined klass and a truncated last
+// event.
+fn make_malformed() -> Vec<u8> {
+    let mut data = make_small();
+    data.extend(encode_header(42, 3000, 100));
+    data.extend(
+        EventPayload {
+            klass_id: CALLSTACK_STRING_KLASS_ID,
+            timestamp: 3100,
+            id: 101,
+            data: callstack_values(80, 1, TestValue::Str("task_0".to_owned()))
+                .iter()
+                .flat_map(TestValue::encode)
+                .collect(),
+        }
+        .encode(),
+    );
+    data.truncate(data.len() - 4);
+    data
+}
+
+fn main() {
+    let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures");
+    for (name, data) in &[
+        ("small", make_small()),
+        ("medium", make_medium()),
+        ("malformed", make_malformed()),
+    ] {
+        let path = dir.join(format!("{}.htdump", name));
+        std::fs::write(&path, data).unwrap();
+        println!("{}: {} bytes", path.display(), data.len());
+    }
+}
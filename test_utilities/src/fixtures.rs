@@ -0,0 +1,129 @@
+// Trace fixtures stored in the fixtures/ directory of this crate. Each
+// <name>.htdump file has a <name>.expected file listing the number of events
+// of each klass (one "<klass name> <count>" line per klass, metadata klasses
+// included) and, for malformed traces, an "error" line with the Debug
+// representation of the expected error, e.g. "error DataError(EndOfStream)".
+//
+// The small, medium and malformed traces are synthetic (generated by
+// examples/generate_fixtures.rs); traces recorded with the HawkTracer client
+// library are stored in fixtures/recorded/, see the README there.
+
+pub const SMALL: &str = "small";
+pub const MEDIUM: &str = "medium";
+pub const MALFORMED: &str = "malformed";
+
+pub const ALL: &[&str] = &[SMALL, MEDIUM, MALFORMED];
+
+const RECORDED_DIR: &str = "recorded";
+
+// Names (e.g. "recorded/game_loop") of the recorded traces, sorted.
+pub fn get_recorded_fixtures() -> Vec<String> {
+    let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("fixtures")
+        .join(RECORDED_DIR);
+    let entries = match std::fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension() == Some(std::ffi::OsStr::new("htdump")))
+        .filter_map(|path| {
+            path.file_stem()
+                .map(|stem| format!("{}/{}", RECORDED_DIR, stem.to_string_lossy()))
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+// Synthetic fixtures followed by the recorded ones.
+pub fn get_all_fixtures() -> Vec<String> {
+    ALL.iter()
+        .map(|name| name.to_string())
+        .chain(get_recorded_fixtures())
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ExpectedSummary {
+    pub klass_counts: std::collections::BTreeMap<String, usize>,
+    pub error: Option<String>,
+}
+
+pub fn get_fixture_path(name: &str) -> std::path::PathBuf {
+    std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("fixtures")
+        .join(format!("{}.htdump", name))
+}
+
+pub fn load_fixture(name: &str) -> Vec<u8> {
+    let path = get_fixture_path(name);
+    std::fs::read(&path).unwrap_or_else(|err| panic!("Cannot read {}: {}", path.display(), err))
+}
+
+pub fn load_expected_summary(name: &str) -> ExpectedSummary {
+    let path = get_fixture_path(name).with_extension("expected");
+    let content = std::fs::read_to_string(&path)
+        .unwrap_or_else(|err| panic!("Cannot read {}: {}", path.display(), err));
+
+    let mut summary = ExpectedSummary::default();
+    for line in content.lines().filter(|line| !line.trim().is_empty()) {
+        let (key, value) = match line.rsplit_once(' ') {
+            Some(entry) => entry,
+            None => panic!("Invalid line in {}: {}", path.display(), line),
+        };
+        if key == "error" {
+            summary.error = Some(value.to_owned());
+            continue;
+        }
+        let count = value
+            .parse()
+            .unwrap_or_else(|_| panic!("Invalid count in {}: {}", path.display(), line));
+        summary.klass_counts.insert(key.to_owned(), count);
+    }
+    summary
+}
+
+// Writes the .expected file of a newly added fixture; the result has to be
+// reviewed, as it describes whatever the parser decoded.
+pub fn write_expected_summary(name: &str, summary: &ExpectedSummary) {
+    let mut content = String::new();
+    for (klass_name, count) in &summary.klass_counts {
+        content.push_str(&format!("{} {}\n", klass_name, count));
+    }
+    if let Some(error) = &summary.error {
+        content.push_str(&format!("error {}\n", error));
+    }
+    let path = get_fixture_path(name).with_extension("expected");
+    std::fs::write(&path, content)
+        .unwrap_or_else(|err| panic!("Cannot write {}: {}", path.display(), err));
+}
+
+// Compares the summary of the decoded fixture with the expected one, with
+// messages pointing at the first difference.
+pub fn assert_fixture_summary(name: &str, actual: &ExpectedSummary) {
+    let expected = load_expected_summary(name);
+    for (klass_name, count) in &expected.klass_counts {
+        assert_eq!(
+            actual.klass_counts.get(klass_name),
+            Some(count),
+            "Unexpected number of {} events in the {} fixture",
+            klass_name,
+            name
+        );
+    }
+    for klass_name in actual.klass_counts.keys() {
+        assert!(
+            expected.klass_counts.contains_key(klass_name),
+            "Unexpected {} events in the {} fixture",
+            klass_name,
+            name
+        );
+    }
+    assert_eq!(
+        actual.error, expected.error,
+        "Unexpected error when decoding the {} fixture",
+        name
+    );
+}
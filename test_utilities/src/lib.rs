@@ -1,7 +1,18 @@
+pub mod fixtures;
+pub mod stream;
+
+// Reader returning the data of the buffer. Besides failing on every read, it
+// can simulate the behavior of sockets and pipes: short reads, retryable
+// errors (e.g. Interrupted or WouldBlock) at given positions and failures
+// after some amount of data.
 pub struct FakeDataReader {
     buffer: Vec<u8>,
     pointer: usize,
     failing: bool,
+    chunk_size: Option<usize>,
+    // Errors returned once when the reader reaches the position.
+    errors: Vec<(usize, std::io::ErrorKind)>,
+    fail_after: Option<usize>,
 }
 
 impl FakeDataReader {
@@ -10,24 +21,97 @@ impl FakeDataReader {
             buffer,
             pointer: 0,
             failing,
+            chunk_size: None,
+            errors: Vec::new(),
+            fail_after: None,
         }
     }
+
+    // Each read returns at most chunk_size bytes.
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> FakeDataReader {
+        self.chunk_size = Some(std::cmp::max(chunk_size, 1));
+        self
+    }
+
+    // Reads stop at the position, and the next read returns the error
+    // instead of data. Reading again continues from the position.
+    pub fn with_error_at(mut self, position: usize, kind: std::io::ErrorKind) -> FakeDataReader {
+        self.errors.push((position, kind));
+        self
+    }
+
+    // Every read fails once count bytes have been returned.
+    pub fn with_fail_after(mut self, count: usize) -> FakeDataReader {
+        self.fail_after = Some(count);
+        self
+    }
+
+    pub fn get_position(&self) -> usize {
+        self.pointer
+    }
 }
 
 impl std::io::Read for FakeDataReader {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        if self.failing {
-            return Err(std::io::Error::new(std::io::ErrorKind::Other, "Fail"));
-        }
-        let copy_size = std::cmp::min(buf.len(), self.buffer.len() - self.pointer);
-        match copy_size {
-            0 => Ok(0),
-            _v => {
-                buf[..copy_size]
-                    .copy_from_slice(&self.buffer[self.pointer..self.pointer + copy_size]);
-                self.pointer = self.pointer + copy_size;
-                Ok(copy_size)
+        if self.failing || self.fail_after.is_some_and(|count| self.pointer >= count) {
+            return Err(std::io::Error::other("Fail"));
+        }
+        if let Some(index) = self.errors.iter().position(|(p, _)| *p == self.pointer) {
+            let (_, kind) = self.errors.remove(index);
+            return Err(std::io::Error::new(kind, "Fake error"));
+        }
+
+        let mut end = std::cmp::min(self.buffer.len(), self.pointer + buf.len());
+        if let Some(chunk_size) = self.chunk_size {
+            end = std::cmp::min(end, self.pointer + chunk_size);
+        }
+        if let Some(count) = self.fail_after {
+            end = std::cmp::min(end, count);
+        }
+        for (position, _) in &self.errors {
+            if *position > self.pointer {
+                end = std::cmp::min(end, *position);
             }
         }
+
+        let copy_size = end - self.pointer;
+        buf[..copy_size].copy_from_slice(&self.buffer[self.pointer..end]);
+        self.pointer = end;
+        Ok(copy_size)
+    }
+}
+
+// Writer appending the data to a buffer which is shared with its clones, so
+// the data can be checked after the writer is moved into a boxed sink.
+#[derive(Clone, Default)]
+pub struct FakeDataWriter {
+    buffer: std::rc::Rc<std::cell::RefCell<Vec<u8>>>,
+    failing: bool,
+}
+
+impl FakeDataWriter {
+    pub fn new(failing: bool) -> FakeDataWriter {
+        FakeDataWriter {
+            buffer: Default::default(),
+            failing,
+        }
+    }
+
+    pub fn get_data(&self) -> Vec<u8> {
+        self.buffer.borrow().clone()
     }
-}
\ No newline at end of file
+}
+
+impl std::io::Write for FakeDataWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.failing {
+            return Err(std::io::Error::other("Fail"));
+        }
+        self.buffer.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,319 @@
+// Descriptions of HawkTracer streams which are encoded to bytes (in the
+// native byte order) by TraceStream::encode() or TestStreamBuilder.
+
+pub const KLASS_INFO_KLASS_ID: u32 = 2;
+pub const FIELD_INFO_KLASS_ID: u32 = 3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldDefinition {
+    pub type_name: String,
+    pub name: String,
+    pub size: u64,
+    // MKCREFLECT data type identifier.
+    pub data_type: u8,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KlassDefinition {
+    pub id: u32,
+    pub name: String,
+    // Usually the number of fields, but it's not enforced so invalid
+    // streams can be described as well.
+    pub field_count: u8,
+    pub fields: Vec<FieldDefinition>,
+}
+
+// Event with a raw payload following the header.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventPayload {
+    pub klass_id: u32,
+    pub timestamp: u64,
+    pub id: u64,
+    pub data: Vec<u8>,
+}
+
+// Klasses are defined before all the events.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TraceStream {
+    pub klasses: Vec<KlassDefinition>,
+    pub events: Vec<EventPayload>,
+}
+
+// Value of an event field; strings are encoded zero-terminated.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TestValue {
+    U8(u8),
+    I8(i8),
+    U16(u16),
+    I16(i16),
+    U32(u32),
+    I32(i32),
+    U64(u64),
+    I64(i64),
+    Str(String),
+    Bytes(Vec<u8>),
+}
+
+impl TestValue {
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            TestValue::U8(v) => v.to_ne_bytes().to_vec(),
+            TestValue::I8(v) => v.to_ne_bytes().to_vec(),
+            TestValue::U16(v) => v.to_ne_bytes().to_vec(),
+            TestValue::I16(v) => v.to_ne_bytes().to_vec(),
+            TestValue::U32(v) => v.to_ne_bytes().to_vec(),
+            TestValue::I32(v) => v.to_ne_bytes().to_vec(),
+            TestValue::U64(v) => v.to_ne_bytes().to_vec(),
+            TestValue::I64(v) => v.to_ne_bytes().to_vec(),
+            TestValue::Str(v) => encode_string(v),
+            TestValue::Bytes(v) => v.clone(),
+        }
+    }
+}
+
+pub fn encode_header(klass_id: u32, timestamp: u64, id: u64) -> Vec<u8> {
+    let mut data = klass_id.to_ne_bytes().to_vec();
+    data.extend_from_slice(&timestamp.to_ne_bytes());
+    data.extend_from_slice(&id.to_ne_bytes());
+    data
+}
+
+pub fn encode_string(value: &str) -> Vec<u8> {
+    let mut data = value.as_bytes().to_vec();
+    data.push(0);
+    data
+}
+
+impl FieldDefinition {
+    // Size and data type are derived from the type name: integer types
+    // (e.g. "uint32_t"), strings ("const char*") and structs (any other
+    // type name, e.g. "HT_Event" for the base event).
+    pub fn new(type_name: &str, name: &str) -> FieldDefinition {
+        let (size, data_type) = match type_name {
+            "uint8_t" => (1, 99),
+            "int8_t" => (1, 3),
+            "uint16_t" => (2, 99),
+            "int16_t" => (2, 3),
+            "uint32_t" => (4, 99),
+            "int32_t" => (4, 3),
+            "uint64_t" => (8, 99),
+            "int64_t" => (8, 3),
+            "const char*" | "char*" => (8, 2),
+            _ => (0, 1),
+        };
+        FieldDefinition {
+            type_name: type_name.to_owned(),
+            name: name.to_owned(),
+            size,
+            data_type,
+        }
+    }
+}
+
+impl KlassDefinition {
+    // Fields are given as (type name, field name) pairs, see
+    // FieldDefinition::new().
+    pub fn new(id: u32, name: &str, fields: &[(&str, &str)]) -> KlassDefinition {
+        KlassDefinition {
+            id,
+            name: name.to_owned(),
+            field_count: fields.len() as u8,
+            fields: fields
+                .iter()
+                .map(|(type_name, name)| FieldDefinition::new(type_name, name))
+                .collect(),
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = encode_header(KLASS_INFO_KLASS_ID, 0, 0);
+        data.extend_from_slice(&self.id.to_ne_bytes());
+        data.extend(encode_string(&self.name));
+        data.push(self.field_count);
+
+        for field in &self.fields {
+            data.extend(encode_header(FIELD_INFO_KLASS_ID, 0, 0));
+            data.extend_from_slice(&self.id.to_ne_bytes());
+            data.extend(encode_string(&field.type_name));
+            data.extend(encode_string(&field.name));
+            data.extend_from_slice(&field.size.to_ne_bytes());
+            data.push(field.data_type);
+        }
+        data
+    }
+}
+
+impl EventPayload {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = encode_header(self.klass_id, self.timestamp, self.id);
+        data.extend_from_slice(&self.data);
+        data
+    }
+}
+
+impl TraceStream {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        for klass in &self.klasses {
+            data.extend(klass.encode());
+        }
+        for event in &self.events {
+            data.extend(event.encode());
+        }
+        data
+    }
+}
+
+// Encodes streams in the order of the calls, e.g.:
+//     TestStreamBuilder::new()
+//         .klass(100, "foo", &[("HT_Event", "base"), ("uint32_t", "value")])
+//         .event(100, 5, &[TestValue::U32(7)])
+//         .build()
+// Values of events are encoded after the header (the fields of the base
+// event), in the order given. Event ids are assigned incrementally.
+#[derive(Default)]
+pub struct TestStreamBuilder {
+    data: Vec<u8>,
+    next_event_id: u64,
+}
+
+impl TestStreamBuilder {
+    pub fn new() -> TestStreamBuilder {
+        TestStreamBuilder::default()
+    }
+
+    pub fn klass(self, id: u32, name: &str, fields: &[(&str, &str)]) -> TestStreamBuilder {
+        self.klass_definition(&KlassDefinition::new(id, name, fields))
+    }
+
+    pub fn klass_definition(mut self, klass: &KlassDefinition) -> TestStreamBuilder {
+        self.data.extend(klass.encode());
+        self
+    }
+
+    pub fn event(
+        mut self,
+        klass_id: u32,
+        timestamp: u64,
+        values: &[TestValue],
+    ) -> TestStreamBuilder {
+        let payload = EventPayload {
+            klass_id,
+            timestamp,
+            id: self.next_event_id,
+            data: values.iter().flat_map(TestValue::encode).collect(),
+        };
+        self.next_event_id += 1;
+        self.raw_event(&payload)
+    }
+
+    pub fn raw_event(mut self, event: &EventPayload) -> TestStreamBuilder {
+        self.data.extend(event.encode());
+        self
+    }
+
+    pub fn build(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+// The generated streams mostly use a few klass ids and well-formed field
+// definitions, so the fuzzer reaches event decoding instead of failing on
+// the metadata.
+#[cfg(feature = "arbitrary")]
+mod arbitrary_impls {
+    use super::*;
+    use arbitrary::{Arbitrary, Result, Unstructured};
+
+    const FIRST_KLASS_ID: u32 = 100;
+    const LAST_KLASS_ID: u32 = 107;
+    const MAX_FIELD_COUNT: usize = 8;
+
+    // Type name, size and data type of fields.
+    const FIELD_TYPES: &[(&str, u64, u8)] = &[
+        ("uint8_t", 1, 99),
+        ("int16_t", 2, 3),
+        ("uint32_t", 4, 99),
+        ("int64_t", 8, 3),
+        ("const char*", 8, 2),
+        ("void*", 8, 6),
+        ("double", 8, 5),
+        ("uint32_t[4]", 16, 99),
+        ("uint16_t[]", 2, 99),
+        ("HT_Event", 20, 1),
+    ];
+
+    fn arbitrary_klass_id(u: &mut Unstructured) -> Result<u32> {
+        if u.ratio(1, 16)? {
+            u.arbitrary()
+        } else {
+            u.int_in_range(FIRST_KLASS_ID..=LAST_KLASS_ID)
+        }
+    }
+
+    impl<'a> Arbitrary<'a> for FieldDefinition {
+        fn arbitrary(u: &mut Unstructured<'a>) -> Result<FieldDefinition> {
+            let name = format!("field_{}", u.int_in_range(0..=MAX_FIELD_COUNT)?);
+            let (type_name, size, data_type) = match u.int_in_range(0..=FIELD_TYPES.len() + 1)? {
+                index if index < FIELD_TYPES.len() => {
+                    let (type_name, size, data_type) = FIELD_TYPES[index];
+                    (type_name.to_owned(), size, data_type)
+                }
+                // Struct of a (possibly undefined) klass.
+                index if index == FIELD_TYPES.len() => (
+                    format!("klass_{}", u.int_in_range(FIRST_KLASS_ID..=LAST_KLASS_ID)?),
+                    0,
+                    1,
+                ),
+                _ => (u.arbitrary()?, u.arbitrary()?, u.arbitrary()?),
+            };
+            Ok(FieldDefinition {
+                type_name,
+                name,
+                size,
+                data_type,
+            })
+        }
+    }
+
+    impl<'a> Arbitrary<'a> for KlassDefinition {
+        fn arbitrary(u: &mut Unstructured<'a>) -> Result<KlassDefinition> {
+            let id = arbitrary_klass_id(u)?;
+            let mut fields = Vec::new();
+            for _ in 0..u.int_in_range(0..=MAX_FIELD_COUNT)? {
+                fields.push(u.arbitrary()?);
+            }
+            let field_count = if u.ratio(1, 8)? {
+                u.arbitrary()?
+            } else {
+                fields.len() as u8
+            };
+            Ok(KlassDefinition {
+                id,
+                name: format!("klass_{}", id),
+                field_count,
+                fields,
+            })
+        }
+    }
+
+    impl<'a> Arbitrary<'a> for EventPayload {
+        fn arbitrary(u: &mut Unstructured<'a>) -> Result<EventPayload> {
+            Ok(EventPayload {
+                klass_id: arbitrary_klass_id(u)?,
+                timestamp: u.arbitrary()?,
+                id: u.arbitrary()?,
+                data: u.arbitrary()?,
+            })
+        }
+    }
+
+    impl<'a> Arbitrary<'a> for TraceStream {
+        fn arbitrary(u: &mut Unstructured<'a>) -> Result<TraceStream> {
+            Ok(TraceStream {
+                klasses: u.arbitrary()?,
+                events: u.arbitrary()?,
+            })
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use hawktracer_parser::analysis::callstack::CallstackBuilder;
+use hawktracer_parser::data_provider::DataError;
+use hawktracer_parser::{
+    EventKlassRegistry, EventReaderBuilder, LabelResolver, Parser, ReadEventError,
+};
+use hawktracer_parser_test_utilities::fixtures::{self, ExpectedSummary};
+
+fn count_klass(summary: &mut ExpectedSummary, klass_id: u32, registry: &EventKlassRegistry) {
+    let name = match registry.get_klass_by_id(klass_id) {
+        Some(klass) => klass.get_name().clone(),
+        None => klass_id.to_string(),
+    };
+    *summary.klass_counts.entry(name).or_insert(0) += 1;
+}
+
+fn read_summary(name: &str) -> ExpectedSummary {
+    let mut reader = EventReaderBuilder::new()
+        .build(Box::new(std::io::Cursor::new(fixtures::load_fixture(name))));
+    let mut registry = EventKlassRegistry::new();
+    let mut summary = ExpectedSummary::default();
+    loop {
+        match reader.read_event(&mut registry) {
+            Ok(event) => count_klass(&mut summary, event.get_klass_id(), &registry),
+            Err(ReadEventError::DataError(DataError::EndOfStream)) => return summary,
+            Err(err) => {
+                summary.error = Some(format!("{:?}", err));
+                return summary;
+            }
+        }
+    }
+}
+
+// With HT_UPDATE_FIXTURES set, .expected files of recorded traces which
+// don't have one yet are written instead of being checked.
+#[test]
+fn fixtures_should_be_decoded_by_event_reader() {
+    let update = std::env::var_os("HT_UPDATE_FIXTURES").is_some();
+    for name in fixtures::get_all_fixtures() {
+        let summary = read_summary(&name);
+        let expected_path = fixtures::get_fixture_path(&name).with_extension("expected");
+        if update && !expected_path.exists() {
+            fixtures::write_expected_summary(&name, &summary);
+        } else {
+            fixtures::assert_fixture_summary(&name, &summary);
+        }
+    }
+}
+
+#[test]
+fn fixtures_should_be_decoded_by_push_parser() {
+    for name in fixtures::get_all_fixtures() {
+        let mut parser = Parser::new();
+        let mut summary = ExpectedSummary::default();
+        // Errors are reported once all the events before them are returned,
+        // so an empty chunk is fed at the end to get the error.
+        let data = fixtures::load_fixture(&name);
+        for chunk in data.chunks(61).chain(std::iter::once(&[][..])) {
+            match parser.feed(chunk) {
+                Ok(events) => {
+                    for event in events {
+                        count_klass(&mut summary, event.get_klass_id(), parser.get_registry());
+                    }
+                }
+                Err(err) => {
+                    summary.error = Some(format!("{:?}", err));
+                    break;
+                }
+            }
+        }
+        // The push parser waits for more data instead of reporting a
+        // truncated event.
+        if summary.error.is_none() && parser.get_pending_size() > 0 {
+            summary.error = Some(format!(
+                "{:?}",
+                ReadEventError::DataError(DataError::EndOfStream)
+            ));
+        }
+        fixtures::assert_fixture_summary(&name, &summary);
+    }
+}
+
+#[test]
+fn medium_fixture_should_build_callstacks() {
+    let mut reader = EventReaderBuilder::new().build(Box::new(std::io::Cursor::new(
+        fixtures::load_fixture(fixtures::MEDIUM),
+    )));
+    let mut registry = EventKlassRegistry::new();
+    let mut label_resolver = LabelResolver::new().with_rewrite_fields(&["label"]);
+    let mut callstack_builder = CallstackBuilder::new();
+    while let Ok(mut event) = reader.read_event(&mut registry) {
+        label_resolver.process(&mut event, &registry);
+        callstack_builder.update(&event, &registry);
+    }
+
+    let trees = callstack_builder.build();
+    assert_eq!(trees.keys().copied().collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+    for roots in trees.values() {
+        assert_eq!(roots.len(), 100);
+        let labels: Vec<&str> = roots[0]
+            .children
+            .iter()
+            .map(|node| node.label.as_str())
+            .collect();
+        assert_eq!(roots[0].label, "main_loop");
+        assert_eq!(labels, vec!["update", "render"]);
+        assert_eq!(roots[0].children[0].children[0].label, "physics");
+    }
+}